@@ -1,10 +1,27 @@
-use std::collections::LinkedList;
+use std::collections::{BTreeSet, LinkedList};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::time::Instant;
 
 use crate::align_up;
+use crate::collector::marker::Marker;
+use crate::heap_dump::heap_dumper::{self, HeapDumpSummary, HeapDumper};
+use crate::model::address::Address;
 use crate::model::block::Block;
 use crate::model::block::{BLOCK_SIZE, LINE_SIZE};
+use crate::model::heap_obj::HeapObj;
 use crate::model::line_map::LineMap;
+use crate::model::object_model::{object_model, ObjectModel};
+use crate::reference::reference_handler;
+use crate::reference::reference_processor::{self, ReferenceCounts, SoftReferencePolicy};
+use crate::stats::gc_record::{BlockCounts, GcCause, GcRecord};
+use crate::stats::gc_stats::{GcStats, GcSummary, HeapUsage};
 use crate::utils::mmap::MemoryMap;
+use crate::verifier::heap_verifier::{self, HeapVerifier, VerifyPhase, VerifyReport};
+
+use super::large_object_space::LargeObjectSpace;
+use super::pin_table::{NotInHeap, NotPinned, PinTable, PinnedObject};
 
 const DEFAULT_HEAP_SIZE: usize = 1024 * 1024;
 
@@ -14,6 +31,11 @@ pub struct GlobalAllocator {
     used_blocks: LinkedList<Block>,
     line_map: LineMap,
     total_blocks: usize,
+    heap_size: usize,
+    start_time: Instant,
+    stats: GcStats,
+    pin_table: PinTable,
+    large_objects: LargeObjectSpace,
 }
 
 impl GlobalAllocator {
@@ -30,6 +52,11 @@ impl GlobalAllocator {
             used_blocks: LinkedList::new(),
             line_map,
             total_blocks: 0,
+            heap_size,
+            start_time: Instant::now(),
+            stats: GcStats::new(),
+            pin_table: PinTable::new(),
+            large_objects: LargeObjectSpace::new(),
         }
     }
 
@@ -41,13 +68,39 @@ impl GlobalAllocator {
         if let Some(block) = self.free_blocks.pop_front() {
             return block;
         };
-        self.collect();
+        self.collect(GcCause::AllocationFailure);
         if let Some(block) = self.free_blocks.pop_front() {
             return block;
         };
+        self.collect(GcCause::LastDitchCollection);
+        if let Some(block) = self.free_blocks.pop_front() {
+            return block;
+        };
+        self.dump_heap_on_out_of_memory();
         panic!("out of memory");
     }
 
+    /// Allocate an object larger than a block, collecting when the heap has no room
+    /// for it.
+    pub fn allocate_large(&mut self, size: usize) -> Option<Address> {
+        for cause in [GcCause::AllocationFailure, GcCause::LastDitchCollection] {
+            if self.has_room(size) {
+                break;
+            }
+            self.collect(cause);
+        }
+        if !self.has_room(size) {
+            self.dump_heap_on_out_of_memory();
+            return None;
+        }
+        Some(self.large_objects.allocate(size))
+    }
+
+    /// Whether the committed blocks, the large objects and the bytes fit in the heap.
+    fn has_room(&self, size: usize) -> bool {
+        self.total_blocks * BLOCK_SIZE + self.large_objects.bytes() + size <= self.heap_size
+    }
+
     pub fn return_blocks<I>(&mut self, free_blocks: I, used_blocks: I)
     where
         I: Iterator<Item = Block>,
@@ -56,19 +109,315 @@ impl GlobalAllocator {
         self.used_blocks.extend(used_blocks);
     }
 
-    pub fn collect(&self) {
-        todo!()
+    pub fn collect(&mut self, cause: GcCause) {
+        if heap_verifier::should_verify(VerifyPhase::BeforeGc) {
+            self.verify_or_abort(VerifyPhase::BeforeGc);
+        }
+        let mut record = self
+            .stats
+            .begin(cause, self.start_time.elapsed(), self.block_counts());
+        let start = Instant::now();
+        record.pinned_objects = self.pin_table.len();
+        record.long_pinned = self.pin_table.survive_collection();
+        let policy = if cause == GcCause::LastDitchCollection {
+            SoftReferencePolicy::clear_all()
+        } else {
+            let free_heap = self.heap_size - record.before.used() * BLOCK_SIZE;
+            SoftReferencePolicy::new(free_heap)
+        };
+        match object_model() {
+            Some(model) => record.references = self.mark(model, policy),
+            None => self.mark_pinned_lines(),
+        }
+        record.lines_marked = self.sweep();
+        reference_processor::update_soft_reference_clock(
+            self.start_time.elapsed().as_millis() as u64
+        );
+        record.pause = start.elapsed();
+        record.after = self.block_counts();
+        self.stats.finish(record);
+        if heap_verifier::should_verify(VerifyPhase::AfterGc) {
+            self.verify_or_abort(VerifyPhase::AfterGc);
+        }
     }
 
+    /// Visit every object in the committed blocks.
+    pub fn for_each_object(&self, model: &dyn ObjectModel, visitor: &mut dyn FnMut(Address)) {
+        let heap_start = self.memory_map.start();
+        for index in 0..self.total_blocks {
+            let block = Block::new(
+                heap_start.plus(index * BLOCK_SIZE),
+                self.line_map.block_line_marks(index),
+            );
+            block.for_each_object(model, visitor);
+        }
+        self.large_objects.objects().for_each(visitor);
+    }
+
+    /// Walk all committed blocks, block marks of used blocks are only up to date
+    /// after a collection swept them.
+    pub fn verify(&self, phase: VerifyPhase) -> Result<(), VerifyReport> {
+        let heap_start = self.memory_map.start();
+        let heap_end = heap_start.plus(self.total_blocks * BLOCK_SIZE);
+        let mut verifier = HeapVerifier::new(heap_start, heap_end, object_model());
+        for index in 0..self.total_blocks {
+            let block = Block::new(
+                heap_start.plus(index * BLOCK_SIZE),
+                self.line_map.block_line_marks(index),
+            );
+            verifier.verify_block(&block);
+        }
+        self.large_objects
+            .objects()
+            .for_each(|object| verifier.verify_large_object(object));
+        self.free_blocks
+            .iter()
+            .for_each(|block| verifier.verify_free_block(block));
+        self.pin_table
+            .objects()
+            .for_each(|object| verifier.verify_pinned_object(object));
+        if phase == VerifyPhase::AfterGc {
+            self.used_blocks
+                .iter()
+                .for_each(|block| verifier.verify_block_mark(block));
+        }
+        verifier.finish(phase)
+    }
+
+    fn verify_or_abort(&self, phase: VerifyPhase) {
+        if let Err(report) = self.verify(phase) {
+            panic!("{}", report);
+        }
+    }
+
+    /// Pin the object of the size so it is neither moved nor reclaimed until it is
+    /// unpinned.
+    pub fn pin_object(&mut self, object: Address, size: usize) -> Result<(), NotInHeap> {
+        let end = object.plus(size.max(1));
+        let in_blocks = self.is_in_heap(object) && self.is_in_heap(end.minus(1));
+        if !in_blocks && !self.large_objects.contains_range(object, end) {
+            return Err(NotInHeap(object));
+        }
+        if self.pin_table.pin(object, size) {
+            unsafe { HeapObj::from_address(object) }.set_pinned();
+        }
+        Ok(())
+    }
+
+    pub fn unpin_object(&mut self, object: Address) -> Result<(), NotPinned> {
+        if self.pin_table.unpin(object)? {
+            unsafe { HeapObj::from_address(object) }.clear_pinned();
+        }
+        Ok(())
+    }
+
+    pub fn pinned_objects(&self) -> Vec<PinnedObject> {
+        self.pin_table.pinned_objects()
+    }
+
+    pub fn gc_records(&self) -> Vec<GcRecord> {
+        self.stats.records()
+    }
+
+    pub fn gc_summary(&self) -> GcSummary {
+        self.stats.summary()
+    }
+
+    pub fn heap_usage(&self) -> HeapUsage {
+        let counts = self.block_counts();
+        HeapUsage {
+            used: counts.used() * BLOCK_SIZE + self.large_objects.bytes(),
+            committed: counts.total() * BLOCK_SIZE + self.large_objects.bytes(),
+            max: self.heap_size,
+        }
+    }
+
+    /// Block states of all committed blocks, blocks held by thread-local allocators
+    /// are unavailable for the global allocator.
+    pub fn block_counts(&self) -> BlockCounts {
+        let mut counts = BlockCounts {
+            free: self.free_blocks.len(),
+            ..Default::default()
+        };
+        for block in self.used_blocks.iter() {
+            if block.is_free() {
+                counts.free += 1;
+            } else if block.is_recyclable() {
+                counts.recyclable += 1;
+            } else {
+                counts.unavailable += 1;
+            }
+        }
+        counts.unavailable += self.total_blocks - self.free_blocks.len() - self.used_blocks.len();
+        counts
+    }
+
+    fn is_in_heap(&self, address: Address) -> bool {
+        let heap_start = self.memory_map.start();
+        heap_start <= address && address < heap_start.plus(self.total_blocks * BLOCK_SIZE)
+    }
+
+    /// Trace the heap from the roots, the pinned objects and the queued references,
+    /// then mark the lines of the live objects only. Pending references are handed to
+    /// the reference handler. Unreachable large objects are freed.
+    fn mark(&mut self, model: &dyn ObjectModel, policy: SoftReferencePolicy) -> ReferenceCounts {
+        let heap_start = self.memory_map.start();
+        for index in 0..self.total_blocks {
+            Block::new(
+                heap_start.plus(index * BLOCK_SIZE),
+                self.line_map.block_line_marks(index),
+            )
+            .clear_line_marks();
+        }
+        let heap_end = heap_start.plus(self.total_blocks * BLOCK_SIZE);
+        let mut marker =
+            Marker::new(heap_start, heap_end, model).with_large_objects(&self.large_objects);
+        model.for_each_root(&mut |root| marker.mark(root.object()));
+        self.pin_table
+            .objects()
+            .for_each(|object| marker.mark(object));
+        reference_handler::for_each_queued_reference(&mut |reference| marker.mark(reference));
+        marker.drain();
+        let processed = reference_processor::process_references(&mut marker, policy);
+        let mut live_large_objects = BTreeSet::new();
+        for object in marker.finish() {
+            if self.large_objects.contains(object) {
+                live_large_objects.insert(object);
+            } else {
+                self.mark_object_lines(object, model.object_size(object));
+            }
+        }
+        self.large_objects.sweep(&live_large_objects);
+        reference_handler::push_pending(processed.pending);
+        processed.counts
+    }
+
+    /// Without an object model nothing can be traced, the lines stay marked as they
+    /// were allocated. Pinned objects are live whether they are reachable or not, so
+    /// their lines are marked before sweeping. Large objects are not swept at all.
+    fn mark_pinned_lines(&mut self) {
+        for pinned in self.pin_table.pinned_objects() {
+            if !self.large_objects.contains(pinned.address) {
+                self.mark_object_lines(pinned.address, pinned.size);
+            }
+        }
+    }
+
+    fn mark_object_lines(&mut self, object: Address, size: usize) {
+        let heap_start = self.memory_map.start();
+        let index = object.diff(heap_start) / BLOCK_SIZE;
+        let mut block = Block::new(
+            heap_start.plus(index * BLOCK_SIZE),
+            self.line_map.block_line_marks(index),
+        );
+        let end = block.block_limit().min(object.plus(size.max(1)));
+        block.mark_lines(object, end);
+    }
+
+    /// Recompute block marks from line marks and move free blocks to the free list,
+    /// returns the number of live lines in the swept blocks.
+    fn sweep(&mut self) -> usize {
+        let mut lines_marked = 0;
+        let mut used_blocks = LinkedList::new();
+        while let Some(mut block) = self.used_blocks.pop_front() {
+            lines_marked += block.sweep();
+            if block.is_free() {
+                self.free_blocks.push_back(block);
+            } else {
+                used_blocks.push_back(block);
+            }
+        }
+        self.used_blocks = used_blocks;
+        lines_marked
+    }
+
+    /// Commit a new block, nothing is added to the free list when the heap limit is reached.
     fn require_block_from_system(&mut self) {
-        if let Some(address) = self.memory_map.allocate_memory(BLOCK_SIZE) {
+        if !self.has_room(BLOCK_SIZE) {
+            return;
+        }
+        if let Some(address) = self.memory_map.commit_block(self.total_blocks) {
             self.total_blocks += 1;
             let index = self.total_blocks - 1;
             let line_mark = self.line_map.block_line_marks(index);
             let block = Block::new(address, line_mark);
             self.free_blocks.push_back(block);
-        } else {
-            panic!("out of memory");
         }
     }
+
+    pub fn dump_heap(&self, path: &Path) -> io::Result<HeapDumpSummary> {
+        let model = object_model().ok_or_else(|| io::Error::other("no object model registered"))?;
+        let mut objects = Vec::new();
+        self.for_each_object(model, &mut |object| objects.push(object));
+        let out = BufWriter::new(File::create(path)?);
+        HeapDumper::new(model).dump(&objects, out)
+    }
+
+    fn dump_heap_on_out_of_memory(&self) {
+        let Some(path) = heap_dumper::out_of_memory_dump_path() else {
+            return;
+        };
+        println!("Dumping heap to {} ...", path.display());
+        let start = Instant::now();
+        match self.dump_heap(&path) {
+            Ok(_) => println!(
+                "Heap dump file created [{} bytes in {:.3} secs]",
+                std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0),
+                start.elapsed().as_secs_f64()
+            ),
+            Err(error) => println!("Unable to create {}: {}", path.display(), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlobalAllocator;
+    use crate::allocator::pin_table::NotInHeap;
+    use crate::model::address::Address;
+    use crate::model::block::{Block, LineMark, BLOCK_SIZE, LINE_SIZE};
+
+    #[test]
+    fn pinned_objects_keep_all_their_lines() {
+        let mut allocator = GlobalAllocator::initialize();
+        let outside = Address::new(0x1000);
+        assert_eq!(allocator.pin_object(outside, 16), Err(NotInHeap(outside)));
+        let block = allocator.require_block();
+        let object = block.base_address().plus(LINE_SIZE);
+        let end = block.block_limit();
+        assert_eq!(
+            allocator.pin_object(end.minus(8), 16),
+            Err(NotInHeap(end.minus(8)))
+        );
+        allocator.pin_object(object, 2 * LINE_SIZE + 1).unwrap();
+        let line_marks = allocator.line_map.block_line_marks(0);
+        let mut block = Block::new(block.base_address(), line_marks);
+        block.clear_line_marks();
+        allocator.mark_pinned_lines();
+        let marks: Vec<_> = (0..5).map(|index| block.line_mark(index)).collect();
+        assert_eq!(
+            marks,
+            [
+                LineMark::Free,
+                LineMark::Live,
+                LineMark::Live,
+                LineMark::Live,
+                LineMark::Free
+            ]
+        );
+    }
+
+    #[test]
+    fn large_objects_count_against_the_heap() {
+        let mut allocator = GlobalAllocator::initialize();
+        let object = allocator.allocate_large(2 * BLOCK_SIZE).unwrap();
+        let usage = allocator.heap_usage();
+        assert_eq!(usage.used, 2 * BLOCK_SIZE);
+        assert!(!allocator.has_room(usage.max - BLOCK_SIZE));
+        allocator.pin_object(object, 2 * BLOCK_SIZE).unwrap();
+        assert_eq!(
+            allocator.pin_object(object.plus(8), 2 * BLOCK_SIZE),
+            Err(NotInHeap(object.plus(8)))
+        );
+    }
 }
@@ -0,0 +1,103 @@
+//! Objects that do not fit in a block are allocated one by one from the system. They
+//! are marked like the objects in the blocks and freed by the collection that finds
+//! them unreachable, their bytes count against the heap size.
+use std::alloc::{self, Layout};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::model::address::Address;
+use crate::model::block::LINE_SIZE;
+
+pub struct LargeObjectSpace {
+    objects: BTreeMap<Address, Layout>,
+    bytes: usize,
+}
+
+impl LargeObjectSpace {
+    pub fn new() -> LargeObjectSpace {
+        LargeObjectSpace {
+            objects: BTreeMap::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Allocate zeroed bytes for an object, aligned like the lines of a block.
+    pub fn allocate(&mut self, size: usize) -> Address {
+        let layout = Layout::from_size_align(size.max(1), LINE_SIZE).expect("invalid object size.");
+        let object = unsafe { alloc::alloc_zeroed(layout) };
+        if object.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        let object = Address::new(object as usize);
+        self.objects.insert(object, layout);
+        self.bytes += layout.size();
+        object
+    }
+
+    /// Whether the address is the start of a large object.
+    pub fn contains(&self, object: Address) -> bool {
+        self.objects.contains_key(&object)
+    }
+
+    /// Whether the range `[start, end)` is inside of a single large object.
+    pub fn contains_range(&self, start: Address, end: Address) -> bool {
+        self.objects
+            .range(..=start)
+            .next_back()
+            .is_some_and(|(object, layout)| end <= object.plus(layout.size()))
+    }
+
+    /// Bytes of the allocated objects.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = Address> + '_ {
+        self.objects.keys().copied()
+    }
+
+    /// Free the objects that are not live, returns the bytes freed.
+    pub fn sweep(&mut self, live: &BTreeSet<Address>) -> usize {
+        let mut freed = 0;
+        self.objects.retain(|object, layout| {
+            if live.contains(object) {
+                return true;
+            }
+            unsafe { alloc::dealloc(object.as_mut_ptr::<u8>(), *layout) };
+            freed += layout.size();
+            false
+        });
+        self.bytes -= freed;
+        freed
+    }
+}
+
+impl Drop for LargeObjectSpace {
+    fn drop(&mut self) {
+        self.sweep(&BTreeSet::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::LargeObjectSpace;
+    use crate::model::block::{BLOCK_SIZE, LINE_SIZE};
+
+    #[test]
+    fn unreachable_large_objects_are_freed() {
+        let mut space = LargeObjectSpace::new();
+        let live = space.allocate(BLOCK_SIZE + 8);
+        let dead = space.allocate(2 * BLOCK_SIZE);
+        assert_eq!(live.to_usize() % LINE_SIZE, 0);
+        assert_eq!(live.plus(BLOCK_SIZE).load::<u64>(), 0);
+        assert_eq!(space.bytes(), 3 * BLOCK_SIZE + 8);
+        assert!(space.contains(live) && !space.contains(live.plus(8)));
+        assert!(space.contains_range(live.plus(8), live.plus(BLOCK_SIZE + 8)));
+        assert!(!space.contains_range(live, live.plus(BLOCK_SIZE + 16)));
+        assert_eq!(space.sweep(&BTreeSet::from([live])), 2 * BLOCK_SIZE);
+        assert!(!space.contains(dead));
+        assert_eq!(space.objects().collect::<Vec<_>>(), [live]);
+        assert_eq!(space.bytes(), BLOCK_SIZE + 8);
+    }
+}
@@ -1,17 +1,28 @@
 use std::cell::RefCell;
+use std::io;
+use std::path::Path;
 use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 
+use crate::heap_dump::heap_dumper::HeapDumpSummary;
 use crate::model::address::Address;
+use crate::model::block::BLOCK_SIZE;
+use crate::stats::gc_record::{GcCause, GcRecord};
+use crate::stats::gc_stats::{GcSummary, HeapUsage};
+use crate::verifier::heap_verifier::{VerifyPhase, VerifyReport};
 
 use self::{
-    global_allocator::GlobalAllocator, overflow_allocator::OverflowAllocator,
+    global_allocator::GlobalAllocator,
+    overflow_allocator::OverflowAllocator,
+    pin_table::{NotInHeap, NotPinned, PinnedObject},
     thread_local_allocator::ThreadLocalAllocator,
 };
 
 mod global_allocator;
+pub(crate) mod large_object_space;
 mod overflow_allocator;
+pub mod pin_table;
 mod thread_local_allocator;
 
 static GLOBAL_ALLOCATOR: Lazy<Mutex<GlobalAllocator>> =
@@ -23,9 +34,64 @@ thread_local! {
     static OVERFLOW_ALLOCATOR: RefCell<OverflowAllocator> = RefCell::new(OverflowAllocator::new());
 }
 
+/// Allocate the bytes for an object from the blocks of the current thread. An object
+/// larger than a block is a large object, zeroed, `None` when the heap has no room for
+/// it even after a collection.
 pub fn allocate(size: usize) -> Option<Address> {
+    if size > BLOCK_SIZE {
+        return GLOBAL_ALLOCATOR.lock().unwrap().allocate_large(size);
+    }
     THREAD_LOCAL_ALLOCATOR.with(|allocator| {
         let mut allocator = allocator.borrow_mut();
         allocator.allocate(size)
     })
 }
+
+/// Run a garbage collection for the given cause.
+pub fn collect(cause: GcCause) {
+    GLOBAL_ALLOCATOR.lock().unwrap().collect(cause);
+}
+
+/// The latest collection records, oldest first.
+pub fn gc_records() -> Vec<GcRecord> {
+    GLOBAL_ALLOCATOR.lock().unwrap().gc_records()
+}
+
+/// Totals over all collections, backs `GarbageCollectorMXBean`.
+pub fn gc_summary() -> GcSummary {
+    GLOBAL_ALLOCATOR.lock().unwrap().gc_summary()
+}
+
+/// Current heap occupancy, backs `MemoryMXBean.getHeapMemoryUsage`.
+pub fn heap_usage() -> HeapUsage {
+    GLOBAL_ALLOCATOR.lock().unwrap().heap_usage()
+}
+
+/// Verify the heap outside of a collection.
+pub fn verify_heap() -> Result<(), VerifyReport> {
+    GLOBAL_ALLOCATOR
+        .lock()
+        .unwrap()
+        .verify(VerifyPhase::OnDemand)
+}
+
+/// Write an hprof heap dump of all objects to the file.
+pub fn dump_heap(path: &Path) -> io::Result<HeapDumpSummary> {
+    GLOBAL_ALLOCATOR.lock().unwrap().dump_heap(path)
+}
+
+/// Pin the object of the size, it is neither evacuated nor reclaimed until it is
+/// unpinned as often as it was pinned. An error if the object is not in the heap.
+pub fn pin_object(object: Address, size: usize) -> Result<(), NotInHeap> {
+    GLOBAL_ALLOCATOR.lock().unwrap().pin_object(object, size)
+}
+
+/// Release one pin of the object, an error if the object is not pinned.
+pub fn unpin_object(object: Address) -> Result<(), NotPinned> {
+    GLOBAL_ALLOCATOR.lock().unwrap().unpin_object(object)
+}
+
+/// The currently pinned objects.
+pub fn pinned_objects() -> Vec<PinnedObject> {
+    GLOBAL_ALLOCATOR.lock().unwrap().pinned_objects()
+}
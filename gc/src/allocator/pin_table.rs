@@ -0,0 +1,174 @@
+//! Objects pinned by native code, e.g. by `GetPrimitiveArrayCritical` or direct buffer
+//! access. A pinned object is never evacuated and its lines stay marked, pins nest so
+//! the object is released when it is unpinned as often as it was pinned.
+//!
+//! An object that stays pinned for `GC_PIN_WARN_THRESHOLD` collections (16 if unset)
+//! is reported once through the gc log, it usually means a missing unpin.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::model::address::Address;
+
+const DEFAULT_WARN_THRESHOLD: usize = 16;
+
+/// A pinned object and how long it has been pinned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinnedObject {
+    /// The pinned object.
+    pub address: Address,
+    /// Size in bytes of the object, its lines stay marked while it is pinned.
+    pub size: usize,
+    /// How often the object is pinned without being unpinned.
+    pub pin_count: usize,
+    /// Number of collections the object stayed pinned.
+    pub collections: usize,
+}
+
+/// An unpin of an object that is not pinned, the pins and unpins of the native code
+/// do not match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotPinned(pub Address);
+
+impl fmt::Display for NotPinned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "object {} is not pinned", self.0)
+    }
+}
+
+/// A pin of an object that is not in the heap, a bad address from the native code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotInHeap(pub Address);
+
+impl fmt::Display for NotInHeap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "object {} is out of heap", self.0)
+    }
+}
+
+pub struct PinTable {
+    pins: BTreeMap<Address, PinnedObject>,
+    warn_threshold: usize,
+}
+
+impl PinTable {
+    pub fn new() -> PinTable {
+        let warn_threshold = std::env::var("GC_PIN_WARN_THRESHOLD")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_WARN_THRESHOLD);
+        PinTable::with_threshold(warn_threshold)
+    }
+
+    fn with_threshold(warn_threshold: usize) -> PinTable {
+        PinTable {
+            pins: BTreeMap::new(),
+            warn_threshold,
+        }
+    }
+
+    /// Pin the object of the size, returns true if it was not pinned before.
+    pub fn pin(&mut self, object: Address, size: usize) -> bool {
+        let entry = self.pins.entry(object).or_insert(PinnedObject {
+            address: object,
+            size,
+            pin_count: 0,
+            collections: 0,
+        });
+        entry.pin_count += 1;
+        entry.pin_count == 1
+    }
+
+    /// Unpin the object, returns true if it is not pinned anymore.
+    pub fn unpin(&mut self, object: Address) -> Result<bool, NotPinned> {
+        let entry = self.pins.get_mut(&object).ok_or(NotPinned(object))?;
+        entry.pin_count -= 1;
+        if entry.pin_count == 0 {
+            self.pins.remove(&object);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    #[cfg(test)]
+    pub fn is_pinned(&self, object: Address) -> bool {
+        self.pins.contains_key(&object)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = Address> + '_ {
+        self.pins.keys().copied()
+    }
+
+    pub fn pinned_objects(&self) -> Vec<PinnedObject> {
+        self.pins.values().copied().collect()
+    }
+
+    /// Count a collection for every pinned object, returns the objects that just
+    /// reached the warning threshold.
+    pub fn survive_collection(&mut self) -> Vec<PinnedObject> {
+        let mut long_pinned = Vec::new();
+        for entry in self.pins.values_mut() {
+            entry.collections += 1;
+            if entry.collections == self.warn_threshold {
+                long_pinned.push(*entry);
+            }
+        }
+        long_pinned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NotPinned, PinTable};
+    use crate::model::address::Address;
+
+    #[test]
+    fn pins_are_nested() {
+        let mut table = PinTable::with_threshold(4);
+        let object = Address::new(0x1000);
+        assert!(table.pin(object, 16));
+        assert!(!table.pin(object, 16));
+        assert_eq!(table.pinned_objects()[0].pin_count, 2);
+        assert_eq!(table.unpin(object), Ok(false));
+        assert!(table.is_pinned(object));
+        assert_eq!(table.unpin(object), Ok(true));
+        assert!(!table.is_pinned(object));
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn long_pinned_objects_are_reported_once() {
+        let mut table = PinTable::with_threshold(2);
+        let first = Address::new(0x1000);
+        let second = Address::new(0x2000);
+        table.pin(first, 16);
+        assert!(table.survive_collection().is_empty());
+        table.pin(second, 16);
+        let reported = table.survive_collection();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].address, first);
+        assert_eq!(reported[0].collections, 2);
+        let reported = table.survive_collection();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].address, second);
+        assert!(table.survive_collection().is_empty());
+    }
+
+    #[test]
+    fn unpin_without_pin_is_an_error() {
+        let mut table = PinTable::with_threshold(1);
+        let object = Address::new(0x1000);
+        let error = table.unpin(object).unwrap_err();
+        assert_eq!(error, NotPinned(object));
+        assert_eq!(
+            error.to_string(),
+            format!("object {} is not pinned", object)
+        );
+        table.pin(object, 16);
+        table.unpin(object).unwrap();
+        assert_eq!(table.unpin(object), Err(NotPinned(object)));
+    }
+}
@@ -0,0 +1,128 @@
+//! Tracing mark of the heap.
+//!
+//! Objects are marked with the mark bit of their `HeapObj` header and traced with an
+//! explicit mark stack. References to addresses outside of the heap and of the large
+//! objects are ignored, they belong to the vm, e.g. klasses. Reference objects are discovered instead of having
+//! their referent traced, see the reference processor.
+use crate::allocator::large_object_space::LargeObjectSpace;
+use crate::model::address::Address;
+use crate::model::heap_obj::HeapObj;
+use crate::model::object_model::ObjectModel;
+use crate::reference::reference_processor::{DiscoveredReference, ReferenceType};
+
+pub struct Marker<'a> {
+    heap_start: Address,
+    heap_end: Address,
+    large_objects: Option<&'a LargeObjectSpace>,
+    model: &'a dyn ObjectModel,
+    mark_stack: Vec<Address>,
+    marked: Vec<Address>,
+    discovered: [Vec<DiscoveredReference>; 4],
+    discovering: bool,
+}
+
+impl<'a> Marker<'a> {
+    pub fn new(heap_start: Address, heap_end: Address, model: &'a dyn ObjectModel) -> Marker<'a> {
+        Marker {
+            heap_start,
+            heap_end,
+            large_objects: None,
+            model,
+            mark_stack: Vec::new(),
+            marked: Vec::new(),
+            discovered: Default::default(),
+            discovering: true,
+        }
+    }
+
+    /// Trace the large objects too.
+    pub fn with_large_objects(mut self, large_objects: &'a LargeObjectSpace) -> Marker<'a> {
+        self.large_objects = Some(large_objects);
+        self
+    }
+
+    fn is_in_heap(&self, address: Address) -> bool {
+        self.heap_start <= address && address < self.heap_end
+            || self
+                .large_objects
+                .is_some_and(|large_objects| large_objects.contains(address))
+    }
+
+    /// Mark the object and push it for tracing, null and out of heap addresses are
+    /// ignored.
+    pub fn mark(&mut self, object: Address) {
+        if !self.is_in_heap(object) {
+            return;
+        }
+        let heap_obj = unsafe { HeapObj::from_address(object) };
+        if heap_obj.is_mark() {
+            return;
+        }
+        heap_obj.set_mark();
+        self.marked.push(object);
+        self.mark_stack.push(object);
+    }
+
+    /// Objects outside of the heap are never collected, so they count as marked.
+    pub fn is_marked(&self, object: Address) -> bool {
+        !self.is_in_heap(object) || unsafe { HeapObj::from_address(object) }.is_mark()
+    }
+
+    /// Trace until the mark stack is empty.
+    pub fn drain(&mut self) {
+        while let Some(object) = self.mark_stack.pop() {
+            let referent_slot = self.discover(object);
+            let mut slots = Vec::new();
+            self.model
+                .for_each_reference(object, &mut |slot| slots.push(slot));
+            for slot in slots {
+                if Some(slot) != referent_slot {
+                    self.mark(slot.load());
+                }
+            }
+        }
+    }
+
+    /// Discover the object if it is a reference with an unmarked referent, returns
+    /// the referent field that must not be traced.
+    fn discover(&mut self, object: Address) -> Option<Address> {
+        if !self.discovering {
+            return None;
+        }
+        let (reference_type, referent_slot) = self.model.as_reference(object)?;
+        let referent: Address = referent_slot.load();
+        if referent.is_null() || self.is_marked(referent) {
+            return None;
+        }
+        self.discovered[reference_type.index()].push(DiscoveredReference {
+            reference: object,
+            referent_slot,
+        });
+        Some(referent_slot)
+    }
+
+    /// Objects traced after this are traced completely, including their referents.
+    pub fn stop_discovery(&mut self) {
+        self.discovering = false;
+    }
+
+    pub fn discovered(&self, reference_type: ReferenceType) -> &[DiscoveredReference] {
+        &self.discovered[reference_type.index()]
+    }
+
+    pub fn take_discovered(&mut self, reference_type: ReferenceType) -> Vec<DiscoveredReference> {
+        std::mem::take(&mut self.discovered[reference_type.index()])
+    }
+
+    pub fn soft_reference_timestamp(&self, reference: Address) -> u64 {
+        self.model.soft_reference_timestamp(reference)
+    }
+
+    /// Clear the mark bits, returns the marked objects.
+    pub fn finish(self) -> Vec<Address> {
+        self.marked
+            .iter()
+            .for_each(|object| unsafe { HeapObj::from_address(*object) }.clear_mark());
+        self.marked
+    }
+}
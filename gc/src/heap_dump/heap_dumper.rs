@@ -0,0 +1,532 @@
+//! Heap dumps in hprof format.
+//!
+//! A dump can be requested at any time and is written automatically on the first
+//! out of memory when enabled with `-XX:+HeapDumpOnOutOfMemoryError`. The dump is
+//! written to `java_pid<pid>.hprof` in the working directory unless
+//! `-XX:HeapDumpPath` names another file or directory.
+//!
+//! The options are read from the `HEAP_DUMP_OPTIONS` environment variable and can be
+//! changed at runtime with [`configure`].
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::model::address::Address;
+use crate::model::object_description::{BasicType, GcRoot, KlassDescription, ObjectDescription};
+use crate::model::object_model::ObjectModel;
+
+use super::hprof_writer::*;
+
+#[derive(Default)]
+struct HeapDumpOptions {
+    on_out_of_memory: bool,
+    path: Option<PathBuf>,
+    dumped: bool,
+}
+
+static OPTIONS: Lazy<Mutex<HeapDumpOptions>> = Lazy::new(|| {
+    let mut options = HeapDumpOptions::default();
+    if let Ok(value) = std::env::var("HEAP_DUMP_OPTIONS") {
+        value
+            .split_whitespace()
+            .for_each(|option| _ = apply_option(&mut options, option));
+    }
+    Mutex::new(options)
+});
+
+/// Apply `-XX:[+-]HeapDumpOnOutOfMemoryError` or `-XX:HeapDumpPath=<path>`.
+///
+/// Returns false if the option is not a heap dump option.
+pub fn configure(option: &str) -> bool {
+    apply_option(&mut OPTIONS.lock().unwrap(), option)
+}
+
+fn apply_option(options: &mut HeapDumpOptions, option: &str) -> bool {
+    match option {
+        "-XX:+HeapDumpOnOutOfMemoryError" => options.on_out_of_memory = true,
+        "-XX:-HeapDumpOnOutOfMemoryError" => options.on_out_of_memory = false,
+        _ => match option.strip_prefix("-XX:HeapDumpPath=") {
+            Some(path) if !path.is_empty() => options.path = Some(PathBuf::from(path)),
+            _ => return false,
+        },
+    }
+    true
+}
+
+/// Where to dump the heap on out of memory, only the first out of memory is dumped.
+pub fn out_of_memory_dump_path() -> Option<PathBuf> {
+    OPTIONS.lock().unwrap().take_dump_path()
+}
+
+impl HeapDumpOptions {
+    fn take_dump_path(&mut self) -> Option<PathBuf> {
+        if !self.on_out_of_memory || self.dumped {
+            return None;
+        }
+        self.dumped = true;
+        let file_name = format!("java_pid{}.hprof", std::process::id());
+        Some(match self.path.as_ref() {
+            Some(path) if path.is_dir() => path.join(file_name),
+            Some(path) => path.clone(),
+            None => PathBuf::from(file_name),
+        })
+    }
+}
+
+/// What was written to a heap dump.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapDumpSummary {
+    /// Objects written as instance or array dumps.
+    pub objects: usize,
+    /// Klasses written as class dumps.
+    pub klasses: usize,
+    /// Roots written.
+    pub roots: usize,
+    /// Objects the vm could not describe.
+    pub skipped: usize,
+}
+
+pub struct HeapDumper<'a> {
+    model: &'a dyn ObjectModel,
+}
+
+impl<'a> HeapDumper<'a> {
+    pub fn new(model: &'a dyn ObjectModel) -> HeapDumper<'a> {
+        HeapDumper { model }
+    }
+
+    pub fn dump<W: Write>(&self, objects: &[Address], out: W) -> io::Result<HeapDumpSummary> {
+        let mut summary = HeapDumpSummary::default();
+        let mut writer = HprofWriter::new(out)?;
+        let mut roots = Vec::new();
+        self.model.for_each_root(&mut |root| roots.push(root));
+        let klasses = self.collect_klasses(objects, &roots);
+        for (serial, (klass, description)) in klasses.iter().enumerate() {
+            writer.write_load_class(serial as u32 + 1, *klass, &description.name)?;
+        }
+        for root in roots.iter() {
+            if let Some(record) = root_record(root) {
+                writer.write_sub_record(&record)?;
+                summary.roots += 1;
+            }
+        }
+        for (klass, description) in klasses.iter() {
+            let record = self.class_record(&mut writer, *klass, description)?;
+            writer.write_sub_record(&record)?;
+        }
+        summary.klasses = klasses.len();
+        for &object in objects {
+            match self.model.describe_object(object) {
+                Some(description) => {
+                    let record = self.object_record(object, description);
+                    writer.write_sub_record(&record)?;
+                    summary.objects += 1;
+                }
+                None => summary.skipped += 1,
+            }
+        }
+        writer.finish()?;
+        Ok(summary)
+    }
+
+    /// The klasses of all objects and roots, and their super klasses.
+    fn collect_klasses(
+        &self,
+        objects: &[Address],
+        roots: &[GcRoot],
+    ) -> BTreeMap<Address, KlassDescription> {
+        let root_klasses = roots.iter().filter_map(|root| match *root {
+            GcRoot::StickyClass(klass) | GcRoot::StaticField { klass, .. } => Some(klass),
+            _ => None,
+        });
+        let object_klasses = objects.iter().map(|&object| self.model.klass(object));
+        let mut klasses = BTreeMap::new();
+        for klass in object_klasses.chain(root_klasses) {
+            let mut klass = klass;
+            while !klass.is_null() && !klasses.contains_key(&klass) {
+                let Some(description) = self.model.describe_klass(klass) else {
+                    break;
+                };
+                let super_klass = description.super_klass;
+                klasses.insert(klass, description);
+                klass = super_klass;
+            }
+        }
+        klasses
+    }
+
+    fn class_record<W: Write>(
+        &self,
+        writer: &mut HprofWriter<W>,
+        klass: Address,
+        description: &KlassDescription,
+    ) -> io::Result<Vec<u8>> {
+        let mut record = Vec::new();
+        put_u1(&mut record, SUB_CLASS_DUMP);
+        put_id(&mut record, klass);
+        put_u4(&mut record, STACK_TRACE_SERIAL);
+        put_id(&mut record, description.super_klass);
+        put_id(&mut record, description.class_loader);
+        // signers, protection domain and two reserved ids
+        (0..4).for_each(|_| put_id(&mut record, Address::zero()));
+        put_u4(&mut record, description.instance_size);
+        put_u2(&mut record, 0);
+        put_u2(&mut record, description.static_fields.len() as u16);
+        for (name, value) in description.static_fields.iter() {
+            put_id(&mut record, Address::new(writer.string_id(name)? as usize));
+            put_type(&mut record, value.basic_type());
+            put_value(&mut record, value);
+        }
+        put_u2(&mut record, description.instance_fields.len() as u16);
+        for (name, basic_type) in description.instance_fields.iter() {
+            put_id(&mut record, Address::new(writer.string_id(name)? as usize));
+            put_type(&mut record, *basic_type);
+        }
+        Ok(record)
+    }
+
+    fn object_record(&self, object: Address, description: ObjectDescription) -> Vec<u8> {
+        let mut record = Vec::new();
+        match description {
+            ObjectDescription::Instance(fields) => {
+                let mut values = Vec::new();
+                fields
+                    .iter()
+                    .for_each(|value| put_value(&mut values, value));
+                put_u1(&mut record, SUB_INSTANCE_DUMP);
+                put_id(&mut record, object);
+                put_u4(&mut record, STACK_TRACE_SERIAL);
+                put_id(&mut record, self.model.klass(object));
+                put_u4(&mut record, values.len() as u32);
+                record.extend_from_slice(&values);
+            }
+            ObjectDescription::ObjectArray(elements) => {
+                put_u1(&mut record, SUB_OBJ_ARRAY_DUMP);
+                put_id(&mut record, object);
+                put_u4(&mut record, STACK_TRACE_SERIAL);
+                put_u4(&mut record, elements.len() as u32);
+                put_id(&mut record, self.model.klass(object));
+                elements
+                    .iter()
+                    .for_each(|&element| put_id(&mut record, element));
+            }
+            ObjectDescription::PrimitiveArray(element_type, elements) => {
+                assert!(
+                    element_type != BasicType::Object,
+                    "primitive array of references."
+                );
+                put_u1(&mut record, SUB_PRIM_ARRAY_DUMP);
+                put_id(&mut record, object);
+                put_u4(&mut record, STACK_TRACE_SERIAL);
+                put_u4(&mut record, elements.len() as u32);
+                put_type(&mut record, element_type);
+                elements
+                    .iter()
+                    .for_each(|element| put_value(&mut record, element));
+            }
+        }
+        record
+    }
+}
+
+/// The root sub record, none for a static field which is written with the class dump
+/// of its klass.
+fn root_record(root: &GcRoot) -> Option<Vec<u8>> {
+    let mut record = Vec::new();
+    match *root {
+        GcRoot::Unknown(object) => {
+            put_u1(&mut record, SUB_ROOT_UNKNOWN);
+            put_id(&mut record, object);
+        }
+        GcRoot::JniGlobal(object) => {
+            put_u1(&mut record, SUB_ROOT_JNI_GLOBAL);
+            put_id(&mut record, object);
+            put_id(&mut record, object);
+        }
+        GcRoot::JavaFrame {
+            object,
+            thread,
+            frame,
+        } => {
+            put_u1(&mut record, SUB_ROOT_JAVA_FRAME);
+            put_id(&mut record, object);
+            put_u4(&mut record, thread);
+            put_u4(&mut record, frame);
+        }
+        GcRoot::StickyClass(klass) => {
+            put_u1(&mut record, SUB_ROOT_STICKY_CLASS);
+            put_id(&mut record, klass);
+        }
+        GcRoot::ThreadObject { object, thread } => {
+            put_u1(&mut record, SUB_ROOT_THREAD_OBJECT);
+            put_id(&mut record, object);
+            put_u4(&mut record, thread);
+            put_u4(&mut record, STACK_TRACE_SERIAL);
+        }
+        GcRoot::StaticField { .. } => return None,
+    }
+    Some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use super::{apply_option, HeapDumpOptions, HeapDumper};
+    use crate::heap_dump::hprof_writer::*;
+    use crate::model::address::Address;
+    use crate::model::object_description::{
+        BasicType, GcRoot, KlassDescription, ObjectDescription, Value,
+    };
+    use crate::model::object_model::ObjectModel;
+
+    const OBJECT: Address = Address::new(0x10);
+    const POINT: Address = Address::new(0x100);
+    const INTS: Address = Address::new(0x200);
+    const POINTS: Address = Address::new(0x300);
+
+    struct TestModel {
+        klasses: BTreeMap<Address, Address>,
+    }
+
+    impl TestModel {
+        fn new() -> TestModel {
+            let mut klasses = BTreeMap::new();
+            klasses.insert(Address::new(0x1000), POINT);
+            klasses.insert(Address::new(0x2000), INTS);
+            klasses.insert(Address::new(0x3000), POINTS);
+            klasses.insert(Address::new(0x4000), Address::new(0x5000));
+            TestModel { klasses }
+        }
+    }
+
+    impl ObjectModel for TestModel {
+        fn object_size(&self, _object: Address) -> usize {
+            32
+        }
+
+        fn klass(&self, object: Address) -> Address {
+            self.klasses[&object]
+        }
+
+        fn is_klass(&self, _klass: Address) -> bool {
+            true
+        }
+
+        fn for_each_reference(&self, _object: Address, _visitor: &mut dyn FnMut(Address)) {}
+
+        fn for_each_root(&self, visitor: &mut dyn FnMut(GcRoot)) {
+            visitor(GcRoot::StickyClass(POINT));
+            visitor(GcRoot::StaticField {
+                object: Address::new(0x1000),
+                klass: POINT,
+            });
+            visitor(GcRoot::JavaFrame {
+                object: Address::new(0x1000),
+                thread: 1,
+                frame: 0,
+            });
+        }
+
+        fn describe_object(&self, object: Address) -> Option<ObjectDescription> {
+            match object.to_usize() {
+                0x1000 => Some(ObjectDescription::Instance(vec![
+                    Value::Int(3),
+                    Value::Int(4),
+                ])),
+                0x2000 => Some(ObjectDescription::PrimitiveArray(
+                    BasicType::Int,
+                    vec![Value::Int(1), Value::Int(2), Value::Int(3)],
+                )),
+                0x3000 => Some(ObjectDescription::ObjectArray(vec![
+                    Address::new(0x1000),
+                    Address::zero(),
+                ])),
+                _ => None,
+            }
+        }
+
+        fn describe_klass(&self, klass: Address) -> Option<KlassDescription> {
+            let (name, super_klass, instance_fields) = match klass {
+                OBJECT => ("java/lang/Object", Address::zero(), vec![]),
+                POINT => (
+                    "Point",
+                    OBJECT,
+                    vec![
+                        ("x".to_string(), BasicType::Int),
+                        ("y".to_string(), BasicType::Int),
+                    ],
+                ),
+                INTS => ("[I", OBJECT, vec![]),
+                POINTS => ("[LPoint;", OBJECT, vec![]),
+                _ => return None,
+            };
+            Some(KlassDescription {
+                name: name.to_string(),
+                super_klass,
+                class_loader: Address::zero(),
+                instance_size: 8,
+                static_fields: vec![("ORIGIN".to_string(), Value::Object(Address::zero()))],
+                instance_fields,
+            })
+        }
+    }
+
+    fn u4(bytes: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Split the top level records into tags and bodies.
+    fn records(bytes: &[u8]) -> Vec<(u8, &[u8])> {
+        let mut records = Vec::new();
+        let mut index = b"JAVA PROFILE 1.0.2\0".len() + 4 + 8;
+        while index < bytes.len() {
+            let length = u4(bytes, index + 5) as usize;
+            records.push((bytes[index], &bytes[index + 9..index + 9 + length]));
+            index += 9 + length;
+        }
+        records
+    }
+
+    #[test]
+    fn heap_is_dumped_as_hprof_records() {
+        let model = TestModel::new();
+        let objects = [
+            Address::new(0x1000),
+            Address::new(0x2000),
+            Address::new(0x3000),
+            Address::new(0x4000),
+        ];
+        let mut bytes = Vec::new();
+        let summary = HeapDumper::new(&model).dump(&objects, &mut bytes).unwrap();
+        assert_eq!(summary.objects, 3);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.klasses, 4);
+        assert_eq!(summary.roots, 2);
+        assert!(bytes.starts_with(b"JAVA PROFILE 1.0.2\0"));
+        assert_eq!(u4(&bytes, 19), 8);
+
+        let records = records(&bytes);
+        let tags: Vec<u8> = records.iter().map(|(tag, _)| *tag).collect();
+        let load_classes = tags.iter().filter(|&&tag| tag == TAG_LOAD_CLASS).count();
+        assert_eq!(load_classes, 4);
+        assert_eq!(tags[0], TAG_STACK_TRACE);
+        assert_eq!(tags[tags.len() - 2], TAG_HEAP_DUMP_SEGMENT);
+        assert_eq!(tags[tags.len() - 1], TAG_HEAP_DUMP_END);
+        let names: Vec<&[u8]> = records
+            .iter()
+            .filter(|(tag, _)| *tag == TAG_UTF8)
+            .map(|(_, body)| &body[8..])
+            .collect();
+        assert!(names.contains(&b"[LPoint;".as_slice()));
+        assert!(names.contains(&b"ORIGIN".as_slice()));
+
+        let segment = records[records.len() - 2].1;
+        let sub_records = sub_records(segment);
+        let tags: Vec<u8> = sub_records.iter().map(|(tag, _)| *tag).collect();
+        assert_eq!(
+            tags,
+            vec![
+                SUB_ROOT_STICKY_CLASS,
+                SUB_ROOT_JAVA_FRAME,
+                SUB_CLASS_DUMP,
+                SUB_CLASS_DUMP,
+                SUB_CLASS_DUMP,
+                SUB_CLASS_DUMP,
+                SUB_INSTANCE_DUMP,
+                SUB_PRIM_ARRAY_DUMP,
+                SUB_OBJ_ARRAY_DUMP,
+            ]
+        );
+        let point = sub_records[3].1;
+        assert_eq!(&point[..8], &0x100usize.to_be_bytes());
+        assert_eq!(&point[12..20], &0x10usize.to_be_bytes());
+        let instance = sub_records[6].1;
+        assert_eq!(u4(instance, 20), 8);
+        assert_eq!(u4(instance, 24), 3);
+        assert_eq!(u4(instance, 28), 4);
+        let ints = sub_records[7].1;
+        assert_eq!(u4(ints, 12), 3);
+        assert_eq!(ints[16], BasicType::Int as u8);
+        assert_eq!(u4(ints, 25), 3);
+        let points = sub_records[8].1;
+        assert_eq!(u4(points, 12), 2);
+        assert_eq!(&points[16..24], &0x300usize.to_be_bytes());
+    }
+
+    /// Split a heap dump segment into sub record tags and bodies.
+    fn sub_records(segment: &[u8]) -> Vec<(u8, &[u8])> {
+        let type_size = |tag: u8| match tag {
+            2 => 8,
+            4 | 8 => 1,
+            5 | 9 => 2,
+            6 | 10 => 4,
+            _ => 8,
+        };
+        let u2 = |at: usize| u16::from_be_bytes([segment[at], segment[at + 1]]) as usize;
+        let mut sub_records = Vec::new();
+        let mut index = 0;
+        while index < segment.len() {
+            let tag = segment[index];
+            let body = index + 1;
+            let length = match tag {
+                SUB_ROOT_STICKY_CLASS => 8,
+                SUB_ROOT_JAVA_FRAME => 16,
+                SUB_CLASS_DUMP => {
+                    let mut at = body + 68;
+                    for _ in 0..u2(body + 66) {
+                        at += 8 + 1 + type_size(segment[at + 8]);
+                    }
+                    at += 2 + u2(at) * 9;
+                    at - body
+                }
+                SUB_INSTANCE_DUMP => 24 + u4(segment, body + 20) as usize,
+                SUB_OBJ_ARRAY_DUMP => 24 + u4(segment, body + 12) as usize * 8,
+                SUB_PRIM_ARRAY_DUMP => {
+                    17 + u4(segment, body + 12) as usize * type_size(segment[body + 16])
+                }
+                _ => panic!("unexpected sub record {:#x}", tag),
+            };
+            sub_records.push((tag, &segment[body..body + length]));
+            index = body + length;
+        }
+        sub_records
+    }
+
+    #[test]
+    fn options_are_parsed_like_xx_flags() {
+        let mut options = HeapDumpOptions::default();
+        assert!(apply_option(
+            &mut options,
+            "-XX:+HeapDumpOnOutOfMemoryError"
+        ));
+        assert!(options.on_out_of_memory);
+        assert!(apply_option(&mut options, "-XX:HeapDumpPath=/tmp/dumps"));
+        assert_eq!(
+            options.path.as_deref(),
+            Some(std::path::Path::new("/tmp/dumps"))
+        );
+        assert!(apply_option(
+            &mut options,
+            "-XX:-HeapDumpOnOutOfMemoryError"
+        ));
+        assert!(!options.on_out_of_memory);
+        assert!(!apply_option(&mut options, "-XX:HeapDumpPath="));
+        assert!(!apply_option(&mut options, "-Xmx1g"));
+    }
+
+    #[test]
+    fn only_the_first_out_of_memory_is_dumped() {
+        let mut options = HeapDumpOptions::default();
+        assert_eq!(options.take_dump_path(), None);
+        apply_option(&mut options, "-XX:+HeapDumpOnOutOfMemoryError");
+        apply_option(&mut options, "-XX:HeapDumpPath=/tmp");
+        let expected = format!("/tmp/java_pid{}.hprof", std::process::id());
+        assert_eq!(options.take_dump_path(), Some(PathBuf::from(expected)));
+        assert_eq!(options.take_dump_path(), None);
+    }
+}
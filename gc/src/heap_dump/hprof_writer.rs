@@ -0,0 +1,155 @@
+//! Writer for the binary hprof format (`JAVA PROFILE 1.0.2`).
+//!
+//! All numbers are written in big endian and identifiers are the size of an
+//! address. Heap content is written as heap dump segments that are flushed when
+//! they grow over `SEGMENT_LIMIT`.
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::model::address::Address;
+use crate::model::object_description::{BasicType, Value};
+
+const HPROF_HEADER: &[u8] = b"JAVA PROFILE 1.0.2\0";
+const SEGMENT_LIMIT: usize = 1024 * 1024;
+
+pub const TAG_UTF8: u8 = 0x01;
+pub const TAG_LOAD_CLASS: u8 = 0x02;
+pub const TAG_STACK_TRACE: u8 = 0x05;
+pub const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1C;
+pub const TAG_HEAP_DUMP_END: u8 = 0x2C;
+
+pub const SUB_ROOT_UNKNOWN: u8 = 0xFF;
+pub const SUB_ROOT_JNI_GLOBAL: u8 = 0x01;
+pub const SUB_ROOT_JAVA_FRAME: u8 = 0x03;
+pub const SUB_ROOT_STICKY_CLASS: u8 = 0x05;
+pub const SUB_ROOT_THREAD_OBJECT: u8 = 0x08;
+pub const SUB_CLASS_DUMP: u8 = 0x20;
+pub const SUB_INSTANCE_DUMP: u8 = 0x21;
+pub const SUB_OBJ_ARRAY_DUMP: u8 = 0x22;
+pub const SUB_PRIM_ARRAY_DUMP: u8 = 0x23;
+
+/// Serial number of the empty stack trace every object refers to.
+pub const STACK_TRACE_SERIAL: u32 = 1;
+
+pub struct HprofWriter<W: Write> {
+    out: W,
+    segment: Vec<u8>,
+    strings: HashMap<String, u64>,
+}
+
+impl<W: Write> HprofWriter<W> {
+    pub fn new(mut out: W) -> io::Result<HprofWriter<W>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or(0);
+        out.write_all(HPROF_HEADER)?;
+        out.write_all(&(size_of::<Address>() as u32).to_be_bytes())?;
+        out.write_all(&timestamp.to_be_bytes())?;
+        let mut writer = HprofWriter {
+            out,
+            segment: Vec::new(),
+            strings: HashMap::new(),
+        };
+        let mut trace = Vec::new();
+        put_u4(&mut trace, STACK_TRACE_SERIAL);
+        put_u4(&mut trace, 0);
+        put_u4(&mut trace, 0);
+        writer.write_record(TAG_STACK_TRACE, &trace)?;
+        Ok(writer)
+    }
+
+    /// The identifier of the string, the utf8 record is written on first use.
+    pub fn string_id(&mut self, string: &str) -> io::Result<u64> {
+        if let Some(&id) = self.strings.get(string) {
+            return Ok(id);
+        }
+        let id = self.strings.len() as u64 + 1;
+        let mut body = Vec::with_capacity(string.len() + size_of::<u64>());
+        put_id(&mut body, Address::new(id as usize));
+        body.extend_from_slice(string.as_bytes());
+        self.write_record(TAG_UTF8, &body)?;
+        self.strings.insert(string.to_string(), id);
+        Ok(id)
+    }
+
+    pub fn write_load_class(&mut self, serial: u32, klass: Address, name: &str) -> io::Result<()> {
+        let name_id = self.string_id(name)?;
+        let mut body = Vec::new();
+        put_u4(&mut body, serial);
+        put_id(&mut body, klass);
+        put_u4(&mut body, STACK_TRACE_SERIAL);
+        put_id(&mut body, Address::new(name_id as usize));
+        self.write_record(TAG_LOAD_CLASS, &body)
+    }
+
+    /// Append a sub record to the current heap dump segment.
+    pub fn write_sub_record(&mut self, body: &[u8]) -> io::Result<()> {
+        if !self.segment.is_empty() && self.segment.len() + body.len() > SEGMENT_LIMIT {
+            self.flush_segment()?;
+        }
+        self.segment.extend_from_slice(body);
+        Ok(())
+    }
+
+    /// Write the pending segment and the end of the heap dump.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_segment()?;
+        self.write_record(TAG_HEAP_DUMP_END, &[])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn flush_segment(&mut self) -> io::Result<()> {
+        if self.segment.is_empty() {
+            return Ok(());
+        }
+        let segment = std::mem::take(&mut self.segment);
+        self.write_record(TAG_HEAP_DUMP_SEGMENT, &segment)
+    }
+
+    fn write_record(&mut self, tag: u8, body: &[u8]) -> io::Result<()> {
+        let length = u32::try_from(body.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "hprof record too large"))?;
+        self.out.write_all(&[tag])?;
+        self.out.write_all(&0u32.to_be_bytes())?;
+        self.out.write_all(&length.to_be_bytes())?;
+        self.out.write_all(body)
+    }
+}
+
+pub fn put_u1(buf: &mut Vec<u8>, value: u8) {
+    buf.push(value);
+}
+
+pub fn put_u2(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn put_u4(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn put_id(buf: &mut Vec<u8>, id: Address) {
+    buf.extend_from_slice(&id.to_usize().to_be_bytes());
+}
+
+pub fn put_value(buf: &mut Vec<u8>, value: &Value) {
+    match *value {
+        Value::Object(address) => put_id(buf, address),
+        Value::Boolean(value) => buf.push(value as u8),
+        Value::Char(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Float(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Double(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Byte(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Short(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Int(value) => buf.extend_from_slice(&value.to_be_bytes()),
+        Value::Long(value) => buf.extend_from_slice(&value.to_be_bytes()),
+    }
+}
+
+pub fn put_type(buf: &mut Vec<u8>, basic_type: BasicType) {
+    buf.push(basic_type as u8);
+}
@@ -0,0 +1,2 @@
+pub mod heap_dumper;
+pub mod hprof_writer;
@@ -6,5 +6,31 @@
 #![warn(missing_docs)]
 
 mod allocator;
+mod collector;
+mod heap_dump;
 mod model;
+mod reference;
+mod stats;
 mod utils;
+mod verifier;
+
+pub use allocator::pin_table::{NotInHeap, NotPinned, PinnedObject};
+pub use allocator::{
+    allocate, collect, dump_heap, gc_records, gc_summary, heap_usage, pin_object, pinned_objects,
+    unpin_object, verify_heap,
+};
+pub use heap_dump::heap_dumper::configure as configure_heap_dump;
+pub use heap_dump::heap_dumper::HeapDumpSummary;
+pub use model::address::Address;
+pub use model::object_description::{
+    BasicType, GcRoot, KlassDescription, ObjectDescription, Value,
+};
+pub use model::object_model::{set_object_model, ObjectModel};
+pub use reference::reference_handler::start as start_reference_handler;
+pub use reference::reference_handler::ReferenceHandler;
+pub use reference::reference_processor::{soft_reference_clock, ReferenceCounts, ReferenceType};
+pub use stats::gc_log::configure as configure_gc_log;
+pub use stats::gc_record::{BlockCounts, GcCause, GcRecord};
+pub use stats::gc_stats::{GcSummary, HeapUsage};
+pub use verifier::heap_verifier::configure as configure_verify_heap;
+pub use verifier::heap_verifier::{VerifyError, VerifyPhase, VerifyReport};
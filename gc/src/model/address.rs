@@ -1,31 +1,38 @@
+/// A raw address in the heap or in memory managed by the collector.
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
 pub struct Address(usize);
 
 impl Address {
-    pub fn new(address: usize) -> Address {
+    /// Wrap a raw address.
+    pub const fn new(address: usize) -> Address {
         Address(address)
     }
 
+    /// The address `offset` bytes above.
     #[inline(always)]
     pub fn plus(&self, offset: usize) -> Address {
         Address(self.0 + offset)
     }
 
+    /// The address `offset` bytes below.
     #[inline(always)]
     pub fn minus(&self, offset: usize) -> Address {
         Address(self.0 - offset)
     }
 
+    /// Number of bytes from `other` up to this address.
     #[inline(always)]
     pub fn diff(&self, other: Address) -> usize {
         self.0 - other.0
     }
 
+    /// The raw address.
     #[inline(always)]
     pub fn to_usize(&self) -> usize {
         self.0
     }
 
+    /// Write a value at this address, the address must be valid and aligned for `T`.
     #[inline(always)]
     pub fn store<T>(&self, value: T) {
         unsafe {
@@ -33,16 +40,31 @@ impl Address {
         }
     }
 
+    /// Read a value at this address, the address must be valid and aligned for `T`.
     #[inline(always)]
     pub fn load<T>(&self) -> T {
         unsafe { std::ptr::read(self.0 as *const T) }
     }
 
+    /// The address as a raw pointer.
+    #[inline(always)]
+    pub fn as_ptr<T>(&self) -> *const T {
+        self.0 as *const T
+    }
+
+    /// The address as a raw mutable pointer.
+    #[inline(always)]
+    pub fn as_mut_ptr<T>(&self) -> *mut T {
+        self.0 as *mut T
+    }
+
+    /// The null address.
     #[inline(always)]
     pub fn zero() -> Address {
         Address(0)
     }
 
+    /// Whether this is the null address.
     #[inline(always)]
     pub fn is_null(&self) -> bool {
         self.0 == 0
@@ -56,3 +78,9 @@ impl std::ops::Add<usize> for Address {
         Address(self.0 + rhs)
     }
 }
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
@@ -3,10 +3,11 @@ use std::ops::{Index, IndexMut};
 use crate::align_up;
 
 use super::address::Address;
+use super::object_model::ObjectModel;
 
 pub const BLOCK_SIZE: usize = 32 * 1024;
 pub const LINE_SIZE: usize = 128;
-const LINE_COUNT: usize = BLOCK_SIZE / LINE_SIZE;
+pub const LINE_COUNT: usize = BLOCK_SIZE / LINE_SIZE;
 
 pub struct Block {
     block_mark: BlockMark,
@@ -19,7 +20,7 @@ pub struct LineMarks {
 }
 
 #[repr(u8)]
-#[derive(PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum BlockMark {
     Free,
     Unavailable,
@@ -27,7 +28,7 @@ pub enum BlockMark {
 }
 
 #[repr(u8)]
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum LineMark {
     Free,
     Live,
@@ -58,6 +59,34 @@ impl Block {
         self.line_marks[index] = LineMark::Live;
     }
 
+    /// Index of the line that contains the address.
+    #[inline(always)]
+    pub fn line_index(&self, address: Address) -> usize {
+        assert!(self.contains(address), "address is out of block.");
+        address.diff(self.base) / LINE_SIZE
+    }
+
+    #[inline(always)]
+    pub fn line_address(&self, index: usize) -> Address {
+        assert!(index < LINE_COUNT, "invalid line index.");
+        self.base.plus(index * LINE_SIZE)
+    }
+
+    #[inline(always)]
+    pub fn line_mark(&self, index: usize) -> LineMark {
+        self.line_marks[index]
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, address: Address) -> bool {
+        self.base <= address && address < self.block_limit()
+    }
+
+    #[inline(always)]
+    pub fn block_mark(&self) -> BlockMark {
+        self.block_mark
+    }
+
     #[inline(always)]
     pub fn is_free(&self) -> bool {
         self.block_mark == BlockMark::Free
@@ -73,13 +102,19 @@ impl Block {
         self.block_mark == BlockMark::Unavailable
     }
 
+    /// Free all lines, the mark sets the lines of the objects it finds again.
+    pub fn clear_line_marks(&mut self) {
+        (0..LINE_COUNT).for_each(|i| self.line_marks.mark_line(i, LineMark::Free));
+    }
+
+    /// Mark every line that overlaps with `[start, end)`.
     pub fn mark_lines(&mut self, start: Address, end: Address) {
         assert!(
-            start > self.base && end <= self.base.plus(BLOCK_SIZE),
+            start >= self.base && start < end && end <= self.block_limit(),
             "invalid address range."
         );
-        let start_index = start.diff(self.base) / LINE_SIZE;
-        let end_index = end.diff(self.base) / LINE_SIZE;
+        let start_index = self.line_index(start);
+        let end_index = align_up!(end.diff(self.base), LINE_SIZE) / LINE_SIZE;
         (start_index..end_index).for_each(|i| self.mark_line(i));
     }
 
@@ -93,6 +128,37 @@ impl Block {
         self.base.plus(BLOCK_SIZE)
     }
 
+    /// Set the block mark from the line marks, returns the number of live lines.
+    pub fn sweep(&mut self) -> usize {
+        let live_lines = (0..LINE_COUNT)
+            .filter(|&index| self.line_marks[index] == LineMark::Live)
+            .count();
+        if live_lines == 0 {
+            self.mark_free();
+        } else if live_lines == LINE_COUNT {
+            self.mark_unavailable();
+        } else {
+            self.mark_recyclable();
+        }
+        live_lines
+    }
+
+    /// Visit the objects in the live lines, objects are allocated at line granularity
+    /// so each live line that is not covered by the previous object starts a new one.
+    pub fn for_each_object(&self, model: &dyn ObjectModel, visitor: &mut dyn FnMut(Address)) {
+        let mut index = 0;
+        while index < LINE_COUNT {
+            if self.line_marks[index] == LineMark::Free {
+                index += 1;
+                continue;
+            }
+            let object = self.line_address(index);
+            visitor(object);
+            let lines = align_up!(model.object_size(object), LINE_SIZE) / LINE_SIZE;
+            index += lines.max(1);
+        }
+    }
+
     pub fn find_next_hole(&self) -> Option<(Address, Address)> {
         todo!("find next hole")
     }
@@ -104,9 +170,9 @@ impl Block {
             if self.line_marks[index] == LineMark::Free {
                 available_lines += 1;
                 if available_lines == lines {
-                    let start = index - lines;
-                    (start..index).for_each(|i| self.mark_line(i));
-                    return Some(self.base.plus(start * LINE_SIZE));
+                    let start = index + 1 - lines;
+                    (start..=index).for_each(|i| self.mark_line(i));
+                    return Some(self.line_address(start));
                 }
             } else {
                 available_lines = 0;
@@ -133,13 +199,13 @@ impl Index<usize> for LineMarks {
 
     fn index(&self, index: usize) -> &Self::Output {
         assert!(index < LINE_COUNT, "invalid line index.");
-        self.base.plus(index).load()
+        unsafe { &*self.base.plus(index).as_ptr::<LineMark>() }
     }
 }
 
 impl IndexMut<usize> for LineMarks {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         assert!(index < LINE_COUNT, "invalid line index.");
-        self.base.plus(index).load()
+        unsafe { &mut *self.base.plus(index).as_mut_ptr::<LineMark>() }
     }
 }
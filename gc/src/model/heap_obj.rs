@@ -12,35 +12,78 @@
 //! # forwarding_pointer
 //! The address of the forwarding pointer that the object moved to and any reference to this location
 //! will be replaced with this forwarding pointer address.
+//!
+//! The vm places its own object layout right after this header.
 use super::address::Address;
+
+#[repr(C)]
 pub struct HeapObj {
     header: u8,
     forwarding_pointer: Address,
 }
 
 impl HeapObj {
-    fn is_mark(&self) -> bool {
-        self.header & 0b0000_0001 == 1
+    const MARK_BIT: u8 = 0b0000_0001;
+    const MEDIUM_BIT: u8 = 0b0000_0010;
+    const PINNED_BIT: u8 = 0b0000_0100;
+    const FORWARDING_BIT: u8 = 0b0000_1000;
+    const USED_BITS: u8 =
+        HeapObj::MARK_BIT | HeapObj::MEDIUM_BIT | HeapObj::PINNED_BIT | HeapObj::FORWARDING_BIT;
+
+    /// # Safety
+    /// The address must be the start of an object in the heap.
+    pub unsafe fn from_address<'a>(address: Address) -> &'a mut HeapObj {
+        &mut *address.as_mut_ptr::<HeapObj>()
+    }
+
+    pub fn header(&self) -> u8 {
+        self.header
+    }
+
+    /// Whether only the known header bits are set.
+    pub fn has_valid_header(&self) -> bool {
+        self.header & !HeapObj::USED_BITS == 0
+    }
+
+    pub fn is_mark(&self) -> bool {
+        self.header & HeapObj::MARK_BIT != 0
     }
 
-    fn set_mark(&mut self) {
-        self.header |= 0b0000_0001;
+    pub fn set_mark(&mut self) {
+        self.header |= HeapObj::MARK_BIT;
+    }
+
+    pub fn clear_mark(&mut self) {
+        self.header &= !HeapObj::MARK_BIT;
     }
 
     fn is_medium(&self) -> bool {
-        self.header & 0b0000_0010 == 1
+        self.header & HeapObj::MEDIUM_BIT != 0
     }
 
     fn set_medium(&mut self) {
-        self.header |= 0b0000_0010;
+        self.header |= HeapObj::MEDIUM_BIT;
+    }
+
+    /// A pinned object must not be moved by evacuation.
+    pub fn is_pinned(&self) -> bool {
+        self.header & HeapObj::PINNED_BIT != 0
+    }
+
+    pub fn set_pinned(&mut self) {
+        self.header |= HeapObj::PINNED_BIT;
+    }
+
+    pub fn clear_pinned(&mut self) {
+        self.header &= !HeapObj::PINNED_BIT;
     }
 
-    fn is_forwarding(&self) -> bool {
-        self.header & 0b0000_1000 == 1
+    pub fn is_forwarding(&self) -> bool {
+        self.header & HeapObj::FORWARDING_BIT != 0
     }
 
     fn set_forwarding(&mut self) {
-        self.header |= 0b0000_1000;
+        self.header |= HeapObj::FORWARDING_BIT;
     }
 
     fn get_forwarding_pointer(&self) -> Address {
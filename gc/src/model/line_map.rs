@@ -1,6 +1,6 @@
 use std::{mem::size_of, ops::Add};
 
-use super::{address::Address, block::LINE_SIZE};
+use super::{address::Address, block::LINE_COUNT};
 
 pub struct LineMap {
     base: Address,
@@ -9,7 +9,8 @@ pub struct LineMap {
 
 impl LineMap {
     pub fn new(line_size: usize) -> LineMap {
-        let base = unsafe { libc::malloc(line_size * size_of::<u8>()) };
+        let base = unsafe { libc::calloc(line_size, size_of::<u8>()) };
+        assert!(!base.is_null(), "fail to allocate line map.");
         LineMap {
             base: Address::new(base as usize),
             len: line_size,
@@ -17,7 +18,8 @@ impl LineMap {
     }
 
     pub fn block_line_marks(&self, index: usize) -> Address {
-        self.base.add(index * size_of::<u8>() * LINE_SIZE)
+        assert!((index + 1) * LINE_COUNT <= self.len, "invalid block index.");
+        self.base.add(index * size_of::<u8>() * LINE_COUNT)
     }
 }
 
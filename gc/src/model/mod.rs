@@ -3,3 +3,5 @@ pub mod block;
 pub mod heap_obj;
 pub mod layout;
 pub mod line_map;
+pub mod object_description;
+pub mod object_model;
@@ -0,0 +1,157 @@
+//! What the vm tells the collector about objects, klasses and roots when the heap
+//! has to be presented outside of the vm, e.g. in a heap dump.
+use super::address::Address;
+
+/// Java basic types, the values are the type tags used by hprof.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BasicType {
+    /// A reference.
+    Object = 2,
+    /// `boolean`
+    Boolean = 4,
+    /// `char`
+    Char = 5,
+    /// `float`
+    Float = 6,
+    /// `double`
+    Double = 7,
+    /// `byte`
+    Byte = 8,
+    /// `short`
+    Short = 9,
+    /// `int`
+    Int = 10,
+    /// `long`
+    Long = 11,
+}
+
+impl BasicType {
+    /// Size in bytes of a value of this type, references use the address size.
+    pub fn size(&self) -> usize {
+        match self {
+            BasicType::Object => std::mem::size_of::<Address>(),
+            BasicType::Boolean | BasicType::Byte => 1,
+            BasicType::Char | BasicType::Short => 2,
+            BasicType::Float | BasicType::Int => 4,
+            BasicType::Double | BasicType::Long => 8,
+        }
+    }
+}
+
+/// A typed field or array element value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    /// A reference, null is the zero address.
+    Object(Address),
+    /// `boolean`
+    Boolean(bool),
+    /// `char`
+    Char(u16),
+    /// `float`
+    Float(f32),
+    /// `double`
+    Double(f64),
+    /// `byte`
+    Byte(i8),
+    /// `short`
+    Short(i16),
+    /// `int`
+    Int(i32),
+    /// `long`
+    Long(i64),
+}
+
+impl Value {
+    /// The type of the value.
+    pub fn basic_type(&self) -> BasicType {
+        match self {
+            Value::Object(_) => BasicType::Object,
+            Value::Boolean(_) => BasicType::Boolean,
+            Value::Char(_) => BasicType::Char,
+            Value::Float(_) => BasicType::Float,
+            Value::Double(_) => BasicType::Double,
+            Value::Byte(_) => BasicType::Byte,
+            Value::Short(_) => BasicType::Short,
+            Value::Int(_) => BasicType::Int,
+            Value::Long(_) => BasicType::Long,
+        }
+    }
+}
+
+/// The content of an object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectDescription {
+    /// An ordinary object, the field values are ordered like the instance fields of
+    /// its klass followed by the instance fields of each super klass.
+    Instance(Vec<Value>),
+    /// An array of references.
+    ObjectArray(Vec<Address>),
+    /// An array of primitive values of the same type.
+    PrimitiveArray(BasicType, Vec<Value>),
+}
+
+/// A loaded class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KlassDescription {
+    /// The internal name, e.g. `java/lang/String` or `[I`.
+    pub name: String,
+    /// The super klass, zero for `java/lang/Object` and primitive arrays.
+    pub super_klass: Address,
+    /// The mirror of the defining class loader, zero for the boot loader.
+    pub class_loader: Address,
+    /// Size in bytes of an instance, zero for arrays.
+    pub instance_size: u32,
+    /// Static fields and their values.
+    pub static_fields: Vec<(String, Value)>,
+    /// Instance fields declared by the klass itself.
+    pub instance_fields: Vec<(String, BasicType)>,
+}
+
+/// A reference held outside of the heap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcRoot {
+    /// A root of unknown origin.
+    Unknown(Address),
+    /// A jni global reference.
+    JniGlobal(Address),
+    /// A local variable or operand of a java frame.
+    JavaFrame {
+        /// The referenced object.
+        object: Address,
+        /// Serial number of the thread.
+        thread: u32,
+        /// Depth of the frame in the stack.
+        frame: u32,
+    },
+    /// A class that is never unloaded.
+    StickyClass(Address),
+    /// An object referenced by a static field.
+    StaticField {
+        /// The referenced object.
+        object: Address,
+        /// The klass declaring the field.
+        klass: Address,
+    },
+    /// A started `java.lang.Thread`.
+    ThreadObject {
+        /// The thread object.
+        object: Address,
+        /// Serial number of the thread.
+        thread: u32,
+    },
+}
+
+impl GcRoot {
+    /// The referenced object, or klass for a sticky class.
+    pub fn object(&self) -> Address {
+        match *self {
+            GcRoot::Unknown(object)
+            | GcRoot::JniGlobal(object)
+            | GcRoot::JavaFrame { object, .. }
+            | GcRoot::StickyClass(object)
+            | GcRoot::StaticField { object, .. }
+            | GcRoot::ThreadObject { object, .. } => object,
+        }
+    }
+}
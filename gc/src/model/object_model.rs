@@ -0,0 +1,58 @@
+//! The object layout is defined by the vm, the collector only knows the `HeapObj`
+//! header at the start of each object. Everything else it needs to walk the heap and
+//! trace references is asked from the registered `ObjectModel`.
+use once_cell::sync::OnceCell;
+
+use super::address::Address;
+use super::object_description::{GcRoot, KlassDescription, ObjectDescription};
+use crate::reference::reference_processor::ReferenceType;
+
+/// Describes vm objects to the collector.
+pub trait ObjectModel: Send + Sync {
+    /// Size in bytes of the object, including the `HeapObj` header.
+    fn object_size(&self, object: Address) -> usize;
+
+    /// The klass pointer stored in the object.
+    fn klass(&self, object: Address) -> Address;
+
+    /// Whether the address is a klass known by the vm.
+    fn is_klass(&self, klass: Address) -> bool;
+
+    /// Visit the address of each reference field of the object.
+    fn for_each_reference(&self, object: Address, visitor: &mut dyn FnMut(Address));
+
+    /// The reference type and the address of the `referent` field when the object is
+    /// a `java.lang.ref.Reference`.
+    fn as_reference(&self, _object: Address) -> Option<(ReferenceType, Address)> {
+        None
+    }
+
+    /// The `timestamp` field of a `SoftReference`.
+    fn soft_reference_timestamp(&self, _reference: Address) -> u64 {
+        0
+    }
+
+    /// Visit each root the vm holds.
+    fn for_each_root(&self, _visitor: &mut dyn FnMut(GcRoot)) {}
+
+    /// The content of the object for a heap dump.
+    fn describe_object(&self, _object: Address) -> Option<ObjectDescription> {
+        None
+    }
+
+    /// The klass for a heap dump.
+    fn describe_klass(&self, _klass: Address) -> Option<KlassDescription> {
+        None
+    }
+}
+
+static OBJECT_MODEL: OnceCell<Box<dyn ObjectModel>> = OnceCell::new();
+
+/// Register the object model of the vm, it can only be registered once.
+pub fn set_object_model(model: Box<dyn ObjectModel>) -> bool {
+    OBJECT_MODEL.set(model).is_ok()
+}
+
+pub fn object_model() -> Option<&'static dyn ObjectModel> {
+    OBJECT_MODEL.get().map(|model| model.as_ref())
+}
@@ -0,0 +1,2 @@
+pub mod reference_handler;
+pub mod reference_processor;
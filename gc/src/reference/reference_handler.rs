@@ -0,0 +1,211 @@
+//! The reference handler and finalizer threads.
+//!
+//! References made pending by a collection wait on the pending list until the
+//! "Reference Handler" thread takes them. Cleared references are enqueued on their
+//! `ReferenceQueue` by the vm, final references are moved to the finalizer queue where
+//! the "Finalizer" thread runs `Object.finalize` of their referent.
+//!
+//! A reference stays on its queue until the vm returned from the callback, the
+//! collector traces both queues as roots so queued references and the referents of
+//! final references survive. Until the threads are started nothing takes the
+//! references, so the collector drops them instead of keeping them alive forever.
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use once_cell::sync::Lazy;
+
+use crate::model::address::Address;
+
+use super::reference_processor::ReferenceType;
+
+/// What the vm does with references handed out by the collector.
+pub trait ReferenceHandler: Send + Sync {
+    /// Enqueue the cleared reference on its `ReferenceQueue`, if it has one.
+    fn enqueue(&self, reference: Address);
+
+    /// Run `Object.finalize` of the referent of the final reference, then clear it.
+    fn finalize(&self, reference: Address);
+}
+
+struct ReferenceQueue {
+    references: Mutex<VecDeque<(Address, ReferenceType)>>,
+    not_empty: Condvar,
+    /// Whether a thread takes the references, pushes are dropped until then.
+    open: AtomicBool,
+}
+
+impl ReferenceQueue {
+    fn new() -> ReferenceQueue {
+        ReferenceQueue {
+            references: Mutex::new(VecDeque::new()),
+            not_empty: Condvar::new(),
+            open: AtomicBool::new(false),
+        }
+    }
+
+    fn open(&self) {
+        self.open.store(true, Ordering::Release);
+    }
+
+    fn push_all(&self, references: impl IntoIterator<Item = (Address, ReferenceType)>) {
+        if !self.open.load(Ordering::Acquire) {
+            return;
+        }
+        let mut queue = self.references.lock().unwrap();
+        let len = queue.len();
+        queue.extend(references);
+        if queue.len() > len {
+            self.not_empty.notify_all();
+        }
+    }
+
+    /// Block until the queue is not empty, the front stays queued.
+    fn wait_front(&self) -> (Address, ReferenceType) {
+        let mut queue = self.references.lock().unwrap();
+        loop {
+            if let Some(front) = queue.front() {
+                return *front;
+            }
+            queue = self.not_empty.wait(queue).unwrap();
+        }
+    }
+
+    fn pop_front(&self) {
+        self.references.lock().unwrap().pop_front();
+    }
+
+    fn for_each(&self, visitor: &mut dyn FnMut(Address)) {
+        self.references
+            .lock()
+            .unwrap()
+            .iter()
+            .for_each(|(reference, _)| visitor(*reference));
+    }
+}
+
+static PENDING_LIST: Lazy<ReferenceQueue> = Lazy::new(ReferenceQueue::new);
+
+static FINALIZER_QUEUE: Lazy<ReferenceQueue> = Lazy::new(ReferenceQueue::new);
+
+pub fn push_pending(references: Vec<(Address, ReferenceType)>) {
+    PENDING_LIST.push_all(references);
+}
+
+/// Visit the references waiting on the pending list or the finalizer queue.
+pub fn for_each_queued_reference(visitor: &mut dyn FnMut(Address)) {
+    PENDING_LIST.for_each(visitor);
+    FINALIZER_QUEUE.for_each(visitor);
+}
+
+/// Start the reference handler and finalizer threads, they run until the process exits.
+pub fn start(handler: Arc<dyn ReferenceHandler>) -> io::Result<Vec<JoinHandle<()>>> {
+    PENDING_LIST.open();
+    FINALIZER_QUEUE.open();
+    let reference_handler = {
+        let handler = handler.clone();
+        thread::Builder::new()
+            .name("Reference Handler".to_string())
+            .spawn(move || loop {
+                let (reference, reference_type) = PENDING_LIST.wait_front();
+                if reference_type == ReferenceType::Final {
+                    FINALIZER_QUEUE.push_all([(reference, reference_type)]);
+                } else {
+                    handler.enqueue(reference);
+                }
+                PENDING_LIST.pop_front();
+            })?
+    };
+    let finalizer = thread::Builder::new()
+        .name("Finalizer".to_string())
+        .spawn(move || loop {
+            let (reference, _) = FINALIZER_QUEUE.wait_front();
+            handler.finalize(reference);
+            FINALIZER_QUEUE.pop_front();
+        })?;
+    Ok(vec![reference_handler, finalizer])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{for_each_queued_reference, push_pending, start, ReferenceHandler, ReferenceQueue};
+    use crate::model::address::Address;
+    use crate::reference::reference_processor::ReferenceType;
+
+    struct TestHandler {
+        events: Mutex<Sender<(&'static str, Address)>>,
+    }
+
+    impl ReferenceHandler for TestHandler {
+        fn enqueue(&self, reference: Address) {
+            self.events
+                .lock()
+                .unwrap()
+                .send(("enqueue", reference))
+                .unwrap();
+        }
+
+        fn finalize(&self, reference: Address) {
+            let mut queued = Vec::new();
+            for_each_queued_reference(&mut |reference| queued.push(reference));
+            assert!(queued.contains(&reference));
+            self.events
+                .lock()
+                .unwrap()
+                .send(("finalize", reference))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn references_are_only_queued_for_a_running_handler() {
+        let queue = ReferenceQueue::new();
+        let weak = Address::new(0x1000);
+        queue.push_all([(weak, ReferenceType::Weak)]);
+        let mut queued = Vec::new();
+        queue.for_each(&mut |reference| queued.push(reference));
+        assert!(queued.is_empty());
+        queue.open();
+        queue.push_all([(weak, ReferenceType::Weak)]);
+        queue.for_each(&mut |reference| queued.push(reference));
+        assert_eq!(queued, [weak]);
+    }
+
+    #[test]
+    fn pending_references_are_handed_to_the_vm() {
+        let (sender, receiver) = mpsc::channel();
+        let handler = Arc::new(TestHandler {
+            events: Mutex::new(sender),
+        });
+        let threads = start(handler).unwrap();
+        assert_eq!(threads[0].thread().name(), Some("Reference Handler"));
+        assert_eq!(threads[1].thread().name(), Some("Finalizer"));
+
+        let weak = Address::new(0x1000);
+        let finalizer = Address::new(0x2000);
+        let phantom = Address::new(0x3000);
+        push_pending(vec![
+            (weak, ReferenceType::Weak),
+            (finalizer, ReferenceType::Final),
+            (phantom, ReferenceType::Phantom),
+        ]);
+        let mut events: Vec<_> = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        events.sort();
+        assert_eq!(
+            events,
+            vec![
+                ("enqueue", weak),
+                ("enqueue", phantom),
+                ("finalize", finalizer)
+            ]
+        );
+    }
+}
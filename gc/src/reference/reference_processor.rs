@@ -0,0 +1,430 @@
+//! Processing of `java.lang.ref.Reference` objects discovered by the mark.
+//!
+//! While tracing, a reference whose referent is not marked yet is discovered instead
+//! of having its referent traced. After the strong closure is complete the discovered
+//! references are processed by strength:
+//! - soft references are kept alive or cleared by the [`SoftReferencePolicy`]
+//! - weak references are cleared when their referent is not marked
+//! - final references keep their unmarked referent alive so that it can be finalized
+//! - phantom references are cleared when their referent is still not marked
+//!
+//! Cleared and final references become pending, the reference handler enqueues them
+//! on their `ReferenceQueue` and hands final references to the finalizer thread.
+//!
+//! References found while keeping final referents alive are traced like ordinary
+//! objects, their referents survive until the next collection.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use once_cell::sync::Lazy;
+
+use crate::collector::marker::Marker;
+use crate::model::address::Address;
+
+const DEFAULT_MS_PER_MB: u64 = 1000;
+
+/// Read once, a value that is not a number falls back to the default.
+static MS_PER_MB: Lazy<u64> = Lazy::new(|| {
+    std::env::var("SOFT_REF_LRU_POLICY_MS_PER_MB")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MS_PER_MB)
+});
+
+static SOFT_REFERENCE_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// The value for `SoftReference.clock`, in milliseconds since the heap was initialized.
+///
+/// `SoftReference.get` stores the clock into the `timestamp` of the reference, the
+/// collector updates it at the end of each collection.
+pub fn soft_reference_clock() -> u64 {
+    SOFT_REFERENCE_CLOCK.load(Ordering::Relaxed)
+}
+
+pub fn update_soft_reference_clock(clock: u64) {
+    SOFT_REFERENCE_CLOCK.store(clock, Ordering::Relaxed);
+}
+
+/// The strength of a `java.lang.ref.Reference`, in processing order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceType {
+    /// `java.lang.ref.SoftReference`
+    Soft,
+    /// `java.lang.ref.WeakReference`
+    Weak,
+    /// `java.lang.ref.FinalReference`, registered by the vm for each object whose
+    /// class overrides `Object.finalize`.
+    Final,
+    /// `java.lang.ref.PhantomReference`
+    Phantom,
+}
+
+impl ReferenceType {
+    /// All reference types, in processing order.
+    pub const ALL: [ReferenceType; 4] = [
+        ReferenceType::Soft,
+        ReferenceType::Weak,
+        ReferenceType::Final,
+        ReferenceType::Phantom,
+    ];
+
+    pub(crate) fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// The class name as printed by the gc log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReferenceType::Soft => "SoftReference",
+            ReferenceType::Weak => "WeakReference",
+            ReferenceType::Final => "FinalReference",
+            ReferenceType::Phantom => "PhantomReference",
+        }
+    }
+}
+
+/// Number of references made pending by a collection, for each reference type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReferenceCounts {
+    /// Cleared soft references.
+    pub soft: usize,
+    /// Cleared weak references.
+    pub weak: usize,
+    /// Final references whose referent is ready for finalization.
+    pub finalizable: usize,
+    /// Cleared phantom references.
+    pub phantom: usize,
+}
+
+impl ReferenceCounts {
+    /// The count of the reference type.
+    pub fn get(&self, reference_type: ReferenceType) -> usize {
+        match reference_type {
+            ReferenceType::Soft => self.soft,
+            ReferenceType::Weak => self.weak,
+            ReferenceType::Final => self.finalizable,
+            ReferenceType::Phantom => self.phantom,
+        }
+    }
+
+    fn increment(&mut self, reference_type: ReferenceType) {
+        match reference_type {
+            ReferenceType::Soft => self.soft += 1,
+            ReferenceType::Weak => self.weak += 1,
+            ReferenceType::Final => self.finalizable += 1,
+            ReferenceType::Phantom => self.phantom += 1,
+        }
+    }
+}
+
+/// A reference found by the mark whose referent was not marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoveredReference {
+    pub reference: Address,
+    /// The address of the `referent` field.
+    pub referent_slot: Address,
+}
+
+impl DiscoveredReference {
+    fn referent(&self) -> Address {
+        self.referent_slot.load()
+    }
+
+    fn clear(&self) {
+        self.referent_slot.store(Address::zero());
+    }
+}
+
+/// Least recently used policy of hotspot, a soft reference is cleared when its
+/// referent has not been accessed for `SOFT_REF_LRU_POLICY_MS_PER_MB` milliseconds
+/// (1000 if unset) for each free megabyte of the heap.
+///
+/// The last collection before running out of memory clears all soft references.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoftReferencePolicy {
+    clock: u64,
+    max_interval: u64,
+}
+
+impl SoftReferencePolicy {
+    pub fn new(free_heap: usize) -> SoftReferencePolicy {
+        SoftReferencePolicy::with_ms_per_mb(free_heap, *MS_PER_MB)
+    }
+
+    pub fn with_ms_per_mb(free_heap: usize, ms_per_mb: u64) -> SoftReferencePolicy {
+        SoftReferencePolicy {
+            clock: soft_reference_clock(),
+            max_interval: ((free_heap / (1024 * 1024)) as u64).saturating_mul(ms_per_mb),
+        }
+    }
+
+    pub fn clear_all() -> SoftReferencePolicy {
+        SoftReferencePolicy {
+            clock: u64::MAX,
+            max_interval: 0,
+        }
+    }
+
+    /// Whether a soft reference with the given `timestamp` should be cleared.
+    pub fn should_clear(&self, timestamp: u64) -> bool {
+        self.clock.saturating_sub(timestamp) > self.max_interval
+    }
+}
+
+/// References that became pending in a collection.
+#[derive(Debug, Default)]
+pub struct ProcessedReferences {
+    pub pending: Vec<(Address, ReferenceType)>,
+    pub counts: ReferenceCounts,
+}
+
+impl ProcessedReferences {
+    fn push(&mut self, reference: Address, reference_type: ReferenceType) {
+        self.pending.push((reference, reference_type));
+        self.counts.increment(reference_type);
+    }
+}
+
+/// Process the references discovered by the mark, the marker must have drained its
+/// mark stack.
+pub fn process_references(marker: &mut Marker, policy: SoftReferencePolicy) -> ProcessedReferences {
+    let mut processed = ProcessedReferences::default();
+    keep_alive_soft_referents(marker, policy);
+    marker.stop_discovery();
+    for reference_type in ReferenceType::ALL {
+        for discovered in marker.take_discovered(reference_type) {
+            let referent = discovered.referent();
+            if referent.is_null() || marker.is_marked(referent) {
+                continue;
+            }
+            if reference_type == ReferenceType::Final {
+                marker.mark(referent);
+                marker.drain();
+            } else {
+                discovered.clear();
+            }
+            processed.push(discovered.reference, reference_type);
+        }
+    }
+    processed
+}
+
+/// Soft references kept by the policy are traced like strong references, tracing a
+/// referent can discover more soft references.
+fn keep_alive_soft_referents(marker: &mut Marker, policy: SoftReferencePolicy) {
+    let mut index = 0;
+    while let Some(discovered) = marker.discovered(ReferenceType::Soft).get(index).copied() {
+        index += 1;
+        let referent = discovered.referent();
+        if referent.is_null() || marker.is_marked(referent) {
+            continue;
+        }
+        if !policy.should_clear(marker.soft_reference_timestamp(discovered.reference)) {
+            marker.mark(referent);
+            marker.drain();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::{process_references, ReferenceType, SoftReferencePolicy};
+    use crate::collector::marker::Marker;
+    use crate::model::address::Address;
+    use crate::model::block::{Block, BLOCK_SIZE, LINE_COUNT};
+    use crate::model::heap_obj::HeapObj;
+    use crate::model::line_map::LineMap;
+    use crate::model::object_model::ObjectModel;
+    use crate::utils::mmap::MemoryMap;
+
+    const KIND_OFFSET: usize = size_of::<HeapObj>();
+    const TIMESTAMP_OFFSET: usize = KIND_OFFSET + 8;
+    const REFS_OFFSET: usize = TIMESTAMP_OFFSET + 8;
+    const OBJECT_SIZE: usize = REFS_OFFSET + 2 * size_of::<Address>();
+
+    /// Objects are laid out as header, kind, timestamp and two reference fields, the
+    /// first field of a reference object is its referent.
+    struct TestModel;
+
+    impl ObjectModel for TestModel {
+        fn object_size(&self, _object: Address) -> usize {
+            OBJECT_SIZE
+        }
+
+        fn klass(&self, _object: Address) -> Address {
+            Address::zero()
+        }
+
+        fn is_klass(&self, _klass: Address) -> bool {
+            false
+        }
+
+        fn for_each_reference(&self, object: Address, visitor: &mut dyn FnMut(Address)) {
+            visitor(object.plus(REFS_OFFSET));
+            visitor(object.plus(REFS_OFFSET + size_of::<Address>()));
+        }
+
+        fn as_reference(&self, object: Address) -> Option<(ReferenceType, Address)> {
+            let reference_type = match object.plus(KIND_OFFSET).load::<usize>() {
+                1 => ReferenceType::Soft,
+                2 => ReferenceType::Weak,
+                3 => ReferenceType::Final,
+                4 => ReferenceType::Phantom,
+                _ => return None,
+            };
+            Some((reference_type, object.plus(REFS_OFFSET)))
+        }
+
+        fn soft_reference_timestamp(&self, reference: Address) -> u64 {
+            reference.plus(TIMESTAMP_OFFSET).load()
+        }
+    }
+
+    struct TestHeap {
+        _memory_map: MemoryMap,
+        _line_map: LineMap,
+        block: Block,
+        next_line: usize,
+    }
+
+    impl TestHeap {
+        fn new() -> TestHeap {
+            let memory_map = MemoryMap::new(BLOCK_SIZE);
+            let line_map = LineMap::new(LINE_COUNT);
+            let base = memory_map.commit_block(0).unwrap();
+            let block = Block::new(base, line_map.block_line_marks(0));
+            TestHeap {
+                _memory_map: memory_map,
+                _line_map: line_map,
+                block,
+                next_line: 0,
+            }
+        }
+
+        fn object(&mut self, kind: usize, refs: [Address; 2]) -> Address {
+            let object = self.block.line_address(self.next_line);
+            self.next_line += 1;
+            object.store(0u8);
+            object.plus(KIND_OFFSET).store(kind);
+            object.plus(TIMESTAMP_OFFSET).store(0u64);
+            object.plus(REFS_OFFSET).store(refs[0]);
+            object
+                .plus(REFS_OFFSET + size_of::<Address>())
+                .store(refs[1]);
+            object
+        }
+
+        fn marker<'a>(&self, model: &'a TestModel) -> Marker<'a> {
+            let base = self.block.base_address();
+            Marker::new(base, base.plus(BLOCK_SIZE), model)
+        }
+    }
+
+    fn referent(reference: Address) -> Address {
+        reference.plus(REFS_OFFSET).load()
+    }
+
+    #[test]
+    fn references_are_cleared_by_strength() {
+        let mut heap = TestHeap::new();
+        let model = TestModel;
+        let zero = Address::zero();
+        let strong = heap.object(0, [zero, zero]);
+        let weakly_reachable = heap.object(0, [zero, zero]);
+        let weak_to_strong = heap.object(2, [strong, zero]);
+        let weak = heap.object(2, [weakly_reachable, zero]);
+        let finalizable = heap.object(0, [zero, zero]);
+        let finalizer = heap.object(3, [finalizable, zero]);
+        let phantom_reachable = heap.object(0, [zero, zero]);
+        let phantom = heap.object(4, [phantom_reachable, zero]);
+        let phantom_to_finalizable = heap.object(4, [finalizable, zero]);
+        let unreachable = heap.object(2, [strong, zero]);
+        let roots = heap.object(0, [weak_to_strong, weak]);
+        let more_roots = heap.object(0, [finalizer, phantom]);
+        let last_root = heap.object(0, [phantom_to_finalizable, strong]);
+
+        let mut marker = heap.marker(&model);
+        [roots, more_roots, last_root]
+            .into_iter()
+            .for_each(|root| marker.mark(root));
+        marker.drain();
+        let processed = process_references(&mut marker, SoftReferencePolicy::clear_all());
+
+        assert_eq!(
+            processed.pending,
+            vec![
+                (weak, ReferenceType::Weak),
+                (finalizer, ReferenceType::Final),
+                (phantom, ReferenceType::Phantom),
+            ]
+        );
+        assert_eq!(referent(weak_to_strong), strong);
+        assert!(referent(weak).is_null());
+        assert_eq!(referent(finalizer), finalizable);
+        assert!(marker.is_marked(finalizable));
+        assert!(referent(phantom).is_null());
+        assert_eq!(referent(phantom_to_finalizable), finalizable);
+        assert!(!marker.is_marked(weakly_reachable));
+        assert!(!marker.is_marked(unreachable));
+        assert_eq!(processed.counts.weak, 1);
+        assert_eq!(processed.counts.finalizable, 1);
+        assert_eq!(processed.counts.phantom, 1);
+
+        let marked = marker.finish();
+        assert!(marked.contains(&finalizable));
+        assert!(!marked.contains(&phantom_reachable));
+        assert!(marked
+            .iter()
+            .all(|object| !unsafe { HeapObj::from_address(*object) }.is_mark()));
+    }
+
+    #[test]
+    fn soft_references_are_cleared_by_policy() {
+        let mut heap = TestHeap::new();
+        let model = TestModel;
+        let zero = Address::zero();
+        let recently_used = heap.object(0, [zero, zero]);
+        let weakly_reachable = heap.object(0, [zero, zero]);
+        let through_soft = heap.object(2, [weakly_reachable, zero]);
+        let recent = heap.object(1, [recently_used, through_soft]);
+        recent.plus(TIMESTAMP_OFFSET).store(9_500u64);
+        let stale_referent = heap.object(0, [zero, zero]);
+        let stale = heap.object(1, [stale_referent, zero]);
+        stale.plus(TIMESTAMP_OFFSET).store(1_000u64);
+        let roots = heap.object(0, [recent, stale]);
+
+        let policy = SoftReferencePolicy {
+            clock: 10_000,
+            max_interval: 1_000,
+        };
+        assert!(!policy.should_clear(9_500));
+        assert!(policy.should_clear(1_000));
+        let mut marker = heap.marker(&model);
+        marker.mark(roots);
+        marker.drain();
+        let processed = process_references(&mut marker, policy);
+
+        assert_eq!(
+            processed.pending,
+            vec![
+                (stale, ReferenceType::Soft),
+                (through_soft, ReferenceType::Weak)
+            ]
+        );
+        assert_eq!(referent(recent), recently_used);
+        assert!(marker.is_marked(recently_used));
+        assert!(referent(stale).is_null());
+        assert!(referent(through_soft).is_null());
+        marker.finish();
+    }
+
+    #[test]
+    fn policy_interval_grows_with_free_heap() {
+        let policy = SoftReferencePolicy::with_ms_per_mb(4 * 1024 * 1024, 1000);
+        assert_eq!(policy.max_interval, 4000);
+        let policy = SoftReferencePolicy::with_ms_per_mb(usize::MAX, u64::MAX);
+        assert_eq!(policy.max_interval, u64::MAX);
+        assert!(SoftReferencePolicy::clear_all().should_clear(u64::MAX - 1));
+    }
+}
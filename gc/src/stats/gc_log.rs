@@ -0,0 +1,269 @@
+//! Unified logging styled gc output.
+//!
+//! The selection uses the `-Xlog` syntax of hotspot, only the gc tags are recognized
+//! and the output always goes to stdout:
+//! - `gc` prints one line for each collection
+//! - `gc+heap` prints the block states before and after each collection
+//! - `gc+phases` prints the marked lines and pinned objects of each collection
+//! - `gc+ref` prints the references made pending by each collection
+//! - `gc+pin` warns about objects that stay pinned too long, it is enabled by default
+//! - `gc*` prints all of above, and `disable` turns the gc log off
+//!
+//! The selection is read from the `GC_LOG` environment variable and can be changed
+//! at runtime with [`configure`].
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use once_cell::sync::Lazy;
+
+use crate::model::block::BLOCK_SIZE;
+
+use crate::reference::reference_processor::ReferenceType;
+
+use super::gc_record::GcRecord;
+
+pub const TAG_GC: u8 = 0b0000_0001;
+pub const TAG_HEAP: u8 = 0b0000_0010;
+pub const TAG_PHASES: u8 = 0b0000_0100;
+pub const TAG_PIN: u8 = 0b0000_1000;
+pub const TAG_REF: u8 = 0b0001_0000;
+const TAG_ALL: u8 = TAG_GC | TAG_HEAP | TAG_PHASES | TAG_PIN | TAG_REF;
+
+static LOG_TAGS: Lazy<AtomicU8> = Lazy::new(|| {
+    let tags = std::env::var("GC_LOG")
+        .ok()
+        .and_then(|selection| parse_selection(&selection))
+        .unwrap_or(TAG_PIN);
+    AtomicU8::new(tags)
+});
+
+/// Change the gc log selection, e.g. `-Xlog:gc*` or `gc+heap`.
+///
+/// Returns false and keeps the current selection if it is not recognized.
+pub fn configure(selection: &str) -> bool {
+    if let Some(tags) = parse_selection(selection) {
+        LOG_TAGS.store(tags, Ordering::Relaxed);
+        return true;
+    }
+    false
+}
+
+pub fn log_record(record: &GcRecord) {
+    let tags = LOG_TAGS.load(Ordering::Relaxed);
+    format_record(record, tags)
+        .iter()
+        .for_each(|line| println!("{}", line));
+}
+
+fn parse_selection(selection: &str) -> Option<u8> {
+    let selection = selection.strip_prefix("-Xlog:").unwrap_or(selection);
+    let selectors = selection.split(':').next().unwrap_or_default();
+    if selectors == "disable" {
+        return Some(0);
+    }
+    let mut tags = TAG_PIN;
+    for selector in selectors.split(',') {
+        let (tag_set, level) = selector.split_once('=').unwrap_or((selector, "info"));
+        let selected = match tag_set {
+            "gc" => TAG_GC,
+            "gc+heap" => TAG_HEAP,
+            "gc+phases" => TAG_PHASES,
+            "gc+pin" => TAG_PIN,
+            "gc+ref" => TAG_REF,
+            "gc*" => TAG_ALL,
+            _ => return None,
+        };
+        match level {
+            "off" => tags &= !selected,
+            "info" | "debug" | "trace" => tags |= selected,
+            _ => return None,
+        }
+    }
+    Some(tags)
+}
+
+fn format_record(record: &GcRecord, tags: u8) -> Vec<String> {
+    let mut lines = Vec::new();
+    let uptime = (record.start + record.pause).as_secs_f64();
+    let decorate_level = |level: &str, tag: &str, message: String| {
+        format!(
+            "[{:.3}s][{}][{}] GC({}) {}",
+            uptime, level, tag, record.id, message
+        )
+    };
+    let decorate = |tag: &str, message: String| decorate_level("info", tag, message);
+    if tags & TAG_PIN != 0 {
+        for pinned in &record.long_pinned {
+            lines.push(decorate_level(
+                "warning",
+                "gc,pin",
+                format!(
+                    "Object {} has been pinned for {} collections (pin count {})",
+                    pinned.address, pinned.collections, pinned.pin_count
+                ),
+            ));
+        }
+    }
+    if tags & TAG_PHASES != 0 {
+        lines.push(decorate(
+            "gc,phases",
+            format!("Lines marked: {}", record.lines_marked),
+        ));
+        lines.push(decorate(
+            "gc,phases",
+            format!("Pinned objects: {}", record.pinned_objects),
+        ));
+    }
+    if tags & TAG_REF != 0 {
+        let counts = ReferenceType::ALL
+            .iter()
+            .map(|reference_type| {
+                format!(
+                    "{} {}",
+                    reference_type.name(),
+                    record.references.get(*reference_type)
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push(decorate(
+            "gc,ref",
+            format!("Pending references: {}", counts.join(", ")),
+        ));
+    }
+    if tags & TAG_HEAP != 0 {
+        let counts = [
+            ("Free", record.before.free, record.after.free),
+            (
+                "Recyclable",
+                record.before.recyclable,
+                record.after.recyclable,
+            ),
+            (
+                "Unavailable",
+                record.before.unavailable,
+                record.after.unavailable,
+            ),
+        ];
+        for (state, before, after) in counts {
+            lines.push(decorate(
+                "gc,heap",
+                format!("{} blocks: {}->{}", state, before, after),
+            ));
+        }
+    }
+    if tags & TAG_GC != 0 {
+        lines.push(decorate(
+            "gc",
+            format!(
+                "Pause Immix ({}) {}->{}({}) {:.3}ms",
+                record.cause.name(),
+                format_size(record.before.used() * BLOCK_SIZE),
+                format_size(record.after.used() * BLOCK_SIZE),
+                format_size(record.after.total() * BLOCK_SIZE),
+                record.pause.as_secs_f64() * 1000.0
+            ),
+        ));
+    }
+    lines
+}
+
+fn format_size(bytes: usize) -> String {
+    if bytes >= 10 * 1024 * 1024 {
+        format!("{}M", bytes / (1024 * 1024))
+    } else if bytes >= 10 * 1024 {
+        format!("{}K", bytes / 1024)
+    } else {
+        format!("{}B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        format_record, parse_selection, TAG_ALL, TAG_GC, TAG_HEAP, TAG_PHASES, TAG_PIN, TAG_REF,
+    };
+    use crate::allocator::pin_table::PinnedObject;
+    use crate::model::address::Address;
+    use crate::reference::reference_processor::ReferenceCounts;
+    use crate::stats::gc_record::{BlockCounts, GcCause, GcRecord};
+
+    #[test]
+    fn selections_are_parsed_like_xlog() {
+        assert_eq!(parse_selection("-Xlog:gc"), Some(TAG_GC | TAG_PIN));
+        assert_eq!(parse_selection("gc*"), Some(TAG_ALL));
+        assert_eq!(
+            parse_selection("gc,gc+heap:stdout"),
+            Some(TAG_GC | TAG_HEAP | TAG_PIN)
+        );
+        assert_eq!(
+            parse_selection("gc*,gc+phases=off"),
+            Some(TAG_GC | TAG_HEAP | TAG_PIN | TAG_REF)
+        );
+        assert_eq!(parse_selection("gc,gc+pin=off"), Some(TAG_GC));
+        assert_eq!(parse_selection("gc+ref"), Some(TAG_REF | TAG_PIN));
+        assert_eq!(parse_selection("disable"), Some(0));
+        assert_eq!(parse_selection("class+load"), None);
+        assert_eq!(parse_selection("gc=verbose"), None);
+    }
+
+    #[test]
+    fn records_are_formatted_with_decorations() {
+        let record = GcRecord {
+            id: 3,
+            cause: GcCause::AllocationFailure,
+            start: Duration::from_millis(1500),
+            pause: Duration::from_micros(250),
+            before: BlockCounts {
+                free: 0,
+                recyclable: 8,
+                unavailable: 24,
+            },
+            after: BlockCounts {
+                free: 12,
+                recyclable: 10,
+                unavailable: 10,
+            },
+            lines_marked: 2900,
+            pinned_objects: 2,
+            long_pinned: vec![PinnedObject {
+                address: Address::new(0x1000),
+                size: 16,
+                pin_count: 1,
+                collections: 16,
+            }],
+            references: ReferenceCounts {
+                soft: 0,
+                weak: 5,
+                finalizable: 1,
+                phantom: 2,
+            },
+        };
+        assert_eq!(
+            format_record(&record, TAG_GC),
+            vec!["[1.500s][info][gc] GC(3) Pause Immix (Allocation Failure) 1024K->640K(1024K) 0.250ms"]
+        );
+        let lines = format_record(&record, TAG_ALL);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[0],
+            "[1.500s][warning][gc,pin] GC(3) Object 0x1000 has been pinned for 16 collections (pin count 1)"
+        );
+        assert_eq!(
+            lines[1],
+            "[1.500s][info][gc,phases] GC(3) Lines marked: 2900"
+        );
+        assert_eq!(
+            lines[2],
+            "[1.500s][info][gc,phases] GC(3) Pinned objects: 2"
+        );
+        assert_eq!(
+            lines[3],
+            "[1.500s][info][gc,ref] GC(3) Pending references: SoftReference 0, WeakReference 5, FinalReference 1, PhantomReference 2"
+        );
+        assert_eq!(lines[4], "[1.500s][info][gc,heap] GC(3) Free blocks: 0->12");
+        assert!(format_record(&record, TAG_PHASES)
+            .iter()
+            .all(|line| line.contains("[gc,phases]")));
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use crate::allocator::pin_table::PinnedObject;
+use crate::reference::reference_processor::ReferenceCounts;
+
+/// The reason a garbage collection was started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcCause {
+    /// The global allocator could not hand out a free block.
+    AllocationFailure,
+    /// The collection was explicitly requested, e.g. by `System.gc()`.
+    Explicit,
+    /// The collection after an allocation failure did not free a block, all soft
+    /// references are cleared before running out of memory.
+    LastDitchCollection,
+}
+
+impl GcCause {
+    /// The cause name as printed by the gc log, following the hotspot naming.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GcCause::AllocationFailure => "Allocation Failure",
+            GcCause::Explicit => "System.gc()",
+            GcCause::LastDitchCollection => "Last ditch collection",
+        }
+    }
+}
+
+/// Number of blocks in each block state at a point of time.
+///
+/// Blocks still owned by a thread-local allocator cannot be handed out by the
+/// global allocator, so they are counted as unavailable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockCounts {
+    /// Blocks that have no live line.
+    pub free: usize,
+    /// Blocks that have at least one free line.
+    pub recyclable: usize,
+    /// Blocks that have no free line or are in use by a thread-local allocator.
+    pub unavailable: usize,
+}
+
+impl BlockCounts {
+    /// Total number of blocks committed from the system.
+    pub fn total(&self) -> usize {
+        self.free + self.recyclable + self.unavailable
+    }
+
+    /// Number of blocks that are not free.
+    pub fn used(&self) -> usize {
+        self.recyclable + self.unavailable
+    }
+}
+
+/// What a single garbage collection did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcRecord {
+    /// Sequence number of the collection, starting from zero.
+    pub id: usize,
+    /// Why the collection was started.
+    pub cause: GcCause,
+    /// Time since the heap was initialized when the collection started.
+    pub start: Duration,
+    /// How long the mutators were paused.
+    pub pause: Duration,
+    /// Block states before the collection.
+    pub before: BlockCounts,
+    /// Block states after the collection.
+    pub after: BlockCounts,
+    /// Lines that are marked live after the collection.
+    pub lines_marked: usize,
+    /// Objects pinned during the collection.
+    pub pinned_objects: usize,
+    /// Pinned objects that just reached the warning threshold.
+    pub long_pinned: Vec<PinnedObject>,
+    /// References made pending for the reference handler.
+    pub references: ReferenceCounts,
+}
@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::reference::reference_processor::ReferenceCounts;
+
+use super::gc_log;
+use super::gc_record::{BlockCounts, GcCause, GcRecord};
+
+/// How many of the latest records are kept, older records only contribute to the totals.
+const MAX_HISTORY: usize = 256;
+
+/// Accumulated statistics of all collections, this is what the management beans read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GcSummary {
+    /// Number of finished collections.
+    pub collections: usize,
+    /// Sum of all pause times.
+    pub total_pause: Duration,
+}
+
+/// Heap occupancy in bytes, shaped after `java.lang.management.MemoryUsage`.
+///
+/// Blocks are committed on demand, so there is no initial size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapUsage {
+    /// Bytes in blocks that are not free.
+    pub used: usize,
+    /// Bytes in blocks committed from the system.
+    pub committed: usize,
+    /// Maximum bytes the heap can grow to.
+    pub max: usize,
+}
+
+pub struct GcStats {
+    history: VecDeque<GcRecord>,
+    summary: GcSummary,
+}
+
+impl GcStats {
+    pub fn new() -> GcStats {
+        GcStats {
+            history: VecDeque::with_capacity(MAX_HISTORY),
+            summary: GcSummary::default(),
+        }
+    }
+
+    /// Start a new record, the caller fills the rest of it while collecting.
+    pub fn begin(&self, cause: GcCause, start: Duration, before: BlockCounts) -> GcRecord {
+        GcRecord {
+            id: self.summary.collections,
+            cause,
+            start,
+            pause: Duration::ZERO,
+            before,
+            after: before,
+            lines_marked: 0,
+            pinned_objects: 0,
+            long_pinned: Vec::new(),
+            references: ReferenceCounts::default(),
+        }
+    }
+
+    /// Store a finished record and print it if gc logging is enabled.
+    pub fn finish(&mut self, record: GcRecord) {
+        gc_log::log_record(&record);
+        self.summary.collections += 1;
+        self.summary.total_pause += record.pause;
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(record);
+    }
+
+    pub fn records(&self) -> Vec<GcRecord> {
+        self.history.iter().cloned().collect()
+    }
+
+    pub fn summary(&self) -> GcSummary {
+        self.summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{GcStats, MAX_HISTORY};
+    use crate::stats::gc_record::{BlockCounts, GcCause};
+
+    #[test]
+    fn finished_records_are_accumulated() {
+        let mut stats = GcStats::new();
+        let before = BlockCounts {
+            free: 0,
+            recyclable: 2,
+            unavailable: 6,
+        };
+        for _ in 0..3 {
+            let mut record = stats.begin(GcCause::AllocationFailure, Duration::ZERO, before);
+            record.pause = Duration::from_millis(2);
+            stats.finish(record);
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.collections, 3);
+        assert_eq!(summary.total_pause, Duration::from_millis(6));
+        assert_eq!(stats.records().last().map(|r| r.id), Some(2));
+    }
+
+    #[test]
+    fn history_keeps_only_latest_records() {
+        let mut stats = GcStats::new();
+        for _ in 0..MAX_HISTORY + 10 {
+            let record = stats.begin(GcCause::Explicit, Duration::ZERO, BlockCounts::default());
+            stats.finish(record);
+        }
+        let records = stats.records();
+        assert_eq!(records.len(), MAX_HISTORY);
+        assert_eq!(records[0].id, 10);
+        assert_eq!(stats.summary().collections, MAX_HISTORY + 10);
+    }
+}
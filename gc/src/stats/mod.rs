@@ -0,0 +1,3 @@
+pub mod gc_log;
+pub mod gc_record;
+pub mod gc_stats;
@@ -8,24 +8,40 @@ use crate::{
 #[cfg(target_os = "windows")]
 extern crate winapi;
 
-#[cfg(target_os = "macos")]
-extern crate libc;
-
 pub struct MemoryMap {
     base: *mut u8,
-    ptr: *mut u8,
+    /// Size of the mapping, the heap and the padding to align it to a block.
+    #[cfg(unix)]
+    reserved: usize,
+    start: *mut u8,
     end: *mut u8,
 }
 
 unsafe impl Send for MemoryMap {}
 
+impl MemoryMap {
+    /// The block aligned address the memory is committed from.
+    pub fn start(&self) -> Address {
+        Address::new(self.start as usize)
+    }
+
+    /// Address of the block at the index, `None` past the end of the reserved memory.
+    fn block_address(&self, index: usize) -> Option<*mut u8> {
+        let offset = index.checked_mul(BLOCK_SIZE)?;
+        if offset + BLOCK_SIZE > self.end as usize - self.start as usize {
+            return None;
+        }
+        Some(unsafe { self.start.add(offset) })
+    }
+}
+
 #[cfg(target_os = "windows")]
 impl MemoryMap {
     pub fn new(size: usize) -> MemoryMap {
         unsafe {
             let mem = winapi::um::memoryapi::VirtualAlloc(
                 ptr::null_mut(),
-                size,
+                size + BLOCK_SIZE,
                 winapi::um::winnt::MEM_RESERVE,
                 winapi::um::winnt::PAGE_READWRITE,
             );
@@ -33,31 +49,28 @@ impl MemoryMap {
             if mem.is_null() {
                 panic!("VirtualAlloc failed");
             }
-            let ptr: *mut u8 = align_up!(mem as usize, BLOCK_SIZE) as *mut u8;
+            let start: *mut u8 = align_up!(mem as usize, BLOCK_SIZE) as *mut u8;
             MemoryMap {
                 base: mem,
-                ptr,
-                end: mem.add(size),
+                start,
+                end: start.add(size),
             }
         }
     }
 
-    pub fn allocate_memory(&mut self, size: usize) -> Option<Address> {
+    /// Commit the block at the index, `None` when the heap limit is reached.
+    pub fn commit_block(&self, index: usize) -> Option<Address> {
+        let block = self.block_address(index)?;
         unsafe {
-            if self.ptr.add(size) <= self.end {
-                return None;
-            }
             let mem = winapi::um::memoryapi::VirtualAlloc(
-                self.ptr.cast(),
-                size,
+                block.cast(),
+                BLOCK_SIZE,
                 winapi::um::winnt::MEM_COMMIT,
                 winapi::um::winnt::PAGE_READWRITE,
             );
-            let mem = mem as *mut u8;
             if mem.is_null() {
                 None
             } else {
-                self.ptr = mem.add(size);
                 Some(Address::new(mem as usize))
             }
         }
@@ -73,23 +86,70 @@ impl Drop for MemoryMap {
     }
 }
 
-#[cfg(target_os = "macos")]
+#[cfg(unix)]
 impl MemoryMap {
     pub fn new(size: usize) -> MemoryMap {
         unsafe {
+            let reserved = size + BLOCK_SIZE;
             let mem = libc::mmap(
                 ptr::null_mut(),
-                size,
+                reserved,
                 libc::PROT_NONE,
-                libc::MAP_ANON | libc::MAP_PRIVATE,
+                libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_NORESERVE,
                 -1,
                 0,
             );
-            let mem = mem as *mut u8;
-            if mem.is_null() {
+            if mem == libc::MAP_FAILED {
                 panic!("mmap failed");
             }
-            MemoryMap { ptr: mem, size }
+            let mem = mem as *mut u8;
+            let start: *mut u8 = align_up!(mem as usize, BLOCK_SIZE) as *mut u8;
+            MemoryMap {
+                base: mem,
+                reserved,
+                start,
+                end: start.add(size),
+            }
         }
     }
+
+    /// Commit the block at the index, `None` when the heap limit is reached.
+    pub fn commit_block(&self, index: usize) -> Option<Address> {
+        let block = self.block_address(index)?;
+        unsafe {
+            if libc::mprotect(block.cast(), BLOCK_SIZE, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+                return None;
+            }
+        }
+        Some(Address::new(block as usize))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MemoryMap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base.cast(), self.reserved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::block::BLOCK_SIZE;
+
+    use super::MemoryMap;
+
+    #[test]
+    fn blocks_are_committed_up_to_the_heap_size() {
+        let memory_map = MemoryMap::new(2 * BLOCK_SIZE);
+        let first = memory_map.commit_block(0).unwrap();
+        let second = memory_map.commit_block(1).unwrap();
+        assert_eq!(first, memory_map.start());
+        assert_eq!(second, first.plus(BLOCK_SIZE));
+        assert_eq!(first.to_usize() % BLOCK_SIZE, 0);
+        second.plus(BLOCK_SIZE - 8).store(42usize);
+        assert_eq!(second.plus(BLOCK_SIZE - 8).load::<usize>(), 42);
+        assert!(memory_map.commit_block(2).is_none());
+    }
 }
@@ -0,0 +1,480 @@
+//! Heap verification for debugging the collector.
+//!
+//! When enabled the heap is walked before and/or after each collection, every object
+//! is checked for a valid header and klass, that the lines it spans are marked and
+//! that its reference fields point to objects in the heap. Block marks are checked
+//! against their line marks. Any violation aborts with a report naming the addresses.
+//!
+//! The walk relies on objects being allocated at line granularity, so every live line
+//! that does not belong to the previous object starts a new one.
+//!
+//! The phases are selected with the `GC_VERIFY` environment variable, e.g. `before`,
+//! `after` or `before,after`, and can be changed at runtime with [`configure`].
+use std::collections::BTreeSet;
+use std::fmt;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use once_cell::sync::Lazy;
+
+use crate::align_up;
+use crate::model::address::Address;
+use crate::model::block::{Block, BlockMark, LineMark, LINE_COUNT, LINE_SIZE};
+use crate::model::heap_obj::HeapObj;
+use crate::model::object_model::ObjectModel;
+
+const VERIFY_BEFORE: u8 = 0b01;
+const VERIFY_AFTER: u8 = 0b10;
+
+static VERIFY_PHASES: Lazy<AtomicU8> = Lazy::new(|| {
+    let phases = std::env::var("GC_VERIFY")
+        .ok()
+        .and_then(|selection| parse_selection(&selection))
+        .unwrap_or(0);
+    AtomicU8::new(phases)
+});
+
+/// Change the verification phases, e.g. `before,after` or `none`.
+///
+/// Returns false and keeps the current phases if the selection is not recognized.
+pub fn configure(selection: &str) -> bool {
+    if let Some(phases) = parse_selection(selection) {
+        VERIFY_PHASES.store(phases, Ordering::Relaxed);
+        return true;
+    }
+    false
+}
+
+pub fn should_verify(phase: VerifyPhase) -> bool {
+    let bit = match phase {
+        VerifyPhase::BeforeGc => VERIFY_BEFORE,
+        VerifyPhase::AfterGc => VERIFY_AFTER,
+        VerifyPhase::OnDemand => return true,
+    };
+    VERIFY_PHASES.load(Ordering::Relaxed) & bit != 0
+}
+
+fn parse_selection(selection: &str) -> Option<u8> {
+    let mut phases = 0;
+    for phase in selection.split(',') {
+        phases |= match phase.trim() {
+            "before" => VERIFY_BEFORE,
+            "after" => VERIFY_AFTER,
+            "all" => VERIFY_BEFORE | VERIFY_AFTER,
+            "none" => 0,
+            _ => return None,
+        };
+    }
+    Some(phases)
+}
+
+/// When the heap is verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyPhase {
+    /// Before a collection starts.
+    BeforeGc,
+    /// After a collection finished.
+    AfterGc,
+    /// On demand, outside of a collection.
+    OnDemand,
+}
+
+/// A single violation found by the verifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    /// The object or block the violation belongs to.
+    pub address: Address,
+    /// What is wrong with it.
+    pub message: String,
+}
+
+/// All violations found in one verification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// When the verification ran.
+    pub phase: VerifyPhase,
+    /// The violations in heap order.
+    pub errors: Vec<VerifyError>,
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self.phase {
+            VerifyPhase::BeforeGc => "before gc",
+            VerifyPhase::AfterGc => "after gc",
+            VerifyPhase::OnDemand => "on demand",
+        };
+        write!(
+            f,
+            "heap verification failed {} with {} error(s):",
+            phase,
+            self.errors.len()
+        )?;
+        for error in self.errors.iter() {
+            write!(f, "\n  {}: {}", error.address, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct HeapVerifier<'a> {
+    heap_start: Address,
+    heap_end: Address,
+    model: Option<&'a dyn ObjectModel>,
+    objects: BTreeSet<Address>,
+    pinned: Vec<Address>,
+    references: Vec<(Address, Address, Address)>,
+    errors: Vec<VerifyError>,
+}
+
+impl<'a> HeapVerifier<'a> {
+    pub fn new(
+        heap_start: Address,
+        heap_end: Address,
+        model: Option<&'a dyn ObjectModel>,
+    ) -> HeapVerifier<'a> {
+        HeapVerifier {
+            heap_start,
+            heap_end,
+            model,
+            objects: BTreeSet::new(),
+            pinned: Vec::new(),
+            references: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Walk the objects of the block, without an object model only the line marks
+    /// can be looked at.
+    pub fn verify_block(&mut self, block: &Block) {
+        let Some(model) = self.model else {
+            return;
+        };
+        let mut index = 0;
+        while index < LINE_COUNT {
+            if block.line_mark(index) == LineMark::Free {
+                index += 1;
+                continue;
+            }
+            let object = block.line_address(index);
+            match self.verify_object(block, object, model) {
+                Some(size) => index += align_up!(size, LINE_SIZE) / LINE_SIZE,
+                None => index += 1,
+            }
+        }
+    }
+
+    /// Check a large object, it has no lines to look at.
+    pub fn verify_large_object(&mut self, object: Address) {
+        let Some(model) = self.model else {
+            return;
+        };
+        if let Some(size) = self.verify_header(object, model) {
+            self.objects.insert(object);
+            self.verify_references(object, size, model);
+        }
+    }
+
+    /// Check that the block mark agrees with the line marks.
+    pub fn verify_block_mark(&mut self, block: &Block) {
+        let live_lines = (0..LINE_COUNT)
+            .filter(|&index| block.line_mark(index) == LineMark::Live)
+            .count();
+        let expected = match live_lines {
+            0 => BlockMark::Free,
+            LINE_COUNT => BlockMark::Unavailable,
+            _ => BlockMark::Recyclable,
+        };
+        if block.block_mark() != expected {
+            self.error(
+                block.base_address(),
+                format!(
+                    "block is marked {:?} but {} of {} lines are live",
+                    block.block_mark(),
+                    live_lines,
+                    LINE_COUNT
+                ),
+            );
+        }
+    }
+
+    /// A block in the free list must be marked free and must not have live lines.
+    pub fn verify_free_block(&mut self, block: &Block) {
+        if !block.is_free() {
+            self.error(
+                block.base_address(),
+                format!("block in free list is marked {:?}", block.block_mark()),
+            );
+        }
+        self.verify_block_mark(block);
+    }
+
+    /// A pinned object must have the pinned bit set and must be found by the walk.
+    pub fn verify_pinned_object(&mut self, object: Address) {
+        if !unsafe { HeapObj::from_address(object) }.is_pinned() {
+            self.error(object, "pinned object has no pinned bit".to_string());
+        }
+        self.pinned.push(object);
+    }
+
+    /// Check the collected references and produce the report.
+    pub fn finish(mut self, phase: VerifyPhase) -> Result<(), VerifyReport> {
+        let pinned = std::mem::take(&mut self.pinned);
+        for object in pinned {
+            if self.model.is_some() && !self.objects.contains(&object) {
+                self.error(object, "pinned object is not an object start".to_string());
+            }
+        }
+        let references = std::mem::take(&mut self.references);
+        for (object, slot, target) in references {
+            if self.objects.contains(&target) {
+                continue;
+            }
+            if target < self.heap_start || target >= self.heap_end {
+                self.error(
+                    object,
+                    format!(
+                        "reference field at {} points to {} outside of the heap",
+                        slot, target
+                    ),
+                );
+            } else {
+                self.error(
+                    object,
+                    format!(
+                        "reference field at {} points to {} that is not an object start",
+                        slot, target
+                    ),
+                );
+            }
+        }
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        self.errors.sort_by_key(|error| error.address);
+        Err(VerifyReport {
+            phase,
+            errors: self.errors,
+        })
+    }
+
+    /// Verify a single object, returns its size if the object could be parsed.
+    fn verify_object(
+        &mut self,
+        block: &Block,
+        object: Address,
+        model: &dyn ObjectModel,
+    ) -> Option<usize> {
+        let size = self.verify_header(object, model)?;
+        if size < size_of::<HeapObj>() || object.plus(size) > block.block_limit() {
+            self.error(
+                object,
+                format!(
+                    "object size {} does not fit into block {}",
+                    size,
+                    block.base_address()
+                ),
+            );
+            return None;
+        }
+        let first_line = block.line_index(object);
+        let last_line = block.line_index(object.plus(size - 1));
+        for index in first_line..=last_line {
+            if block.line_mark(index) != LineMark::Live {
+                self.error(
+                    object,
+                    format!(
+                        "line {} at {} spanned by the object is not marked",
+                        index,
+                        block.line_address(index)
+                    ),
+                );
+            }
+        }
+        self.objects.insert(object);
+        self.verify_references(object, size, model);
+        Some(size)
+    }
+
+    /// Check the header and the klass of an object, returns its size if the klass is
+    /// known.
+    fn verify_header(&mut self, object: Address, model: &dyn ObjectModel) -> Option<usize> {
+        let header = unsafe { HeapObj::from_address(object) };
+        if !header.has_valid_header() {
+            self.error(
+                object,
+                format!("invalid header bits {:#010b}", header.header()),
+            );
+        } else if header.is_forwarding() {
+            self.error(
+                object,
+                "object is forwarded outside of a collection".to_string(),
+            );
+        }
+        let klass = model.klass(object);
+        if !model.is_klass(klass) {
+            self.error(
+                object,
+                format!("klass pointer {} is not a known klass", klass),
+            );
+            return None;
+        }
+        Some(model.object_size(object))
+    }
+
+    /// Collect the non null references of the object, checking that their fields are
+    /// inside of it.
+    fn verify_references(&mut self, object: Address, size: usize, model: &dyn ObjectModel) {
+        let mut slots = Vec::new();
+        model.for_each_reference(object, &mut |slot| slots.push(slot));
+        for slot in slots {
+            if slot < object || slot.plus(size_of::<Address>()) > object.plus(size) {
+                self.error(
+                    object,
+                    format!("reference field at {} is outside of the object", slot),
+                );
+                continue;
+            }
+            let target: Address = slot.load();
+            if !target.is_null() {
+                self.references.push((object, slot, target));
+            }
+        }
+    }
+
+    fn error(&mut self, address: Address, message: String) {
+        self.errors.push(VerifyError { address, message });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::{parse_selection, HeapVerifier, VerifyPhase, VERIFY_AFTER, VERIFY_BEFORE};
+    use crate::model::address::Address;
+    use crate::model::block::{Block, BLOCK_SIZE, LINE_COUNT, LINE_SIZE};
+    use crate::model::heap_obj::HeapObj;
+    use crate::model::line_map::LineMap;
+    use crate::model::object_model::ObjectModel;
+    use crate::utils::mmap::MemoryMap;
+
+    const KLASS_OFFSET: usize = size_of::<HeapObj>();
+    const SIZE_OFFSET: usize = KLASS_OFFSET + 8;
+    const REFS_OFFSET: usize = SIZE_OFFSET + 8;
+
+    /// Objects are laid out as header, klass, size and then reference fields.
+    struct TestModel {
+        klass: Address,
+    }
+
+    impl ObjectModel for TestModel {
+        fn object_size(&self, object: Address) -> usize {
+            object.plus(SIZE_OFFSET).load()
+        }
+
+        fn klass(&self, object: Address) -> Address {
+            object.plus(KLASS_OFFSET).load()
+        }
+
+        fn is_klass(&self, klass: Address) -> bool {
+            klass == self.klass
+        }
+
+        fn for_each_reference(&self, object: Address, visitor: &mut dyn FnMut(Address)) {
+            let mut slot = object.plus(REFS_OFFSET);
+            while slot < object.plus(self.object_size(object)) {
+                visitor(slot);
+                slot = slot.plus(size_of::<Address>());
+            }
+        }
+    }
+
+    fn new_object(block: &mut Block, line: usize, klass: Address, refs: &[Address]) -> Address {
+        let object = block.line_address(line);
+        let size = REFS_OFFSET + std::mem::size_of_val(refs);
+        object.store(0u8);
+        object.plus(KLASS_OFFSET).store(klass);
+        object.plus(SIZE_OFFSET).store(size);
+        for (index, target) in refs.iter().enumerate() {
+            object
+                .plus(REFS_OFFSET + index * size_of::<Address>())
+                .store(*target);
+        }
+        block.mark_lines(object, object.plus(size));
+        object
+    }
+
+    #[test]
+    fn phases_are_parsed() {
+        assert_eq!(parse_selection("before"), Some(VERIFY_BEFORE));
+        assert_eq!(
+            parse_selection("before, after"),
+            Some(VERIFY_BEFORE | VERIFY_AFTER)
+        );
+        assert_eq!(parse_selection("none"), Some(0));
+        assert_eq!(parse_selection("sometimes"), None);
+    }
+
+    #[test]
+    fn broken_objects_are_reported_with_their_address() {
+        let memory_map = MemoryMap::new(BLOCK_SIZE);
+        let line_map = LineMap::new(LINE_COUNT);
+        let base = memory_map.commit_block(0).unwrap();
+        let mut block = Block::new(base, line_map.block_line_marks(0));
+        let klass = Address::new(0x1000);
+        let model = TestModel { klass };
+        let heap_end = base.plus(BLOCK_SIZE);
+
+        let first = new_object(&mut block, 0, klass, &[]);
+        let second = new_object(&mut block, 1, klass, &[first, Address::zero()]);
+        let mut verifier = HeapVerifier::new(base, heap_end, Some(&model));
+        verifier.verify_block(&block);
+        assert!(verifier.finish(VerifyPhase::OnDemand).is_ok());
+
+        let dangling = new_object(&mut block, 2, klass, &[first.plus(8)]);
+        let outside = new_object(&mut block, 3, klass, &[heap_end.plus(LINE_SIZE)]);
+        let unknown = new_object(&mut block, 4, Address::new(0x2000), &[]);
+        let oversized = new_object(&mut block, 6, klass, &[]);
+        oversized.plus(SIZE_OFFSET).store(LINE_SIZE * 2);
+        second.store(0b1000_0000u8);
+        let mut verifier = HeapVerifier::new(base, heap_end, Some(&model));
+        verifier.verify_block(&block);
+        let report = verifier.finish(VerifyPhase::AfterGc).unwrap_err();
+        let addresses: Vec<Address> = report.errors.iter().map(|error| error.address).collect();
+        assert_eq!(
+            addresses,
+            vec![second, dangling, outside, unknown, oversized]
+        );
+        let message = report.to_string();
+        assert!(message.starts_with("heap verification failed after gc with 5 error(s):"));
+        assert!(message.contains(&format!(
+            "points to {} that is not an object start",
+            first.plus(8)
+        )));
+        assert!(message.contains(&format!(
+            "line 7 at {} spanned by the object",
+            block.line_address(7)
+        )));
+    }
+
+    #[test]
+    fn block_marks_must_agree_with_line_marks() {
+        let memory_map = MemoryMap::new(BLOCK_SIZE);
+        let line_map = LineMap::new(LINE_COUNT);
+        let base = memory_map.commit_block(0).unwrap();
+        let mut block = Block::new(base, line_map.block_line_marks(0));
+        block.mark_lines(base, base.plus(LINE_SIZE));
+
+        let mut verifier = HeapVerifier::new(base, base.plus(BLOCK_SIZE), None);
+        verifier.verify_free_block(&block);
+        let report = verifier.finish(VerifyPhase::BeforeGc).unwrap_err();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].address, base);
+
+        block.sweep();
+        let mut verifier = HeapVerifier::new(base, base.plus(BLOCK_SIZE), None);
+        verifier.verify_block_mark(&block);
+        assert!(verifier.finish(VerifyPhase::AfterGc).is_ok());
+    }
+}
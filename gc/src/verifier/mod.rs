@@ -0,0 +1 @@
+pub mod heap_verifier;
@@ -0,0 +1,334 @@
+//! The `javap` tool: prints the declarations, constant pool, code and attributes of
+//! class files, with the options and output of the JDK tool.
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::UNIX_EPOCH,
+};
+
+use clap::Parser;
+use jvm::{
+    classfile::{
+        class_file_parser::ClassFileParser,
+        class_printer::{print_class, Access, PrintOptions},
+    },
+    classloader::{
+        class_file_stream::ClassFileStream,
+        class_path::{create_class_path_entry, ClassPathEntry},
+    },
+};
+use lib::zip::checksum::Sha256;
+
+const USAGE: &str = "\
+Usage: javap <options> <classes>
+where possible options include:
+  -? -h --help -help               Print this help message
+  -version                         Version information
+  -v  -verbose                     Print additional information
+  -l                               Print line number and local variable tables
+  -public                          Show only public classes and members
+  -protected                       Show protected/public classes and members
+  -package                         Show package/protected/public classes
+                                   and members (default)
+  -p  -private                     Show all classes and members
+  -c                               Disassemble the code
+  -s                               Print internal type signatures
+  -constants                       Show final constants
+  --class-path <path>              Specify where to find user class files
+  -classpath <path>                Specify where to find user class files
+  -cp <path>                       Specify where to find user class files
+
+Each class to be shown can be specified by a filename or a class name.";
+
+#[derive(Debug, PartialEq, Eq, Parser)]
+#[command(disable_help_flag = true, disable_version_flag = true)]
+struct Options {
+    #[arg(short, long)]
+    help: bool,
+    #[arg(long)]
+    version: bool,
+    #[arg(short, long)]
+    verbose: bool,
+    #[arg(short = 'l')]
+    line_numbers: bool,
+    #[arg(short = 'c')]
+    code: bool,
+    #[arg(short = 's')]
+    descriptors: bool,
+    #[arg(long)]
+    constants: bool,
+    #[arg(long, overrides_with_all = ["protected", "package", "private"])]
+    public: bool,
+    #[arg(long, overrides_with_all = ["public", "package", "private"])]
+    protected: bool,
+    #[arg(long, overrides_with_all = ["public", "protected", "private"])]
+    package: bool,
+    #[arg(short, long, overrides_with_all = ["public", "protected", "package"])]
+    private: bool,
+    #[arg(long)]
+    class_path: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Options {
+    fn print_options(&self) -> PrintOptions {
+        let access = if self.public {
+            Access::Public
+        } else if self.protected {
+            Access::Protected
+        } else if self.private {
+            Access::Private
+        } else {
+            Access::Package
+        };
+        PrintOptions {
+            verbose: self.verbose,
+            code: self.code,
+            line_numbers: self.line_numbers,
+            descriptors: self.descriptors,
+            constants: self.constants,
+            access,
+        }
+    }
+}
+
+/// The long options of the JDK tool take a single dash, e.g. `-verbose` or `-cp`, they
+/// are given to clap with two.
+fn long_option(arg: String) -> String {
+    match arg.as_str() {
+        "-?" | "-help" => "--help".to_string(),
+        "-version" | "-verbose" | "-constants" | "-public" | "-protected" | "-package"
+        | "-private" => format!("-{}", arg),
+        "-cp" | "-classpath" => "--class-path".to_string(),
+        _ => arg,
+    }
+}
+
+fn parse_options<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let args = std::iter::once("javap".to_string()).chain(args.map(long_option));
+    let options = Options::try_parse_from(args).map_err(|error| {
+        let message = error.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+        first_line.trim_start_matches("error: ").to_string()
+    })?;
+    if options.classes.is_empty() && !options.help && !options.version {
+        return Err("no classes specified".to_string());
+    }
+    Ok(options)
+}
+
+/// The class file, its location for the `Classfile` line and its file when there is one.
+struct ClassFile {
+    stream: ClassFileStream,
+    location: String,
+    file: Option<PathBuf>,
+}
+
+/// The class file of the path, or of the class name in the class path then in the
+/// modules of the JDK.
+fn find_class(
+    class: &str,
+    class_path: &[Box<dyn ClassPathEntry>],
+) -> io::Result<Option<ClassFile>> {
+    let path = Path::new(class);
+    if class.ends_with(".class") && path.is_file() {
+        let bytes = fs::read(path)?;
+        let file = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        return Ok(Some(ClassFile {
+            stream: ClassFileStream::new(bytes, class.to_string()),
+            location: file.display().to_string(),
+            file: Some(file),
+        }));
+    }
+    let name = format!("{}.class", class.replace('.', "/"));
+    for entry in class_path {
+        let Some(stream) = entry.open_stream(&name)? else {
+            continue;
+        };
+        let (location, file) = if entry.is_modules_image() {
+            (format!("{}/{}", stream.source(), name), None)
+        } else if entry.is_jar_file() {
+            let jar = fs::canonicalize(entry.name()).unwrap_or_else(|_| entry.name().into());
+            (format!("jar:file:{}!/{}", jar.display(), name), None)
+        } else {
+            let file = Path::new(entry.name()).join(&name);
+            let file = fs::canonicalize(&file).unwrap_or(file);
+            (file.display().to_string(), Some(file))
+        };
+        return Ok(Some(ClassFile {
+            stream,
+            location,
+            file,
+        }));
+    }
+    Ok(None)
+}
+
+/// The entries of the class path, then the modules of the running JDK.
+fn class_path_entries(class_path: Option<&str>) -> Vec<Box<dyn ClassPathEntry>> {
+    let mut paths: Vec<PathBuf> = env::split_paths(class_path.unwrap_or(".")).collect();
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+        paths.push(Path::new(&java_home).join("lib").join("modules"));
+    }
+    paths
+        .iter()
+        .filter_map(|path| create_class_path_entry(path, false).ok())
+        .collect()
+}
+
+/// `Mon d, yyyy` of the days since 1970-01-01.
+fn format_date(days: i64) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    // the civil calendar from the days, counted in eras of 400 years from March
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{} {}, {}", MONTHS[month as usize - 1], day, year)
+}
+
+fn print_header(class_file: &ClassFile) {
+    println!("Classfile {}", class_file.location);
+    let metadata = class_file
+        .file
+        .as_ref()
+        .and_then(|file| fs::metadata(file).ok());
+    if let Some(metadata) = metadata {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs());
+        println!(
+            "  Last modified {}; size {} bytes",
+            format_date((modified / 86400) as i64),
+            metadata.len()
+        );
+    }
+    println!(
+        "  SHA-256 checksum {}",
+        checksum(class_file.stream.buffer())
+    );
+}
+
+/// The SHA-256 digest of the class file in lower case hex, like the JDK prints it.
+fn checksum(bytes: &[u8]) -> String {
+    let mut sha = Sha256::new();
+    sha.update(bytes);
+    sha.finish()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
+    if options.version {
+        println!("{}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    let class_path = class_path_entries(options.class_path.as_deref());
+    let mut success = true;
+    for class in &options.classes {
+        let mut class_file = match find_class(class, &class_path) {
+            Ok(Some(class_file)) => class_file,
+            Ok(None) => {
+                eprintln!("Error: class not found: {}", class);
+                success = false;
+                continue;
+            }
+            Err(error) => {
+                eprintln!("Error: error while reading class {}: {}", class, error);
+                success = false;
+                continue;
+            }
+        };
+        let parser = ClassFileParser::new(&mut class_file.stream).accept_module_info();
+        let klass = match parser.parse() {
+            Ok(klass) => klass,
+            Err(error) => {
+                eprintln!("Error: error while reading class {}: {}", class, error);
+                success = false;
+                continue;
+            }
+        };
+        let print = options.print_options();
+        if print.verbose {
+            print_header(&class_file);
+        }
+        print!("{}", print_class(&klass, &print));
+    }
+    if !success {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jvm::classfile::class_printer::{Access, PrintOptions};
+
+    use super::{format_date, parse_options, Options};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn we_can_parse_the_options() {
+        let options = parse(&["-c", "-p", "-cp", "classes", "T", "java.lang.Object"]).unwrap();
+        assert_eq!(
+            options.print_options(),
+            PrintOptions {
+                code: true,
+                access: Access::Private,
+                ..PrintOptions::default()
+            }
+        );
+        assert_eq!(options.class_path.as_deref(), Some("classes"));
+        assert_eq!(options.classes, ["T", "java.lang.Object"]);
+        let options = parse(&["-v", "-l", "-s", "-constants", "-public", "T.class"]).unwrap();
+        let print = options.print_options();
+        assert!(print.verbose && print.line_numbers);
+        assert!(print.descriptors && print.constants);
+        assert_eq!(print.access, Access::Public);
+        let options = parse(&["-private", "-protected", "--class-path", "a:b", "T"]).unwrap();
+        assert_eq!(options.print_options().access, Access::Protected);
+        assert_eq!(options.class_path.as_deref(), Some("a:b"));
+        assert!(parse(&["-help"]).unwrap().help);
+        assert!(parse(&["-?"]).unwrap().help);
+        assert!(parse(&["-version"]).unwrap().version);
+        assert_eq!(parse(&[]).unwrap_err(), "no classes specified");
+        assert_eq!(
+            parse(&["-x", "T"]).unwrap_err(),
+            "unexpected argument '-x' found"
+        );
+        assert!(parse(&["T", "-cp"]).is_err());
+    }
+
+    #[test]
+    fn we_can_format_the_dates() {
+        assert_eq!(format_date(0), "Jan 1, 1970");
+        assert_eq!(format_date(11016), "Feb 29, 2000");
+        assert_eq!(format_date(20745), "Oct 19, 2026");
+    }
+}
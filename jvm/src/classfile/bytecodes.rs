@@ -0,0 +1,481 @@
+//! The Java bytecodes, their names and lengths.
+use crate::utilities::definition::u1;
+
+pub const NOP: u1 = 0;
+pub const ACONST_NULL: u1 = 1;
+pub const ICONST_M1: u1 = 2;
+pub const ICONST_0: u1 = 3;
+pub const ICONST_1: u1 = 4;
+pub const ICONST_2: u1 = 5;
+pub const ICONST_3: u1 = 6;
+pub const ICONST_4: u1 = 7;
+pub const ICONST_5: u1 = 8;
+pub const LCONST_0: u1 = 9;
+pub const LCONST_1: u1 = 10;
+pub const FCONST_0: u1 = 11;
+pub const FCONST_1: u1 = 12;
+pub const FCONST_2: u1 = 13;
+pub const DCONST_0: u1 = 14;
+pub const DCONST_1: u1 = 15;
+pub const BIPUSH: u1 = 16;
+pub const SIPUSH: u1 = 17;
+pub const LDC: u1 = 18;
+pub const LDC_W: u1 = 19;
+pub const LDC2_W: u1 = 20;
+pub const ILOAD: u1 = 21;
+pub const LLOAD: u1 = 22;
+pub const FLOAD: u1 = 23;
+pub const DLOAD: u1 = 24;
+pub const ALOAD: u1 = 25;
+pub const ILOAD_0: u1 = 26;
+pub const LLOAD_0: u1 = 30;
+pub const FLOAD_0: u1 = 34;
+pub const DLOAD_0: u1 = 38;
+pub const ALOAD_0: u1 = 42;
+pub const ALOAD_3: u1 = 45;
+pub const IALOAD: u1 = 46;
+pub const LALOAD: u1 = 47;
+pub const FALOAD: u1 = 48;
+pub const DALOAD: u1 = 49;
+pub const AALOAD: u1 = 50;
+pub const BALOAD: u1 = 51;
+pub const CALOAD: u1 = 52;
+pub const SALOAD: u1 = 53;
+pub const ISTORE: u1 = 54;
+pub const LSTORE: u1 = 55;
+pub const FSTORE: u1 = 56;
+pub const DSTORE: u1 = 57;
+pub const ASTORE: u1 = 58;
+pub const ISTORE_0: u1 = 59;
+pub const LSTORE_0: u1 = 63;
+pub const FSTORE_0: u1 = 67;
+pub const DSTORE_0: u1 = 71;
+pub const ASTORE_0: u1 = 75;
+pub const ASTORE_3: u1 = 78;
+pub const IASTORE: u1 = 79;
+pub const LASTORE: u1 = 80;
+pub const FASTORE: u1 = 81;
+pub const DASTORE: u1 = 82;
+pub const AASTORE: u1 = 83;
+pub const BASTORE: u1 = 84;
+pub const CASTORE: u1 = 85;
+pub const SASTORE: u1 = 86;
+pub const POP: u1 = 87;
+pub const POP2: u1 = 88;
+pub const DUP: u1 = 89;
+pub const DUP_X1: u1 = 90;
+pub const DUP_X2: u1 = 91;
+pub const DUP2: u1 = 92;
+pub const DUP2_X1: u1 = 93;
+pub const DUP2_X2: u1 = 94;
+pub const SWAP: u1 = 95;
+pub const IADD: u1 = 96;
+pub const LADD: u1 = 97;
+pub const FADD: u1 = 98;
+pub const DADD: u1 = 99;
+pub const ISUB: u1 = 100;
+pub const LSUB: u1 = 101;
+pub const FSUB: u1 = 102;
+pub const DSUB: u1 = 103;
+pub const IMUL: u1 = 104;
+pub const LMUL: u1 = 105;
+pub const FMUL: u1 = 106;
+pub const DMUL: u1 = 107;
+pub const IDIV: u1 = 108;
+pub const LDIV: u1 = 109;
+pub const FDIV: u1 = 110;
+pub const DDIV: u1 = 111;
+pub const IREM: u1 = 112;
+pub const LREM: u1 = 113;
+pub const FREM: u1 = 114;
+pub const DREM: u1 = 115;
+pub const INEG: u1 = 116;
+pub const LNEG: u1 = 117;
+pub const FNEG: u1 = 118;
+pub const DNEG: u1 = 119;
+pub const ISHL: u1 = 120;
+pub const LSHL: u1 = 121;
+pub const ISHR: u1 = 122;
+pub const LSHR: u1 = 123;
+pub const IUSHR: u1 = 124;
+pub const LUSHR: u1 = 125;
+pub const IAND: u1 = 126;
+pub const LAND: u1 = 127;
+pub const IOR: u1 = 128;
+pub const LOR: u1 = 129;
+pub const IXOR: u1 = 130;
+pub const LXOR: u1 = 131;
+pub const IINC: u1 = 132;
+pub const I2L: u1 = 133;
+pub const I2F: u1 = 134;
+pub const I2D: u1 = 135;
+pub const L2I: u1 = 136;
+pub const L2F: u1 = 137;
+pub const L2D: u1 = 138;
+pub const F2I: u1 = 139;
+pub const F2L: u1 = 140;
+pub const F2D: u1 = 141;
+pub const D2I: u1 = 142;
+pub const D2L: u1 = 143;
+pub const D2F: u1 = 144;
+pub const I2B: u1 = 145;
+pub const I2C: u1 = 146;
+pub const I2S: u1 = 147;
+pub const LCMP: u1 = 148;
+pub const FCMPL: u1 = 149;
+pub const FCMPG: u1 = 150;
+pub const DCMPL: u1 = 151;
+pub const DCMPG: u1 = 152;
+pub const IFEQ: u1 = 153;
+pub const IFNE: u1 = 154;
+pub const IFLT: u1 = 155;
+pub const IFGE: u1 = 156;
+pub const IFGT: u1 = 157;
+pub const IFLE: u1 = 158;
+pub const IF_ICMPEQ: u1 = 159;
+pub const IF_ICMPNE: u1 = 160;
+pub const IF_ICMPLT: u1 = 161;
+pub const IF_ICMPGE: u1 = 162;
+pub const IF_ICMPGT: u1 = 163;
+pub const IF_ICMPLE: u1 = 164;
+pub const IF_ACMPEQ: u1 = 165;
+pub const IF_ACMPNE: u1 = 166;
+pub const GOTO: u1 = 167;
+pub const JSR: u1 = 168;
+pub const RET: u1 = 169;
+pub const TABLESWITCH: u1 = 170;
+pub const LOOKUPSWITCH: u1 = 171;
+pub const IRETURN: u1 = 172;
+pub const LRETURN: u1 = 173;
+pub const FRETURN: u1 = 174;
+pub const DRETURN: u1 = 175;
+pub const ARETURN: u1 = 176;
+pub const RETURN: u1 = 177;
+pub const GETSTATIC: u1 = 178;
+pub const PUTSTATIC: u1 = 179;
+pub const GETFIELD: u1 = 180;
+pub const PUTFIELD: u1 = 181;
+pub const INVOKEVIRTUAL: u1 = 182;
+pub const INVOKESPECIAL: u1 = 183;
+pub const INVOKESTATIC: u1 = 184;
+pub const INVOKEINTERFACE: u1 = 185;
+pub const INVOKEDYNAMIC: u1 = 186;
+pub const NEW: u1 = 187;
+pub const NEWARRAY: u1 = 188;
+pub const ANEWARRAY: u1 = 189;
+pub const ARRAYLENGTH: u1 = 190;
+pub const ATHROW: u1 = 191;
+pub const CHECKCAST: u1 = 192;
+pub const INSTANCEOF: u1 = 193;
+pub const MONITORENTER: u1 = 194;
+pub const MONITOREXIT: u1 = 195;
+pub const WIDE: u1 = 196;
+pub const MULTIANEWARRAY: u1 = 197;
+pub const IFNULL: u1 = 198;
+pub const IFNONNULL: u1 = 199;
+pub const GOTO_W: u1 = 200;
+pub const JSR_W: u1 = 201;
+
+/// Name and length of each bytecode, a length of 0 is a variable length.
+const BYTECODES: [(&str, u8); 202] = [
+    ("nop", 1),
+    ("aconst_null", 1),
+    ("iconst_m1", 1),
+    ("iconst_0", 1),
+    ("iconst_1", 1),
+    ("iconst_2", 1),
+    ("iconst_3", 1),
+    ("iconst_4", 1),
+    ("iconst_5", 1),
+    ("lconst_0", 1),
+    ("lconst_1", 1),
+    ("fconst_0", 1),
+    ("fconst_1", 1),
+    ("fconst_2", 1),
+    ("dconst_0", 1),
+    ("dconst_1", 1),
+    ("bipush", 2),
+    ("sipush", 3),
+    ("ldc", 2),
+    ("ldc_w", 3),
+    ("ldc2_w", 3),
+    ("iload", 2),
+    ("lload", 2),
+    ("fload", 2),
+    ("dload", 2),
+    ("aload", 2),
+    ("iload_0", 1),
+    ("iload_1", 1),
+    ("iload_2", 1),
+    ("iload_3", 1),
+    ("lload_0", 1),
+    ("lload_1", 1),
+    ("lload_2", 1),
+    ("lload_3", 1),
+    ("fload_0", 1),
+    ("fload_1", 1),
+    ("fload_2", 1),
+    ("fload_3", 1),
+    ("dload_0", 1),
+    ("dload_1", 1),
+    ("dload_2", 1),
+    ("dload_3", 1),
+    ("aload_0", 1),
+    ("aload_1", 1),
+    ("aload_2", 1),
+    ("aload_3", 1),
+    ("iaload", 1),
+    ("laload", 1),
+    ("faload", 1),
+    ("daload", 1),
+    ("aaload", 1),
+    ("baload", 1),
+    ("caload", 1),
+    ("saload", 1),
+    ("istore", 2),
+    ("lstore", 2),
+    ("fstore", 2),
+    ("dstore", 2),
+    ("astore", 2),
+    ("istore_0", 1),
+    ("istore_1", 1),
+    ("istore_2", 1),
+    ("istore_3", 1),
+    ("lstore_0", 1),
+    ("lstore_1", 1),
+    ("lstore_2", 1),
+    ("lstore_3", 1),
+    ("fstore_0", 1),
+    ("fstore_1", 1),
+    ("fstore_2", 1),
+    ("fstore_3", 1),
+    ("dstore_0", 1),
+    ("dstore_1", 1),
+    ("dstore_2", 1),
+    ("dstore_3", 1),
+    ("astore_0", 1),
+    ("astore_1", 1),
+    ("astore_2", 1),
+    ("astore_3", 1),
+    ("iastore", 1),
+    ("lastore", 1),
+    ("fastore", 1),
+    ("dastore", 1),
+    ("aastore", 1),
+    ("bastore", 1),
+    ("castore", 1),
+    ("sastore", 1),
+    ("pop", 1),
+    ("pop2", 1),
+    ("dup", 1),
+    ("dup_x1", 1),
+    ("dup_x2", 1),
+    ("dup2", 1),
+    ("dup2_x1", 1),
+    ("dup2_x2", 1),
+    ("swap", 1),
+    ("iadd", 1),
+    ("ladd", 1),
+    ("fadd", 1),
+    ("dadd", 1),
+    ("isub", 1),
+    ("lsub", 1),
+    ("fsub", 1),
+    ("dsub", 1),
+    ("imul", 1),
+    ("lmul", 1),
+    ("fmul", 1),
+    ("dmul", 1),
+    ("idiv", 1),
+    ("ldiv", 1),
+    ("fdiv", 1),
+    ("ddiv", 1),
+    ("irem", 1),
+    ("lrem", 1),
+    ("frem", 1),
+    ("drem", 1),
+    ("ineg", 1),
+    ("lneg", 1),
+    ("fneg", 1),
+    ("dneg", 1),
+    ("ishl", 1),
+    ("lshl", 1),
+    ("ishr", 1),
+    ("lshr", 1),
+    ("iushr", 1),
+    ("lushr", 1),
+    ("iand", 1),
+    ("land", 1),
+    ("ior", 1),
+    ("lor", 1),
+    ("ixor", 1),
+    ("lxor", 1),
+    ("iinc", 3),
+    ("i2l", 1),
+    ("i2f", 1),
+    ("i2d", 1),
+    ("l2i", 1),
+    ("l2f", 1),
+    ("l2d", 1),
+    ("f2i", 1),
+    ("f2l", 1),
+    ("f2d", 1),
+    ("d2i", 1),
+    ("d2l", 1),
+    ("d2f", 1),
+    ("i2b", 1),
+    ("i2c", 1),
+    ("i2s", 1),
+    ("lcmp", 1),
+    ("fcmpl", 1),
+    ("fcmpg", 1),
+    ("dcmpl", 1),
+    ("dcmpg", 1),
+    ("ifeq", 3),
+    ("ifne", 3),
+    ("iflt", 3),
+    ("ifge", 3),
+    ("ifgt", 3),
+    ("ifle", 3),
+    ("if_icmpeq", 3),
+    ("if_icmpne", 3),
+    ("if_icmplt", 3),
+    ("if_icmpge", 3),
+    ("if_icmpgt", 3),
+    ("if_icmple", 3),
+    ("if_acmpeq", 3),
+    ("if_acmpne", 3),
+    ("goto", 3),
+    ("jsr", 3),
+    ("ret", 2),
+    ("tableswitch", 0),
+    ("lookupswitch", 0),
+    ("ireturn", 1),
+    ("lreturn", 1),
+    ("freturn", 1),
+    ("dreturn", 1),
+    ("areturn", 1),
+    ("return", 1),
+    ("getstatic", 3),
+    ("putstatic", 3),
+    ("getfield", 3),
+    ("putfield", 3),
+    ("invokevirtual", 3),
+    ("invokespecial", 3),
+    ("invokestatic", 3),
+    ("invokeinterface", 5),
+    ("invokedynamic", 5),
+    ("new", 3),
+    ("newarray", 2),
+    ("anewarray", 3),
+    ("arraylength", 1),
+    ("athrow", 1),
+    ("checkcast", 3),
+    ("instanceof", 3),
+    ("monitorenter", 1),
+    ("monitorexit", 1),
+    ("wide", 0),
+    ("multianewarray", 4),
+    ("ifnull", 3),
+    ("ifnonnull", 3),
+    ("goto_w", 5),
+    ("jsr_w", 5),
+];
+
+/// Name of the bytecode, `None` for an undefined bytecode.
+pub fn name(code: u1) -> Option<&'static str> {
+    BYTECODES.get(code as usize).map(|(name, _)| *name)
+}
+
+/// Read a big endian u2 of the code.
+pub fn u2_at(code: &[u1], index: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        code.get(index..index + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Read a big endian s4 of the code.
+pub fn s4_at(code: &[u1], index: usize) -> Option<i32> {
+    Some(i32::from_be_bytes(
+        code.get(index..index + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Length of the instruction at the bci, `None` for an undefined bytecode or an
+/// instruction that does not fit in the code.
+pub fn length_at(code: &[u1], bci: usize) -> Option<usize> {
+    let opcode = *code.get(bci)?;
+    let length = match BYTECODES.get(opcode as usize)?.1 {
+        0 if opcode == WIDE => match *code.get(bci + 1)? {
+            IINC => 6,
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET => 4,
+            _ => return None,
+        },
+        0 => {
+            // the operands are aligned on four bytes from the start of the code
+            let operands = (bci + 4) & !3;
+            if opcode == TABLESWITCH {
+                let low = s4_at(code, operands + 4)? as i64;
+                let high = s4_at(code, operands + 8)? as i64;
+                if low > high {
+                    return None;
+                }
+                operands + 12 + 4 * (high - low + 1) as usize - bci
+            } else {
+                let pairs = s4_at(code, operands + 4)?;
+                if pairs < 0 {
+                    return None;
+                }
+                operands + 8 + 8 * pairs as usize - bci
+            }
+        }
+        length => length as usize,
+    };
+    (bci + length <= code.len()).then_some(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{length_at, name, GOTO_W, IINC, LOOKUPSWITCH, TABLESWITCH, WIDE};
+
+    #[test]
+    fn we_can_get_the_length_of_instructions() {
+        assert_eq!(name(GOTO_W), Some("goto_w"));
+        assert_eq!(name(0xCA), None);
+        assert_eq!(length_at(&[0x00, 0x10, 0x05], 1), Some(2));
+        assert_eq!(length_at(&[0x10], 0), None);
+        assert_eq!(length_at(&[WIDE, IINC, 0, 1, 0, 1], 0), Some(6));
+        assert_eq!(length_at(&[WIDE, 0x15, 0, 1], 0), Some(4));
+        assert_eq!(length_at(&[WIDE, 0x60, 0, 1], 0), None);
+        // tableswitch at 1 is padded to 4, low 0 high 1
+        let mut code = vec![0x00, TABLESWITCH, 0, 0];
+        code.extend_from_slice(&[
+            0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 20, 0, 0, 0, 20,
+        ]);
+        assert_eq!(length_at(&code, 1), Some(23));
+        let code = [
+            LOOKUPSWITCH,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            9,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            5,
+            0,
+            0,
+            0,
+            9,
+        ];
+        assert_eq!(length_at(&code, 0), Some(20));
+        assert_eq!(length_at(&code[..19], 0), None);
+    }
+}
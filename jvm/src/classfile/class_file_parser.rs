@@ -0,0 +1,2054 @@
+use std::{collections::HashSet, mem};
+
+use crate::{
+    classloader::class_file_stream::ClassFileStream,
+    model::{
+        attribute::{
+            Annotation, Attribute, BootstrapMethod, ElementValue, EnclosingMethod, InnerClass,
+            LocalVariable, MethodParameter, Module, ModulePackage, ModuleProvides, ModuleRequires,
+            RecordComponent, TargetInfo, TypeAnnotation,
+        },
+        constant_pool::{ConstantPool, ConstantPoolEntry},
+        field::Field,
+        instance_klass::InstanceKlass,
+        method::{Code, ExceptionTableEntry, LineNumberTable, Method},
+    },
+    utilities::{
+        access_flags::*,
+        definition::{u1, u2, u4},
+        exceptions::Exception,
+    },
+};
+
+pub const JAVA_MAGIC: u4 = 0xCAFEBABE;
+
+pub const JAVA_MIN_SUPPORTED_VERSION: u2 = 45;
+pub const JAVA_MAX_SUPPORTED_VERSION: u2 = 61;
+pub const JAVA_PREVIEW_MINOR_VERSION: u2 = 0xFFFF;
+
+const JAVA_1_5_VERSION: u2 = 49;
+const JAVA_6_VERSION: u2 = 50;
+const JAVA_7_VERSION: u2 = 51;
+const JAVA_8_VERSION: u2 = 52;
+const JAVA_9_VERSION: u2 = 53;
+const JAVA_11_VERSION: u2 = 55;
+const JAVA_12_VERSION: u2 = 56;
+const JAVA_16_VERSION: u2 = 60;
+const JAVA_17_VERSION: u2 = 61;
+
+const OBJECT_INITIALIZER_NAME: &str = "<init>";
+const CLASS_INITIALIZER_NAME: &str = "<clinit>";
+/// The most slots of the parameters of a method, with `this` (JVMS §4.3.3).
+const MAX_PARAMETER_SLOTS: usize = 255;
+/// The most dimensions of an array type (JVMS §4.4.1).
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+/// The most nested annotations and arrays in an element value.
+const MAX_ANNOTATION_DEPTH: usize = 255;
+
+/// The structures with attributes (JVMS §4.7).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AttributeOwner {
+    Class,
+    Field,
+    Method,
+    Code,
+    RecordComponent,
+}
+
+/// Parse the class file of the stream into an `InstanceKlass`.
+pub struct ClassFileParser<'a> {
+    stream: &'a mut ClassFileStream,
+    accept_module_info: bool,
+}
+
+impl<'a> ClassFileParser<'a> {
+    pub fn new(stream: &'a mut ClassFileStream) -> Self {
+        ClassFileParser {
+            stream,
+            accept_module_info: false,
+        }
+    }
+
+    /// Accept a `module-info` class, which the class loaders reject, for the tools.
+    pub fn accept_module_info(mut self) -> Self {
+        self.accept_module_info = true;
+        self
+    }
+
+    fn error(&self, message: &str) -> Exception {
+        Exception::ClassFormatError(format!(
+            "{} in class file {}",
+            message,
+            self.stream.source()
+        ))
+    }
+
+    /// Parse the class file and check its format (JVMS §4.8).
+    pub fn parse(mut self) -> Result<InstanceKlass, Exception> {
+        if self.stream.get_u4()? != JAVA_MAGIC {
+            return Err(self.error("Incompatible magic value"));
+        }
+        let minor_version = self.stream.get_u2()?;
+        let major_version = self.stream.get_u2()?;
+        self.verify_class_version(minor_version, major_version)?;
+        let constants = self.parse_constant_pool()?;
+        self.verify_constant_pool(&constants, major_version)?;
+
+        let mut access_flags = self.stream.get_u2()?;
+        if access_flags & JVM_ACC_INTERFACE != 0 && major_version < JAVA_6_VERSION {
+            // interfaces of old class files may not be marked abstract
+            access_flags |= JVM_ACC_ABSTRACT;
+        }
+        let this_class_index = self.stream.get_u2()?;
+        let class_name = match constants.class_name(this_class_index) {
+            Some(name) if !name.starts_with('[') => name,
+            _ => return Err(self.error(&format!("Invalid this class index {}", this_class_index))),
+        };
+        self.verify_legal_class_modifiers(access_flags, class_name, major_version)?;
+        let is_interface = access_flags & JVM_ACC_INTERFACE != 0;
+        let is_module = access_flags & JVM_ACC_MODULE != 0;
+
+        let super_class_index = self.stream.get_u2()?;
+        match constants.class_name(super_class_index) {
+            None if super_class_index == 0 && (class_name == "java/lang/Object" || is_module) => {}
+            Some(super_name) if !super_name.starts_with('[') => {
+                if is_interface && super_name != "java/lang/Object" {
+                    return Err(self.error("Interfaces must have java.lang.Object as superclass"));
+                }
+            }
+            _ => return Err(self.error(&format!("Invalid superclass index {}", super_class_index))),
+        }
+
+        let interfaces_count = self.stream.get_u2()?;
+        let mut interfaces = Vec::with_capacity(interfaces_count as usize);
+        let mut interface_names = HashSet::new();
+        for _ in 0..interfaces_count {
+            let index = self.stream.get_u2()?;
+            match constants.class_name(index) {
+                Some(name) if name.starts_with('[') => {
+                    return Err(self.error(&format!("Bad interface name \"{}\"", name)))
+                }
+                Some(name) if !interface_names.insert(name) => {
+                    return Err(self.error(&format!("Duplicate interface name \"{}\"", name)))
+                }
+                Some(_) => interfaces.push(index),
+                None => {
+                    return Err(self.error(&format!(
+                        "Interface name has bad constant pool index {}",
+                        index
+                    )))
+                }
+            }
+        }
+
+        let fields_count = self.stream.get_u2()?;
+        let mut fields = Vec::with_capacity(fields_count as usize);
+        let mut field_signatures = HashSet::new();
+        for _ in 0..fields_count {
+            let field = self.parse_field(&constants, class_name, major_version, is_interface)?;
+            let name = constants.utf8(field.name_index()).unwrap();
+            let descriptor = constants.utf8(field.descriptor_index()).unwrap();
+            if !field_signatures.insert((name, descriptor)) {
+                return Err(self.error(&format!(
+                    "Duplicate field name \"{}\" with signature \"{}\"",
+                    name, descriptor
+                )));
+            }
+            fields.push(field);
+        }
+        let methods_count = self.stream.get_u2()?;
+        let mut methods = Vec::with_capacity(methods_count as usize);
+        let mut method_signatures = HashSet::new();
+        for _ in 0..methods_count {
+            let method = self.parse_method(&constants, class_name, major_version, is_interface)?;
+            let name = constants.utf8(method.name_index()).unwrap();
+            let descriptor = constants.utf8(method.descriptor_index()).unwrap();
+            if !method_signatures.insert((name, descriptor)) {
+                return Err(self.error(&format!(
+                    "Duplicate method name \"{}\" with signature \"{}\"",
+                    name, descriptor
+                )));
+            }
+            methods.push(method);
+        }
+        if is_module && !(interfaces.is_empty() && fields.is_empty() && methods.is_empty()) {
+            return Err(self.error("Interfaces, fields or methods in module-info"));
+        }
+        let attributes_count = self.stream.get_u2()?;
+        let mut attributes = Vec::with_capacity(attributes_count as usize);
+        for _ in 0..attributes_count {
+            let (name_index, name) = self.attribute_name(&constants)?;
+            let length = self.stream.get_u4()? as usize;
+            let attribute = self.parse_attribute(
+                &constants,
+                name_index,
+                name,
+                length,
+                AttributeOwner::Class,
+                major_version,
+            )?;
+            self.add_attribute(&mut attributes, attribute, name)?;
+        }
+        if !self.stream.at_eos() {
+            return Err(self.error("Extra bytes at the end"));
+        }
+        if is_module && !attributes.iter().any(|a| matches!(a, Attribute::Module(_))) {
+            return Err(self.error("Missing Module attribute in module-info"));
+        }
+        self.verify_bootstrap_method_indexes(&constants, &attributes)?;
+        Ok(InstanceKlass::new(
+            constants,
+            minor_version,
+            major_version,
+            access_flags,
+            this_class_index,
+            super_class_index,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+        ))
+    }
+
+    fn parse_constant_pool(&mut self) -> Result<ConstantPool, Exception> {
+        let count = self.stream.get_u2()?;
+        let mut entries = Vec::with_capacity(count as usize);
+        entries.push(ConstantPoolEntry::Invalid);
+        while entries.len() < count as usize {
+            let tag = self.stream.get_u1()?;
+            let entry = match tag {
+                ConstantPoolEntry::JVM_CONSTANT_UTF8 => {
+                    let length = self.stream.get_u2()?;
+                    let bytes = self.stream.get_bytes(length as usize)?;
+                    match decode_modified_utf8(bytes) {
+                        Some(string) => ConstantPoolEntry::Utf8(string),
+                        None => {
+                            return Err(self.error(&format!(
+                                "Illegal UTF8 string in constant pool at {}",
+                                entries.len()
+                            )))
+                        }
+                    }
+                }
+                ConstantPoolEntry::JVM_CONSTANT_INTEGER => {
+                    ConstantPoolEntry::Integer(self.stream.get_u4()? as i32)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_FLOAT => {
+                    ConstantPoolEntry::Float(f32::from_bits(self.stream.get_u4()?))
+                }
+                ConstantPoolEntry::JVM_CONSTANT_LONG | ConstantPoolEntry::JVM_CONSTANT_DOUBLE => {
+                    let high = self.stream.get_u4()? as u64;
+                    let bits = high << 32 | self.stream.get_u4()? as u64;
+                    if entries.len() + 1 >= count as usize {
+                        return Err(
+                            self.error(&format!("Invalid constant pool entry {}", entries.len()))
+                        );
+                    }
+                    entries.push(if tag == ConstantPoolEntry::JVM_CONSTANT_LONG {
+                        ConstantPoolEntry::Long(bits as i64)
+                    } else {
+                        ConstantPoolEntry::Double(f64::from_bits(bits))
+                    });
+                    // the entry after a long or a double is unusable
+                    ConstantPoolEntry::Invalid
+                }
+                ConstantPoolEntry::JVM_CONSTANT_CLASS => {
+                    ConstantPoolEntry::Class(self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_STRING => {
+                    ConstantPoolEntry::String(self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_FIELDREF => {
+                    ConstantPoolEntry::FieldRef(self.stream.get_u2()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_METHODREF => {
+                    ConstantPoolEntry::MethodRef(self.stream.get_u2()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_INTERFACE_METHODREF => {
+                    ConstantPoolEntry::InterfaceMethodRef(
+                        self.stream.get_u2()?,
+                        self.stream.get_u2()?,
+                    )
+                }
+                ConstantPoolEntry::JVM_CONSTANT_NAME_AND_TYPE => {
+                    ConstantPoolEntry::NameAndType(self.stream.get_u2()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_METHOD_HANDLE => {
+                    ConstantPoolEntry::MethodHandle(self.stream.get_u1()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_METHOD_TYPE => {
+                    ConstantPoolEntry::MethodType(self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_DYNAMIC => {
+                    ConstantPoolEntry::Dynamic(self.stream.get_u2()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_INVOKE_DYNAMIC => {
+                    ConstantPoolEntry::InvokeDynamic(self.stream.get_u2()?, self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_MODULE => {
+                    ConstantPoolEntry::Module(self.stream.get_u2()?)
+                }
+                ConstantPoolEntry::JVM_CONSTANT_PACKAGE => {
+                    ConstantPoolEntry::Package(self.stream.get_u2()?)
+                }
+                _ => {
+                    return Err(self.error(&format!(
+                        "Unknown constant tag {} at {}",
+                        tag,
+                        entries.len()
+                    )))
+                }
+            };
+            entries.push(entry);
+        }
+        Ok(ConstantPool::new(entries))
+    }
+
+    fn verify_class_version(&self, minor_version: u2, major_version: u2) -> Result<(), Exception> {
+        let unsupported = |message: String| Err(Exception::UnsupportedClassVersionError(message));
+        let source = self.stream.source();
+        if major_version < JAVA_MIN_SUPPORTED_VERSION {
+            return unsupported(format!(
+                "{} (class file version {}.{}) was compiled with an invalid major version",
+                source, major_version, minor_version
+            ));
+        }
+        if major_version > JAVA_MAX_SUPPORTED_VERSION {
+            return unsupported(format!(
+                "{} has been compiled by a more recent version of the Java Runtime (class file \
+                 version {}.{}), this version of the Java Runtime only recognizes class file \
+                 versions up to {}.0",
+                source, major_version, minor_version, JAVA_MAX_SUPPORTED_VERSION
+            ));
+        }
+        if major_version < JAVA_12_VERSION || minor_version == 0 {
+            return Ok(());
+        }
+        if minor_version != JAVA_PREVIEW_MINOR_VERSION {
+            return unsupported(format!(
+                "{} (class file version {}.{}) was compiled with an invalid non-zero minor version",
+                source, major_version, minor_version
+            ));
+        }
+        if major_version != JAVA_MAX_SUPPORTED_VERSION {
+            return unsupported(format!(
+                "{} (class file version {}.{}) was compiled with preview features that are \
+                 unsupported. This version of the Java Runtime only recognizes preview features \
+                 for class file version {}.{}",
+                source,
+                major_version,
+                minor_version,
+                JAVA_MAX_SUPPORTED_VERSION,
+                JAVA_PREVIEW_MINOR_VERSION
+            ));
+        }
+        unsupported(format!(
+            "Preview features are not enabled for {} (class file version {}.{}). Try running \
+             with '--enable-preview'",
+            source, major_version, minor_version
+        ))
+    }
+
+    /// Check the tags and the names and descriptors of the references between entries.
+    fn verify_constant_pool(
+        &self,
+        constants: &ConstantPool,
+        major_version: u2,
+    ) -> Result<(), Exception> {
+        for (index, entry) in constants.entries().iter().enumerate() {
+            let since = match entry.tag() {
+                ConstantPoolEntry::JVM_CONSTANT_METHOD_HANDLE
+                | ConstantPoolEntry::JVM_CONSTANT_METHOD_TYPE
+                | ConstantPoolEntry::JVM_CONSTANT_INVOKE_DYNAMIC => JAVA_7_VERSION,
+                ConstantPoolEntry::JVM_CONSTANT_MODULE
+                | ConstantPoolEntry::JVM_CONSTANT_PACKAGE => JAVA_9_VERSION,
+                ConstantPoolEntry::JVM_CONSTANT_DYNAMIC => JAVA_11_VERSION,
+                _ => JAVA_MIN_SUPPORTED_VERSION,
+            };
+            if major_version < since {
+                return Err(self.error(&format!(
+                    "Class file version does not support constant tag {} at {}",
+                    entry.tag(),
+                    index
+                )));
+            }
+            let invalid_index = |referenced: u2| {
+                self.error(&format!(
+                    "Invalid constant pool index {} at {}",
+                    referenced, index
+                ))
+            };
+            let utf8 = |referenced: u2| {
+                constants
+                    .utf8(referenced)
+                    .ok_or_else(|| invalid_index(referenced))
+            };
+            let name_and_type = |referenced: u2| {
+                constants
+                    .name_and_type(referenced)
+                    .ok_or_else(|| invalid_index(referenced))
+            };
+            match *entry {
+                ConstantPoolEntry::Class(name) => {
+                    let name = utf8(name)?;
+                    if !is_legal_class_name(name) {
+                        return Err(self.error(&format!("Illegal class name \"{}\"", name)));
+                    }
+                }
+                ConstantPoolEntry::String(string) => {
+                    utf8(string)?;
+                }
+                ConstantPoolEntry::NameAndType(name, descriptor) => {
+                    utf8(name)?;
+                    utf8(descriptor)?;
+                }
+                ConstantPoolEntry::FieldRef(class, name_and_type_index)
+                | ConstantPoolEntry::MethodRef(class, name_and_type_index)
+                | ConstantPoolEntry::InterfaceMethodRef(class, name_and_type_index) => {
+                    if !matches!(constants.get(class), Some(ConstantPoolEntry::Class(_))) {
+                        return Err(invalid_index(class));
+                    }
+                    let (name, descriptor) = name_and_type(name_and_type_index)?;
+                    if let ConstantPoolEntry::FieldRef(..) = entry {
+                        self.verify_legal_field_name(name)?;
+                        self.verify_legal_field_signature(name, descriptor)?;
+                    } else {
+                        self.verify_legal_method_name(name)?;
+                        if name == CLASS_INITIALIZER_NAME {
+                            return Err(self.error(&format!(
+                                "Bad method name at constant pool index {}",
+                                index
+                            )));
+                        }
+                        self.verify_legal_method_signature(name, descriptor)?;
+                    }
+                }
+                ConstantPoolEntry::MethodHandle(kind, reference) => {
+                    let (tags, interface_since) = match kind {
+                        1..=4 => (ConstantPoolEntry::JVM_CONSTANT_FIELDREF, None),
+                        5 | 8 => (ConstantPoolEntry::JVM_CONSTANT_METHODREF, None),
+                        6 | 7 => (
+                            ConstantPoolEntry::JVM_CONSTANT_METHODREF,
+                            Some(JAVA_8_VERSION),
+                        ),
+                        9 => (ConstantPoolEntry::JVM_CONSTANT_INTERFACE_METHODREF, None),
+                        _ => {
+                            return Err(self.error(&format!(
+                                "Bad method handle kind at constant pool index {}",
+                                index
+                            )))
+                        }
+                    };
+                    let tag = constants.get(reference).map(ConstantPoolEntry::tag);
+                    let interface = tag
+                        == Some(ConstantPoolEntry::JVM_CONSTANT_INTERFACE_METHODREF)
+                        && interface_since.is_some_and(|since| major_version >= since);
+                    let name = match constants.member_ref(reference) {
+                        Some((_, name, _)) if tag == Some(tags) || interface => name,
+                        _ => {
+                            return Err(self.error(&format!(
+                                "Bad method handle reference at constant pool index {}",
+                                index
+                            )))
+                        }
+                    };
+                    // only `newInvokeSpecial` handles refer to constructors
+                    if kind >= 5 && (kind == 8) != (name == OBJECT_INITIALIZER_NAME) {
+                        return Err(self
+                            .error(&format!("Bad method name at constant pool index {}", index)));
+                    }
+                }
+                ConstantPoolEntry::MethodType(descriptor) => {
+                    let descriptor = utf8(descriptor)?;
+                    if method_parameter_slots(descriptor).is_none() {
+                        return Err(self.error(&format!(
+                            "Illegal method type \"{}\" at constant pool index {}",
+                            descriptor, index
+                        )));
+                    }
+                }
+                ConstantPoolEntry::Dynamic(_, name_and_type_index) => {
+                    let (name, descriptor) = name_and_type(name_and_type_index)?;
+                    self.verify_legal_field_name(name)?;
+                    self.verify_legal_field_signature(name, descriptor)?;
+                }
+                ConstantPoolEntry::InvokeDynamic(_, name_and_type_index) => {
+                    let (name, descriptor) = name_and_type(name_and_type_index)?;
+                    self.verify_legal_method_name(name)?;
+                    if name.starts_with('<') {
+                        return Err(self
+                            .error(&format!("Bad method name at constant pool index {}", index)));
+                    }
+                    self.verify_legal_method_signature(name, descriptor)?;
+                }
+                ConstantPoolEntry::Module(name) | ConstantPoolEntry::Package(name) => {
+                    utf8(name)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_legal_class_modifiers(
+        &self,
+        flags: u2,
+        class_name: &str,
+        major_version: u2,
+    ) -> Result<(), Exception> {
+        let is_interface = flags & JVM_ACC_INTERFACE != 0;
+        let is_abstract = flags & JVM_ACC_ABSTRACT != 0;
+        let major_gte_1_5 = major_version >= JAVA_1_5_VERSION;
+        if flags & JVM_ACC_MODULE != 0 {
+            if self.accept_module_info && major_version >= JAVA_9_VERSION {
+                // the other flags of a module-info class must be zero (JVMS §4.1)
+                if flags != JVM_ACC_MODULE || class_name != "module-info" {
+                    return Err(Exception::ClassFormatError(format!(
+                        "Illegal class modifiers in class {}: 0x{:X}",
+                        class_name, flags
+                    )));
+                }
+                return Ok(());
+            }
+            return Err(self.error(&format!(
+                "{} is not a class because access_flag ACC_MODULE is set",
+                class_name
+            )));
+        }
+        if (is_abstract && flags & JVM_ACC_FINAL != 0)
+            || (is_interface && !is_abstract)
+            || (is_interface && major_gte_1_5 && flags & (JVM_ACC_SUPER | JVM_ACC_ENUM) != 0)
+            || (!is_interface && major_gte_1_5 && flags & JVM_ACC_ANNOTATION != 0)
+        {
+            return Err(Exception::ClassFormatError(format!(
+                "Illegal class modifiers in class {}: 0x{:X}",
+                class_name, flags
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify_legal_field_modifiers(
+        &self,
+        flags: u2,
+        class_name: &str,
+        major_version: u2,
+        is_interface: bool,
+    ) -> Result<(), Exception> {
+        let is_illegal = if is_interface {
+            flags & (JVM_ACC_PUBLIC | JVM_ACC_STATIC | JVM_ACC_FINAL)
+                != JVM_ACC_PUBLIC | JVM_ACC_STATIC | JVM_ACC_FINAL
+                || flags
+                    & (JVM_ACC_PRIVATE | JVM_ACC_PROTECTED | JVM_ACC_VOLATILE | JVM_ACC_TRANSIENT)
+                    != 0
+                || (major_version >= JAVA_1_5_VERSION && flags & JVM_ACC_ENUM != 0)
+        } else {
+            has_illegal_visibility(flags)
+                || flags & (JVM_ACC_FINAL | JVM_ACC_VOLATILE) == JVM_ACC_FINAL | JVM_ACC_VOLATILE
+        };
+        if is_illegal {
+            return Err(Exception::ClassFormatError(format!(
+                "Illegal field modifiers in class {}: 0x{:X}",
+                class_name, flags
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify_legal_method_modifiers(
+        &self,
+        flags: u2,
+        name: &str,
+        class_name: &str,
+        major_version: u2,
+        is_interface: bool,
+    ) -> Result<(), Exception> {
+        let is_public = flags & JVM_ACC_PUBLIC != 0;
+        let is_private = flags & JVM_ACC_PRIVATE != 0;
+        let is_static = flags & JVM_ACC_STATIC != 0;
+        let is_abstract = flags & JVM_ACC_ABSTRACT != 0;
+        let is_strict = flags & JVM_ACC_STRICT != 0;
+        let major_gte_1_5 = major_version >= JAVA_1_5_VERSION;
+        // the strict flag is ignored from Java 17
+        let is_strict_before_17 = is_strict && major_version < JAVA_17_VERSION;
+        let never_in_interfaces =
+            JVM_ACC_PROTECTED | JVM_ACC_FINAL | JVM_ACC_SYNCHRONIZED | JVM_ACC_NATIVE;
+        let is_illegal = if is_interface {
+            if major_version >= JAVA_8_VERSION {
+                is_public == is_private
+                    || flags & never_in_interfaces != 0
+                    || (is_abstract && (is_private || is_static || is_strict_before_17))
+            } else if major_gte_1_5 {
+                !is_public
+                    || flags & (JVM_ACC_PRIVATE | JVM_ACC_STATIC | never_in_interfaces) != 0
+                    || !is_abstract
+                    || is_strict
+            } else {
+                !is_public
+                    || flags & (JVM_ACC_STATIC | JVM_ACC_FINAL | JVM_ACC_NATIVE) != 0
+                    || !is_abstract
+            }
+        } else if has_illegal_visibility(flags) {
+            true
+        } else if name == OBJECT_INITIALIZER_NAME {
+            flags
+                & (JVM_ACC_STATIC
+                    | JVM_ACC_FINAL
+                    | JVM_ACC_SYNCHRONIZED
+                    | JVM_ACC_NATIVE
+                    | JVM_ACC_ABSTRACT)
+                != 0
+                || (major_gte_1_5 && flags & JVM_ACC_BRIDGE != 0)
+        } else {
+            is_abstract
+                && (flags & (JVM_ACC_FINAL | JVM_ACC_NATIVE | JVM_ACC_PRIVATE | JVM_ACC_STATIC)
+                    != 0
+                    || (major_gte_1_5
+                        && (flags & JVM_ACC_SYNCHRONIZED != 0 || is_strict_before_17)))
+        };
+        if is_illegal {
+            return Err(Exception::ClassFormatError(format!(
+                "Method {} in class {} has illegal modifiers: 0x{:X}",
+                name, class_name, flags
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify_legal_field_name(&self, name: &str) -> Result<(), Exception> {
+        if !is_legal_unqualified_name(name, false) {
+            return Err(self.error(&format!("Illegal field name \"{}\"", name)));
+        }
+        Ok(())
+    }
+
+    fn verify_legal_method_name(&self, name: &str) -> Result<(), Exception> {
+        if name != OBJECT_INITIALIZER_NAME
+            && name != CLASS_INITIALIZER_NAME
+            && !is_legal_unqualified_name(name, true)
+        {
+            return Err(self.error(&format!("Illegal method name \"{}\"", name)));
+        }
+        Ok(())
+    }
+
+    fn verify_legal_field_signature(&self, name: &str, descriptor: &str) -> Result<(), Exception> {
+        if field_descriptor_length(descriptor) != Some(descriptor.len()) {
+            return Err(self.error(&format!(
+                "Field \"{}\" has illegal signature \"{}\"",
+                name, descriptor
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the descriptor of the method and return the number of slots of its parameters.
+    fn verify_legal_method_signature(
+        &self,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<usize, Exception> {
+        match method_parameter_slots(descriptor) {
+            // the initializers return void
+            Some(slots) if !name.starts_with('<') || descriptor.ends_with(")V") => Ok(slots),
+            _ => Err(self.error(&format!(
+                "Method \"{}\" has illegal signature \"{}\"",
+                name, descriptor
+            ))),
+        }
+    }
+
+    /// Check that the dynamic constants refer to bootstrap methods of the class.
+    fn verify_bootstrap_method_indexes(
+        &self,
+        constants: &ConstantPool,
+        attributes: &[Attribute],
+    ) -> Result<(), Exception> {
+        let bootstrap_methods = attributes.iter().find_map(|attribute| match attribute {
+            Attribute::BootstrapMethods(methods) => Some(methods.len()),
+            _ => None,
+        });
+        for (index, entry) in constants.entries().iter().enumerate() {
+            if let ConstantPoolEntry::Dynamic(bootstrap_method, _)
+            | ConstantPoolEntry::InvokeDynamic(bootstrap_method, _) = *entry
+            {
+                match bootstrap_methods {
+                    None => return Err(self.error("Missing BootstrapMethods attribute")),
+                    Some(count) if bootstrap_method as usize >= count => {
+                        return Err(self.error(&format!(
+                            "Invalid bootstrap method index {} at constant pool index {}",
+                            bootstrap_method, index
+                        )))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the name index of an attribute, and return it with the name.
+    fn attribute_name<'c>(
+        &mut self,
+        constants: &'c ConstantPool,
+    ) -> Result<(u2, &'c str), Exception> {
+        let index = self.stream.get_u2()?;
+        let name = constants
+            .utf8(index)
+            .ok_or_else(|| self.error(&format!("Invalid attribute name index {}", index)))?;
+        Ok((index, name))
+    }
+
+    /// Keep the content of an attribute that is not modeled.
+    fn raw_attribute(&mut self, name_index: u2, length: usize) -> Result<Attribute, Exception> {
+        let info = self.stream.get_bytes(length)?.to_vec();
+        Ok(Attribute::Unknown { name_index, info })
+    }
+
+    /// Parse a standard attribute of the owner in the class file version, or keep the
+    /// content of the other attributes, and check its length.
+    fn parse_attribute(
+        &mut self,
+        constants: &ConstantPool,
+        name_index: u2,
+        name: &str,
+        length: usize,
+        owner: AttributeOwner,
+        major_version: u2,
+    ) -> Result<Attribute, Exception> {
+        match standard_attribute(name) {
+            Some((since, owners)) if major_version >= since && owners.contains(&owner) => {}
+            _ => return self.raw_attribute(name_index, length),
+        }
+        let start = self.stream.current_offset();
+        let attribute = match name {
+            "SourceFile" => Attribute::SourceFile(self.constant_index(constants, name, is_utf8)?),
+            "SourceDebugExtension" => {
+                Attribute::SourceDebugExtension(self.stream.get_bytes(length)?.to_vec())
+            }
+            "Signature" => Attribute::Signature(self.constant_index(constants, name, is_utf8)?),
+            "Deprecated" => Attribute::Deprecated,
+            "Synthetic" => Attribute::Synthetic,
+            "Exceptions" => {
+                Attribute::Exceptions(self.constant_indexes(constants, name, is_class)?)
+            }
+            "InnerClasses" => {
+                let count = self.stream.get_u2()?;
+                let mut classes = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let inner_class = InnerClass {
+                        inner_class_info_index: self.constant_index(constants, name, is_class)?,
+                        outer_class_info_index: self
+                            .optional_constant_index(constants, name, is_class)?,
+                        inner_name_index: self.optional_constant_index(constants, name, is_utf8)?,
+                        inner_class_access_flags: self.stream.get_u2()?,
+                    };
+                    if inner_class.inner_class_info_index == inner_class.outer_class_info_index {
+                        return Err(self.error("Class is both inner and outer class"));
+                    }
+                    classes.push(inner_class);
+                }
+                Attribute::InnerClasses(classes)
+            }
+            "EnclosingMethod" => Attribute::EnclosingMethod(EnclosingMethod {
+                class_index: self.constant_index(constants, name, is_class)?,
+                method_index: self.optional_constant_index(constants, name, is_name_and_type)?,
+            }),
+            "NestHost" => Attribute::NestHost(self.constant_index(constants, name, is_class)?),
+            "NestMembers" => {
+                Attribute::NestMembers(self.constant_indexes(constants, name, is_class)?)
+            }
+            "PermittedSubclasses" => {
+                Attribute::PermittedSubclasses(self.constant_indexes(constants, name, is_class)?)
+            }
+            "Record" => {
+                let count = self.stream.get_u2()?;
+                let mut components = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let name_index = self.constant_index(constants, name, is_utf8)?;
+                    let descriptor_index = self.constant_index(constants, name, is_utf8)?;
+                    let attributes_count = self.stream.get_u2()?;
+                    let mut attributes = Vec::with_capacity(attributes_count as usize);
+                    for _ in 0..attributes_count {
+                        let (name_index, name) = self.attribute_name(constants)?;
+                        let length = self.stream.get_u4()? as usize;
+                        let attribute = self.parse_attribute(
+                            constants,
+                            name_index,
+                            name,
+                            length,
+                            AttributeOwner::RecordComponent,
+                            major_version,
+                        )?;
+                        self.add_attribute(&mut attributes, attribute, name)?;
+                    }
+                    components.push(RecordComponent {
+                        name_index,
+                        descriptor_index,
+                        attributes,
+                    });
+                }
+                Attribute::Record(components)
+            }
+            "BootstrapMethods" => {
+                let count = self.stream.get_u2()?;
+                let mut methods = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    methods.push(BootstrapMethod {
+                        method_ref: self.constant_index(constants, name, is_method_handle)?,
+                        arguments: self.constant_indexes(constants, name, is_loadable)?,
+                    });
+                }
+                Attribute::BootstrapMethods(methods)
+            }
+            "MethodParameters" => {
+                let count = self.stream.get_u1()?;
+                let mut parameters = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    parameters.push(MethodParameter {
+                        name_index: self.optional_constant_index(constants, name, is_utf8)?,
+                        access_flags: self.stream.get_u2()?,
+                    });
+                }
+                Attribute::MethodParameters(parameters)
+            }
+            "LocalVariableTable" | "LocalVariableTypeTable" => {
+                let count = self.stream.get_u2()?;
+                let mut variables = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    variables.push(LocalVariable {
+                        start_pc: self.stream.get_u2()?,
+                        length: self.stream.get_u2()?,
+                        name_index: self.constant_index(constants, name, is_utf8)?,
+                        descriptor_index: self.constant_index(constants, name, is_utf8)?,
+                        index: self.stream.get_u2()?,
+                    });
+                }
+                if name == "LocalVariableTable" {
+                    Attribute::LocalVariableTable(variables)
+                } else {
+                    Attribute::LocalVariableTypeTable(variables)
+                }
+            }
+            "RuntimeVisibleAnnotations" => {
+                Attribute::RuntimeVisibleAnnotations(self.parse_annotations(constants, name)?)
+            }
+            "RuntimeInvisibleAnnotations" => {
+                Attribute::RuntimeInvisibleAnnotations(self.parse_annotations(constants, name)?)
+            }
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                let count = self.stream.get_u1()?;
+                let mut parameters = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    parameters.push(self.parse_annotations(constants, name)?);
+                }
+                if name == "RuntimeVisibleParameterAnnotations" {
+                    Attribute::RuntimeVisibleParameterAnnotations(parameters)
+                } else {
+                    Attribute::RuntimeInvisibleParameterAnnotations(parameters)
+                }
+            }
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                let count = self.stream.get_u2()?;
+                let mut annotations = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    annotations.push(self.parse_type_annotation(constants, name)?);
+                }
+                if name == "RuntimeVisibleTypeAnnotations" {
+                    Attribute::RuntimeVisibleTypeAnnotations(annotations)
+                } else {
+                    Attribute::RuntimeInvisibleTypeAnnotations(annotations)
+                }
+            }
+            "AnnotationDefault" => {
+                Attribute::AnnotationDefault(self.parse_element_value(constants, name, 0)?)
+            }
+            "Module" => Attribute::Module(self.parse_module(constants)?),
+            "ModulePackages" => {
+                Attribute::ModulePackages(self.constant_indexes(constants, name, is_package)?)
+            }
+            "ModuleMainClass" => {
+                Attribute::ModuleMainClass(self.constant_index(constants, name, is_class)?)
+            }
+            _ => unreachable!("{} is not a standard attribute", name),
+        };
+        if self.stream.current_offset() - start != length {
+            return Err(self.error(&format!("Invalid {} attribute length", name)));
+        }
+        Ok(attribute)
+    }
+
+    /// Add the attribute, unless it is a second one of the attributes that appear at
+    /// most once.
+    fn add_attribute(
+        &self,
+        attributes: &mut Vec<Attribute>,
+        attribute: Attribute,
+        name: &str,
+    ) -> Result<(), Exception> {
+        let repeatable = matches!(
+            attribute,
+            Attribute::LocalVariableTable(_)
+                | Attribute::LocalVariableTypeTable(_)
+                | Attribute::Deprecated
+                | Attribute::Synthetic
+                | Attribute::Unknown { .. }
+        );
+        let discriminant = mem::discriminant(&attribute);
+        if !repeatable
+            && attributes
+                .iter()
+                .any(|a| mem::discriminant(a) == discriminant)
+        {
+            return Err(self.error(&format!("Multiple {} attributes", name)));
+        }
+        attributes.push(attribute);
+        Ok(())
+    }
+
+    /// Read a constant pool index of an attribute and check the entry.
+    fn constant_index(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+        valid: fn(&ConstantPoolEntry) -> bool,
+    ) -> Result<u2, Exception> {
+        let index = self.stream.get_u2()?;
+        match constants.get(index) {
+            Some(entry) if valid(entry) => Ok(index),
+            _ => Err(self.error(&format!(
+                "Invalid constant pool index {} in {} attribute",
+                index, attribute
+            ))),
+        }
+    }
+
+    /// Read a constant pool index of an attribute that may be 0.
+    fn optional_constant_index(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+        valid: fn(&ConstantPoolEntry) -> bool,
+    ) -> Result<u2, Exception> {
+        let index = self.stream.get_u2()?;
+        match constants.get(index) {
+            _ if index == 0 => Ok(0),
+            Some(entry) if valid(entry) => Ok(index),
+            _ => Err(self.error(&format!(
+                "Invalid constant pool index {} in {} attribute",
+                index, attribute
+            ))),
+        }
+    }
+
+    /// Read a count of constant pool indexes of an attribute, then the indexes.
+    fn constant_indexes(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+        valid: fn(&ConstantPoolEntry) -> bool,
+    ) -> Result<Vec<u2>, Exception> {
+        let count = self.stream.get_u2()?;
+        (0..count)
+            .map(|_| self.constant_index(constants, attribute, valid))
+            .collect()
+    }
+
+    fn parse_annotations(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+    ) -> Result<Vec<Annotation>, Exception> {
+        let count = self.stream.get_u2()?;
+        (0..count)
+            .map(|_| self.parse_annotation(constants, attribute, 0))
+            .collect()
+    }
+
+    fn parse_annotation(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+        depth: usize,
+    ) -> Result<Annotation, Exception> {
+        let type_index = self.constant_index(constants, attribute, is_utf8)?;
+        let count = self.stream.get_u2()?;
+        let mut element_value_pairs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name_index = self.constant_index(constants, attribute, is_utf8)?;
+            let value = self.parse_element_value(constants, attribute, depth)?;
+            element_value_pairs.push((name_index, value));
+        }
+        Ok(Annotation {
+            type_index,
+            element_value_pairs,
+        })
+    }
+
+    fn parse_element_value(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+        depth: usize,
+    ) -> Result<ElementValue, Exception> {
+        if depth > MAX_ANNOTATION_DEPTH {
+            return Err(self.error(&format!("Too deeply nested {} attribute", attribute)));
+        }
+        let tag = self.stream.get_u1()?;
+        let valid: fn(&ConstantPoolEntry) -> bool = match tag {
+            b'B' | b'C' | b'I' | b'S' | b'Z' => {
+                |entry| matches!(entry, ConstantPoolEntry::Integer(_))
+            }
+            b'D' => |entry| matches!(entry, ConstantPoolEntry::Double(_)),
+            b'F' => |entry| matches!(entry, ConstantPoolEntry::Float(_)),
+            b'J' => |entry| matches!(entry, ConstantPoolEntry::Long(_)),
+            b's' => is_utf8,
+            b'e' => {
+                return Ok(ElementValue::Enum {
+                    type_name_index: self.constant_index(constants, attribute, is_utf8)?,
+                    const_name_index: self.constant_index(constants, attribute, is_utf8)?,
+                })
+            }
+            b'c' => {
+                return Ok(ElementValue::Class(
+                    self.constant_index(constants, attribute, is_utf8)?,
+                ))
+            }
+            b'@' => {
+                return Ok(ElementValue::Annotation(self.parse_annotation(
+                    constants,
+                    attribute,
+                    depth + 1,
+                )?))
+            }
+            b'[' => {
+                let count = self.stream.get_u2()?;
+                let mut values = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    values.push(self.parse_element_value(constants, attribute, depth + 1)?);
+                }
+                return Ok(ElementValue::Array(values));
+            }
+            _ => {
+                return Err(self.error(&format!(
+                    "Invalid element value tag {} in {} attribute",
+                    tag, attribute
+                )))
+            }
+        };
+        Ok(ElementValue::Const(
+            tag,
+            self.constant_index(constants, attribute, valid)?,
+        ))
+    }
+
+    fn parse_type_annotation(
+        &mut self,
+        constants: &ConstantPool,
+        attribute: &str,
+    ) -> Result<TypeAnnotation, Exception> {
+        let target_type = self.stream.get_u1()?;
+        let target_info = match target_type {
+            0x00 | 0x01 => TargetInfo::TypeParameter(self.stream.get_u1()?),
+            0x10 => TargetInfo::SuperType(self.stream.get_u2()?),
+            0x11 | 0x12 => TargetInfo::TypeParameterBound {
+                type_parameter_index: self.stream.get_u1()?,
+                bound_index: self.stream.get_u1()?,
+            },
+            0x13..=0x15 => TargetInfo::Empty,
+            0x16 => TargetInfo::FormalParameter(self.stream.get_u1()?),
+            0x17 => TargetInfo::Throws(self.stream.get_u2()?),
+            0x40 | 0x41 => {
+                let count = self.stream.get_u2()?;
+                let mut ranges = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    ranges.push((
+                        self.stream.get_u2()?,
+                        self.stream.get_u2()?,
+                        self.stream.get_u2()?,
+                    ));
+                }
+                TargetInfo::LocalVariable(ranges)
+            }
+            0x42 => TargetInfo::Catch(self.stream.get_u2()?),
+            0x43..=0x46 => TargetInfo::Offset(self.stream.get_u2()?),
+            0x47..=0x4B => TargetInfo::TypeArgument {
+                offset: self.stream.get_u2()?,
+                type_argument_index: self.stream.get_u1()?,
+            },
+            _ => {
+                return Err(self.error(&format!(
+                    "Invalid target type 0x{:X} in {} attribute",
+                    target_type, attribute
+                )))
+            }
+        };
+        let path_length = self.stream.get_u1()?;
+        let mut type_path = Vec::with_capacity(path_length as usize);
+        for _ in 0..path_length {
+            type_path.push((self.stream.get_u1()?, self.stream.get_u1()?));
+        }
+        Ok(TypeAnnotation {
+            target_type,
+            target_info,
+            type_path,
+            annotation: self.parse_annotation(constants, attribute, 0)?,
+        })
+    }
+
+    fn parse_module(&mut self, constants: &ConstantPool) -> Result<Module, Exception> {
+        const NAME: &str = "Module";
+        let name_index = self.constant_index(constants, NAME, is_module)?;
+        let flags = self.stream.get_u2()?;
+        let version_index = self.optional_constant_index(constants, NAME, is_utf8)?;
+        let requires_count = self.stream.get_u2()?;
+        let mut requires = Vec::with_capacity(requires_count as usize);
+        for _ in 0..requires_count {
+            requires.push(ModuleRequires {
+                requires_index: self.constant_index(constants, NAME, is_module)?,
+                flags: self.stream.get_u2()?,
+                version_index: self.optional_constant_index(constants, NAME, is_utf8)?,
+            });
+        }
+        let mut packages = [Vec::new(), Vec::new()];
+        for directives in &mut packages {
+            let count = self.stream.get_u2()?;
+            for _ in 0..count {
+                directives.push(ModulePackage {
+                    package_index: self.constant_index(constants, NAME, is_package)?,
+                    flags: self.stream.get_u2()?,
+                    to: self.constant_indexes(constants, NAME, is_module)?,
+                });
+            }
+        }
+        let [exports, opens] = packages;
+        let uses = self.constant_indexes(constants, NAME, is_class)?;
+        let provides_count = self.stream.get_u2()?;
+        let mut provides = Vec::with_capacity(provides_count as usize);
+        for _ in 0..provides_count {
+            provides.push(ModuleProvides {
+                provides_index: self.constant_index(constants, NAME, is_class)?,
+                with: self.constant_indexes(constants, NAME, is_class)?,
+            });
+        }
+        Ok(Module {
+            name_index,
+            flags,
+            version_index,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        })
+    }
+
+    fn parse_field(
+        &mut self,
+        constants: &ConstantPool,
+        class_name: &str,
+        major_version: u2,
+        is_interface: bool,
+    ) -> Result<Field, Exception> {
+        let flags = self.stream.get_u2()?;
+        self.verify_legal_field_modifiers(flags, class_name, major_version, is_interface)?;
+        let name_index = self.stream.get_u2()?;
+        let name = constants.utf8(name_index).ok_or_else(|| {
+            self.error(&format!(
+                "Invalid constant pool index {} for field name",
+                name_index
+            ))
+        })?;
+        self.verify_legal_field_name(name)?;
+        let descriptor_index = self.stream.get_u2()?;
+        let descriptor = constants.utf8(descriptor_index).ok_or_else(|| {
+            self.error(&format!(
+                "Invalid constant pool index {} for field signature",
+                descriptor_index
+            ))
+        })?;
+        self.verify_legal_field_signature(name, descriptor)?;
+        let mut constant_value_index = 0;
+        let attributes_count = self.stream.get_u2()?;
+        let mut attributes = Vec::new();
+        for _ in 0..attributes_count {
+            let (attribute_name_index, attribute_name) = self.attribute_name(constants)?;
+            let length = self.stream.get_u4()?;
+            if attribute_name == "ConstantValue" && flags & JVM_ACC_STATIC != 0 {
+                if constant_value_index != 0 {
+                    return Err(self.error(&format!(
+                        "Duplicate ConstantValue attribute in field {}",
+                        name
+                    )));
+                }
+                if length != 2 {
+                    return Err(self.error("Invalid ConstantValue field attribute length"));
+                }
+                constant_value_index = self.stream.get_u2()?;
+                let matches = match constants.get(constant_value_index) {
+                    Some(ConstantPoolEntry::Long(_)) => descriptor == "J",
+                    Some(ConstantPoolEntry::Float(_)) => descriptor == "F",
+                    Some(ConstantPoolEntry::Double(_)) => descriptor == "D",
+                    Some(ConstantPoolEntry::Integer(_)) => "ZBCSI".contains(descriptor),
+                    Some(ConstantPoolEntry::String(_)) => descriptor == "Ljava/lang/String;",
+                    _ => {
+                        return Err(self.error(&format!(
+                            "Bad initial value index {} in ConstantValue attribute of field {}",
+                            constant_value_index, name
+                        )))
+                    }
+                };
+                if !matches {
+                    return Err(self.error(&format!(
+                        "Inconsistent constant value type of field {}",
+                        name
+                    )));
+                }
+            } else {
+                let attribute = self.parse_attribute(
+                    constants,
+                    attribute_name_index,
+                    attribute_name,
+                    length as usize,
+                    AttributeOwner::Field,
+                    major_version,
+                )?;
+                self.add_attribute(&mut attributes, attribute, attribute_name)?;
+            }
+        }
+        Ok(Field::new(
+            flags,
+            name_index,
+            descriptor_index,
+            constant_value_index,
+            attributes,
+        ))
+    }
+
+    fn parse_method(
+        &mut self,
+        constants: &ConstantPool,
+        class_name: &str,
+        major_version: u2,
+        is_interface: bool,
+    ) -> Result<Method, Exception> {
+        let mut flags = self.stream.get_u2()?;
+        let name_index = self.stream.get_u2()?;
+        let name = constants.utf8(name_index).ok_or_else(|| {
+            self.error(&format!(
+                "Invalid constant pool index {} for method name",
+                name_index
+            ))
+        })?;
+        self.verify_legal_method_name(name)?;
+        let descriptor_index = self.stream.get_u2()?;
+        let descriptor = constants.utf8(descriptor_index).ok_or_else(|| {
+            self.error(&format!(
+                "Invalid constant pool index {} for method signature",
+                descriptor_index
+            ))
+        })?;
+        let mut parameter_slots = self.verify_legal_method_signature(name, descriptor)?;
+        if name == CLASS_INITIALIZER_NAME {
+            // the other flags of a class initializer are ignored
+            if major_version < JAVA_7_VERSION {
+                flags = JVM_ACC_STATIC;
+            } else if flags & JVM_ACC_STATIC != 0 {
+                flags &= JVM_ACC_STATIC | JVM_ACC_STRICT;
+            } else {
+                return Err(self.error("Method <clinit> is not static"));
+            }
+        } else {
+            if is_interface && name == OBJECT_INITIALIZER_NAME {
+                return Err(self.error("Interface cannot have a method named <init>"));
+            }
+            self.verify_legal_method_modifiers(
+                flags,
+                name,
+                class_name,
+                major_version,
+                is_interface,
+            )?;
+        }
+        if flags & JVM_ACC_STATIC == 0 {
+            parameter_slots += 1;
+        }
+        if parameter_slots > MAX_PARAMETER_SLOTS {
+            return Err(self.error(&format!(
+                "Too many arguments in method signature of {}{}",
+                name, descriptor
+            )));
+        }
+
+        let mut code: Option<Code> = None;
+        let attributes_count = self.stream.get_u2()?;
+        let mut attributes = Vec::new();
+        for _ in 0..attributes_count {
+            let (attribute_name_index, attribute_name) = self.attribute_name(constants)?;
+            let length = self.stream.get_u4()? as usize;
+            if attribute_name == "Code" {
+                if code.is_some() {
+                    return Err(self.error("Multiple Code attributes"));
+                }
+                let start = self.stream.current_offset();
+                code = Some(self.parse_code(constants, major_version)?);
+                if self.stream.current_offset() - start != length {
+                    return Err(self.error("Code attribute has wrong length"));
+                }
+            } else {
+                let attribute = self.parse_attribute(
+                    constants,
+                    attribute_name_index,
+                    attribute_name,
+                    length,
+                    AttributeOwner::Method,
+                    major_version,
+                )?;
+                self.add_attribute(&mut attributes, attribute, attribute_name)?;
+            }
+        }
+        match &code {
+            Some(_) if flags & (JVM_ACC_ABSTRACT | JVM_ACC_NATIVE) != 0 => {
+                return Err(self.error(&format!(
+                    "Code attribute in native or abstract method {}{}",
+                    name, descriptor
+                )))
+            }
+            None if flags & (JVM_ACC_ABSTRACT | JVM_ACC_NATIVE) == 0 => {
+                return Err(self.error(&format!(
+                    "Absent Code attribute in method {}{} that is not native or abstract",
+                    name, descriptor
+                )))
+            }
+            Some(code) if (code.max_locals() as usize) < parameter_slots => {
+                return Err(self.error(&format!(
+                    "Arguments can't fit into locals of method {}{}",
+                    name, descriptor
+                )))
+            }
+            _ => {}
+        }
+        Ok(Method::new(
+            flags,
+            name_index,
+            descriptor_index,
+            code,
+            attributes,
+        ))
+    }
+
+    fn parse_code(
+        &mut self,
+        constants: &ConstantPool,
+        major_version: u2,
+    ) -> Result<Code, Exception> {
+        let max_stack = self.stream.get_u2()?;
+        let max_locals = self.stream.get_u2()?;
+        let code_length = self.stream.get_u4()?;
+        if code_length == 0 || code_length > 0xFFFF {
+            return Err(self.error(&format!("Invalid method Code length {}", code_length)));
+        }
+        let code = self.stream.get_bytes(code_length as usize)?.to_vec();
+        let exception_table_length = self.stream.get_u2()?;
+        let mut exception_table = Vec::with_capacity(exception_table_length as usize);
+        for _ in 0..exception_table_length {
+            let entry = ExceptionTableEntry {
+                start_pc: self.stream.get_u2()?,
+                end_pc: self.stream.get_u2()?,
+                handler_pc: self.stream.get_u2()?,
+                catch_type: self.stream.get_u2()?,
+            };
+            if entry.start_pc >= entry.end_pc || entry.end_pc as u4 > code_length {
+                return Err(self.error("Illegal exception table range"));
+            }
+            if entry.handler_pc as u4 >= code_length {
+                return Err(self.error("Illegal exception table handler"));
+            }
+            if entry.catch_type != 0 && constants.class_name(entry.catch_type).is_none() {
+                return Err(self.error("Catch type in exception table has bad constant type"));
+            }
+            exception_table.push(entry);
+        }
+        let mut line_number_table = None;
+        let mut stack_map_table = None;
+        let attributes_count = self.stream.get_u2()?;
+        let mut attributes = Vec::new();
+        for _ in 0..attributes_count {
+            let (name_index, name) = self.attribute_name(constants)?;
+            let length = self.stream.get_u4()? as usize;
+            match name {
+                "LineNumberTable" => {
+                    let count = self.stream.get_u2()?;
+                    if length != 2 + 4 * count as usize {
+                        return Err(self.error("Invalid LineNumberTable attribute length"));
+                    }
+                    let mut entries = Vec::with_capacity(count as usize);
+                    for _ in 0..count {
+                        let start_pc = self.stream.get_u2()?;
+                        if start_pc as u4 >= code_length {
+                            return Err(self.error("Invalid pc in LineNumberTable"));
+                        }
+                        entries.push((start_pc, self.stream.get_u2()?));
+                    }
+                    line_number_table = Some(LineNumberTable::new(entries));
+                }
+                "StackMapTable" => {
+                    if stack_map_table.is_some() {
+                        return Err(self.error("Multiple StackMapTable attributes"));
+                    }
+                    stack_map_table = Some(self.stream.get_bytes(length)?.to_vec());
+                }
+                _ => {
+                    let attribute = self.parse_attribute(
+                        constants,
+                        name_index,
+                        name,
+                        length,
+                        AttributeOwner::Code,
+                        major_version,
+                    )?;
+                    if let Attribute::LocalVariableTable(variables)
+                    | Attribute::LocalVariableTypeTable(variables) = &attribute
+                    {
+                        for variable in variables {
+                            if variable.start_pc as u4 >= code_length
+                                || variable.start_pc as u4 + variable.length as u4 > code_length
+                            {
+                                return Err(self.error(&format!(
+                                    "Invalid range of local variable in {} attribute",
+                                    name
+                                )));
+                            }
+                        }
+                    }
+                    self.add_attribute(&mut attributes, attribute, name)?;
+                }
+            }
+        }
+        Ok(Code::new(
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            line_number_table,
+            stack_map_table,
+            attributes,
+        ))
+    }
+}
+
+/// The first class file version and the owners of a standard attribute parsed into
+/// its own variant, `None` for the others (JVMS table 4.7-B and 4.7-C).
+fn standard_attribute(name: &str) -> Option<(u2, &'static [AttributeOwner])> {
+    use AttributeOwner::*;
+    let (since, owners): (u2, &'static [AttributeOwner]) = match name {
+        "SourceFile" | "InnerClasses" => (JAVA_MIN_SUPPORTED_VERSION, &[Class]),
+        "Exceptions" => (JAVA_MIN_SUPPORTED_VERSION, &[Method]),
+        "LocalVariableTable" => (JAVA_MIN_SUPPORTED_VERSION, &[Code]),
+        "Synthetic" | "Deprecated" => (JAVA_MIN_SUPPORTED_VERSION, &[Class, Field, Method]),
+        "EnclosingMethod" | "SourceDebugExtension" => (JAVA_1_5_VERSION, &[Class]),
+        "LocalVariableTypeTable" => (JAVA_1_5_VERSION, &[Code]),
+        "Signature" | "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+            (JAVA_1_5_VERSION, &[Class, Field, Method, RecordComponent])
+        }
+        "RuntimeVisibleParameterAnnotations"
+        | "RuntimeInvisibleParameterAnnotations"
+        | "AnnotationDefault" => (JAVA_1_5_VERSION, &[Method]),
+        "BootstrapMethods" => (JAVA_7_VERSION, &[Class]),
+        "MethodParameters" => (JAVA_8_VERSION, &[Method]),
+        "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => (
+            JAVA_8_VERSION,
+            &[Class, Field, Method, Code, RecordComponent],
+        ),
+        "Module" | "ModulePackages" | "ModuleMainClass" => (JAVA_9_VERSION, &[Class]),
+        "NestHost" | "NestMembers" => (JAVA_11_VERSION, &[Class]),
+        "Record" => (JAVA_16_VERSION, &[Class]),
+        "PermittedSubclasses" => (JAVA_17_VERSION, &[Class]),
+        _ => return None,
+    };
+    Some((since, owners))
+}
+
+fn is_utf8(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::Utf8(_))
+}
+
+fn is_class(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::Class(_))
+}
+
+fn is_name_and_type(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::NameAndType(..))
+}
+
+fn is_method_handle(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::MethodHandle(..))
+}
+
+fn is_module(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::Module(_))
+}
+
+fn is_package(entry: &ConstantPoolEntry) -> bool {
+    matches!(entry, ConstantPoolEntry::Package(_))
+}
+
+/// Whether the entry can be loaded by `ldc` or be a static argument (JVMS §4.4).
+fn is_loadable(entry: &ConstantPoolEntry) -> bool {
+    matches!(
+        entry,
+        ConstantPoolEntry::Integer(_)
+            | ConstantPoolEntry::Float(_)
+            | ConstantPoolEntry::Long(_)
+            | ConstantPoolEntry::Double(_)
+            | ConstantPoolEntry::Class(_)
+            | ConstantPoolEntry::String(_)
+            | ConstantPoolEntry::MethodHandle(..)
+            | ConstantPoolEntry::MethodType(_)
+            | ConstantPoolEntry::Dynamic(..)
+    )
+}
+
+/// Whether no two of the public, private and protected flags are set.
+fn has_illegal_visibility(flags: u2) -> bool {
+    (flags & (JVM_ACC_PUBLIC | JVM_ACC_PRIVATE | JVM_ACC_PROTECTED)).count_ones() > 1
+}
+
+/// Whether the name is an unqualified name (JVMS §4.2.2), without `<` and `>`
+/// for a method name.
+fn is_legal_unqualified_name(name: &str, method: bool) -> bool {
+    !name.is_empty()
+        && !name
+            .contains(|c| matches!(c, '.' | ';' | '[' | '/') || method && matches!(c, '<' | '>'))
+}
+
+/// Whether the name is an internal class name or an array descriptor (JVMS §4.2.1).
+fn is_legal_class_name(name: &str) -> bool {
+    if name.starts_with('[') {
+        return field_descriptor_length(name) == Some(name.len());
+    }
+    name.split('/')
+        .all(|part| is_legal_unqualified_name(part, false))
+}
+
+/// The length of the field descriptor at the start of the string (JVMS §4.3.2).
+fn field_descriptor_length(descriptor: &str) -> Option<usize> {
+    let dimensions = descriptor.bytes().take_while(|&byte| byte == b'[').count();
+    if dimensions > MAX_ARRAY_DIMENSIONS {
+        return None;
+    }
+    let element = &descriptor[dimensions..];
+    match element.bytes().next()? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => Some(dimensions + 1),
+        b'L' => {
+            let end = element.find(';')?;
+            let name = &element[1..end];
+            (!name.starts_with('[') && is_legal_class_name(name)).then_some(dimensions + end + 1)
+        }
+        _ => None,
+    }
+}
+
+/// The number of slots of the parameters of a method descriptor (JVMS §4.3.3),
+/// `None` when it is illegal.
+fn method_parameter_slots(descriptor: &str) -> Option<usize> {
+    let mut parameters = descriptor.strip_prefix('(')?;
+    let mut slots = 0;
+    while !parameters.starts_with(')') {
+        let length = field_descriptor_length(parameters)?;
+        slots += if matches!(&parameters[..length], "J" | "D") {
+            2
+        } else {
+            1
+        };
+        parameters = &parameters[length..];
+    }
+    let return_type = &parameters[1..];
+    (return_type == "V" || field_descriptor_length(return_type) == Some(return_type.len()))
+        .then_some(slots)
+}
+
+/// Decode the modified UTF-8 of a class file: no null byte, the null
+/// character on two bytes and supplementary characters as surrogate pairs.
+pub fn decode_modified_utf8(bytes: &[u1]) -> Option<String> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index] as u16;
+        let continuation = |offset: usize| -> Option<u16> {
+            let byte = *bytes.get(index + offset)?;
+            (byte & 0xC0 == 0x80).then_some((byte & 0x3F) as u16)
+        };
+        let (unit, length) = match byte {
+            0x01..=0x7F => (byte, 1),
+            0xC0..=0xDF => ((byte & 0x1F) << 6 | continuation(1)?, 2),
+            0xE0..=0xEF => (
+                (byte & 0x0F) << 12 | continuation(1)? << 6 | continuation(2)?,
+                3,
+            ),
+            _ => return None,
+        };
+        units.push(unit);
+        index += length;
+    }
+    // unpaired surrogates are legal in class files, keep them as replacement characters
+    Some(
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_modified_utf8, method_parameter_slots, ClassFileParser};
+    use crate::{
+        classfile::test_class::TestClass,
+        classloader::class_file_stream::ClassFileStream,
+        model::{
+            attribute::{Annotation, Attribute, ElementValue, InnerClass, MethodParameter},
+            instance_klass::InstanceKlass,
+        },
+        utilities::{definition::u4, exceptions::Exception},
+    };
+
+    fn parse(class: &TestClass) -> Result<InstanceKlass, Exception> {
+        ClassFileParser::new(&mut ClassFileStream::new(
+            class.bytes(),
+            "Test.class".to_string(),
+        ))
+        .parse()
+    }
+
+    fn format_error(class: &TestClass, message: &str) {
+        match parse(class) {
+            Err(Exception::ClassFormatError(error)) => {
+                assert!(error.starts_with(message), "{}", error)
+            }
+            Err(error) => panic!("expecting a ClassFormatError, found {}", error),
+            Ok(_) => panic!("expecting a ClassFormatError"),
+        }
+    }
+
+    #[test]
+    fn we_can_decode_modified_utf8() {
+        assert_eq!(
+            decode_modified_utf8(b"java/lang/Object").unwrap(),
+            "java/lang/Object"
+        );
+        assert_eq!(decode_modified_utf8(&[0xC0, 0x80]).unwrap(), "\0");
+        assert_eq!(decode_modified_utf8(&[0xC3, 0xA9]).unwrap(), "é");
+        assert_eq!(
+            decode_modified_utf8(&[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]).unwrap(),
+            "😀"
+        );
+        assert!(decode_modified_utf8(&[0]).is_none());
+        assert!(decode_modified_utf8(&[0xC3]).is_none());
+        assert!(decode_modified_utf8(&[0xF0, 0x9F, 0x98, 0x80]).is_none());
+    }
+
+    #[test]
+    fn we_can_parse_a_class() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.field(0x0008, "count", "I", &[]);
+        class.method(0x0009, "f", "()I", 1, 0, &[0x04, 0xAC], &[], None);
+        let klass = ClassFileParser::new(&mut ClassFileStream::new(class.bytes(), String::new()))
+            .parse()
+            .unwrap();
+        assert_eq!(klass.name(), "Test");
+        assert_eq!(klass.super_name(), Some("java/lang/Object"));
+        assert_eq!(klass.fields().len(), 1);
+        let method = &klass.methods()[0];
+        assert_eq!(klass.method_name(method), "f");
+        assert_eq!(klass.method_descriptor(method), "()I");
+        assert_eq!(method.code().unwrap().code(), &[0x04, 0xAC]);
+
+        let mut bytes = class.bytes();
+        bytes.push(0);
+        let result = ClassFileParser::new(&mut ClassFileStream::new(bytes, String::new())).parse();
+        assert!(matches!(result, Err(Exception::ClassFormatError(_))));
+        let bytes = class.bytes()[..40].to_vec();
+        let result = ClassFileParser::new(&mut ClassFileStream::new(bytes, String::new())).parse();
+        assert!(matches!(result, Err(Exception::ClassFormatError(_))));
+    }
+
+    #[test]
+    fn we_can_check_the_class_version() {
+        let unsupported = |major_version, minor_version: u16| {
+            let mut class = TestClass::new("Test", "java/lang/Object");
+            class.major_version(major_version);
+            let mut bytes = class.bytes();
+            bytes[4..6].copy_from_slice(&minor_version.to_be_bytes());
+            let result =
+                ClassFileParser::new(&mut ClassFileStream::new(bytes, "Test".to_string())).parse();
+            match result {
+                Err(Exception::UnsupportedClassVersionError(message)) => message,
+                _ => panic!("expecting an UnsupportedClassVersionError"),
+            }
+        };
+        assert_eq!(
+            unsupported(62, 0),
+            "Test has been compiled by a more recent version of the Java Runtime (class file \
+             version 62.0), this version of the Java Runtime only recognizes class file versions \
+             up to 61.0"
+        );
+        assert!(unsupported(44, 0).ends_with("was compiled with an invalid major version"));
+        assert!(unsupported(61, 1).ends_with("invalid non-zero minor version"));
+        assert!(unsupported(61, 0xFFFF).starts_with("Preview features are not enabled"));
+        assert!(unsupported(60, 0xFFFF).contains("preview features that are unsupported"));
+    }
+
+    #[test]
+    fn we_can_check_the_constant_pool() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.constant(vec![9, 0, 1, 0, 1]);
+        format_error(&class, "Invalid constant pool index 1 at");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.class("java.lang.String");
+        format_error(&class, "Illegal class name \"java.lang.String\"");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method_ref("Test", "<clinit>", "()V");
+        format_error(&class, "Bad method name at constant pool index");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.field_ref("Test", "f", "Ljava/lang/String");
+        format_error(
+            &class,
+            "Field \"f\" has illegal signature \"Ljava/lang/String\"",
+        );
+
+        // a method handle of kind invokeVirtual to a constructor, and to a field
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let method = class.method_ref("Test", "<init>", "()V").to_be_bytes();
+        class.constant(vec![15, 5, method[0], method[1]]);
+        format_error(&class, "Bad method name at constant pool index");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let field = class.field_ref("Test", "f", "I").to_be_bytes();
+        class.constant(vec![15, 5, field[0], field[1]]);
+        format_error(&class, "Bad method handle reference at constant pool index");
+
+        // method types came with Java 7
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let descriptor = class.utf8("()V").to_be_bytes();
+        class.constant(vec![16, descriptor[0], descriptor[1]]);
+        assert!(parse(&class).is_ok());
+        class.major_version(50);
+        format_error(
+            &class,
+            "Class file version does not support constant tag 16",
+        );
+    }
+
+    #[test]
+    fn we_can_check_the_access_flags() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.access_flags(0x0611);
+        format_error(&class, "Illegal class modifiers in class Test: 0x611");
+        class.access_flags(0x0601);
+        assert!(parse(&class).is_ok());
+        class.access_flags(0x0201);
+        format_error(&class, "Illegal class modifiers in class Test: 0x201");
+        // the interfaces of old class files are abstract
+        class.major_version(49);
+        assert!(parse(&class).is_ok());
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.field(0x0003, "f", "I", &[]);
+        format_error(&class, "Illegal field modifiers in class Test: 0x3");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.access_flags(0x0601);
+        class.field(0x0009, "f", "I", &[]);
+        format_error(&class, "Illegal field modifiers in class Test: 0x9");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method_with_attributes(0x0411, "m", "()V", &[]);
+        format_error(
+            &class,
+            "Method m in class Test has illegal modifiers: 0x411",
+        );
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0009, "<init>", "()V", 1, 1, &[0xB1], &[], None);
+        format_error(
+            &class,
+            "Method <init> in class Test has illegal modifiers: 0x9",
+        );
+        // private methods of interfaces from Java 8
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.access_flags(0x0601);
+        class.method(0x0002, "m", "()V", 0, 1, &[0xB1], &[], None);
+        assert!(parse(&class).is_ok());
+        class.major_version(51);
+        format_error(&class, "Method m in class Test has illegal modifiers: 0x2");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0001, "<clinit>", "()V", 0, 1, &[0xB1], &[], None);
+        format_error(&class, "Method <clinit> is not static");
+    }
+
+    #[test]
+    fn we_can_check_fields_and_methods() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.field(0x0001, "f", "I", &[]);
+        class.field(0x0001, "f", "J", &[]);
+        assert!(parse(&class).is_ok());
+        class.field(0x0002, "f", "I", &[]);
+        format_error(&class, "Duplicate field name \"f\" with signature \"I\"");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let value = class.string("1");
+        let attribute = class.attribute("ConstantValue", &value.to_be_bytes());
+        class.field(0x0018, "f", "I", &[attribute]);
+        format_error(&class, "Inconsistent constant value type of field f");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0001, "m", "(JI)V", 0, 3, &[0xB1], &[], None);
+        format_error(&class, "Arguments can't fit into locals of method m(JI)V");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0001, "m<>", "()V", 0, 1, &[0xB1], &[], None);
+        format_error(&class, "Illegal method name \"m<>\"");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0001, "<init>", "()I", 1, 1, &[0x03, 0xAC], &[], None);
+        format_error(&class, "Method \"<init>\" has illegal signature \"()I\"");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method_with_attributes(0x0001, "m", "()V", &[]);
+        format_error(&class, "Absent Code attribute in method m()V");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0009, "m", "()V", 0, 0, &[0xB1], &[[0, 1, 1, 0]], None);
+        format_error(&class, "Illegal exception table handler");
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0009, "m", "()V", 0, 0, &[0xB1], &[], None);
+        class.method(0x0009, "m", "()V", 0, 0, &[0xB1], &[], None);
+        format_error(&class, "Duplicate method name \"m\" with signature \"()V\"");
+
+        assert_eq!(method_parameter_slots("(IJ[DLjava/lang/String;)V"), Some(5));
+        assert_eq!(method_parameter_slots("([[Ljava/lang/Object;)[I"), Some(1));
+        assert_eq!(method_parameter_slots("(V)V"), None);
+        assert_eq!(method_parameter_slots("(L;)V"), None);
+        assert_eq!(method_parameter_slots("()"), None);
+        assert_eq!(
+            method_parameter_slots(&format!("({}I)V", "[".repeat(256))),
+            None
+        );
+    }
+
+    #[test]
+    fn we_can_parse_the_attributes() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let source_file = class.utf8("Test.java");
+        let attribute = class.attribute("SourceFile", &source_file.to_be_bytes());
+        class.class_attribute(attribute);
+        let signature = class.utf8("<T:Ljava/lang/Object;>Ljava/lang/Object;");
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        class.class_attribute(attribute);
+        let inner = class.class("Test$Inner");
+        let outer = class.class("Test");
+        let inner_name = class.utf8("Inner");
+        let content = [[0, 1], inner.to_be_bytes(), outer.to_be_bytes()].concat();
+        let attribute = class.attribute(
+            "InnerClasses",
+            &[content, inner_name.to_be_bytes().to_vec(), vec![0, 8]].concat(),
+        );
+        class.class_attribute(attribute);
+        let attribute =
+            class.attribute("NestMembers", &[&[0, 1][..], &inner.to_be_bytes()].concat());
+        class.class_attribute(attribute);
+        let custom = class.attribute("Custom", &[1, 2, 3]);
+        class.class_attribute(custom);
+
+        let annotation_type = class.utf8("Ljava/lang/Deprecated;");
+        let value = class.utf8("since");
+        let string = class.utf8("17");
+        let content = [
+            &[0, 1][..],
+            &annotation_type.to_be_bytes(),
+            &[0, 1],
+            &value.to_be_bytes(),
+            b"s",
+            &string.to_be_bytes(),
+        ]
+        .concat();
+        let annotations = class.attribute("RuntimeVisibleAnnotations", &content);
+        class.field(0x0001, "f", "I", &[annotations]);
+        let exception = class.class("java/lang/Exception");
+        let exceptions = class.attribute(
+            "Exceptions",
+            &[&[0, 1][..], &exception.to_be_bytes()].concat(),
+        );
+        let parameter = class.utf8("a");
+        let parameters = class.attribute(
+            "MethodParameters",
+            &[&[1][..], &parameter.to_be_bytes(), &[0, 0x10]].concat(),
+        );
+        class.method_with_attributes(0x0401, "m", "(I)V", &[exceptions, parameters]);
+
+        let klass = parse(&class).unwrap();
+        assert_eq!(klass.source_file(), Some("Test.java"));
+        assert_eq!(klass.signature_index(), Some(signature));
+        assert_eq!(
+            klass.inner_classes(),
+            &[InnerClass {
+                inner_class_info_index: inner,
+                outer_class_info_index: outer,
+                inner_name_index: inner_name,
+                inner_class_access_flags: 0x0008,
+            }]
+        );
+        assert_eq!(klass.nest_members(), &[inner]);
+        assert_eq!(klass.nest_host_index(), None);
+        assert!(klass.record_components().is_none());
+        assert!(matches!(
+            &klass.attributes()[4],
+            Attribute::Unknown { info, .. } if info == &[1, 2, 3]
+        ));
+        assert!(matches!(
+            &klass.fields()[0].attributes()[0],
+            Attribute::RuntimeVisibleAnnotations(annotations) if annotations == &[Annotation {
+                type_index: annotation_type,
+                element_value_pairs: vec![(value, ElementValue::Const(b's', string))],
+            }]
+        ));
+        let method = &klass.methods()[0];
+        assert_eq!(method.exceptions(), &[exception]);
+        assert!(matches!(
+            &method.attributes()[1],
+            Attribute::MethodParameters(parameters) if parameters == &[MethodParameter {
+                name_index: parameter,
+                access_flags: 0x0010,
+            }]
+        ));
+
+        // the attributes of later versions are not interpreted
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.major_version(48);
+        let signature = class.utf8("bad");
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        class.class_attribute(attribute);
+        let klass = parse(&class).unwrap();
+        assert_eq!(klass.signature_index(), None);
+    }
+
+    #[test]
+    fn we_can_check_the_attributes() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let signature = class.utf8("Ljava/lang/Object;");
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        class.class_attribute(attribute.clone());
+        class.class_attribute(attribute);
+        format_error(&class, "Multiple Signature attributes");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let name = class.utf8("Test");
+        let attribute = class.attribute("NestHost", &name.to_be_bytes());
+        class.class_attribute(attribute);
+        format_error(
+            &class,
+            &format!("Invalid constant pool index {} in NestHost attribute", name),
+        );
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let attribute = class.attribute("Deprecated", &[0]);
+        class.class_attribute(attribute);
+        format_error(&class, "Invalid Deprecated attribute length");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let inner = class.class("Test$Inner");
+        let content = [
+            [0, 1],
+            inner.to_be_bytes(),
+            inner.to_be_bytes(),
+            [0, 0],
+            [0, 8],
+        ]
+        .concat();
+        let attribute = class.attribute("InnerClasses", &content);
+        class.class_attribute(attribute);
+        format_error(&class, "Class is both inner and outer class");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let name_and_type = class.name_and_type("run", "()Ljava/lang/Runnable;");
+        let [high, low] = name_and_type.to_be_bytes();
+        class.constant(vec![18, 0, 0, high, low]);
+        format_error(&class, "Missing BootstrapMethods attribute");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let name = class.utf8("Test");
+        let annotation = [
+            &[0, 1][..],
+            &name.to_be_bytes(),
+            &[0, 1],
+            &name.to_be_bytes(),
+            b"x",
+        ];
+        let attribute = class.attribute("RuntimeVisibleAnnotations", &annotation.concat());
+        class.class_attribute(attribute);
+        format_error(&class, "Invalid element value tag 120");
+    }
+
+    #[test]
+    fn we_can_check_the_ranges_of_the_local_variables() {
+        // a variable from 0xFFF0 for 0x20 bytes ends past the code, the end would wrap
+        // around to 0x10 in 16 bits
+        for (attribute, start_pc, length) in [
+            ("LocalVariableTable", 0, 0xFFF8),
+            ("LocalVariableTable", 0xFFF0, 0x20),
+            ("LocalVariableTypeTable", 0xFFF0, 0x20),
+        ] {
+            let mut class = TestClass::new("Test", "java/lang/Object");
+            let name = class.utf8("t");
+            let descriptor = class.utf8("Ljava/lang/Object;");
+            let variable = [start_pc, length, name, descriptor, 0u16];
+            let mut content = 1u16.to_be_bytes().to_vec();
+            for value in variable {
+                content.extend_from_slice(&value.to_be_bytes());
+            }
+            let variables = class.attribute(attribute, &content);
+            let code_length: u4 = 0xFFF8;
+            let mut code = [[0, 1], [0, 1]].concat();
+            code.extend_from_slice(&code_length.to_be_bytes());
+            code.resize(code.len() + code_length as usize, 0);
+            code.extend_from_slice(&[0, 0, 0, 1]);
+            code.extend(variables);
+            let code = class.attribute("Code", &code);
+            class.method_with_attributes(0x0009, "m", "()V", &[code]);
+            if start_pc == 0 {
+                assert!(parse(&class).is_ok());
+            } else {
+                format_error(
+                    &class,
+                    &format!("Invalid range of local variable in {} attribute", attribute),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn we_can_parse_a_module_info_for_the_tools() {
+        let mut class = TestClass::new("module-info", "");
+        class.access_flags(0x8000);
+        let name = class.utf8("m");
+        let [high, low] = name.to_be_bytes();
+        let module_index = class.constant(vec![19, high, low]);
+        let content = [&module_index.to_be_bytes()[..], &[0; 14]].concat();
+        let attribute = class.attribute("Module", &content);
+        class.class_attribute(attribute);
+        format_error(
+            &class,
+            "module-info is not a class because access_flag ACC_MODULE is set",
+        );
+        let klass = ClassFileParser::new(&mut ClassFileStream::new(class.bytes(), String::new()))
+            .accept_module_info()
+            .parse()
+            .unwrap();
+        let module = klass.module().unwrap();
+        assert_eq!(module.name_index, module_index);
+        assert!(module.requires.is_empty() && module.provides.is_empty());
+
+        class.field(0x0001, "f", "I", &[]);
+        let error = ClassFileParser::new(&mut ClassFileStream::new(class.bytes(), String::new()))
+            .accept_module_info()
+            .parse();
+        assert!(error.is_err());
+    }
+}
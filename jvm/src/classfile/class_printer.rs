@@ -0,0 +1,2258 @@
+//! Print a parsed class file like `javap`: the declarations of the class and
+//! its members, the constant pool, the disassembled code and the attributes.
+use crate::{
+    classfile::bytecodes::{self, *},
+    classloader::class_file_stream::ClassFileStream,
+    model::{
+        attribute::{
+            self, Annotation, Attribute, ElementValue, InnerClass, LocalVariable, Module,
+            TargetInfo, TypeAnnotation,
+        },
+        constant_pool::{ConstantPool, ConstantPoolEntry},
+        field::Field,
+        instance_klass::InstanceKlass,
+        method::{Code, Method},
+    },
+    utilities::{access_flags::*, definition::u2},
+};
+
+/// Width of an indentation level.
+const INDENT_WIDTH: usize = 2;
+/// Column of the comments after the operands, from the indentation.
+const TAB_COLUMN: usize = 40;
+
+/// The least access of the members to print.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Protected,
+    Package,
+    Private,
+}
+
+/// What to print, the options of `javap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// `-v`: the versions, flags, constant pool, descriptors and all the attributes.
+    pub verbose: bool,
+    /// `-c`: the disassembled code.
+    pub code: bool,
+    /// `-l`: the line number tables.
+    pub line_numbers: bool,
+    /// `-s`: the descriptors of the members.
+    pub descriptors: bool,
+    /// `-constants`: the values of the constant fields.
+    pub constants: bool,
+    pub access: Access,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            verbose: false,
+            code: false,
+            line_numbers: false,
+            descriptors: false,
+            constants: false,
+            access: Access::Package,
+        }
+    }
+}
+
+impl PrintOptions {
+    fn check_access(&self, flags: u2) -> bool {
+        let is_public = flags & JVM_ACC_PUBLIC != 0;
+        let is_protected = flags & JVM_ACC_PROTECTED != 0;
+        let is_private = flags & JVM_ACC_PRIVATE != 0;
+        match self.access {
+            Access::Public => is_public,
+            Access::Protected => is_public || is_protected,
+            Access::Package => !is_private,
+            Access::Private => true,
+        }
+    }
+}
+
+/// The text of the class file printed like `javap` with the options.
+pub fn print_class(klass: &InstanceKlass, options: &PrintOptions) -> String {
+    let mut printer = ClassPrinter {
+        klass,
+        constants: klass.constants(),
+        options,
+        writer: LineWriter::default(),
+    };
+    printer.write_class();
+    printer.writer.out
+}
+
+/// Lines indented by levels, without trailing spaces.
+#[derive(Default)]
+struct LineWriter {
+    out: String,
+    buffer: String,
+    indent: usize,
+    pending_spaces: usize,
+    /// Start a new line before the next text.
+    pending_newline: bool,
+}
+
+impl LineWriter {
+    fn print(&mut self, text: &str) {
+        if self.pending_newline {
+            self.pending_newline = false;
+            self.println();
+        }
+        for c in text.chars() {
+            match c {
+                ' ' => self.pending_spaces += 1,
+                '\n' => self.println(),
+                _ => {
+                    if self.buffer.is_empty() {
+                        self.buffer
+                            .push_str(&" ".repeat(self.indent * INDENT_WIDTH));
+                    }
+                    self.buffer.push_str(&" ".repeat(self.pending_spaces));
+                    self.pending_spaces = 0;
+                    self.buffer.push(c);
+                }
+            }
+        }
+    }
+
+    fn println(&mut self) {
+        self.pending_spaces = 0;
+        self.out.push_str(&self.buffer);
+        self.out.push('\n');
+        self.buffer.clear();
+    }
+
+    fn print_line(&mut self, text: &str) {
+        self.print(text);
+        self.println();
+    }
+
+    /// Move to the column of the comments.
+    fn tab(&mut self) {
+        let column = self.indent * INDENT_WIDTH + TAB_COLUMN;
+        self.pending_spaces += column.saturating_sub(self.buffer.len()).max(1);
+    }
+}
+
+struct ClassPrinter<'a> {
+    klass: &'a InstanceKlass,
+    constants: &'a ConstantPool,
+    options: &'a PrintOptions,
+    writer: LineWriter,
+}
+
+impl<'a> ClassPrinter<'a> {
+    fn print(&mut self, text: &str) {
+        self.writer.print(text);
+    }
+
+    fn println(&mut self, text: &str) {
+        self.writer.print_line(text);
+    }
+
+    fn indent(&mut self) {
+        self.writer.indent += 1;
+    }
+
+    fn outdent(&mut self) {
+        self.writer.indent -= 1;
+    }
+
+    fn utf8(&self, index: u2) -> &'a str {
+        let constants: &'a ConstantPool = self.constants;
+        constants.utf8(index).unwrap_or_default()
+    }
+
+    /// The name of a class, module or package constant.
+    fn name(&self, index: u2) -> &'a str {
+        match self.constants.get(index) {
+            Some(
+                &ConstantPoolEntry::Class(name)
+                | &ConstantPoolEntry::Module(name)
+                | &ConstantPoolEntry::Package(name),
+            ) => self.utf8(name),
+            _ => "",
+        }
+    }
+
+    fn write_modifiers(&mut self, modifiers: Vec<&str>) {
+        for modifier in modifiers {
+            self.print(modifier);
+            self.print(" ");
+        }
+    }
+
+    fn write_flags(&mut self, flags: u2, names: &[(u2, &str)]) {
+        self.print(&format!("flags: (0x{:04x}) ", flags));
+        self.print(&flag_names(flags, names).join(", "));
+        self.print("\n");
+    }
+
+    fn write_class(&mut self) {
+        let klass = self.klass;
+        let flags = klass.access_flags();
+        let is_interface = flags & JVM_ACC_INTERFACE != 0;
+        if let Some(source_file) = klass.source_file() {
+            // under the class file lines in verbose mode
+            let indent = usize::from(self.options.verbose);
+            self.writer.indent += indent;
+            self.println(&format!("Compiled from \"{}\"", source_file));
+            self.writer.indent -= indent;
+        }
+        let module = klass.module().filter(|_| flags & JVM_ACC_MODULE != 0);
+        if let Some(module) = module {
+            if module.flags & JVM_ACC_OPEN != 0 {
+                self.print("open ");
+            }
+            self.print("module ");
+            self.print(self.name(module.name_index));
+            if module.version_index != 0 {
+                self.print(&format!("@{}", self.utf8(module.version_index)));
+            }
+        } else {
+            let class_flags = if is_interface {
+                flags & !JVM_ACC_ABSTRACT
+            } else {
+                flags
+            };
+            self.write_modifiers(modifiers(
+                class_flags,
+                JVM_ACC_PUBLIC | JVM_ACC_FINAL | JVM_ACC_ABSTRACT,
+            ));
+            self.print(if is_interface { "interface " } else { "class " });
+            self.print(&java_name(klass.name()));
+        }
+        let super_types = klass.signature_index().and_then(|index| {
+            SignatureReader::new(self.utf8(index), self.options.verbose)
+                .class_signature(is_interface)
+        });
+        if let Some(super_types) = super_types {
+            self.print(&java_name(&super_types));
+        } else {
+            if let Some(super_name) = klass.super_name().filter(|_| !is_interface) {
+                let super_name = java_name(super_name);
+                if super_name != "java.lang.Object" {
+                    self.print(" extends ");
+                    self.print(&super_name);
+                }
+            }
+            for (i, interface) in klass.interface_names().iter().enumerate() {
+                self.print(match (i, is_interface) {
+                    (0, false) => " implements ",
+                    (0, true) => " extends ",
+                    _ => ",",
+                });
+                self.print(&java_name(interface));
+            }
+        }
+
+        if self.options.verbose {
+            self.writer.println();
+            self.indent();
+            self.println(&format!("minor version: {}", klass.minor_version()));
+            self.println(&format!("major version: {}", klass.major_version()));
+            self.write_flags(flags, CLASS_FLAGS);
+            for (label, index) in [
+                ("this_class", klass.this_class_index()),
+                ("super_class", klass.super_class_index()),
+            ] {
+                self.print(&format!("{}: #{}", label, index));
+                if index != 0 {
+                    self.writer.tab();
+                    self.print(&format!("// {}", self.string_value(index)));
+                }
+                self.writer.println();
+            }
+            self.println(&format!(
+                "interfaces: {}, fields: {}, methods: {}, attributes: {}",
+                klass.interfaces().len(),
+                klass.fields().len(),
+                klass.methods().len(),
+                klass.attributes().len()
+            ));
+            self.outdent();
+            self.write_constant_pool();
+        } else {
+            self.print(" ");
+        }
+        self.println("{");
+        self.indent();
+        if let Some(module) = module.filter(|_| !self.options.verbose) {
+            self.write_directives(module);
+        }
+        for field in klass.fields() {
+            self.write_field(field);
+        }
+        for method in klass.methods() {
+            self.write_method(method);
+        }
+        self.writer.pending_newline = false;
+        self.outdent();
+        self.println("}");
+        if self.options.verbose {
+            for attribute in klass.attributes() {
+                self.write_attribute(attribute);
+            }
+        }
+    }
+
+    fn write_constant_pool(&mut self) {
+        self.println("Constant pool:");
+        self.indent();
+        let width = self.constants.len().to_string().len() + 1;
+        for (index, entry) in self.constants.entries().iter().enumerate() {
+            let tag = match tag_name(entry) {
+                Some(tag) if index > 0 => tag,
+                // the invalid entries at 0 and after longs and doubles
+                _ => continue,
+            };
+            self.print(&format!("{:>width$} = {:<18} ", format!("#{}", index), tag));
+            let (operands, comment_prefix) = match *entry {
+                ConstantPoolEntry::Class(name)
+                | ConstantPoolEntry::Module(name)
+                | ConstantPoolEntry::Package(name) => (format!("#{}", name), "// "),
+                ConstantPoolEntry::String(string) => (format!("#{}", string), "// "),
+                ConstantPoolEntry::FieldRef(class, name_and_type)
+                | ConstantPoolEntry::MethodRef(class, name_and_type)
+                | ConstantPoolEntry::InterfaceMethodRef(class, name_and_type) => {
+                    (format!("#{}.#{}", class, name_and_type), "// ")
+                }
+                ConstantPoolEntry::NameAndType(name, descriptor) => {
+                    (format!("#{}:#{}", name, descriptor), "// ")
+                }
+                ConstantPoolEntry::MethodHandle(kind, reference) => {
+                    (format!("{}:#{}", kind, reference), "// ")
+                }
+                // sic, javap prints two spaces
+                ConstantPoolEntry::MethodType(descriptor) => (format!("#{}", descriptor), "//  "),
+                ConstantPoolEntry::Dynamic(bootstrap, name_and_type)
+                | ConstantPoolEntry::InvokeDynamic(bootstrap, name_and_type) => {
+                    (format!("#{}:#{}", bootstrap, name_and_type), "// ")
+                }
+                _ => {
+                    let value = self.string_value(index as u2);
+                    self.println(&value);
+                    continue;
+                }
+            };
+            self.print(&operands);
+            self.writer.tab();
+            let value = self.string_value(index as u2);
+            self.println(&format!("{}{}", comment_prefix, value));
+        }
+        self.outdent();
+    }
+
+    /// The value of the constant as shown in the comments.
+    fn string_value(&self, index: u2) -> String {
+        let Some(entry) = self.constants.get(index) else {
+            return format!("#{}", index);
+        };
+        match *entry {
+            ConstantPoolEntry::Utf8(ref string) => escape_utf8(string),
+            ConstantPoolEntry::Integer(value) => value.to_string(),
+            ConstantPoolEntry::Float(value) => format!("{}f", java_float(value)),
+            ConstantPoolEntry::Long(value) => format!("{}l", value),
+            ConstantPoolEntry::Double(value) => format!("{}d", java_double(value)),
+            ConstantPoolEntry::Class(name)
+            | ConstantPoolEntry::Module(name)
+            | ConstantPoolEntry::Package(name) => check_name(self.utf8(name)),
+            ConstantPoolEntry::String(string) => self.string_value(string),
+            ConstantPoolEntry::FieldRef(class, name_and_type)
+            | ConstantPoolEntry::MethodRef(class, name_and_type)
+            | ConstantPoolEntry::InterfaceMethodRef(class, name_and_type) => format!(
+                "{}.{}",
+                self.string_value(class),
+                self.string_value(name_and_type)
+            ),
+            ConstantPoolEntry::NameAndType(name, descriptor) => {
+                format!("{}:{}", check_name(self.utf8(name)), self.utf8(descriptor))
+            }
+            ConstantPoolEntry::MethodHandle(kind, reference) => format!(
+                "{} {}",
+                reference_kind_name(kind),
+                self.string_value(reference)
+            ),
+            ConstantPoolEntry::MethodType(descriptor) => self.utf8(descriptor).to_string(),
+            ConstantPoolEntry::Dynamic(bootstrap, name_and_type)
+            | ConstantPoolEntry::InvokeDynamic(bootstrap, name_and_type) => {
+                format!("#{}:{}", bootstrap, self.string_value(name_and_type))
+            }
+            ConstantPoolEntry::Invalid => format!("#{}", index),
+        }
+    }
+
+    /// The kind and the value of the constant, the members of this class without the class.
+    fn constant(&self, index: u2) -> String {
+        let Some(entry) = self.constants.get(index).filter(|_| index != 0) else {
+            return format!("#{}", index);
+        };
+        let value = match *entry {
+            ConstantPoolEntry::FieldRef(class, name_and_type)
+            | ConstantPoolEntry::MethodRef(class, name_and_type)
+            | ConstantPoolEntry::InterfaceMethodRef(class, name_and_type)
+                if class == self.klass.this_class_index() =>
+            {
+                self.string_value(name_and_type)
+            }
+            _ => self.string_value(index),
+        };
+        let kind = match entry {
+            ConstantPoolEntry::Utf8(_) => "Utf8",
+            ConstantPoolEntry::Integer(_) => "int",
+            ConstantPoolEntry::Float(_) => "float",
+            ConstantPoolEntry::Long(_) => "long",
+            ConstantPoolEntry::Double(_) => "double",
+            ConstantPoolEntry::Class(_) => "class",
+            ConstantPoolEntry::String(_) => "String",
+            ConstantPoolEntry::FieldRef(..) => "Field",
+            ConstantPoolEntry::MethodRef(..) => "Method",
+            ConstantPoolEntry::InterfaceMethodRef(..) => "InterfaceMethod",
+            ConstantPoolEntry::NameAndType(..) => "NameAndType",
+            ConstantPoolEntry::MethodHandle(..) => "MethodHandle",
+            ConstantPoolEntry::MethodType(_) => "MethodType",
+            ConstantPoolEntry::Dynamic(..) => "Dynamic",
+            ConstantPoolEntry::InvokeDynamic(..) => "InvokeDynamic",
+            ConstantPoolEntry::Module(_) => "Module",
+            ConstantPoolEntry::Package(_) => "Package",
+            ConstantPoolEntry::Invalid => return format!("#{}", index),
+        };
+        format!("{} {}", kind, value)
+    }
+
+    /// The value of a constant field as written in Java.
+    fn constant_value(&self, descriptor: &str, index: u2) -> String {
+        match self.constants.get(index) {
+            Some(&ConstantPoolEntry::Integer(value)) => match descriptor {
+                "C" => match char::from_u32(value as u32) {
+                    Some(c) => format!("'{}'", escape_char(c, '\'')),
+                    None => value.to_string(),
+                },
+                "Z" => (value == 1).to_string(),
+                _ => value.to_string(),
+            },
+            Some(&ConstantPoolEntry::String(string)) => {
+                let value = self.utf8(string);
+                format!(
+                    "\"{}\"",
+                    value
+                        .chars()
+                        .map(|c| escape_char(c, '"'))
+                        .collect::<String>()
+                )
+            }
+            _ => self.string_value(index),
+        }
+    }
+
+    fn write_field(&mut self, field: &Field) {
+        let flags = field.flags();
+        if !self.options.check_access(flags) {
+            return;
+        }
+        let descriptor = self.utf8(field.descriptor_index()).to_string();
+        self.write_modifiers(modifiers(flags, FIELD_MODIFIERS));
+        let field_type = field
+            .signature_index()
+            .and_then(|index| {
+                SignatureReader::new(self.utf8(index), self.options.verbose).field_signature()
+            })
+            .map_or_else(|| java_type(&descriptor), |signature| java_name(&signature));
+        self.print(&field_type);
+        self.print(" ");
+        self.print(self.utf8(field.name_index()));
+        if self.options.constants && field.constant_value_index() != 0 {
+            self.print(" = ");
+            let value = self.constant_value(&descriptor, field.constant_value_index());
+            self.print(&value);
+        }
+        self.println(";");
+        self.indent();
+        if self.options.descriptors || self.options.verbose {
+            self.println(&format!("descriptor: {}", descriptor));
+        }
+        if self.options.verbose {
+            self.write_flags(flags, FIELD_FLAGS);
+            if field.constant_value_index() != 0 {
+                let constant = self.constant(field.constant_value_index());
+                self.println(&format!("ConstantValue: {}", constant));
+            }
+            for attribute in field.attributes() {
+                self.write_attribute(attribute);
+            }
+        }
+        self.outdent();
+        if self.options.verbose || self.options.code || self.options.line_numbers {
+            self.writer.println();
+        }
+    }
+
+    fn write_method(&mut self, method: &Method) {
+        let flags = method.flags();
+        if !self.options.check_access(flags) {
+            return;
+        }
+        let name = self.utf8(method.name_index());
+        let descriptor = self.utf8(method.descriptor_index());
+        let mut method_modifiers = modifiers(flags, METHOD_MODIFIERS);
+        if self.klass.access_flags() & JVM_ACC_INTERFACE != 0
+            && flags & (JVM_ACC_ABSTRACT | JVM_ACC_STATIC | JVM_ACC_PRIVATE) == 0
+            && name != "<clinit>"
+        {
+            method_modifiers.push("default");
+        }
+        self.write_modifiers(method_modifiers);
+        let signature = method.signature_index().and_then(|index| {
+            SignatureReader::new(self.utf8(index), self.options.verbose).method_signature()
+        });
+        let (mut parameters, return_type, throws) = match signature {
+            Some(signature) => {
+                if !signature.type_parameters.is_empty() {
+                    let type_parameters = signature.type_parameters.join(", ");
+                    self.print(&java_name(&format!("<{}> ", type_parameters)));
+                }
+                let parameters = signature.parameters.iter().map(|p| java_name(p));
+                let return_type = java_name(&signature.return_type);
+                (parameters.collect(), return_type, signature.throws)
+            }
+            None => {
+                let (parameters, return_type) = split_method_descriptor(descriptor);
+                let parameters = parameters.iter().map(|p| java_type(p));
+                (
+                    parameters.collect::<Vec<_>>(),
+                    java_type(return_type),
+                    Vec::new(),
+                )
+            }
+        };
+        if flags & JVM_ACC_VARARGS != 0 {
+            if let Some(last) = parameters.last_mut().filter(|p| p.ends_with("[]")) {
+                last.truncate(last.len() - 2);
+                last.push_str("...");
+            }
+        }
+        let parameters = format!("({})", parameters.join(", "));
+        match name {
+            "<init>" => {
+                self.print(&java_name(self.klass.name()));
+                self.print(&parameters);
+            }
+            "<clinit>" => self.print("{}"),
+            _ => {
+                self.print(&return_type);
+                self.print(" ");
+                self.print(name);
+                self.print(&parameters);
+            }
+        }
+        let exceptions = method.exceptions();
+        if !exceptions.is_empty() {
+            self.print(" throws ");
+            if throws.is_empty() {
+                let names: Vec<String> = exceptions
+                    .iter()
+                    .map(|&index| java_name(self.name(index)))
+                    .collect();
+                self.print(&names.join(", "));
+            } else {
+                // the types of the signature keep their internal names, like javap
+                self.print(&throws.join(", "));
+            }
+        }
+        self.println(";");
+
+        self.indent();
+        if self.options.descriptors || self.options.verbose {
+            self.println(&format!("descriptor: {}", descriptor));
+        }
+        if self.options.verbose {
+            self.write_flags(flags, METHOD_FLAGS);
+            if let Some(code) = method.code() {
+                self.write_code(code, method);
+            }
+            for attribute in method.attributes() {
+                self.write_attribute(attribute);
+            }
+        } else if let Some(code) = method.code() {
+            if self.options.code {
+                self.println("Code:");
+                self.write_instructions(code);
+                self.write_exception_table(code);
+            }
+            if self.options.line_numbers {
+                self.write_line_number_table(code);
+                let local_variables = code
+                    .attributes()
+                    .iter()
+                    .find(|attribute| matches!(attribute, Attribute::LocalVariableTable(_)));
+                if let Some(local_variables) = local_variables {
+                    self.write_attribute(local_variables);
+                }
+            }
+        }
+        self.outdent();
+        self.writer.pending_newline = self.options.verbose
+            || self.options.code
+            || self.options.line_numbers
+            || self.options.descriptors;
+    }
+
+    fn write_code(&mut self, code: &Code, method: &Method) {
+        self.println("Code:");
+        self.indent();
+        let descriptor = self.utf8(method.descriptor_index());
+        let (parameters, _) = split_method_descriptor(descriptor);
+        // the parameters rather than their slots, like javap
+        let mut args_size = parameters.len();
+        if method.flags() & JVM_ACC_STATIC == 0 {
+            args_size += 1;
+        }
+        self.println(&format!(
+            "stack={}, locals={}, args_size={}",
+            code.max_stack(),
+            code.max_locals(),
+            args_size
+        ));
+        self.write_instructions(code);
+        self.write_exception_table(code);
+        // in the order of javac
+        self.write_line_number_table(code);
+        let (local_variables, others): (Vec<&Attribute>, Vec<&Attribute>) =
+            code.attributes().iter().partition(|attribute| {
+                matches!(
+                    attribute,
+                    Attribute::LocalVariableTable(_) | Attribute::LocalVariableTypeTable(_)
+                )
+            });
+        for attribute in local_variables {
+            self.write_attribute(attribute);
+        }
+        if let Some(stack_map_table) = code.stack_map_table() {
+            self.write_stack_map_table(stack_map_table);
+        }
+        for attribute in others {
+            self.write_attribute(attribute);
+        }
+        self.outdent();
+    }
+
+    fn write_instructions(&mut self, code: &Code) {
+        let code = code.code();
+        let mut bci = 0;
+        while bci < code.len() {
+            match bytecodes::length_at(code, bci) {
+                Some(length) => {
+                    self.write_instruction(code, bci);
+                    bci += length;
+                }
+                None => {
+                    self.println(&format!("{:4}: error: illegal opcode {}", bci, code[bci]));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn write_instruction(&mut self, code: &[u8], bci: usize) {
+        let wide = code[bci] == WIDE;
+        let opcode = if wide { code[bci + 1] } else { code[bci] };
+        let mut mnemonic = bytecodes::name(opcode).unwrap_or_default().to_string();
+        if wide {
+            mnemonic.push_str("_w");
+        }
+        self.print(&format!("{:4}: {:<13} ", bci, mnemonic));
+        let u1_at = |index: usize| code[index] as u2;
+        let u2_at = |index: usize| bytecodes::u2_at(code, index).unwrap();
+        let s4_at = |index: usize| bytecodes::s4_at(code, index).unwrap();
+        let target = |offset: i32| (bci as i64 + offset as i64).to_string();
+        match opcode {
+            BIPUSH => self.print(&(code[bci + 1] as i8).to_string()),
+            SIPUSH => self.print(&(u2_at(bci + 1) as i16).to_string()),
+            LDC => self.write_constant_operand(u1_at(bci + 1), None),
+            LDC_W
+            | LDC2_W
+            | GETSTATIC..=INVOKESTATIC
+            | NEW
+            | ANEWARRAY
+            | CHECKCAST
+            | INSTANCEOF => self.write_constant_operand(u2_at(bci + 1), None),
+            INVOKEINTERFACE | INVOKEDYNAMIC | MULTIANEWARRAY => {
+                self.write_constant_operand(u2_at(bci + 1), Some(u1_at(bci + 3)))
+            }
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET if wide => {
+                self.print(&u2_at(bci + 2).to_string())
+            }
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET => self.print(&u1_at(bci + 1).to_string()),
+            IINC if wide => self.print(&format!("{}, {}", u2_at(bci + 2), u2_at(bci + 4) as i16)),
+            IINC => self.print(&format!("{}, {}", u1_at(bci + 1), code[bci + 2] as i8)),
+            IFEQ..=JSR | IFNULL | IFNONNULL => self.print(&target(u2_at(bci + 1) as i16 as i32)),
+            GOTO_W | JSR_W => self.print(&target(s4_at(bci + 1))),
+            NEWARRAY => {
+                let element = match code[bci + 1] {
+                    4 => "boolean".to_string(),
+                    5 => "char".to_string(),
+                    6 => "float".to_string(),
+                    7 => "double".to_string(),
+                    8 => "byte".to_string(),
+                    9 => "short".to_string(),
+                    10 => "int".to_string(),
+                    11 => "long".to_string(),
+                    element => format!("BOGUS TYPE:{}", element),
+                };
+                self.print(&format!(" {}", element));
+            }
+            TABLESWITCH | LOOKUPSWITCH => {
+                let operands = (bci + 4) & !3;
+                let default = s4_at(operands);
+                let cases: Vec<(i32, i32)> = if opcode == TABLESWITCH {
+                    let low = s4_at(operands + 4);
+                    let high = s4_at(operands + 8);
+                    self.print(&format!("{{ // {} to {}", low, high));
+                    (low..=high)
+                        .enumerate()
+                        .map(|(i, key)| (key, s4_at(operands + 12 + 4 * i)))
+                        .collect()
+                } else {
+                    let pairs = s4_at(operands + 4) as usize;
+                    self.print(&format!("{{ // {}", pairs));
+                    (0..pairs)
+                        .map(|i| (s4_at(operands + 8 + 8 * i), s4_at(operands + 12 + 8 * i)))
+                        .collect()
+                };
+                // the cases are indented past the bci
+                let indent = 3;
+                self.writer.indent += indent;
+                for (key, offset) in cases {
+                    self.print(&format!("\n{:12}: {}", key, target(offset)));
+                }
+                self.print(&format!("\n     default: {}\n}}", target(default)));
+                self.writer.indent -= indent;
+            }
+            _ => {}
+        }
+        self.writer.println();
+    }
+
+    fn write_constant_operand(&mut self, index: u2, value: Option<u2>) {
+        match value {
+            Some(value) => self.print(&format!("#{},  {}", index, value)),
+            None => self.print(&format!("#{}", index)),
+        }
+        self.writer.tab();
+        let constant = self.constant(index);
+        self.print(&format!("// {}", constant));
+    }
+
+    fn write_exception_table(&mut self, code: &Code) {
+        if code.exception_table().is_empty() {
+            return;
+        }
+        self.println("Exception table:");
+        self.indent();
+        self.println(" from    to  target type");
+        for handler in code.exception_table() {
+            self.print(&format!(
+                " {:5} {:5} {:5}   ",
+                handler.start_pc, handler.end_pc, handler.handler_pc
+            ));
+            if handler.catch_type == 0 {
+                self.println("any");
+            } else {
+                let class = self.string_value(handler.catch_type);
+                self.println(&format!("Class {}", class));
+            }
+        }
+        self.outdent();
+    }
+
+    fn write_line_number_table(&mut self, code: &Code) {
+        let Some(line_number_table) = code.line_number_table() else {
+            return;
+        };
+        self.println("LineNumberTable:");
+        self.indent();
+        for (start_pc, line_number) in line_number_table.entries() {
+            self.println(&format!("line {}: {}", line_number, start_pc));
+        }
+        self.outdent();
+    }
+
+    fn write_stack_map_table(&mut self, bytes: &[u8]) {
+        let mut stream = ClassFileStream::new(bytes.to_vec(), String::new());
+        let Ok(count) = stream.get_u2() else {
+            return;
+        };
+        self.println(&format!("StackMapTable: number_of_entries = {}", count));
+        self.indent();
+        for _ in 0..count {
+            if self.write_stack_map_frame(&mut stream).is_err() {
+                self.println("error: truncated StackMapTable");
+                break;
+            }
+        }
+        self.outdent();
+    }
+
+    fn write_stack_map_frame(
+        &mut self,
+        stream: &mut ClassFileStream,
+    ) -> Result<(), crate::utilities::exceptions::Exception> {
+        let frame_type = stream.get_u1()?;
+        let kind = match frame_type {
+            0..=63 => "same",
+            64..=127 => "same_locals_1_stack_item",
+            247 => "same_locals_1_stack_item_frame_extended",
+            248..=250 => "chop",
+            251 => "same_frame_extended",
+            252..=254 => "append",
+            255 => "full_frame",
+            _ => "unknown",
+        };
+        self.println(&format!("frame_type = {} /* {} */", frame_type, kind));
+        self.indent();
+        if frame_type >= 247 {
+            let offset_delta = stream.get_u2()?;
+            self.println(&format!("offset_delta = {}", offset_delta));
+        }
+        match frame_type {
+            64..=127 | 247 => self.write_verification_types("stack", 1, stream)?,
+            252..=254 => {
+                self.write_verification_types("locals", (frame_type - 251) as usize, stream)?
+            }
+            255 => {
+                let count = stream.get_u2()? as usize;
+                self.write_verification_types("locals", count, stream)?;
+                let count = stream.get_u2()? as usize;
+                self.write_verification_types("stack", count, stream)?;
+            }
+            _ => {}
+        }
+        self.outdent();
+        Ok(())
+    }
+
+    fn write_verification_types(
+        &mut self,
+        label: &str,
+        count: usize,
+        stream: &mut ClassFileStream,
+    ) -> Result<(), crate::utilities::exceptions::Exception> {
+        let mut types = Vec::with_capacity(count);
+        for _ in 0..count {
+            types.push(match stream.get_u1()? {
+                0 => "top".to_string(),
+                1 => "int".to_string(),
+                2 => "float".to_string(),
+                3 => "double".to_string(),
+                4 => "long".to_string(),
+                5 => "null".to_string(),
+                6 => "this".to_string(),
+                7 => format!("class {}", self.string_value(stream.get_u2()?)),
+                8 => format!("uninitialized {}", stream.get_u2()?),
+                tag => format!("[unknown tag {}]", tag),
+            });
+        }
+        if types.is_empty() {
+            self.println(&format!("{} = []", label));
+        } else {
+            self.println(&format!("{} = [ {} ]", label, types.join(", ")));
+        }
+        Ok(())
+    }
+
+    fn write_attribute(&mut self, attribute: &Attribute) {
+        match attribute {
+            Attribute::SourceFile(index) => {
+                self.println(&format!("SourceFile: \"{}\"", self.utf8(*index)))
+            }
+            Attribute::SourceDebugExtension(bytes) => {
+                self.println("SourceDebugExtension:");
+                self.indent();
+                let text = String::from_utf8_lossy(bytes);
+                for line in text.split(['\r', '\n']).filter(|line| !line.is_empty()) {
+                    self.println(line);
+                }
+                self.outdent();
+            }
+            Attribute::Signature(index) => {
+                self.print(&format!("Signature: #{}", index));
+                self.writer.tab();
+                self.println(&format!("// {}", self.utf8(*index)));
+            }
+            Attribute::Deprecated => self.println("Deprecated: true"),
+            Attribute::Synthetic => self.println("Synthetic: true"),
+            Attribute::Exceptions(exceptions) => {
+                self.println("Exceptions:");
+                self.indent();
+                let names: Vec<String> = exceptions
+                    .iter()
+                    .map(|&index| java_name(self.name(index)))
+                    .collect();
+                self.println(&format!("throws {}", names.join(", ")));
+                self.outdent();
+            }
+            Attribute::InnerClasses(classes) => self.write_inner_classes(classes),
+            Attribute::EnclosingMethod(method) => {
+                self.print(&format!(
+                    "EnclosingMethod: #{}.#{}",
+                    method.class_index, method.method_index
+                ));
+                self.writer.tab();
+                self.print(&format!("// {}", java_name(self.name(method.class_index))));
+                if let Some((name, _)) = self.constants.name_and_type(method.method_index) {
+                    self.print(&format!(".{}", name));
+                }
+                self.writer.println();
+            }
+            Attribute::NestHost(index) => {
+                self.println(&format!("NestHost: {}", self.constant(*index)))
+            }
+            Attribute::NestMembers(classes) => self.write_classes("NestMembers:", classes),
+            Attribute::PermittedSubclasses(classes) => {
+                self.write_classes("PermittedSubclasses:", classes)
+            }
+            Attribute::Record(components) => {
+                self.println("Record:");
+                self.indent();
+                for component in components {
+                    let descriptor = self.utf8(component.descriptor_index);
+                    let component_type = attribute::signature_index(&component.attributes)
+                        .and_then(|index| {
+                            SignatureReader::new(self.utf8(index), self.options.verbose)
+                                .field_signature()
+                        })
+                        .map_or_else(|| java_type(descriptor), |signature| java_name(&signature));
+                    let name = self.utf8(component.name_index);
+                    self.println(&format!("{} {};", component_type, name));
+                    self.indent();
+                    self.println(&format!("descriptor: {}", descriptor));
+                    for attribute in &component.attributes {
+                        self.write_attribute(attribute);
+                    }
+                    self.outdent();
+                    self.writer.println();
+                }
+                self.outdent();
+            }
+            Attribute::BootstrapMethods(methods) => {
+                self.println("BootstrapMethods:");
+                self.indent();
+                for (i, method) in methods.iter().enumerate() {
+                    let value = self.string_value(method.method_ref);
+                    self.println(&format!("{}: #{} {}", i, method.method_ref, value));
+                    self.indent();
+                    self.println("Method arguments:");
+                    self.indent();
+                    for &argument in &method.arguments {
+                        let value = self.string_value(argument);
+                        self.println(&format!("#{} {}", argument, value));
+                    }
+                    self.outdent();
+                    self.outdent();
+                }
+                self.outdent();
+            }
+            Attribute::MethodParameters(parameters) => {
+                self.println("MethodParameters:");
+                self.indent();
+                self.println(&format!("{:<31}{}", "Name", "Flags"));
+                for parameter in parameters {
+                    let name = match parameter.name_index {
+                        0 => "<no name>".to_string(),
+                        index => self.string_value(index),
+                    };
+                    let flags = parameter.access_flags;
+                    let flags = [
+                        (JVM_ACC_FINAL, "final "),
+                        (JVM_ACC_MANDATED, "mandated "),
+                        (JVM_ACC_SYNTHETIC, "synthetic"),
+                    ]
+                    .iter()
+                    .filter(|&&(flag, _)| flags & flag != 0)
+                    .map(|&(_, name)| name)
+                    .collect::<String>();
+                    self.println(&format!("{:<31}{}", name, flags));
+                }
+                self.outdent();
+            }
+            Attribute::LocalVariableTable(variables) => {
+                self.write_local_variables("LocalVariableTable:", variables)
+            }
+            Attribute::LocalVariableTypeTable(variables) => {
+                self.write_local_variables("LocalVariableTypeTable:", variables)
+            }
+            Attribute::RuntimeVisibleAnnotations(annotations) => {
+                self.println("RuntimeVisibleAnnotations:");
+                self.write_annotations(annotations);
+            }
+            Attribute::RuntimeInvisibleAnnotations(annotations) => {
+                self.println("RuntimeInvisibleAnnotations:");
+                self.write_annotations(annotations);
+            }
+            Attribute::RuntimeVisibleParameterAnnotations(parameters) => {
+                self.println("RuntimeVisibleParameterAnnotations:");
+                self.write_parameter_annotations(parameters);
+            }
+            Attribute::RuntimeInvisibleParameterAnnotations(parameters) => {
+                self.println("RuntimeInvisibleParameterAnnotations:");
+                self.write_parameter_annotations(parameters);
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(annotations) => {
+                self.println("RuntimeVisibleTypeAnnotations:");
+                self.write_type_annotations(annotations);
+            }
+            Attribute::RuntimeInvisibleTypeAnnotations(annotations) => {
+                self.println("RuntimeInvisibleTypeAnnotations:");
+                self.write_type_annotations(annotations);
+            }
+            Attribute::AnnotationDefault(value) => {
+                self.println("AnnotationDefault:");
+                self.indent();
+                self.println(&format!("default_value: {}", self.element_value(value)));
+                self.indent();
+                self.write_element_value(value);
+                self.writer.println();
+                self.outdent();
+                self.outdent();
+            }
+            Attribute::Module(module) => self.write_module(module),
+            Attribute::ModulePackages(packages) => {
+                self.println("ModulePackages:");
+                self.indent();
+                for &package in packages {
+                    self.print(&format!("#{}", package));
+                    self.writer.tab();
+                    self.println(&format!("// {}", java_name(self.name(package))));
+                }
+                self.outdent();
+            }
+            Attribute::ModuleMainClass(index) => {
+                self.print(&format!("ModuleMainClass: #{}", index));
+                self.writer.tab();
+                self.println(&format!("// {}", java_name(self.name(*index))));
+            }
+            Attribute::Unknown { name_index, info } => {
+                // the content of the unknown attributes is indented further
+                self.print("  ");
+                self.print(&format!(
+                    "{}: length = 0x{:X} (unknown attribute)",
+                    self.utf8(*name_index),
+                    info.len()
+                ));
+                self.writer.println();
+                self.print("   ");
+                for (i, byte) in info.iter().enumerate() {
+                    self.print(&format!("{:02X}", byte));
+                    self.print(if i % 16 == 15 { "\n   " } else { " " });
+                }
+                self.writer.println();
+            }
+        }
+    }
+
+    /// The inner classes with the access of the options, and the header if there are any.
+    fn write_inner_classes(&mut self, classes: &[InnerClass]) {
+        let classes: Vec<&InnerClass> = classes
+            .iter()
+            .filter(|class| self.options.check_access(class.inner_class_access_flags))
+            .collect();
+        if classes.is_empty() {
+            return;
+        }
+        self.println("InnerClasses:");
+        self.indent();
+        for class in classes {
+            let mut flags = class.inner_class_access_flags;
+            if flags & JVM_ACC_INTERFACE != 0 {
+                flags &= !JVM_ACC_ABSTRACT;
+            }
+            self.write_modifiers(modifiers(flags, INNER_CLASS_MODIFIERS));
+            let mut comment = String::from("// ");
+            if class.inner_name_index != 0 {
+                self.print(&format!("#{}= ", class.inner_name_index));
+                comment.push_str(&format!("{}=", self.utf8(class.inner_name_index)));
+            }
+            self.print(&format!("#{}", class.inner_class_info_index));
+            comment.push_str(&self.constant(class.inner_class_info_index));
+            if class.outer_class_info_index != 0 {
+                self.print(&format!(" of #{}", class.outer_class_info_index));
+                comment.push_str(" of ");
+                comment.push_str(&self.constant(class.outer_class_info_index));
+            }
+            self.print(";");
+            self.writer.tab();
+            self.println(&comment);
+        }
+        self.outdent();
+    }
+
+    /// The internal names of the classes after the header.
+    fn write_classes(&mut self, header: &str, classes: &[u2]) {
+        self.println(header);
+        self.indent();
+        for &class in classes {
+            let name = self.string_value(class);
+            self.println(&name);
+        }
+        self.outdent();
+    }
+
+    fn write_local_variables(&mut self, header: &str, variables: &[LocalVariable]) {
+        self.println(header);
+        self.indent();
+        self.println("Start  Length  Slot  Name   Signature");
+        for variable in variables {
+            self.println(&format!(
+                "{:5} {:7} {:5} {:>5}   {}",
+                variable.start_pc,
+                variable.length,
+                variable.index,
+                self.string_value(variable.name_index),
+                self.string_value(variable.descriptor_index)
+            ));
+        }
+        self.outdent();
+    }
+
+    /// Each annotation with the indexes, then resolved.
+    fn write_annotations(&mut self, annotations: &[Annotation]) {
+        self.indent();
+        for (i, annotation) in annotations.iter().enumerate() {
+            self.println(&format!("{}: {}", i, self.annotation(annotation)));
+            self.indent();
+            self.write_annotation(annotation);
+            self.writer.println();
+            self.outdent();
+        }
+        self.outdent();
+    }
+
+    fn write_parameter_annotations(&mut self, parameters: &[Vec<Annotation>]) {
+        self.indent();
+        for (i, annotations) in parameters.iter().enumerate() {
+            self.println(&format!("parameter {}:", i));
+            self.write_annotations(annotations);
+        }
+        self.outdent();
+    }
+
+    fn write_type_annotations(&mut self, annotations: &[TypeAnnotation]) {
+        self.indent();
+        for (i, annotation) in annotations.iter().enumerate() {
+            self.println(&format!(
+                "{}: {}: {}",
+                i,
+                self.annotation(&annotation.annotation),
+                type_annotation_position(annotation)
+            ));
+            self.indent();
+            self.write_annotation(&annotation.annotation);
+            self.writer.println();
+            self.outdent();
+        }
+        self.outdent();
+    }
+
+    /// The annotation with the constant pool indexes, `#12(#13=I#14)`.
+    fn annotation(&self, annotation: &Annotation) -> String {
+        let pairs: Vec<String> = annotation
+            .element_value_pairs
+            .iter()
+            .map(|(name, value)| format!("#{}={}", name, self.element_value(value)))
+            .collect();
+        format!("#{}({})", annotation.type_index, pairs.join(","))
+    }
+
+    fn element_value(&self, value: &ElementValue) -> String {
+        match value {
+            ElementValue::Const(tag, index) => format!("{}#{}", *tag as char, index),
+            ElementValue::Enum {
+                type_name_index,
+                const_name_index,
+            } => format!("e#{}.#{}", type_name_index, const_name_index),
+            ElementValue::Class(index) => format!("c#{}", index),
+            ElementValue::Annotation(annotation) => format!("@{}", self.annotation(annotation)),
+            ElementValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(|v| self.element_value(v)).collect();
+                format!("[{}]", values.join(","))
+            }
+        }
+    }
+
+    /// The annotation with the values, and each element on its own line.
+    fn write_annotation(&mut self, annotation: &Annotation) {
+        self.print(&java_type(self.utf8(annotation.type_index)));
+        if annotation.element_value_pairs.is_empty() {
+            return;
+        }
+        self.println("(");
+        self.indent();
+        for (name, value) in &annotation.element_value_pairs {
+            self.print(&format!("{}=", self.string_value(*name)));
+            self.write_element_value(value);
+            self.writer.println();
+        }
+        self.outdent();
+        self.print(")");
+    }
+
+    fn write_element_value(&mut self, value: &ElementValue) {
+        match value {
+            ElementValue::Const(tag, index) => {
+                let value = match (*tag, self.constants.get(*index)) {
+                    (b'B', Some(ConstantPoolEntry::Integer(value))) => format!("(byte) {}", value),
+                    (b'S', Some(ConstantPoolEntry::Integer(value))) => {
+                        format!("(short) {}", value)
+                    }
+                    (b'C', _) => self.constant_value("C", *index),
+                    (b'Z', _) => self.constant_value("Z", *index),
+                    (b's', Some(ConstantPoolEntry::Utf8(string))) => format!(
+                        "\"{}\"",
+                        string
+                            .chars()
+                            .map(|c| escape_char(c, '"'))
+                            .collect::<String>()
+                    ),
+                    _ => self.string_value(*index),
+                };
+                self.print(&value);
+            }
+            ElementValue::Enum {
+                type_name_index,
+                const_name_index,
+            } => self.print(&format!(
+                "{}.{}",
+                self.utf8(*type_name_index),
+                self.utf8(*const_name_index)
+            )),
+            ElementValue::Class(index) => self.print(&format!("class {}", self.utf8(*index))),
+            ElementValue::Annotation(annotation) => {
+                self.print("@");
+                self.write_annotation(annotation);
+            }
+            ElementValue::Array(values) => {
+                self.print("[");
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        self.print(",");
+                    }
+                    self.write_element_value(value);
+                }
+                self.print("]");
+            }
+        }
+    }
+
+    /// The indexes of the module declaration with their values in the comments.
+    fn write_module(&mut self, module: &Module) {
+        self.println("Module:");
+        self.indent();
+        self.print(&format!("#{},{:x}", module.name_index, module.flags));
+        self.writer.tab();
+        self.print(&format!("// {}", self.string_value(module.name_index)));
+        self.print(&flag_comments(module.flags, MODULE_FLAG_COMMENTS));
+        self.writer.println();
+        self.write_version(module.version_index);
+
+        self.write_count(module.requires.len(), "requires");
+        self.indent();
+        for requires in &module.requires {
+            self.print(&format!(
+                "#{},{:x}",
+                requires.requires_index, requires.flags
+            ));
+            self.writer.tab();
+            self.print(&format!(
+                "// {}",
+                self.string_value(requires.requires_index)
+            ));
+            self.print(&flag_comments(requires.flags, REQUIRES_FLAG_COMMENTS));
+            self.writer.println();
+            self.write_version(requires.version_index);
+        }
+        self.outdent();
+        for (packages, label) in [(&module.exports, "exports"), (&module.opens, "opens")] {
+            self.write_count(packages.len(), label);
+            self.indent();
+            for package in packages {
+                self.print(&format!("#{},{:x}", package.package_index, package.flags));
+                self.writer.tab();
+                self.print(&format!("// {}", self.string_value(package.package_index)));
+                self.print(&flag_comments(package.flags, PACKAGE_FLAG_COMMENTS));
+                if !package.to.is_empty() {
+                    self.print(&format!(" to ... {}", package.to.len()));
+                }
+                self.writer.println();
+                self.indent();
+                for &to in &package.to {
+                    self.print(&format!("#{}", to));
+                    self.writer.tab();
+                    self.println(&format!("// ... to {}", self.string_value(to)));
+                }
+                self.outdent();
+            }
+            self.outdent();
+        }
+        self.write_count(module.uses.len(), "uses");
+        self.indent();
+        for &uses in &module.uses {
+            self.print(&format!("#{}", uses));
+            self.writer.tab();
+            self.println(&format!("// {}", self.string_value(uses)));
+        }
+        self.outdent();
+        self.write_count(module.provides.len(), "provides");
+        self.indent();
+        for provides in &module.provides {
+            self.print(&format!("#{}", provides.provides_index));
+            self.writer.tab();
+            self.println(&format!(
+                "// {} with ... {}",
+                self.string_value(provides.provides_index),
+                provides.with.len()
+            ));
+            self.indent();
+            for &with in &provides.with {
+                self.print(&format!("#{}", with));
+                self.writer.tab();
+                self.println(&format!("// ... with {}", self.string_value(with)));
+            }
+            self.outdent();
+        }
+        self.outdent();
+        self.outdent();
+    }
+
+    fn write_version(&mut self, index: u2) {
+        self.print(&format!("#{}", index));
+        if index != 0 {
+            self.writer.tab();
+            self.print(&format!("// {}", self.string_value(index)));
+        }
+        self.writer.println();
+    }
+
+    fn write_count(&mut self, count: usize, label: &str) {
+        self.print(&count.to_string());
+        self.writer.tab();
+        self.println(&format!("// {}", label));
+    }
+
+    /// The directives of a module in the non verbose mode, like a `module-info.java`.
+    fn write_directives(&mut self, module: &Module) {
+        for requires in &module.requires {
+            self.print("requires");
+            if requires.flags & JVM_ACC_STATIC_PHASE != 0 {
+                self.print(" static");
+            }
+            if requires.flags & JVM_ACC_TRANSITIVE != 0 {
+                self.print(" transitive");
+            }
+            self.println(&format!(" {};", self.name(requires.requires_index)));
+        }
+        for (packages, label) in [(&module.exports, "exports"), (&module.opens, "opens")] {
+            for package in packages {
+                let package_name = java_name(self.name(package.package_index));
+                self.print(&format!("{} {}", label, package_name));
+                let modules: Vec<&str> = package.to.iter().map(|&to| self.name(to)).collect();
+                self.write_list(" to", &modules);
+            }
+        }
+        for &uses in &module.uses {
+            self.println(&format!("uses {};", java_name(self.name(uses))));
+        }
+        for provides in &module.provides {
+            // sic, javap prints two spaces
+            self.print(&format!(
+                "provides  {}",
+                java_name(self.name(provides.provides_index))
+            ));
+            let classes: Vec<String> = provides
+                .with
+                .iter()
+                .map(|&with| java_name(self.name(with)))
+                .collect();
+            let classes: Vec<&str> = classes.iter().map(String::as_str).collect();
+            self.write_list(" with", &classes);
+        }
+    }
+
+    /// The items after the keyword, one per line, and the end of the directive.
+    fn write_list(&mut self, keyword: &str, items: &[&str]) {
+        if items.is_empty() {
+            self.println(";");
+            return;
+        }
+        self.println(keyword);
+        self.indent();
+        self.print(&items.join(",\n"));
+        self.println(";");
+        self.outdent();
+    }
+}
+
+const CLASS_FLAGS: &[(u2, &str)] = &[
+    (JVM_ACC_PUBLIC, "ACC_PUBLIC"),
+    (JVM_ACC_FINAL, "ACC_FINAL"),
+    (JVM_ACC_SUPER, "ACC_SUPER"),
+    (JVM_ACC_INTERFACE, "ACC_INTERFACE"),
+    (JVM_ACC_ABSTRACT, "ACC_ABSTRACT"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+    (JVM_ACC_ANNOTATION, "ACC_ANNOTATION"),
+    (JVM_ACC_ENUM, "ACC_ENUM"),
+    (JVM_ACC_MODULE, "ACC_MODULE"),
+];
+
+const FIELD_FLAGS: &[(u2, &str)] = &[
+    (JVM_ACC_PUBLIC, "ACC_PUBLIC"),
+    (JVM_ACC_PRIVATE, "ACC_PRIVATE"),
+    (JVM_ACC_PROTECTED, "ACC_PROTECTED"),
+    (JVM_ACC_STATIC, "ACC_STATIC"),
+    (JVM_ACC_FINAL, "ACC_FINAL"),
+    (JVM_ACC_VOLATILE, "ACC_VOLATILE"),
+    (JVM_ACC_TRANSIENT, "ACC_TRANSIENT"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+    (JVM_ACC_ENUM, "ACC_ENUM"),
+];
+
+const METHOD_FLAGS: &[(u2, &str)] = &[
+    (JVM_ACC_PUBLIC, "ACC_PUBLIC"),
+    (JVM_ACC_PRIVATE, "ACC_PRIVATE"),
+    (JVM_ACC_PROTECTED, "ACC_PROTECTED"),
+    (JVM_ACC_STATIC, "ACC_STATIC"),
+    (JVM_ACC_FINAL, "ACC_FINAL"),
+    (JVM_ACC_SYNCHRONIZED, "ACC_SYNCHRONIZED"),
+    (JVM_ACC_BRIDGE, "ACC_BRIDGE"),
+    (JVM_ACC_VARARGS, "ACC_VARARGS"),
+    (JVM_ACC_NATIVE, "ACC_NATIVE"),
+    (JVM_ACC_ABSTRACT, "ACC_ABSTRACT"),
+    (JVM_ACC_STRICT, "ACC_STRICT"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+];
+
+const INNER_CLASS_MODIFIERS: u2 = JVM_ACC_PUBLIC
+    | JVM_ACC_PRIVATE
+    | JVM_ACC_PROTECTED
+    | JVM_ACC_STATIC
+    | JVM_ACC_FINAL
+    | JVM_ACC_ABSTRACT;
+
+const FIELD_MODIFIERS: u2 = JVM_ACC_PUBLIC
+    | JVM_ACC_PRIVATE
+    | JVM_ACC_PROTECTED
+    | JVM_ACC_STATIC
+    | JVM_ACC_FINAL
+    | JVM_ACC_VOLATILE
+    | JVM_ACC_TRANSIENT;
+
+const METHOD_MODIFIERS: u2 = JVM_ACC_PUBLIC
+    | JVM_ACC_PRIVATE
+    | JVM_ACC_PROTECTED
+    | JVM_ACC_STATIC
+    | JVM_ACC_FINAL
+    | JVM_ACC_SYNCHRONIZED
+    | JVM_ACC_NATIVE
+    | JVM_ACC_ABSTRACT
+    | JVM_ACC_STRICT;
+
+/// The comments of the flags of a module declaration.
+const MODULE_FLAG_COMMENTS: &[(u2, &str)] = &[
+    (JVM_ACC_OPEN, "ACC_OPEN"),
+    (JVM_ACC_MANDATED, "ACC_MANDATED"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+];
+
+const REQUIRES_FLAG_COMMENTS: &[(u2, &str)] = &[
+    (JVM_ACC_TRANSITIVE, "ACC_TRANSITIVE"),
+    (JVM_ACC_STATIC_PHASE, "ACC_STATIC_PHASE"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+    (JVM_ACC_MANDATED, "ACC_MANDATED"),
+];
+
+const PACKAGE_FLAG_COMMENTS: &[(u2, &str)] = &[
+    (JVM_ACC_MANDATED, "ACC_MANDATED"),
+    (JVM_ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+];
+
+/// The names of the flags, then the remaining bits from the highest.
+fn flag_names(flags: u2, names: &[(u2, &str)]) -> Vec<String> {
+    let mut remaining = flags;
+    let mut result = Vec::new();
+    for &(flag, name) in names {
+        if remaining & flag != 0 {
+            result.push(name.to_string());
+            remaining &= !flag;
+        }
+    }
+    while remaining != 0 {
+        let bit = 1 << (15 - remaining.leading_zeros());
+        result.push(format!("0x{:x}", bit));
+        remaining &= !bit;
+    }
+    result
+}
+
+/// The names of the set flags, each after a space.
+fn flag_comments(flags: u2, names: &[(u2, &str)]) -> String {
+    names
+        .iter()
+        .filter(|&&(flag, _)| flags & flag != 0)
+        .map(|&(_, name)| format!(" {}", name))
+        .collect()
+}
+
+/// The target and the path of a type annotation, `FIELD, location=[ARRAY]`.
+fn type_annotation_position(annotation: &TypeAnnotation) -> String {
+    let target = match annotation.target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4A => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        _ => "METHOD_REFERENCE_TYPE_ARGUMENT",
+    };
+    let mut position = target.to_string();
+    match &annotation.target_info {
+        TargetInfo::TypeParameter(index) | TargetInfo::FormalParameter(index) => {
+            position.push_str(&format!(", param_index={}", index))
+        }
+        TargetInfo::SuperType(index) => {
+            // the super class is -1
+            position.push_str(&format!(", type_index={}", *index as i16))
+        }
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => position.push_str(&format!(
+            ", param_index={}, bound_index={}",
+            type_parameter_index, bound_index
+        )),
+        TargetInfo::Empty => {}
+        TargetInfo::Throws(index) => position.push_str(&format!(", type_index={}", index)),
+        TargetInfo::LocalVariable(ranges) => {
+            let ranges: Vec<String> = ranges
+                .iter()
+                .map(|(start_pc, length, index)| {
+                    format!("start_pc={}, length={}, index={}", start_pc, length, index)
+                })
+                .collect();
+            position.push_str(&format!(", {{{}}}", ranges.join("; ")));
+        }
+        TargetInfo::Catch(index) => position.push_str(&format!(", exception_index={}", index)),
+        TargetInfo::Offset(offset) => position.push_str(&format!(", offset={}", offset)),
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => position.push_str(&format!(
+            ", offset={}, type_index={}",
+            offset, type_argument_index
+        )),
+    }
+    if !annotation.type_path.is_empty() {
+        let path: Vec<String> = annotation
+            .type_path
+            .iter()
+            .map(|&(kind, index)| match kind {
+                0 => "ARRAY".to_string(),
+                1 => "INNER_TYPE".to_string(),
+                2 => "WILDCARD".to_string(),
+                _ => format!("TYPE_ARGUMENT({})", index),
+            })
+            .collect();
+        position.push_str(&format!(", location=[{}]", path.join(", ")));
+    }
+    position
+}
+
+/// The Java modifiers of the flags among the allowed ones.
+fn modifiers(flags: u2, allowed: u2) -> Vec<&'static str> {
+    [
+        (JVM_ACC_PUBLIC, "public"),
+        (JVM_ACC_PRIVATE, "private"),
+        (JVM_ACC_PROTECTED, "protected"),
+        (JVM_ACC_STATIC, "static"),
+        (JVM_ACC_FINAL, "final"),
+        (JVM_ACC_SYNCHRONIZED, "synchronized"),
+        (JVM_ACC_VOLATILE, "volatile"),
+        (JVM_ACC_TRANSIENT, "transient"),
+        (JVM_ACC_NATIVE, "native"),
+        (JVM_ACC_ABSTRACT, "abstract"),
+        (JVM_ACC_STRICT, "strictfp"),
+    ]
+    .into_iter()
+    .filter(|&(flag, _)| flags & allowed & flag != 0)
+    .map(|(_, modifier)| modifier)
+    .collect()
+}
+
+fn tag_name(entry: &ConstantPoolEntry) -> Option<&'static str> {
+    Some(match entry {
+        ConstantPoolEntry::Invalid => return None,
+        ConstantPoolEntry::Utf8(_) => "Utf8",
+        ConstantPoolEntry::Integer(_) => "Integer",
+        ConstantPoolEntry::Float(_) => "Float",
+        ConstantPoolEntry::Long(_) => "Long",
+        ConstantPoolEntry::Double(_) => "Double",
+        ConstantPoolEntry::Class(_) => "Class",
+        ConstantPoolEntry::String(_) => "String",
+        ConstantPoolEntry::FieldRef(..) => "Fieldref",
+        ConstantPoolEntry::MethodRef(..) => "Methodref",
+        ConstantPoolEntry::InterfaceMethodRef(..) => "InterfaceMethodref",
+        ConstantPoolEntry::NameAndType(..) => "NameAndType",
+        ConstantPoolEntry::MethodHandle(..) => "MethodHandle",
+        ConstantPoolEntry::MethodType(_) => "MethodType",
+        ConstantPoolEntry::Dynamic(..) => "Dynamic",
+        ConstantPoolEntry::InvokeDynamic(..) => "InvokeDynamic",
+        ConstantPoolEntry::Module(_) => "Module",
+        ConstantPoolEntry::Package(_) => "Package",
+    })
+}
+
+fn reference_kind_name(kind: u8) -> String {
+    match kind {
+        1 => "REF_getField".to_string(),
+        2 => "REF_getStatic".to_string(),
+        3 => "REF_putField".to_string(),
+        4 => "REF_putStatic".to_string(),
+        5 => "REF_invokeVirtual".to_string(),
+        6 => "REF_invokeStatic".to_string(),
+        7 => "REF_invokeSpecial".to_string(),
+        8 => "REF_newInvokeSpecial".to_string(),
+        9 => "REF_invokeInterface".to_string(),
+        _ => format!("[unknown reference kind {}]", kind),
+    }
+}
+
+/// The binary name of an internal name, `java/lang/Object` is `java.lang.Object`.
+fn java_name(name: &str) -> String {
+    name.replace('/', ".")
+}
+
+/// The Java type of a field descriptor, `[[J` is `long[][]`.
+fn java_type(descriptor: &str) -> String {
+    let dimensions = descriptor.bytes().take_while(|&byte| byte == b'[').count();
+    let element = match &descriptor[dimensions..] {
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "D" => "double".to_string(),
+        "F" => "float".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "S" => "short".to_string(),
+        "Z" => "boolean".to_string(),
+        "V" => "void".to_string(),
+        element => java_name(
+            element
+                .strip_prefix('L')
+                .and_then(|name| name.strip_suffix(';'))
+                .unwrap_or(element),
+        ),
+    };
+    element + &"[]".repeat(dimensions)
+}
+
+/// The descriptors of the parameters and the return type of a method descriptor
+/// checked by the parser.
+fn split_method_descriptor(descriptor: &str) -> (Vec<&str>, &str) {
+    let mut parameters = Vec::new();
+    let mut rest = descriptor.strip_prefix('(').unwrap_or(descriptor);
+    while !rest.is_empty() && !rest.starts_with(')') {
+        let dimensions = rest.bytes().take_while(|&byte| byte == b'[').count();
+        let length = match rest.as_bytes().get(dimensions) {
+            Some(b'L') => rest.find(';').map_or(rest.len(), |end| end + 1),
+            _ => (dimensions + 1).min(rest.len()),
+        };
+        parameters.push(&rest[..length]);
+        rest = &rest[length..];
+    }
+    (parameters, rest.strip_prefix(')').unwrap_or(rest))
+}
+
+/// The name, quoted with escapes when a part of it is not a Java identifier.
+fn check_name(name: &str) -> String {
+    let mut previous = '/';
+    for c in name.chars() {
+        let start = c.is_alphabetic() || c == '_' || c == '$';
+        if (previous == '/' && !start) || (c != '/' && !start && !c.is_numeric()) {
+            let mut quoted = String::from("\"");
+            for c in name.chars() {
+                match c {
+                    '\\' => quoted.push_str("\\\\"),
+                    '"' => quoted.push_str("\\\""),
+                    '\n' => quoted.push_str("\\n"),
+                    '\t' => quoted.push_str("\\t"),
+                    _ => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            return quoted;
+        }
+        previous = c;
+    }
+    if name.is_empty() {
+        "\"\"".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn escape_utf8(string: &str) -> String {
+    string.chars().map(|c| escape_char(c, '\0')).collect()
+}
+
+/// The escape sequence of the character in a Java literal, `quote` is escaped
+/// in the literal only.
+fn escape_char(c: char, quote: char) -> String {
+    match c {
+        '\t' => "\\t".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\u{8}' => "\\b".to_string(),
+        '\u{c}' => "\\f".to_string(),
+        '\\' => "\\\\".to_string(),
+        '"' | '\'' if quote == '\0' || c == quote => format!("\\{}", c),
+        _ if c.is_control() => format!("\\u{:04x}", c as u32),
+        _ => c.to_string(),
+    }
+}
+
+/// `Double.toString`: the shortest decimal, in scientific notation out of [10^-3, 10^7).
+fn java_double(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{:?}", value)
+    } else {
+        scientific(&format!("{:e}", value))
+    }
+}
+
+/// `Float.toString`.
+fn java_float(value: f32) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        format!("{:?}", value)
+    } else {
+        scientific(&format!("{:e}", value))
+    }
+}
+
+/// `1.5e-7` is `1.5E-7` and `1e10` is `1.0E10`.
+fn scientific(rust: &str) -> String {
+    let (mantissa, exponent) = rust.split_once('e').unwrap_or((rust, "0"));
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, exponent)
+    } else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
+/// The types of a method signature as `javap` prints them.
+struct MethodSignature {
+    type_parameters: Vec<String>,
+    parameters: Vec<String>,
+    return_type: String,
+    throws: Vec<String>,
+}
+
+/// Read a generic signature (JVMS §4.7.9.1) into the Java types, with the internal
+/// names of the classes like the types of `javap`.
+struct SignatureReader<'s> {
+    rest: &'s str,
+    /// Show the `Object` bounds of the type parameters.
+    verbose: bool,
+}
+
+impl<'s> SignatureReader<'s> {
+    fn new(signature: &'s str, verbose: bool) -> Self {
+        SignatureReader {
+            rest: signature,
+            verbose,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The text before the character, which is skipped.
+    fn until(&mut self, c: char) -> Option<&'s str> {
+        let (text, rest) = self.rest.split_once(c)?;
+        self.rest = rest;
+        Some(text)
+    }
+
+    fn field_type(&mut self) -> Option<String> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        let base = match c {
+            'B' => "byte",
+            'C' => "char",
+            'D' => "double",
+            'F' => "float",
+            'I' => "int",
+            'J' => "long",
+            'S' => "short",
+            'Z' => "boolean",
+            'V' => "void",
+            '[' => return Some(self.field_type()? + "[]"),
+            'T' => return self.until(';').map(str::to_string),
+            'L' => return self.class_type(),
+            _ => return None,
+        };
+        Some(base.to_string())
+    }
+
+    /// A class type after its `L`, with the outer types before a `.`.
+    fn class_type(&mut self) -> Option<String> {
+        let mut class_type = String::new();
+        loop {
+            let end = self.rest.find(['<', '.', ';'])?;
+            class_type.push_str(&self.rest[..end]);
+            self.rest = &self.rest[end..];
+            if self.eat('<') {
+                let mut arguments = Vec::new();
+                while !self.eat('>') {
+                    arguments.push(self.type_argument()?);
+                }
+                class_type.push_str(&format!("<{}>", arguments.join(", ")));
+            }
+            if self.eat(';') {
+                return Some(class_type);
+            }
+            self.eat('.');
+            class_type.push('.');
+        }
+    }
+
+    fn type_argument(&mut self) -> Option<String> {
+        if self.eat('*') {
+            Some("?".to_string())
+        } else if self.eat('+') {
+            Some(format!("? extends {}", self.field_type()?))
+        } else if self.eat('-') {
+            Some(format!("? super {}", self.field_type()?))
+        } else {
+            self.field_type()
+        }
+    }
+
+    fn type_parameters(&mut self) -> Option<Vec<String>> {
+        let mut parameters = Vec::new();
+        if !self.eat('<') {
+            return Some(parameters);
+        }
+        while !self.eat('>') {
+            let mut parameter = self.until(':')?.to_string();
+            let mut separator = " extends ";
+            if self.peek()? != ':' {
+                let class_bound = self.field_type()?;
+                if self.verbose || class_bound != "java/lang/Object" {
+                    parameter.push_str(separator);
+                    parameter.push_str(&class_bound);
+                    separator = " & ";
+                }
+            }
+            while self.eat(':') {
+                parameter.push_str(separator);
+                parameter.push_str(&self.field_type()?);
+                separator = " & ";
+            }
+            parameters.push(parameter);
+        }
+        Some(parameters)
+    }
+
+    /// The type parameters and the super types printed after the name of a class.
+    fn class_signature(mut self, is_interface: bool) -> Option<String> {
+        let type_parameters = self.type_parameters()?;
+        let super_class = self.field_type()?;
+        let mut interfaces = Vec::new();
+        while !self.rest.is_empty() {
+            interfaces.push(self.field_type()?);
+        }
+        let shown = self.verbose || super_class != "java/lang/Object";
+        // a signature of only a super class is read as a type, like javap
+        if type_parameters.is_empty() && interfaces.is_empty() {
+            return Some(if shown {
+                format!(" extends {}", super_class)
+            } else {
+                String::new()
+            });
+        }
+        let mut text = String::new();
+        if !type_parameters.is_empty() {
+            text.push_str(&format!("<{}>", type_parameters.join(", ")));
+        }
+        if is_interface {
+            if !interfaces.is_empty() {
+                text.push_str(&format!(" extends {}", interfaces.join(", ")));
+            }
+        } else {
+            if shown {
+                text.push_str(&format!(" extends {}", super_class));
+            }
+            if !interfaces.is_empty() {
+                text.push_str(&format!(" implements {}", interfaces.join(", ")));
+            }
+        }
+        Some(text)
+    }
+
+    fn method_signature(mut self) -> Option<MethodSignature> {
+        let type_parameters = self.type_parameters()?;
+        if !self.eat('(') {
+            return None;
+        }
+        let mut parameters = Vec::new();
+        while !self.eat(')') {
+            parameters.push(self.field_type()?);
+        }
+        let return_type = self.field_type()?;
+        let mut throws = Vec::new();
+        while self.eat('^') {
+            throws.push(self.field_type()?);
+        }
+        self.rest.is_empty().then_some(MethodSignature {
+            type_parameters,
+            parameters,
+            return_type,
+            throws,
+        })
+    }
+
+    /// The type of a field or a record component.
+    fn field_signature(mut self) -> Option<String> {
+        let field_type = self.field_type()?;
+        self.rest.is_empty().then_some(field_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_name, escape_utf8, java_double, java_float, print_class, Access, PrintOptions,
+    };
+    use crate::{classfile::test_class::TestClass, utilities::definition::u1};
+
+    fn test_class() -> TestClass {
+        let mut class = TestClass::new("p/Test", "java/lang/Object");
+        class
+            .major_version(52)
+            .access_flags(0x0021)
+            .interface("java/lang/Runnable");
+        let value = class.integer(-7);
+        let constant_value = class.attribute("ConstantValue", &value.to_be_bytes());
+        class.field(0x0019, "MIN", "I", &[constant_value]);
+        class.field(0x0002, "names", "[Ljava/lang/String;", &[]);
+        let init = class.method_ref("java/lang/Object", "<init>", "()V");
+        let [init_high, init_low] = init.to_be_bytes();
+        class.method(
+            0x0001,
+            "<init>",
+            "()V",
+            1,
+            1,
+            &[0x2a, 0xb7, init_high, init_low, 0xb1],
+            &[],
+            None,
+        );
+        let string = class.string("a\tb");
+        let run = class.method_ref("p/Test", "run", "()V");
+        let [run_high, run_low] = run.to_be_bytes();
+        let exception = class.class("java/lang/Exception");
+        #[rustfmt::skip]
+        let code = [
+            0x1a,                               // 0: iload_0
+            0xaa, 0, 0,                         // 1: tableswitch
+            0, 0, 0, 26, 0, 0, 0, 1, 0, 0, 0, 2,
+            0, 0, 0, 23, 0, 0, 0, 25,
+            0x12, string as u1,                 // 24: ldc
+            0x57,                               // 26: pop
+            0x10, 0xff,                         // 27: bipush -1
+            0xb8, run_high, run_low,            // 29: invokestatic
+            0xac,                               // 32: ireturn
+            0x4b,                               // 33: astore_0
+            0x03,                               // 34: iconst_0
+            0xac,                               // 35: ireturn
+        ];
+        class.method(
+            0x0089,
+            "m",
+            "(I[Ljava/lang/String;)I",
+            1,
+            2,
+            &code,
+            &[[24, 33, 33, exception]],
+            Some(&[0, 2, 24, 255, 0, 8, 0, 1, 1, 0, 1, 7, 0, exception as u1]),
+        );
+        class.method_with_attributes(0x0401, "run", "()V", &[]);
+        class
+    }
+
+    #[test]
+    fn we_can_print_the_declarations_and_the_code() {
+        let klass = test_class().parse();
+        assert_eq!(
+            print_class(&klass, &PrintOptions::default()),
+            "\
+public class p.Test implements java.lang.Runnable {
+  public static final int MIN;
+  public p.Test();
+  public static int m(int, java.lang.String...);
+  public abstract void run();
+}
+"
+        );
+        let options = PrintOptions {
+            code: true,
+            constants: true,
+            access: Access::Private,
+            ..PrintOptions::default()
+        };
+        assert_eq!(
+            print_class(&klass, &options),
+            "\
+public class p.Test implements java.lang.Runnable {
+  public static final int MIN = -7;
+
+  private java.lang.String[] names;
+
+  public p.Test();
+    Code:
+       0: aload_0
+       1: invokespecial #16                 // Method java/lang/Object.\"<init>\":()V
+       4: return
+
+  public static int m(int, java.lang.String...);
+    Code:
+       0: iload_0
+       1: tableswitch   { // 1 to 2
+                     1: 24
+                     2: 26
+               default: 27
+          }
+      24: ldc           #19                 // String a\\tb
+      26: pop
+      27: bipush        -1
+      29: invokestatic  #22                 // Method run:()V
+      32: ireturn
+      33: astore_0
+      34: iconst_0
+      35: ireturn
+    Exception table:
+       from    to  target type
+          24    33    33   Class java/lang/Exception
+
+  public abstract void run();
+}
+"
+        );
+    }
+
+    #[test]
+    fn we_can_print_the_constant_pool_and_the_attributes() {
+        let mut class = test_class();
+        let name = class.utf8("Custom");
+        class.field(
+            0x0000,
+            "custom",
+            "J",
+            &[[&name.to_be_bytes()[..], &[0, 0, 0, 17], &[0xab; 17]].concat()],
+        );
+        let options = PrintOptions {
+            verbose: true,
+            ..PrintOptions::default()
+        };
+        let text = print_class(&class.parse(), &options);
+        for expected in [
+            "  flags: (0x0021) ACC_PUBLIC, ACC_SUPER\n",
+            "  this_class: #2                          // p/Test\n",
+            "  interfaces: 1, fields: 3, methods: 3, attributes: 0\n",
+            "   #7 = Integer            -7\n",
+            "  #15 = NameAndType        #13:#14        // \"<init>\":()V\n",
+            "  #16 = Methodref          #4.#15         // java/lang/Object.\"<init>\":()V\n",
+            "  #19 = String             #18            // a\\tb\n",
+            "    flags: (0x0019) ACC_PUBLIC, ACC_STATIC, ACC_FINAL\n    ConstantValue: int -7\n",
+            "\
+  long custom;
+    descriptor: J
+    flags: (0x0000)
+      Custom: length = 0x11 (unknown attribute)
+       AB AB AB AB AB AB AB AB AB AB AB AB AB AB AB AB
+       AB
+",
+            "    flags: (0x0089) ACC_PUBLIC, ACC_STATIC, ACC_VARARGS\n",
+            "      stack=1, locals=2, args_size=2\n",
+            "\
+      StackMapTable: number_of_entries = 2
+        frame_type = 24 /* same */
+        frame_type = 255 /* full_frame */
+          offset_delta = 8
+          locals = [ int ]
+          stack = [ class java/lang/Exception ]
+",
+        ] {
+            assert!(
+                text.contains(expected),
+                "{}not found in\n{}",
+                expected,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn we_can_print_the_signatures_and_the_attributes() {
+        let mut class = TestClass::new("p/Box", "java/lang/Object");
+        class.interface("java/lang/Runnable");
+        let source_file = class.utf8("Box.java");
+        let attribute = class.attribute("SourceFile", &source_file.to_be_bytes());
+        class.class_attribute(attribute);
+        let signature = class.utf8(
+            "<T:Ljava/lang/Object;U::Ljava/lang/Comparable<-TU;>;>Ljava/lang/Object;\
+             Ljava/lang/Runnable;",
+        );
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        class.class_attribute(attribute);
+        let inner = class.class("p/Box$Item");
+        let outer = class.class("p/Box");
+        let inner_name = class.utf8("Item");
+        let content = [[0, 1], inner.to_be_bytes(), outer.to_be_bytes()].concat();
+        let attribute = class.attribute(
+            "InnerClasses",
+            &[content, inner_name.to_be_bytes().to_vec(), vec![0, 0x09]].concat(),
+        );
+        class.class_attribute(attribute);
+        let signature = class.utf8("Ljava/util/Map<TT;[Ljava/util/List<*>;>;");
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        let annotation_type = class.utf8("Lp/Info;");
+        let name = class.utf8("count");
+        let count = class.integer(3);
+        let content = [
+            &[0, 1][..],
+            &annotation_type.to_be_bytes(),
+            &[0, 1],
+            &name.to_be_bytes(),
+            b"B",
+            &count.to_be_bytes(),
+        ]
+        .concat();
+        let annotations = class.attribute("RuntimeVisibleAnnotations", &content);
+        class.field(0x0001, "map", "Ljava/util/Map;", &[attribute, annotations]);
+        let signature = class.utf8("<X:TT;>(TX;[TU;)Lp/Box<TX;*>.Item;^TX;");
+        let attribute = class.attribute("Signature", &signature.to_be_bytes());
+        let exception = class.class("java/lang/Exception");
+        let exceptions = class.attribute(
+            "Exceptions",
+            &[&[0, 1][..], &exception.to_be_bytes()].concat(),
+        );
+        class.method_with_attributes(
+            0x0481,
+            "get",
+            "(Ljava/lang/Object;[Ljava/lang/Comparable;)Lp/Box$Item;",
+            &[attribute, exceptions],
+        );
+        let klass = class.parse();
+
+        assert_eq!(
+            print_class(&klass, &PrintOptions::default()),
+            "\
+Compiled from \"Box.java\"
+public class p.Box<T, U extends java.lang.Comparable<? super U>> implements java.lang.Runnable {
+  public java.util.Map<T, java.util.List<?>[]> map;
+  public abstract <X extends T> p.Box<X, ?>.Item get(X, U...) throws X;
+}
+"
+        );
+        let options = PrintOptions {
+            verbose: true,
+            ..PrintOptions::default()
+        };
+        let text = print_class(&klass, &options);
+        for expected in [
+            "  Compiled from \"Box.java\"\npublic class p.Box<T extends java.lang.Object, U \
+             extends java.lang.Comparable<? super U>> extends java.lang.Object implements \
+             java.lang.Runnable\n",
+            "\
+    Signature: #15                          // Ljava/util/Map<TT;[Ljava/util/List<*>;>;
+    RuntimeVisibleAnnotations:
+      0: #16(#17=B#18)
+        p.Info(
+          count=(byte) 3
+        )
+",
+            "\
+    Exceptions:
+      throws java.lang.Exception
+",
+            "SourceFile: \"Box.java\"\n",
+            "\
+InnerClasses:
+  public static #13= #12 of #2;           // Item=class p/Box$Item of class p/Box
+",
+        ] {
+            assert!(
+                text.contains(expected),
+                "{}not found in\n{}",
+                expected,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn we_can_format_the_values_like_java() {
+        assert_eq!(java_double(1.5), "1.5");
+        assert_eq!(java_double(1e7), "1.0E7");
+        assert_eq!(java_double(-2.5e-4), "-2.5E-4");
+        assert_eq!(java_double(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(java_float(0.1), "0.1");
+        assert_eq!(java_float(f32::MAX), "3.4028235E38");
+        assert_eq!(check_name("java/lang/Object"), "java/lang/Object");
+        assert_eq!(check_name("<init>"), "\"<init>\"");
+        assert_eq!(check_name("[I"), "\"[I\"");
+        assert_eq!(escape_utf8("a\"b\u{1}\n"), "a\\\"b\\u0001\\n");
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bytecodes;
+pub mod class_file_parser;
+pub mod class_printer;
+#[cfg(test)]
+pub(crate) mod test_class;
+pub mod verifier;
@@ -0,0 +1,326 @@
+//! Assemble small class files for the tests of the parser and the verifier.
+use std::collections::HashMap;
+
+use crate::{
+    classfile::{
+        class_file_parser::ClassFileParser,
+        verifier::{verification_type::ClassHierarchy, Verifier},
+    },
+    classloader::class_file_stream::ClassFileStream,
+    model::instance_klass::InstanceKlass,
+    utilities::{
+        definition::{u1, u2},
+        exceptions::Exception,
+    },
+};
+
+pub(crate) struct TestClass {
+    major_version: u2,
+    access_flags: u2,
+    /// Entries of the constant pool with their tag, and the number of slots they take.
+    constants: Vec<(Vec<u1>, u2)>,
+    this_class: u2,
+    super_class: u2,
+    interfaces: Vec<u2>,
+    fields: Vec<u1>,
+    fields_count: u2,
+    methods: Vec<u1>,
+    methods_count: u2,
+    attributes: Vec<Vec<u1>>,
+}
+
+impl TestClass {
+    /// A public class of version 61, without a super class for an empty name.
+    pub(crate) fn new(name: &str, super_name: &str) -> Self {
+        let mut class = TestClass {
+            major_version: 61,
+            access_flags: 0x0021,
+            constants: Vec::new(),
+            this_class: 0,
+            super_class: 0,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            fields_count: 0,
+            methods: Vec::new(),
+            methods_count: 0,
+            attributes: Vec::new(),
+        };
+        class.this_class = class.class(name);
+        if !super_name.is_empty() {
+            class.super_class = class.class(super_name);
+        }
+        class
+    }
+
+    pub(crate) fn major_version(&mut self, major_version: u2) -> &mut Self {
+        self.major_version = major_version;
+        self
+    }
+
+    pub(crate) fn access_flags(&mut self, access_flags: u2) -> &mut Self {
+        self.access_flags = access_flags;
+        self
+    }
+
+    pub(crate) fn interface(&mut self, name: &str) -> &mut Self {
+        let index = self.class(name);
+        self.interfaces.push(index);
+        self
+    }
+
+    /// Add the constant, or find the same one, and return its index.
+    pub(crate) fn constant(&mut self, bytes: Vec<u1>) -> u2 {
+        let mut index = 1;
+        for (constant, slots) in &self.constants {
+            if *constant == bytes {
+                return index;
+            }
+            index += slots;
+        }
+        // longs and doubles take two entries
+        let slots = if bytes[0] == 5 || bytes[0] == 6 { 2 } else { 1 };
+        self.constants.push((bytes, slots));
+        index
+    }
+
+    pub(crate) fn utf8(&mut self, string: &str) -> u2 {
+        let mut bytes = vec![1];
+        bytes.extend_from_slice(&(string.len() as u2).to_be_bytes());
+        bytes.extend_from_slice(string.as_bytes());
+        self.constant(bytes)
+    }
+
+    fn tag_and_index(&mut self, tag: u1, index: u2) -> u2 {
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&index.to_be_bytes());
+        self.constant(bytes)
+    }
+
+    fn tag_and_indexes(&mut self, tag: u1, first: u2, second: u2) -> u2 {
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&first.to_be_bytes());
+        bytes.extend_from_slice(&second.to_be_bytes());
+        self.constant(bytes)
+    }
+
+    pub(crate) fn class(&mut self, name: &str) -> u2 {
+        let name = self.utf8(name);
+        self.tag_and_index(7, name)
+    }
+
+    pub(crate) fn string(&mut self, string: &str) -> u2 {
+        let string = self.utf8(string);
+        self.tag_and_index(8, string)
+    }
+
+    pub(crate) fn integer(&mut self, value: i32) -> u2 {
+        let mut bytes = vec![3];
+        bytes.extend_from_slice(&value.to_be_bytes());
+        self.constant(bytes)
+    }
+
+    pub(crate) fn long(&mut self, value: i64) -> u2 {
+        let mut bytes = vec![5];
+        bytes.extend_from_slice(&value.to_be_bytes());
+        self.constant(bytes)
+    }
+
+    pub(crate) fn name_and_type(&mut self, name: &str, descriptor: &str) -> u2 {
+        let name = self.utf8(name);
+        let descriptor = self.utf8(descriptor);
+        self.tag_and_indexes(12, name, descriptor)
+    }
+
+    fn member_ref(&mut self, tag: u1, class: &str, name: &str, descriptor: &str) -> u2 {
+        let class = self.class(class);
+        let name_and_type = self.name_and_type(name, descriptor);
+        self.tag_and_indexes(tag, class, name_and_type)
+    }
+
+    pub(crate) fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u2 {
+        self.member_ref(9, class, name, descriptor)
+    }
+
+    pub(crate) fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u2 {
+        self.member_ref(10, class, name, descriptor)
+    }
+
+    pub(crate) fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u2 {
+        self.member_ref(11, class, name, descriptor)
+    }
+
+    /// An attribute with its name and length.
+    pub(crate) fn attribute(&mut self, name: &str, content: &[u1]) -> Vec<u1> {
+        let mut bytes = self.utf8(name).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    /// Add an attribute of the class, made with `attribute`.
+    pub(crate) fn class_attribute(&mut self, attribute: Vec<u1>) -> &mut Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub(crate) fn field(
+        &mut self,
+        flags: u2,
+        name: &str,
+        descriptor: &str,
+        attributes: &[Vec<u1>],
+    ) {
+        let name = self.utf8(name);
+        let descriptor = self.utf8(descriptor);
+        for value in [flags, name, descriptor, attributes.len() as u2] {
+            self.fields.extend_from_slice(&value.to_be_bytes());
+        }
+        self.fields.extend(attributes.concat());
+        self.fields_count += 1;
+    }
+
+    /// A method with a `Code` attribute of the code, exception table entries of
+    /// start, end, handler and catch type index, and the stack map table if any.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn method(
+        &mut self,
+        flags: u2,
+        name: &str,
+        descriptor: &str,
+        max_stack: u2,
+        max_locals: u2,
+        code: &[u1],
+        exception_table: &[[u2; 4]],
+        stack_map_table: Option<&[u1]>,
+    ) {
+        let mut content = Vec::new();
+        content.extend_from_slice(&max_stack.to_be_bytes());
+        content.extend_from_slice(&max_locals.to_be_bytes());
+        content.extend_from_slice(&(code.len() as u32).to_be_bytes());
+        content.extend_from_slice(code);
+        content.extend_from_slice(&(exception_table.len() as u2).to_be_bytes());
+        for value in exception_table.concat() {
+            content.extend_from_slice(&value.to_be_bytes());
+        }
+        match stack_map_table {
+            Some(stack_map_table) => {
+                content.extend_from_slice(&1u16.to_be_bytes());
+                let attribute = self.attribute("StackMapTable", stack_map_table);
+                content.extend(attribute);
+            }
+            None => content.extend_from_slice(&0u16.to_be_bytes()),
+        }
+        let code = self.attribute("Code", &content);
+        self.method_with_attributes(flags, name, descriptor, &[code]);
+    }
+
+    pub(crate) fn method_with_attributes(
+        &mut self,
+        flags: u2,
+        name: &str,
+        descriptor: &str,
+        attributes: &[Vec<u1>],
+    ) {
+        let name = self.utf8(name);
+        let descriptor = self.utf8(descriptor);
+        for value in [flags, name, descriptor, attributes.len() as u2] {
+            self.methods.extend_from_slice(&value.to_be_bytes());
+        }
+        self.methods.extend(attributes.concat());
+        self.methods_count += 1;
+    }
+
+    pub(crate) fn bytes(&self) -> Vec<u1> {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0];
+        bytes.extend_from_slice(&self.major_version.to_be_bytes());
+        let count: u2 = 1 + self.constants.iter().map(|(_, slots)| slots).sum::<u2>();
+        bytes.extend_from_slice(&count.to_be_bytes());
+        for (constant, _) in &self.constants {
+            bytes.extend_from_slice(constant);
+        }
+        for value in [self.access_flags, self.this_class, self.super_class] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes.extend_from_slice(&(self.interfaces.len() as u2).to_be_bytes());
+        for interface in &self.interfaces {
+            bytes.extend_from_slice(&interface.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.fields_count.to_be_bytes());
+        bytes.extend_from_slice(&self.fields);
+        bytes.extend_from_slice(&self.methods_count.to_be_bytes());
+        bytes.extend_from_slice(&self.methods);
+        bytes.extend_from_slice(&(self.attributes.len() as u2).to_be_bytes());
+        bytes.extend(self.attributes.concat());
+        bytes
+    }
+
+    pub(crate) fn parse(&self) -> InstanceKlass {
+        ClassFileParser::new(&mut ClassFileStream::new(self.bytes(), "test".to_string()))
+            .parse()
+            .unwrap()
+    }
+}
+
+/// Super class and interface flag by class name.
+pub(crate) struct TestHierarchy(pub(crate) HashMap<&'static str, (Option<&'static str>, bool)>);
+
+impl TestHierarchy {
+    /// `java/lang/Object`, `java/lang/Throwable` and its exceptions, and `java/lang/Number`.
+    pub(crate) fn new() -> Self {
+        TestHierarchy(HashMap::from([
+            ("java/lang/Throwable", (Some("java/lang/Object"), false)),
+            ("java/lang/Exception", (Some("java/lang/Throwable"), false)),
+            (
+                "java/lang/RuntimeException",
+                (Some("java/lang/Exception"), false),
+            ),
+            ("java/lang/Number", (Some("java/lang/Object"), false)),
+            ("java/lang/Integer", (Some("java/lang/Number"), false)),
+            ("java/lang/Runnable", (Some("java/lang/Object"), true)),
+        ]))
+    }
+}
+
+impl ClassHierarchy for TestHierarchy {
+    fn super_class(&self, name: &str) -> Option<String> {
+        self.0.get(name)?.0.map(str::to_string)
+    }
+
+    fn is_interface(&self, name: &str) -> bool {
+        self.0.get(name).is_some_and(|(_, interface)| *interface)
+    }
+}
+
+/// Verify the class with the static method `m` added to it.
+pub(crate) fn verify_method(
+    class: &mut TestClass,
+    descriptor: &str,
+    max_stack: u2,
+    max_locals: u2,
+    code: &[u1],
+    exception_table: &[[u2; 4]],
+    stack_map_table: Option<&[u1]>,
+) -> Result<(), Exception> {
+    class.method(
+        0x0009,
+        "m",
+        descriptor,
+        max_stack,
+        max_locals,
+        code,
+        exception_table,
+        stack_map_table,
+    );
+    Verifier::new(&class.parse(), &TestHierarchy::new()).verify()
+}
+
+/// Check the verification failed for the reason at the location, e.g. `@1: pop`.
+pub(crate) fn verify_error(result: Result<(), Exception>, reason: &str, location: &str) {
+    match result {
+        Err(Exception::VerifyError(message)) => {
+            assert!(message.starts_with(reason), "{}", message);
+            assert!(message.contains(location), "{}", message);
+        }
+        result => panic!("expecting a VerifyError, found {:?}", result),
+    }
+}
@@ -0,0 +1,1468 @@
+//! The bytecode verifiers.
+//!
+//! The type-checking verifier of class files of version 50 and later (JVMS §4.10.1)
+//! checks each method in a single pass over its instructions: the frame of the
+//! locals and the operand stack is carried from one instruction to the next and
+//! must be assignable to the frames of the `StackMapTable` at the branch targets,
+//! the exception handlers and after unconditional branches.
+//!
+//! The older classes are verified by type inference (JVMS §4.10.2), see
+//! [`type_inference`].
+pub mod stack_map_table;
+mod type_inference;
+pub mod verification_type;
+
+use crate::{
+    classloader::class_loader::ClassLoaderType,
+    model::{
+        constant_pool::ConstantPoolEntry,
+        instance_klass::InstanceKlass,
+        method::{Code, ExceptionTableEntry, Method},
+    },
+    utilities::{
+        access_flags::{JVM_ACC_INTERFACE, JVM_ACC_STATIC},
+        definition::u2,
+        exceptions::Exception,
+    },
+};
+
+use super::bytecodes::{self, *};
+use stack_map_table::{parse_stack_map_table, StackMapFrame};
+use verification_type::{ClassHierarchy, VerificationType};
+
+/// The first class file version with a `StackMapTable`.
+pub const STACKMAP_ATTRIBUTE_MAJOR_VERSION: u2 = 50;
+
+/// Which classes are verified, like `-XX:BytecodeVerificationLocal` and
+/// `-XX:BytecodeVerificationRemote` of HotSpot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierOptions {
+    /// Verify the classes of the boot loader.
+    pub bytecode_verification_local: bool,
+    /// Verify the classes of the other loaders.
+    pub bytecode_verification_remote: bool,
+}
+
+impl Default for VerifierOptions {
+    /// The boot classes are trusted, the others are verified.
+    fn default() -> Self {
+        VerifierOptions {
+            bytecode_verification_local: false,
+            bytecode_verification_remote: true,
+        }
+    }
+}
+
+impl VerifierOptions {
+    pub fn should_verify_for(&self, loader: ClassLoaderType) -> bool {
+        match loader {
+            ClassLoaderType::BootLoader => self.bytecode_verification_local,
+            _ => self.bytecode_verification_remote,
+        }
+    }
+}
+
+/// Verify the methods of the class loaded by the loader, unless the options trust it.
+pub fn verify(
+    klass: &InstanceKlass,
+    loader: ClassLoaderType,
+    options: &VerifierOptions,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<(), Exception> {
+    if !options.should_verify_for(loader) {
+        return Ok(());
+    }
+    Verifier::new(klass, hierarchy).verify()
+}
+
+/// The hierarchy of the loaded classes and of the class being verified.
+struct VerifiedClassHierarchy<'a> {
+    klass: &'a InstanceKlass,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl ClassHierarchy for VerifiedClassHierarchy<'_> {
+    fn super_class(&self, name: &str) -> Option<String> {
+        if name == self.klass.name() {
+            return self.klass.super_name().map(str::to_string);
+        }
+        self.hierarchy.super_class(name)
+    }
+
+    fn is_interface(&self, name: &str) -> bool {
+        if name == self.klass.name() {
+            return self.klass.access_flags() & JVM_ACC_INTERFACE != 0;
+        }
+        self.hierarchy.is_interface(name)
+    }
+}
+
+pub struct Verifier<'a> {
+    klass: &'a InstanceKlass,
+    hierarchy: VerifiedClassHierarchy<'a>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(klass: &'a InstanceKlass, hierarchy: &'a dyn ClassHierarchy) -> Self {
+        Verifier {
+            klass,
+            hierarchy: VerifiedClassHierarchy { klass, hierarchy },
+        }
+    }
+
+    /// Verify the methods with code of the class.
+    ///
+    /// The classes before version 50 have no `StackMapTable` and their types are
+    /// inferred, and so are those of version 50 that fail type checking.
+    pub fn verify(&self) -> Result<(), Exception> {
+        let major_version = self.klass.major_version();
+        for method in self.klass.methods() {
+            if let Some(code) = method.code() {
+                let verifier = MethodVerifier::new(self, method, code);
+                if major_version < STACKMAP_ATTRIBUTE_MAJOR_VERSION {
+                    verifier.verify_by_type_inference()?;
+                } else if let Err(error) = verifier.verify_by_type_checking() {
+                    if major_version != STACKMAP_ATTRIBUTE_MAJOR_VERSION {
+                        return Err(error);
+                    }
+                    verifier.verify_by_type_inference()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The types of the locals and of the operand stack before an instruction.
+#[derive(Clone, Debug)]
+struct Frame {
+    /// As many as `max_locals`, the ones not set are `Top`.
+    locals: Vec<VerificationType>,
+    stack: Vec<VerificationType>,
+    max_stack: usize,
+    /// `this` is not initialized yet in a constructor.
+    flag_this_uninit: bool,
+}
+
+impl Frame {
+    fn push(&mut self, verification_type: VerificationType) -> Result<(), String> {
+        let category2 = verification_type.is_category2();
+        self.push_slot(verification_type)?;
+        if category2 {
+            self.push_slot(VerificationType::Top)?;
+        }
+        Ok(())
+    }
+
+    fn push_slot(&mut self, verification_type: VerificationType) -> Result<(), String> {
+        if self.stack.len() == self.max_stack {
+            return Err("Operand stack overflow".to_string());
+        }
+        self.stack.push(verification_type);
+        Ok(())
+    }
+
+    fn pop_slot(&mut self) -> Result<VerificationType, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "Operand stack underflow".to_string())
+    }
+
+    /// Pop the top slots, without splitting a long or a double.
+    fn pop_slots(&mut self, count: usize) -> Result<Vec<VerificationType>, String> {
+        if self.stack.len() < count {
+            return Err("Operand stack underflow".to_string());
+        }
+        let slots = self.stack.split_off(self.stack.len() - count);
+        if slots.first() == Some(&VerificationType::Top) {
+            return Err("Bad type on operand stack: a long or a double is split".to_string());
+        }
+        Ok(slots)
+    }
+
+    /// Pop a value that can be assigned to the expected type.
+    fn pop(
+        &mut self,
+        expected: &VerificationType,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<VerificationType, String> {
+        let value = if expected.is_category2() {
+            if self.pop_slot()? != VerificationType::Top {
+                return Err(format!("Bad type on operand stack: expecting {}", expected));
+            }
+            self.pop_slot()?
+        } else {
+            self.pop_slot()?
+        };
+        if value == VerificationType::Top || !value.is_assignable_to(expected, hierarchy) {
+            return Err(format!(
+                "Bad type on operand stack: {} is not assignable to {}",
+                value, expected
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Pop an initialized or uninitialized reference, or null.
+    fn pop_reference(&mut self) -> Result<VerificationType, String> {
+        let value = self.pop_slot()?;
+        if !value.is_reference() {
+            return Err(format!(
+                "Bad type on operand stack: expecting a reference, found {}",
+                value
+            ));
+        }
+        Ok(value)
+    }
+
+    fn pop_arguments(
+        &mut self,
+        arguments: &[VerificationType],
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<(), String> {
+        for argument in arguments.iter().rev() {
+            self.pop(argument, hierarchy)?;
+        }
+        Ok(())
+    }
+
+    fn local(
+        &self,
+        index: usize,
+        expected: &VerificationType,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<VerificationType, String> {
+        let size = if expected.is_category2() { 2 } else { 1 };
+        if index + size > self.locals.len() {
+            return Err(format!("Local variable index {} is out of range", index));
+        }
+        let value = &self.locals[index];
+        let assignable = if expected.is_category2() {
+            value == expected && self.locals[index + 1] == VerificationType::Top
+        } else {
+            value.is_assignable_to(expected, hierarchy) && *value != VerificationType::Top
+        };
+        if !assignable {
+            return Err(format!(
+                "Bad local variable type: local {} is {}, expecting {}",
+                index, value, expected
+            ));
+        }
+        Ok(value.clone())
+    }
+
+    fn set_local(&mut self, index: usize, value: VerificationType) -> Result<(), String> {
+        let size = if value.is_category2() { 2 } else { 1 };
+        if index + size > self.locals.len() {
+            return Err(format!("Local variable index {} is out of range", index));
+        }
+        // overwriting the second half of a long or a double invalidates it
+        if index > 0 && self.locals[index - 1].is_category2() {
+            self.locals[index - 1] = VerificationType::Top;
+        }
+        if size == 2 {
+            self.locals[index + 1] = VerificationType::Top;
+        }
+        self.locals[index] = value;
+        Ok(())
+    }
+
+    /// Replace every occurrence of the uninitialized type once its constructor is called.
+    fn initialize(&mut self, uninitialized: &VerificationType, initialized: VerificationType) {
+        for value in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if value == uninitialized {
+                *value = initialized.clone();
+            }
+        }
+    }
+
+    fn is_assignable_to(&self, target: &Frame, hierarchy: &dyn ClassHierarchy) -> bool {
+        self.stack.len() == target.stack.len()
+            && (!self.flag_this_uninit || target.flag_this_uninit)
+            && self
+                .locals
+                .iter()
+                .zip(&target.locals)
+                .chain(self.stack.iter().zip(&target.stack))
+                .all(|(from, to)| from.is_assignable_to(to, hierarchy))
+    }
+}
+
+/// What follows an instruction: the next instruction and the branch targets.
+struct Successors {
+    falls_through: bool,
+    targets: Vec<usize>,
+}
+
+struct MethodVerifier<'a> {
+    klass: &'a InstanceKlass,
+    hierarchy: &'a VerifiedClassHierarchy<'a>,
+    name: &'a str,
+    descriptor: &'a str,
+    method: &'a Method,
+    code: &'a Code,
+}
+
+impl<'a> MethodVerifier<'a> {
+    fn new(verifier: &'a Verifier, method: &'a Method, code: &'a Code) -> Self {
+        MethodVerifier {
+            klass: verifier.klass,
+            hierarchy: &verifier.hierarchy,
+            name: verifier.klass.method_name(method),
+            descriptor: verifier.klass.method_descriptor(method),
+            method,
+            code,
+        }
+    }
+
+    fn error(&self, bci: usize, reason: &str) -> Exception {
+        let instruction = self
+            .code
+            .code()
+            .get(bci)
+            .and_then(|&opcode| bytecodes::name(opcode))
+            .unwrap_or("unknown");
+        Exception::VerifyError(format!(
+            "{}\nLocation:\n  {}.{}{} @{}: {}",
+            reason,
+            self.klass.name(),
+            self.name,
+            self.descriptor,
+            bci,
+            instruction
+        ))
+    }
+
+    fn to_frame(&self, stack_map_frame: &StackMapFrame) -> Frame {
+        let mut locals = stack_map_frame.locals.clone();
+        let flag_this_uninit = locals.contains(&VerificationType::UninitializedThis);
+        locals.resize(self.code.max_locals() as usize, VerificationType::Top);
+        Frame {
+            locals,
+            stack: stack_map_frame.stack.clone(),
+            max_stack: self.code.max_stack() as usize,
+            flag_this_uninit,
+        }
+    }
+
+    /// Whether an instruction starts at each offset of the code.
+    fn instructions(&self) -> Result<Vec<bool>, Exception> {
+        let code = self.code.code();
+        let mut instructions = vec![false; code.len()];
+        let mut bci = 0;
+        while bci < code.len() {
+            let length = bytecodes::length_at(code, bci)
+                .ok_or_else(|| self.error(bci, "Bad instruction"))?;
+            instructions[bci] = true;
+            bci += length;
+        }
+        Ok(instructions)
+    }
+
+    fn is_instruction(instructions: &[bool], bci: usize) -> bool {
+        instructions.get(bci) == Some(&true)
+    }
+
+    /// The frame of the receiver and the arguments at the start of the method, and
+    /// its locals up to the last argument.
+    fn initial_frame(&self) -> Result<(Frame, Vec<VerificationType>), Exception> {
+        let (arguments, _) = VerificationType::from_method_descriptor(self.descriptor)
+            .ok_or_else(|| self.error(0, "Bad method descriptor"))?;
+        let mut locals = Vec::new();
+        let mut flag_this_uninit = false;
+        if self.method.flags() & JVM_ACC_STATIC == 0 {
+            if self.name == "<init>" && self.klass.name() != "java/lang/Object" {
+                locals.push(VerificationType::UninitializedThis);
+                flag_this_uninit = true;
+            } else {
+                locals.push(VerificationType::reference(self.klass.name()));
+            }
+        }
+        for argument in arguments {
+            let category2 = argument.is_category2();
+            locals.push(argument);
+            if category2 {
+                locals.push(VerificationType::Top);
+            }
+        }
+        let max_locals = self.code.max_locals() as usize;
+        if locals.len() > max_locals {
+            return Err(self.error(0, "Arguments can't fit into locals"));
+        }
+        let mut frame_locals = locals.clone();
+        frame_locals.resize(max_locals, VerificationType::Top);
+        let frame = Frame {
+            locals: frame_locals,
+            stack: Vec::new(),
+            max_stack: self.code.max_stack() as usize,
+            flag_this_uninit,
+        };
+        Ok((frame, locals))
+    }
+
+    /// Check the ranges and the catch types of the exception handlers.
+    fn check_exception_table(&self, instructions: &[bool]) -> Result<(), Exception> {
+        let is_instruction = |bci| MethodVerifier::is_instruction(instructions, bci);
+        for handler in self.code.exception_table() {
+            let (start, end, handler_pc) = (
+                handler.start_pc as usize,
+                handler.end_pc as usize,
+                handler.handler_pc as usize,
+            );
+            if start >= end
+                || !is_instruction(start)
+                || !(end == instructions.len() || is_instruction(end))
+                || !is_instruction(handler_pc)
+            {
+                return Err(self.error(start, "Illegal exception table range"));
+            }
+            if handler.catch_type != 0 {
+                let catch_type = self
+                    .klass
+                    .constants()
+                    .class_name(handler.catch_type)
+                    .ok_or_else(|| self.error(handler_pc, "Bad catch type index"))?;
+                if !VerificationType::reference(catch_type).is_assignable_to(
+                    &VerificationType::reference("java/lang/Throwable"),
+                    self.hierarchy,
+                ) {
+                    return Err(self.error(
+                        handler_pc,
+                        &format!("Catch type is not a subclass of Throwable: {}", catch_type),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The frame at the start of the handler of the exceptions thrown from the frame.
+    fn exception_frame(&self, frame: &Frame, handler: &ExceptionTableEntry) -> Frame {
+        let catch_type = match handler.catch_type {
+            0 => "java/lang/Throwable",
+            index => self.klass.constants().class_name(index).unwrap_or_default(),
+        };
+        Frame {
+            stack: vec![VerificationType::reference(catch_type)],
+            ..frame.clone()
+        }
+    }
+
+    fn verify_by_type_checking(&self) -> Result<(), Exception> {
+        let code = self.code.code();
+        let instructions = self.instructions()?;
+        let is_instruction = |bci| MethodVerifier::is_instruction(&instructions, bci);
+        let (initial_frame, locals) = self.initial_frame()?;
+        let stack_map = match self.code.stack_map_table() {
+            Some(bytes) => parse_stack_map_table(
+                bytes,
+                &locals,
+                self.klass.constants(),
+                self.code.max_locals() as usize,
+                self.code.max_stack() as usize,
+            )
+            .map_err(|reason| self.error(0, &reason))?,
+            None => Vec::new(),
+        };
+        if let Some(frame) = stack_map
+            .iter()
+            .find(|frame| !is_instruction(frame.offset as usize))
+        {
+            return Err(self.error(frame.offset as usize, "StackMapTable error: bad offset"));
+        }
+        let stack_map_frame = |bci: usize| {
+            stack_map
+                .binary_search_by_key(&bci, |frame| frame.offset as usize)
+                .ok()
+                .map(|index| self.to_frame(&stack_map[index]))
+        };
+        self.check_exception_table(&instructions)?;
+
+        let mut current = Some(initial_frame);
+        let mut bci = 0;
+        while bci < code.len() {
+            if let Some(target) = stack_map_frame(bci) {
+                if let Some(frame) = &current {
+                    if !frame.is_assignable_to(&target, self.hierarchy) {
+                        return Err(self.error(bci, "Instruction type does not match stack map"));
+                    }
+                }
+                current = Some(target);
+            }
+            let mut frame = current
+                .take()
+                .ok_or_else(|| self.error(bci, "Expecting a stackmap frame at branch target"))?;
+
+            for handler in self.code.exception_table() {
+                if (handler.start_pc as usize..handler.end_pc as usize).contains(&bci) {
+                    let exception_frame = self.exception_frame(&frame, handler);
+                    let target = stack_map_frame(handler.handler_pc as usize).ok_or_else(|| {
+                        self.error(bci, "Expecting a stackmap frame at exception handler")
+                    })?;
+                    if !exception_frame.is_assignable_to(&target, self.hierarchy) {
+                        return Err(self
+                            .error(bci, "Stack map does not match the one at exception handler"));
+                    }
+                }
+            }
+
+            if MethodVerifier::is_subroutine_instruction(code, bci) {
+                return Err(self.error(
+                    bci,
+                    "jsr and ret are not allowed in class files of version 50 or later",
+                ));
+            }
+            let successors = self
+                .execute(bci, &mut frame)
+                .map_err(|reason| self.error(bci, &reason))?;
+            for &target in &successors.targets {
+                if !is_instruction(target) {
+                    return Err(self.error(bci, "Illegal target of jump or branch"));
+                }
+                let target_frame = stack_map_frame(target).ok_or_else(|| {
+                    self.error(bci, "Expecting a stackmap frame at branch target")
+                })?;
+                if !frame.is_assignable_to(&target_frame, self.hierarchy) {
+                    return Err(self.error(bci, "Inconsistent stackmap frames at branch target"));
+                }
+            }
+            let next = bci + bytecodes::length_at(code, bci).unwrap();
+            if successors.falls_through {
+                if next == code.len() {
+                    return Err(self.error(bci, "Falling off the end of the code"));
+                }
+                current = Some(frame);
+            }
+            bci = next;
+        }
+        Ok(())
+    }
+
+    /// `jsr`, `jsr_w`, `ret` and `wide ret`.
+    fn is_subroutine_instruction(code: &[u8], bci: usize) -> bool {
+        match code[bci] {
+            JSR | JSR_W | RET => true,
+            WIDE => code[bci + 1] == RET,
+            _ => false,
+        }
+    }
+
+    /// The branch target at the offset of the instruction.
+    fn target(bci: usize, offset: i32) -> usize {
+        // a negative target is out of the code
+        usize::try_from(bci as i64 + offset as i64).unwrap_or(usize::MAX)
+    }
+
+    fn constant_type(&self, index: u2, category2: bool) -> Result<VerificationType, String> {
+        let constants = self.klass.constants();
+        let verification_type = match constants.get(index) {
+            Some(ConstantPoolEntry::Integer(_)) if !category2 => VerificationType::Integer,
+            Some(ConstantPoolEntry::Float(_)) if !category2 => VerificationType::Float,
+            Some(ConstantPoolEntry::Long(_)) if category2 => VerificationType::Long,
+            Some(ConstantPoolEntry::Double(_)) if category2 => VerificationType::Double,
+            Some(ConstantPoolEntry::String(_)) if !category2 => {
+                VerificationType::reference("java/lang/String")
+            }
+            Some(ConstantPoolEntry::Class(_)) if !category2 => {
+                VerificationType::reference("java/lang/Class")
+            }
+            Some(ConstantPoolEntry::MethodType(_)) if !category2 => {
+                VerificationType::reference("java/lang/invoke/MethodType")
+            }
+            Some(ConstantPoolEntry::MethodHandle(_, _)) if !category2 => {
+                VerificationType::reference("java/lang/invoke/MethodHandle")
+            }
+            Some(ConstantPoolEntry::Dynamic(_, name_and_type)) => {
+                let verification_type = constants
+                    .name_and_type(*name_and_type)
+                    .and_then(|(_, descriptor)| VerificationType::from_descriptor(descriptor))
+                    .ok_or_else(|| format!("Bad dynamic constant at index {}", index))?;
+                if verification_type.is_category2() != category2 {
+                    return Err(format!("Bad dynamic constant at index {}", index));
+                }
+                verification_type
+            }
+            _ => return Err(format!("Invalid index {} in ldc", index)),
+        };
+        Ok(verification_type)
+    }
+
+    /// Class, name and type of the field reference.
+    fn field_ref(&self, index: u2) -> Result<(&'a str, &'a str, VerificationType), String> {
+        let constants = self.klass.constants();
+        match constants.get(index) {
+            Some(ConstantPoolEntry::FieldRef(_, _)) => {
+                let (class, name, descriptor) = constants.member_ref(index).unwrap_or_default();
+                VerificationType::from_descriptor(descriptor)
+                    .map(|field_type| (class, name, field_type))
+                    .ok_or_else(|| format!("Bad field reference at index {}", index))
+            }
+            _ => Err(format!("Expecting a field reference at index {}", index)),
+        }
+    }
+
+    /// Class, name, argument and return types of the method reference.
+    #[allow(clippy::type_complexity)]
+    fn method_ref(
+        &self,
+        index: u2,
+        opcode: u8,
+    ) -> Result<
+        (
+            &'a str,
+            &'a str,
+            Vec<VerificationType>,
+            Option<VerificationType>,
+        ),
+        String,
+    > {
+        let constants = self.klass.constants();
+        let expected = matches!(
+            (opcode, constants.get(index)),
+            (INVOKEVIRTUAL, Some(ConstantPoolEntry::MethodRef(_, _)))
+                | (
+                    INVOKEINTERFACE,
+                    Some(ConstantPoolEntry::InterfaceMethodRef(_, _))
+                )
+                | (
+                    INVOKESPECIAL | INVOKESTATIC,
+                    Some(
+                        ConstantPoolEntry::MethodRef(_, _)
+                            | ConstantPoolEntry::InterfaceMethodRef(_, _)
+                    ),
+                )
+        );
+        if !expected {
+            return Err(format!("Bad method reference at index {}", index));
+        }
+        let (class, name, descriptor) = constants.member_ref(index).unwrap_or_default();
+        if name.starts_with('<') && (name != "<init>" || opcode != INVOKESPECIAL) {
+            return Err(format!("Illegal call to internal method {}", name));
+        }
+        let (arguments, result) = VerificationType::from_method_descriptor(descriptor)
+            .ok_or_else(|| format!("Bad method descriptor at index {}", index))?;
+        Ok((class, name, arguments, result))
+    }
+
+    /// The name of the class at the index, or the descriptor of an array class.
+    fn class_ref(&self, index: u2) -> Result<&'a str, String> {
+        self.klass
+            .constants()
+            .class_name(index)
+            .ok_or_else(|| format!("Expecting a class at index {}", index))
+    }
+
+    /// Check the array popped for an array load or store of the component descriptors,
+    /// and return its component type.
+    fn check_array(
+        array: &VerificationType,
+        components: &[&str],
+    ) -> Result<VerificationType, String> {
+        let component = array.component_type();
+        let matches = match (array, &component) {
+            (VerificationType::Null, _) => true,
+            (VerificationType::Reference(name), Some(component)) => match components {
+                ["L"] => component.is_reference(),
+                _ => components.contains(&&name[1..]),
+            },
+            _ => false,
+        };
+        match component {
+            // the loads of primitives from null push their primitive type
+            Some(VerificationType::Null) if components != ["L"] => {
+                Ok(VerificationType::from_descriptor(components[0]).unwrap())
+            }
+            Some(component) if matches => Ok(component),
+            _ => Err(format!(
+                "Bad type on operand stack in array access: {}",
+                array
+            )),
+        }
+    }
+
+    fn check_return(
+        &self,
+        frame: &mut Frame,
+        expected: Option<VerificationType>,
+    ) -> Result<(), String> {
+        let (_, result) =
+            VerificationType::from_method_descriptor(self.descriptor).unwrap_or_default();
+        let matches = match (&result, &expected) {
+            (Some(VerificationType::Reference(_)), Some(VerificationType::Reference(_))) => true,
+            (result, expected) => result == expected,
+        };
+        if !matches {
+            return Err("Method expects a different return type".to_string());
+        }
+        if let Some(result) = result {
+            frame.pop(&result, self.hierarchy)?;
+        }
+        if frame.flag_this_uninit {
+            return Err("Constructor must call super() or this() before return".to_string());
+        }
+        Ok(())
+    }
+
+    /// Apply the effect of the instruction at the bci to the frame.
+    fn execute(&self, bci: usize, frame: &mut Frame) -> Result<Successors, String> {
+        use VerificationType::{Double, Float, Integer, Long, Null};
+
+        let code = self.code.code();
+        let hierarchy = self.hierarchy;
+        let u1_at = |index: usize| code[index];
+        let u2_at = |index: usize| bytecodes::u2_at(code, index).unwrap();
+        let s4_at = |index: usize| bytecodes::s4_at(code, index).unwrap();
+        let (opcode, wide) = match code[bci] {
+            WIDE => (code[bci + 1], true),
+            opcode => (opcode, false),
+        };
+        let local_index = || {
+            if wide {
+                u2_at(bci + 2) as usize
+            } else {
+                u1_at(bci + 1) as usize
+            }
+        };
+        // the types of the loads and stores, in the order of the opcodes
+        let kinds = [Integer, Long, Float, Double, VerificationType::object()];
+
+        match opcode {
+            NOP => {}
+            ACONST_NULL => frame.push(Null)?,
+            ICONST_M1..=ICONST_5 | BIPUSH | SIPUSH => frame.push(Integer)?,
+            LCONST_0 | LCONST_1 => frame.push(Long)?,
+            FCONST_0..=FCONST_2 => frame.push(Float)?,
+            DCONST_0 | DCONST_1 => frame.push(Double)?,
+            LDC => frame.push(self.constant_type(u1_at(bci + 1) as u2, false)?)?,
+            LDC_W => frame.push(self.constant_type(u2_at(bci + 1), false)?)?,
+            LDC2_W => frame.push(self.constant_type(u2_at(bci + 1), true)?)?,
+            ILOAD..=ALOAD | ILOAD_0..=ALOAD_3 => {
+                let (kind, index) = if opcode <= ALOAD {
+                    (&kinds[(opcode - ILOAD) as usize], local_index())
+                } else {
+                    let n = opcode - ILOAD_0;
+                    (&kinds[(n / 4) as usize], (n % 4) as usize)
+                };
+                let value = if opcode == ALOAD || opcode >= ALOAD_0 {
+                    // uninitialized objects can be loaded too
+                    let value = frame.local(index, &VerificationType::Top, hierarchy)?;
+                    if !value.is_reference() {
+                        return Err(format!(
+                            "Bad local variable type: local {} is {}, expecting a reference",
+                            index, value
+                        ));
+                    }
+                    value
+                } else {
+                    frame.local(index, kind, hierarchy)?
+                };
+                frame.push(value)?;
+            }
+            IALOAD | BALOAD | CALOAD | SALOAD | LALOAD | FALOAD | DALOAD | AALOAD => {
+                frame.pop(&Integer, hierarchy)?;
+                let array = frame.pop_reference()?;
+                let components: &[&str] = match opcode {
+                    IALOAD => &["I"],
+                    BALOAD => &["B", "Z"],
+                    CALOAD => &["C"],
+                    SALOAD => &["S"],
+                    LALOAD => &["J"],
+                    FALOAD => &["F"],
+                    DALOAD => &["D"],
+                    _ => &["L"],
+                };
+                frame.push(MethodVerifier::check_array(&array, components)?)?;
+            }
+            ISTORE..=ASTORE | ISTORE_0..=ASTORE_3 => {
+                let (kind, index) = if opcode <= ASTORE {
+                    (&kinds[(opcode - ISTORE) as usize], local_index())
+                } else {
+                    let n = opcode - ISTORE_0;
+                    (&kinds[(n / 4) as usize], (n % 4) as usize)
+                };
+                let value = if opcode == ASTORE || opcode >= ASTORE_0 {
+                    // and the return addresses of jsr
+                    match frame.pop_slot()? {
+                        value if value.is_reference() => value,
+                        VerificationType::ReturnAddress => VerificationType::ReturnAddress,
+                        value => {
+                            return Err(format!(
+                                "Bad type on operand stack: expecting a reference, found {}",
+                                value
+                            ))
+                        }
+                    }
+                } else {
+                    frame.pop(kind, hierarchy)?
+                };
+                frame.set_local(index, value)?;
+            }
+            IASTORE | BASTORE | CASTORE | SASTORE | LASTORE | FASTORE | DASTORE | AASTORE => {
+                let components: &[&str] = match opcode {
+                    IASTORE => &["I"],
+                    BASTORE => &["B", "Z"],
+                    CASTORE => &["C"],
+                    SASTORE => &["S"],
+                    LASTORE => &["J"],
+                    FASTORE => &["F"],
+                    DASTORE => &["D"],
+                    _ => &["L"],
+                };
+                if opcode == AASTORE {
+                    frame.pop(&VerificationType::object(), hierarchy)?;
+                } else {
+                    let value = VerificationType::from_descriptor(components[0]).unwrap();
+                    frame.pop(&value, hierarchy)?;
+                }
+                frame.pop(&Integer, hierarchy)?;
+                let array = frame.pop_reference()?;
+                MethodVerifier::check_array(&array, components)?;
+            }
+            POP => {
+                frame.pop_slots(1)?;
+            }
+            POP2 => {
+                frame.pop_slots(2)?;
+            }
+            DUP | DUP_X1 | DUP_X2 | DUP2 | DUP2_X1 | DUP2_X2 | SWAP => {
+                // the number of slots duplicated and the number of slots they are put under
+                let (top, under) = match opcode {
+                    DUP => (1, 0),
+                    DUP_X1 => (1, 1),
+                    DUP_X2 => (1, 2),
+                    DUP2 => (2, 0),
+                    DUP2_X1 => (2, 1),
+                    DUP2_X2 => (2, 2),
+                    _ => (1, 1),
+                };
+                let values = frame.pop_slots(top)?;
+                let others = frame.pop_slots(under)?;
+                if opcode == SWAP {
+                    if others[0].is_category2() || values[0].is_category2() {
+                        return Err("Bad type on operand stack in swap".to_string());
+                    }
+                    frame.push_slot(values[0].clone())?;
+                    frame.push_slot(others[0].clone())?;
+                } else {
+                    if under == 1 && others[0].is_category2() {
+                        return Err("Bad type on operand stack in dup".to_string());
+                    }
+                    for value in values.iter().chain(&others).chain(&values) {
+                        frame.push_slot(value.clone())?;
+                    }
+                }
+            }
+            IADD | ISUB | IMUL | IDIV | IREM | ISHL | ISHR | IUSHR | IAND | IOR | IXOR => {
+                frame.pop(&Integer, hierarchy)?;
+                frame.pop(&Integer, hierarchy)?;
+                frame.push(Integer)?;
+            }
+            LADD | LSUB | LMUL | LDIV | LREM | LAND | LOR | LXOR => {
+                frame.pop(&Long, hierarchy)?;
+                frame.pop(&Long, hierarchy)?;
+                frame.push(Long)?;
+            }
+            LSHL | LSHR | LUSHR => {
+                frame.pop(&Integer, hierarchy)?;
+                frame.pop(&Long, hierarchy)?;
+                frame.push(Long)?;
+            }
+            FADD | FSUB | FMUL | FDIV | FREM => {
+                frame.pop(&Float, hierarchy)?;
+                frame.pop(&Float, hierarchy)?;
+                frame.push(Float)?;
+            }
+            DADD | DSUB | DMUL | DDIV | DREM => {
+                frame.pop(&Double, hierarchy)?;
+                frame.pop(&Double, hierarchy)?;
+                frame.push(Double)?;
+            }
+            INEG..=DNEG | I2L..=I2S => {
+                let (from, to) = match opcode {
+                    INEG | I2B | I2C | I2S => (Integer, Integer),
+                    LNEG => (Long, Long),
+                    FNEG => (Float, Float),
+                    DNEG => (Double, Double),
+                    I2L => (Integer, Long),
+                    I2F => (Integer, Float),
+                    I2D => (Integer, Double),
+                    L2I => (Long, Integer),
+                    L2F => (Long, Float),
+                    L2D => (Long, Double),
+                    F2I => (Float, Integer),
+                    F2L => (Float, Long),
+                    F2D => (Float, Double),
+                    D2I => (Double, Integer),
+                    D2L => (Double, Long),
+                    _ => (Double, Float),
+                };
+                frame.pop(&from, hierarchy)?;
+                frame.push(to)?;
+            }
+            IINC => {
+                frame.local(local_index(), &Integer, hierarchy)?;
+            }
+            LCMP | FCMPL | FCMPG | DCMPL | DCMPG => {
+                let value = match opcode {
+                    LCMP => Long,
+                    FCMPL | FCMPG => Float,
+                    _ => Double,
+                };
+                frame.pop(&value, hierarchy)?;
+                frame.pop(&value, hierarchy)?;
+                frame.push(Integer)?;
+            }
+            IFEQ..=IF_ACMPNE | IFNULL | IFNONNULL => match opcode {
+                IFEQ..=IFLE => {
+                    frame.pop(&Integer, hierarchy)?;
+                }
+                IF_ICMPEQ..=IF_ICMPLE => {
+                    frame.pop(&Integer, hierarchy)?;
+                    frame.pop(&Integer, hierarchy)?;
+                }
+                IF_ACMPEQ | IF_ACMPNE => {
+                    frame.pop_reference()?;
+                    frame.pop_reference()?;
+                }
+                _ => {
+                    frame.pop_reference()?;
+                }
+            },
+            GOTO | GOTO_W => {}
+            JSR | JSR_W => frame.push(VerificationType::ReturnAddress)?,
+            RET => {
+                let index = local_index();
+                if frame.locals.get(index) != Some(&VerificationType::ReturnAddress) {
+                    return Err(format!(
+                        "Bad local variable type: local {} is not a return address",
+                        index
+                    ));
+                }
+            }
+            TABLESWITCH => {
+                frame.pop(&Integer, hierarchy)?;
+            }
+            LOOKUPSWITCH => {
+                frame.pop(&Integer, hierarchy)?;
+                let operands = (bci + 4) & !3;
+                let pairs = s4_at(operands + 4) as usize;
+                for index in 1..pairs {
+                    let pair = operands + 8 + 8 * index;
+                    if s4_at(pair) <= s4_at(pair - 8) {
+                        return Err("Bad lookupswitch instruction: keys are not sorted".to_string());
+                    }
+                }
+            }
+            IRETURN..=RETURN => {
+                let expected = match opcode {
+                    IRETURN => Some(Integer),
+                    LRETURN => Some(Long),
+                    FRETURN => Some(Float),
+                    DRETURN => Some(Double),
+                    ARETURN => Some(VerificationType::object()),
+                    _ => None,
+                };
+                self.check_return(frame, expected)?;
+            }
+            GETSTATIC | PUTSTATIC | GETFIELD | PUTFIELD => {
+                let (class, _, field_type) = self.field_ref(u2_at(bci + 1))?;
+                let class = VerificationType::reference(class);
+                match opcode {
+                    GETSTATIC => frame.push(field_type)?,
+                    PUTSTATIC => {
+                        frame.pop(&field_type, hierarchy)?;
+                    }
+                    GETFIELD => {
+                        frame.pop(&class, hierarchy)?;
+                        frame.push(field_type)?;
+                    }
+                    _ => {
+                        frame.pop(&field_type, hierarchy)?;
+                        let object = frame.pop_reference()?;
+                        // the fields of this class can be set before the super constructor is called
+                        let this_class = VerificationType::reference(self.klass.name());
+                        let this_uninitialized =
+                            object == VerificationType::UninitializedThis && class == this_class;
+                        if !this_uninitialized && !object.is_assignable_to(&class, hierarchy) {
+                            return Err(format!(
+                                "Bad type on operand stack in putfield: {} is not assignable to {}",
+                                object, class
+                            ));
+                        }
+                    }
+                }
+            }
+            INVOKEVIRTUAL | INVOKESPECIAL | INVOKESTATIC | INVOKEINTERFACE => {
+                let (class, name, arguments, result) = self.method_ref(u2_at(bci + 1), opcode)?;
+                if opcode == INVOKEINTERFACE {
+                    let slots: usize = arguments
+                        .iter()
+                        .map(|argument| if argument.is_category2() { 2 } else { 1 })
+                        .sum();
+                    if u1_at(bci + 3) as usize != slots + 1 || u1_at(bci + 4) != 0 {
+                        return Err(
+                            "Inconsistent args count operand in invokeinterface".to_string()
+                        );
+                    }
+                }
+                frame.pop_arguments(&arguments, hierarchy)?;
+                match opcode {
+                    INVOKESTATIC => {}
+                    INVOKESPECIAL if name == "<init>" => {
+                        if result.is_some() {
+                            return Err("Constructor must return void".to_string());
+                        }
+                        let object = frame.pop_reference()?;
+                        match object {
+                            VerificationType::UninitializedThis => {
+                                if class != self.klass.name()
+                                    && Some(class) != self.klass.super_name()
+                                {
+                                    return Err(format!("Bad <init> method call to {}", class));
+                                }
+                                frame.initialize(
+                                    &object,
+                                    VerificationType::reference(self.klass.name()),
+                                );
+                                frame.flag_this_uninit = false;
+                            }
+                            VerificationType::Uninitialized(offset) => {
+                                let offset = offset as usize;
+                                let created = (code.get(offset) == Some(&NEW))
+                                    .then(|| bytecodes::u2_at(code, offset + 1))
+                                    .flatten()
+                                    .and_then(|index| self.klass.constants().class_name(index));
+                                if created != Some(class) {
+                                    return Err(format!(
+                                        "Bad <init> method call: {} is not created by a new of {}",
+                                        object, class
+                                    ));
+                                }
+                                frame.initialize(&object, VerificationType::reference(class));
+                            }
+                            _ => {
+                                return Err(format!(
+                                    "Bad operand type when invoking <init>: {}",
+                                    object
+                                ))
+                            }
+                        }
+                    }
+                    INVOKESPECIAL => {
+                        frame.pop(&VerificationType::reference(self.klass.name()), hierarchy)?;
+                    }
+                    INVOKEINTERFACE => {
+                        // interfaces are treated like java/lang/Object
+                        frame.pop(&VerificationType::object(), hierarchy)?;
+                    }
+                    _ => {
+                        frame.pop(&VerificationType::reference(class), hierarchy)?;
+                    }
+                }
+                if let Some(result) = result {
+                    frame.push(result)?;
+                }
+            }
+            INVOKEDYNAMIC => {
+                let constants = self.klass.constants();
+                let index = u2_at(bci + 1);
+                let descriptor = match constants.get(index) {
+                    Some(ConstantPoolEntry::InvokeDynamic(_, name_and_type)) => constants
+                        .name_and_type(*name_and_type)
+                        .map(|(_, descriptor)| descriptor),
+                    _ => None,
+                };
+                let (arguments, result) = descriptor
+                    .and_then(VerificationType::from_method_descriptor)
+                    .ok_or_else(|| format!("Bad invokedynamic reference at index {}", index))?;
+                if u2_at(bci + 3) != 0 {
+                    return Err(
+                        "Third and fourth operand bytes of invokedynamic must be zero".to_string(),
+                    );
+                }
+                frame.pop_arguments(&arguments, hierarchy)?;
+                if let Some(result) = result {
+                    frame.push(result)?;
+                }
+            }
+            NEW => {
+                let class = self.class_ref(u2_at(bci + 1))?;
+                if class.starts_with('[') {
+                    return Err(format!("Illegal new instruction of array {}", class));
+                }
+                let uninitialized = VerificationType::Uninitialized(bci as u2);
+                if frame.stack.contains(&uninitialized) {
+                    return Err("Uninitialized object exists on backward branch".to_string());
+                }
+                frame.initialize(&uninitialized, VerificationType::Top);
+                frame.push(uninitialized)?;
+            }
+            NEWARRAY => {
+                let array = match u1_at(bci + 1) {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    atype => return Err(format!("Illegal newarray type {}", atype)),
+                };
+                frame.pop(&Integer, hierarchy)?;
+                frame.push(VerificationType::reference(array))?;
+            }
+            ANEWARRAY => {
+                let class = self.class_ref(u2_at(bci + 1))?;
+                let array = if class.starts_with('[') {
+                    format!("[{}", class)
+                } else {
+                    format!("[L{};", class)
+                };
+                if array.bytes().take_while(|&b| b == b'[').count() > 255 {
+                    return Err(
+                        "Illegal anewarray instruction, array has more than 255 dimensions"
+                            .to_string(),
+                    );
+                }
+                frame.pop(&Integer, hierarchy)?;
+                frame.push(VerificationType::Reference(array))?;
+            }
+            MULTIANEWARRAY => {
+                let class = self.class_ref(u2_at(bci + 1))?;
+                let dimensions = u1_at(bci + 3) as usize;
+                if dimensions == 0 || class.bytes().take_while(|&b| b == b'[').count() < dimensions
+                {
+                    return Err("Illegal dimension in multianewarray instruction".to_string());
+                }
+                for _ in 0..dimensions {
+                    frame.pop(&Integer, hierarchy)?;
+                }
+                frame.push(VerificationType::reference(class))?;
+            }
+            ARRAYLENGTH => {
+                let array = frame.pop_reference()?;
+                if array != Null && !array.is_array() {
+                    return Err(format!(
+                        "Bad type on operand stack in arraylength: {}",
+                        array
+                    ));
+                }
+                frame.push(Integer)?;
+            }
+            ATHROW => {
+                frame.pop(
+                    &VerificationType::reference("java/lang/Throwable"),
+                    hierarchy,
+                )?;
+            }
+            CHECKCAST => {
+                let class = self.class_ref(u2_at(bci + 1))?;
+                frame.pop(&VerificationType::object(), hierarchy)?;
+                frame.push(VerificationType::reference(class))?;
+            }
+            INSTANCEOF => {
+                self.class_ref(u2_at(bci + 1))?;
+                frame.pop(&VerificationType::object(), hierarchy)?;
+                frame.push(Integer)?;
+            }
+            MONITORENTER | MONITOREXIT => {
+                frame.pop(&VerificationType::object(), hierarchy)?;
+            }
+            _ => return Err(format!("Bad instruction {}", opcode)),
+        }
+        Ok(MethodVerifier::successors(code, bci))
+    }
+
+    /// The instructions that can follow the instruction at the bci, without its
+    /// exception handlers: `jsr` branches to its subroutine and `ret` to none.
+    fn successors(code: &[u8], bci: usize) -> Successors {
+        let s2_offset = || bytecodes::u2_at(code, bci + 1).unwrap() as i16 as i32;
+        let s4_at = |index: usize| bytecodes::s4_at(code, index).unwrap();
+        let target = |offset: i32| MethodVerifier::target(bci, offset);
+        let (falls_through, targets) = match code[bci] {
+            IFEQ..=IF_ACMPNE | IFNULL | IFNONNULL => (true, vec![target(s2_offset())]),
+            GOTO | JSR => (false, vec![target(s2_offset())]),
+            GOTO_W | JSR_W => (false, vec![target(s4_at(bci + 1))]),
+            opcode @ (TABLESWITCH | LOOKUPSWITCH) => {
+                let operands = (bci + 4) & !3;
+                let mut targets = vec![target(s4_at(operands))];
+                if opcode == TABLESWITCH {
+                    let (low, high) = (s4_at(operands + 4), s4_at(operands + 8));
+                    for index in 0..=(high as i64 - low as i64) as usize {
+                        targets.push(target(s4_at(operands + 12 + 4 * index)));
+                    }
+                } else {
+                    for index in 0..s4_at(operands + 4) as usize {
+                        targets.push(target(s4_at(operands + 12 + 8 * index)));
+                    }
+                }
+                (false, targets)
+            }
+            IRETURN..=RETURN | ATHROW | RET => (false, vec![]),
+            WIDE if code[bci + 1] == RET => (false, vec![]),
+            _ => (true, vec![]),
+        };
+        Successors {
+            falls_through,
+            targets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, Verifier, VerifierOptions};
+    use crate::{
+        classfile::test_class::{verify_error, verify_method, TestClass, TestHierarchy},
+        classloader::class_loader::ClassLoaderType,
+    };
+
+    #[test]
+    fn we_can_verify_straight_line_code() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let max = class.method_ref("java/lang/Math", "max", "(JJ)J");
+        // (int, long) -> long: return Math.max(i, l) + 1
+        #[rustfmt::skip]
+        let code = [
+            0x1A, 0x85, // iload_0 i2l
+            0x1F, // lload_1
+            0xB8, (max >> 8) as u8, max as u8, // invokestatic
+            0x0A, 0x61, 0xAD, // lconst_1 ladd lreturn
+        ];
+        assert!(verify_method(&mut class, "(IJ)J", 4, 3, &code, &[], None).is_ok());
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        // iload_0 lreturn
+        let result = verify_method(&mut class, "(I)I", 1, 1, &[0x1A, 0xAD], &[], None);
+        verify_error(
+            result,
+            "Method expects a different return type",
+            "Test.m(I)I @1: lreturn",
+        );
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let result = verify_method(&mut class, "(I)J", 1, 1, &[0x1A, 0xAD], &[], None);
+        verify_error(
+            result,
+            "Bad type on operand stack",
+            "Test.m(I)J @1: lreturn",
+        );
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        // iload_0 ireturn but the local is a float
+        let result = verify_method(&mut class, "(F)I", 1, 1, &[0x1A, 0xAC], &[], None);
+        verify_error(result, "Bad local variable type", "@0: iload_0");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        // iconst_0 iconst_0 ireturn with a stack of 1
+        let result = verify_method(&mut class, "()I", 1, 0, &[0x03, 0x03, 0xAC], &[], None);
+        verify_error(result, "Operand stack overflow", "@1: iconst_0");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        // iconst_0 pop
+        let result = verify_method(&mut class, "()V", 1, 0, &[0x03, 0x57], &[], None);
+        verify_error(result, "Falling off the end of the code", "@1: pop");
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        // lconst_0 pop return splits the long
+        let result = verify_method(&mut class, "()V", 2, 0, &[0x09, 0x57, 0xB1], &[], None);
+        verify_error(result, "Bad type on operand stack", "@1: pop");
+        // lconst_0 dup2 pop2 pop2 return
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let code = [0x09, 0x5C, 0x58, 0x58, 0xB1];
+        assert!(verify_method(&mut class, "()V", 4, 0, &code, &[], None).is_ok());
+    }
+
+    #[test]
+    fn we_can_verify_interface_calls() {
+        let interface_call = |count: u8, method_ref: fn(&mut TestClass) -> u16| {
+            let mut class = TestClass::new("Test", "java/lang/Object");
+            let accept = method_ref(&mut class);
+            // (LongConsumer) -> void: consumer.accept(0L)
+            #[rustfmt::skip]
+            let code = [
+                0x2A, 0x09, // aload_0 lconst_0
+                0xB9, (accept >> 8) as u8, accept as u8, count, 0, // invokeinterface
+                0xB1, // return
+            ];
+            let descriptor = "(Ljava/util/function/LongConsumer;)V";
+            verify_method(&mut class, descriptor, 3, 1, &code, &[], None)
+        };
+        let interface_method = |class: &mut TestClass| {
+            class.interface_method_ref("java/util/function/LongConsumer", "accept", "(J)V")
+        };
+        assert!(interface_call(3, interface_method).is_ok());
+        verify_error(
+            interface_call(2, interface_method),
+            "Inconsistent args count operand in invokeinterface",
+            "@2: invokeinterface",
+        );
+        let method = |class: &mut TestClass| {
+            class.method_ref("java/util/function/LongConsumer", "accept", "(J)V")
+        };
+        verify_error(
+            interface_call(3, method),
+            "Bad method reference",
+            "@2: invokeinterface",
+        );
+    }
+
+    #[test]
+    fn we_can_verify_branches_with_the_stack_map_table() {
+        // static int m(int i) { return i > 0 ? i : -i; }
+        let code = [
+            0x1A, 0x9E, 0x00, 0x07, // iload_0 ifle 8
+            0x1A, 0xA7, 0x00, 0x05, // iload_0 goto 10
+            0x1A, 0x74, // 8: iload_0 ineg
+            0xAC, // 10: ireturn
+        ];
+        // same at 8, same locals 1 integer at 10
+        let stack_map_table = [0, 2, 8, 65, 1];
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        assert!(
+            verify_method(&mut class, "(I)I", 1, 1, &code, &[], Some(&stack_map_table)).is_ok()
+        );
+
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let result = verify_method(&mut class, "(I)I", 1, 1, &code, &[], None);
+        verify_error(
+            result,
+            "Expecting a stackmap frame at branch target",
+            "@1: ifle",
+        );
+
+        // a float on the stack at 10
+        let stack_map_table = [0, 2, 8, 65, 2];
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let result = verify_method(&mut class, "(I)I", 1, 1, &code, &[], Some(&stack_map_table));
+        verify_error(
+            result,
+            "Inconsistent stackmap frames at branch target",
+            "@5: goto",
+        );
+
+        // a frame in the middle of an instruction
+        let stack_map_table = [0, 1, 2];
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let result = verify_method(&mut class, "(I)I", 1, 1, &code, &[], Some(&stack_map_table));
+        verify_error(result, "StackMapTable error: bad offset", "@2");
+
+        // a branch in the middle of an instruction
+        let mut code = code;
+        code[3] = 0x06;
+        let stack_map_table = [0, 2, 8, 65, 1];
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let result = verify_method(&mut class, "(I)I", 1, 1, &code, &[], Some(&stack_map_table));
+        verify_error(result, "Illegal target of jump or branch", "@1: ifle");
+    }
+
+    #[test]
+    fn we_can_verify_exception_handlers() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let exception = class.class("java/lang/Exception");
+        // static int m() { try { return 1; } catch (Exception e) { return 0; } }
+        let code = [0x04, 0xAC, 0x4B, 0x03, 0xAC];
+        // same locals 1 exception at 2
+        let stack_map_table = [0, 1, 247, 0, 2, 7, (exception >> 8) as u8, exception as u8];
+        let result = verify_method(
+            &mut class,
+            "()I",
+            1,
+            1,
+            &code,
+            &[[0, 2, 2, exception]],
+            Some(&stack_map_table),
+        );
+        assert!(result.is_ok());
+
+        // the handler expects an integer
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let exception = class.class("java/lang/Exception");
+        let result = verify_method(
+            &mut class,
+            "()I",
+            1,
+            1,
+            &code,
+            &[[0, 2, 2, exception]],
+            Some(&[0, 1, 66, 1]),
+        );
+        verify_error(
+            result,
+            "Stack map does not match the one at exception handler",
+            "@0",
+        );
+
+        // a catch type that is not a throwable
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let number = class.class("java/lang/Number");
+        let result = verify_method(
+            &mut class,
+            "()I",
+            1,
+            1,
+            &code,
+            &[[0, 2, 2, number]],
+            Some(&[0, 1, 247, 0, 2, 7, (number >> 8) as u8, number as u8]),
+        );
+        verify_error(result, "Catch type is not a subclass of Throwable", "@2");
+    }
+
+    #[test]
+    fn we_can_verify_constructors() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let object_init = class.method_ref("java/lang/Object", "<init>", "()V");
+        let test_init = class.method_ref("Test", "<init>", "()V");
+        let test = class.class("Test");
+        // Test() { super(); }
+        let code = [
+            0x2A,
+            0xB7,
+            (object_init >> 8) as u8,
+            object_init as u8,
+            0xB1,
+        ];
+        class.method(0x0001, "<init>", "()V", 1, 1, &code, &[], None);
+        // static Object m() { return new Test(); }
+        #[rustfmt::skip]
+        let code = [
+            0xBB, (test >> 8) as u8, test as u8, 0x59, // new dup
+            0xB7, (test_init >> 8) as u8, test_init as u8, 0xB0, // invokespecial areturn
+        ];
+        assert!(verify_method(&mut class, "()Ljava/lang/Object;", 2, 0, &code, &[], None).is_ok());
+
+        // returning the uninitialized object
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        let test = class.class("Test");
+        let code = [0xBB, (test >> 8) as u8, test as u8, 0xB0];
+        let result = verify_method(&mut class, "()Ljava/lang/Object;", 1, 0, &code, &[], None);
+        verify_error(result, "Bad type on operand stack", "@3: areturn");
+
+        // a constructor that does not call super()
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0001, "<init>", "()V", 0, 1, &[0xB1], &[], None);
+        let result = Verifier::new(&class.parse(), &TestHierarchy::new()).verify();
+        verify_error(
+            result,
+            "Constructor must call super() or this() before return",
+            "Test.<init>()V @0",
+        );
+    }
+
+    #[test]
+    fn we_can_trust_the_boot_classes() {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.method(0x0009, "m", "()V", 0, 0, &[0x57, 0xB1], &[], None);
+        let klass = class.parse();
+        let options = VerifierOptions::default();
+        let hierarchy = TestHierarchy::new();
+        assert!(verify(&klass, ClassLoaderType::BootLoader, &options, &hierarchy).is_ok());
+        let result = verify(&klass, ClassLoaderType::AppLoader, &options, &hierarchy);
+        verify_error(result, "Operand stack underflow", "@0: pop");
+        let options = VerifierOptions {
+            bytecode_verification_local: true,
+            ..options
+        };
+        assert!(verify(&klass, ClassLoaderType::BootLoader, &options, &hierarchy).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    classloader::class_file_stream::ClassFileStream,
+    model::constant_pool::ConstantPool,
+    utilities::definition::{u1, u2},
+};
+
+use super::verification_type::VerificationType;
+
+const ITEM_TOP: u1 = 0;
+const ITEM_INTEGER: u1 = 1;
+const ITEM_FLOAT: u1 = 2;
+const ITEM_DOUBLE: u1 = 3;
+const ITEM_LONG: u1 = 4;
+const ITEM_NULL: u1 = 5;
+const ITEM_UNINITIALIZED_THIS: u1 = 6;
+const ITEM_OBJECT: u1 = 7;
+const ITEM_UNINITIALIZED: u1 = 8;
+
+const SAME_FRAME_END: u1 = 63;
+const SAME_LOCALS_1_STACK_ITEM_END: u1 = 127;
+const SAME_LOCALS_1_STACK_ITEM_EXTENDED: u1 = 247;
+const CHOP_FRAME_START: u1 = 248;
+const CHOP_FRAME_END: u1 = 250;
+const SAME_FRAME_EXTENDED: u1 = 251;
+const APPEND_FRAME_END: u1 = 254;
+const FULL_FRAME: u1 = 255;
+
+/// The types of the locals and the stack at the start of the instruction at the offset.
+///
+/// Longs and doubles take two slots like in the frames of the verifier, and the
+/// locals stop at the last declared one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackMapFrame {
+    pub offset: u2,
+    pub locals: Vec<VerificationType>,
+    pub stack: Vec<VerificationType>,
+}
+
+/// Expand the `StackMapTable` attribute of a method to full frames, from the
+/// locals of the initial frame of the method (JVMS §4.7.4).
+pub fn parse_stack_map_table(
+    bytes: &[u1],
+    initial_locals: &[VerificationType],
+    constants: &ConstantPool,
+    max_locals: usize,
+    max_stack: usize,
+) -> Result<Vec<StackMapFrame>, String> {
+    let mut stream = ClassFileStream::new(bytes.to_vec(), String::new());
+    let truncated = |_| "StackMapTable is truncated".to_string();
+    let number_of_entries = stream.get_u2().map_err(truncated)?;
+    let mut frames: Vec<StackMapFrame> = Vec::with_capacity(number_of_entries as usize);
+    let mut locals = initial_locals.to_vec();
+    for _ in 0..number_of_entries {
+        let frame_type = stream.get_u1().map_err(truncated)?;
+        let (offset_delta, stack) = match frame_type {
+            0..=SAME_FRAME_END => (frame_type as u2, vec![]),
+            64..=SAME_LOCALS_1_STACK_ITEM_END => {
+                let stack = read_types(&mut stream, 1, constants)?;
+                ((frame_type - 64) as u2, stack)
+            }
+            SAME_LOCALS_1_STACK_ITEM_EXTENDED => {
+                let offset_delta = stream.get_u2().map_err(truncated)?;
+                (offset_delta, read_types(&mut stream, 1, constants)?)
+            }
+            CHOP_FRAME_START..=APPEND_FRAME_END => {
+                let offset_delta = stream.get_u2().map_err(truncated)?;
+                if frame_type <= CHOP_FRAME_END {
+                    for _ in 0..SAME_FRAME_EXTENDED - frame_type {
+                        let length = locals.len();
+                        match length {
+                            0 => return Err("Chop frame removes too many locals".to_string()),
+                            _ if length >= 2
+                                && locals[length - 1] == VerificationType::Top
+                                && locals[length - 2].is_category2() =>
+                            {
+                                locals.truncate(length - 2)
+                            }
+                            _ => locals.truncate(length - 1),
+                        }
+                    }
+                } else if frame_type > SAME_FRAME_EXTENDED {
+                    let count = (frame_type - SAME_FRAME_EXTENDED) as usize;
+                    locals.extend(read_types(&mut stream, count, constants)?);
+                }
+                (offset_delta, vec![])
+            }
+            FULL_FRAME => {
+                let offset_delta = stream.get_u2().map_err(truncated)?;
+                let count = stream.get_u2().map_err(truncated)? as usize;
+                locals = read_types(&mut stream, count, constants)?;
+                let count = stream.get_u2().map_err(truncated)? as usize;
+                (offset_delta, read_types(&mut stream, count, constants)?)
+            }
+            _ => return Err(format!("Reserved frame type {}", frame_type)),
+        };
+        let offset = match frames.last() {
+            None => offset_delta as usize,
+            Some(previous) => previous.offset as usize + offset_delta as usize + 1,
+        };
+        if offset > u2::MAX as usize {
+            return Err(format!(
+                "StackMapTable offset {} is out of the code",
+                offset
+            ));
+        }
+        if locals.len() > max_locals {
+            return Err(format!(
+                "StackMapTable frame at {} has more locals than max_locals",
+                offset
+            ));
+        }
+        if stack.len() > max_stack {
+            return Err(format!(
+                "StackMapTable frame at {} has a deeper stack than max_stack",
+                offset
+            ));
+        }
+        frames.push(StackMapFrame {
+            offset: offset as u2,
+            locals: locals.clone(),
+            stack,
+        });
+    }
+    if !stream.at_eos() {
+        return Err("StackMapTable has extra bytes".to_string());
+    }
+    Ok(frames)
+}
+
+/// Read `count` verification types, a long or a double is followed by a `Top`.
+fn read_types(
+    stream: &mut ClassFileStream,
+    count: usize,
+    constants: &ConstantPool,
+) -> Result<Vec<VerificationType>, String> {
+    let truncated = |_| "StackMapTable is truncated".to_string();
+    let mut types = Vec::with_capacity(count);
+    for _ in 0..count {
+        let tag = stream.get_u1().map_err(truncated)?;
+        let verification_type = match tag {
+            ITEM_TOP => VerificationType::Top,
+            ITEM_INTEGER => VerificationType::Integer,
+            ITEM_FLOAT => VerificationType::Float,
+            ITEM_DOUBLE => VerificationType::Double,
+            ITEM_LONG => VerificationType::Long,
+            ITEM_NULL => VerificationType::Null,
+            ITEM_UNINITIALIZED_THIS => VerificationType::UninitializedThis,
+            ITEM_OBJECT => {
+                let index = stream.get_u2().map_err(truncated)?;
+                let name = constants
+                    .class_name(index)
+                    .ok_or_else(|| format!("Bad class index {} in StackMapTable", index))?;
+                VerificationType::reference(name)
+            }
+            ITEM_UNINITIALIZED => {
+                VerificationType::Uninitialized(stream.get_u2().map_err(truncated)?)
+            }
+            _ => {
+                return Err(format!(
+                    "Bad verification type tag {} in StackMapTable",
+                    tag
+                ))
+            }
+        };
+        let category2 = verification_type.is_category2();
+        types.push(verification_type);
+        if category2 {
+            types.push(VerificationType::Top);
+        }
+    }
+    Ok(types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_stack_map_table, StackMapFrame};
+    use crate::{
+        classfile::verifier::verification_type::VerificationType::{self, *},
+        model::constant_pool::{ConstantPool, ConstantPoolEntry},
+    };
+
+    #[test]
+    fn we_can_expand_the_frames_of_a_stack_map_table() {
+        let constants = ConstantPool::new(vec![
+            ConstantPoolEntry::Invalid,
+            ConstantPoolEntry::Utf8("java/lang/String".to_string()),
+            ConstantPoolEntry::Class(1),
+        ]);
+        let bytes = [
+            0, 6, // 6 frames
+            3, // same at 3
+            252, 0, 1, 4, // append long at 5
+            64, 1, // same locals 1 integer at 6
+            250, 0, 0, // chop the long at 7
+            255, 0, 2, 0, 2, 2, 7, 0, 2, 0, 2, 8, 0, 3, 3, // full at 10
+            247, 0, 1, 6, // same locals 1 uninitialized this at 12
+        ];
+        let string = VerificationType::reference("java/lang/String");
+        let frames = parse_stack_map_table(&bytes, &[Integer], &constants, 4, 3).unwrap();
+        let frame =
+            |offset, locals: &[VerificationType], stack: &[VerificationType]| StackMapFrame {
+                offset,
+                locals: locals.to_vec(),
+                stack: stack.to_vec(),
+            };
+        assert_eq!(
+            frames,
+            vec![
+                frame(3, &[Integer], &[]),
+                frame(5, &[Integer, Long, Top], &[]),
+                frame(6, &[Integer, Long, Top], &[Integer]),
+                frame(7, &[Integer], &[]),
+                frame(
+                    10,
+                    &[Float, string.clone()],
+                    &[Uninitialized(3), Double, Top]
+                ),
+                frame(12, &[Float, string], &[UninitializedThis]),
+            ]
+        );
+        // too many locals, a chop of the empty locals and extra bytes
+        assert!(parse_stack_map_table(&bytes, &[Integer], &constants, 2, 3).is_err());
+        assert!(parse_stack_map_table(&[0, 1, 248, 0, 0], &[], &constants, 2, 2).is_err());
+        assert!(parse_stack_map_table(&[0, 1, 0, 0], &[], &constants, 2, 2).is_err());
+    }
+}
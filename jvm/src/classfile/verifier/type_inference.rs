@@ -0,0 +1,524 @@
+//! The type-inference verifier of class files before version 50 (JVMS §4.10.2).
+//!
+//! The frames at the start of the instructions are computed by data-flow
+//! analysis: the frame after an instruction is merged into the frames of its
+//! successors until none of them changes. A subroutine called by `jsr` returns
+//! to the instruction after each of its callers, with its own types for the
+//! locals it uses and the types of the caller for the others.
+use std::collections::BTreeMap;
+
+use super::{
+    bytecodes::{self, *},
+    verification_type::{ClassHierarchy, VerificationType},
+    Frame, MethodVerifier,
+};
+use crate::utilities::exceptions::Exception;
+
+/// The instructions reached from a `jsr` target without returning.
+struct Subroutine {
+    /// The `jsr` instructions calling it.
+    callers: Vec<usize>,
+    instructions: Vec<bool>,
+    rets: Vec<usize>,
+    /// The targets of the `jsr` instructions in it.
+    calls: Vec<usize>,
+    /// The locals loaded, stored or incremented by it or the subroutines it calls.
+    locals_used: Vec<bool>,
+}
+
+/// The inferred frames at the start of the instructions, and the instructions
+/// to execute again since their frame changed.
+struct Frames {
+    frames: Vec<Option<Frame>>,
+    queued: Vec<bool>,
+    pending: Vec<usize>,
+}
+
+impl Frames {
+    fn new(code_length: usize, initial_frame: Frame) -> Self {
+        let mut frames = Frames {
+            frames: vec![None; code_length],
+            queued: vec![false; code_length],
+            pending: vec![0],
+        };
+        frames.frames[0] = Some(initial_frame);
+        frames.queued[0] = true;
+        frames
+    }
+
+    fn at(&self, bci: usize) -> &Frame {
+        self.frames[bci].as_ref().unwrap()
+    }
+}
+
+impl MethodVerifier<'_> {
+    pub(super) fn verify_by_type_inference(&self) -> Result<(), Exception> {
+        let code = self.code.code();
+        let instructions = self.instructions()?;
+        let (initial_frame, _) = self.initial_frame()?;
+        self.check_exception_table(&instructions)?;
+        let subroutines = self.find_subroutines(&instructions)?;
+
+        let mut frames = Frames::new(code.len(), initial_frame);
+        while let Some(bci) = frames.pending.pop() {
+            frames.queued[bci] = false;
+            let incoming = frames.at(bci).clone();
+            for handler in self.code.exception_table() {
+                if (handler.start_pc as usize..handler.end_pc as usize).contains(&bci) {
+                    let exception_frame = self.exception_frame(&incoming, handler);
+                    self.merge(
+                        &mut frames,
+                        bci,
+                        handler.handler_pc as usize,
+                        exception_frame,
+                    )?;
+                }
+            }
+
+            let mut frame = incoming.clone();
+            let successors = self
+                .execute(bci, &mut frame)
+                .map_err(|reason| self.error(bci, &reason))?;
+            for &target in &successors.targets {
+                if !MethodVerifier::is_instruction(&instructions, target) {
+                    return Err(self.error(bci, "Illegal target of jump or branch"));
+                }
+                self.merge(&mut frames, bci, target, frame.clone())?;
+            }
+            let next = bci + bytecodes::length_at(code, bci).unwrap();
+            if successors.falls_through {
+                if next == code.len() {
+                    return Err(self.error(bci, "Falling off the end of the code"));
+                }
+                self.merge(&mut frames, bci, next, frame.clone())?;
+            }
+
+            if code[bci] == JSR || code[bci] == JSR_W {
+                // a new caller of a subroutine that may have returned already
+                let subroutine = &subroutines[&successors.targets[0]];
+                for &ret in &subroutine.rets {
+                    if let Some(ret_frame) = frames.frames[ret].clone() {
+                        if next == code.len() {
+                            return Err(self.error(bci, "Falling off the end of the code"));
+                        }
+                        let frame = return_frame(&incoming, &ret_frame, subroutine);
+                        self.merge(&mut frames, ret, next, frame)?;
+                    }
+                }
+            } else if MethodVerifier::is_subroutine_instruction(code, bci) {
+                let mut returns = false;
+                for subroutine in subroutines.values().filter(|s| s.instructions[bci]) {
+                    returns = true;
+                    for &caller in &subroutine.callers {
+                        let Some(caller_frame) = frames.frames[caller].clone() else {
+                            continue;
+                        };
+                        let caller_next = caller + bytecodes::length_at(code, caller).unwrap();
+                        if caller_next == code.len() {
+                            return Err(self.error(caller, "Falling off the end of the code"));
+                        }
+                        let frame = return_frame(&caller_frame, &frame, subroutine);
+                        self.merge(&mut frames, bci, caller_next, frame)?;
+                    }
+                }
+                if !returns {
+                    return Err(self.error(bci, "ret instruction outside of a subroutine"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the frame after the instruction at `from` into the frame of the target.
+    fn merge(
+        &self,
+        frames: &mut Frames,
+        from: usize,
+        target: usize,
+        frame: Frame,
+    ) -> Result<(), Exception> {
+        let changed = match &mut frames.frames[target] {
+            Some(existing) => merge_frames(existing, &frame, self.hierarchy)
+                .map_err(|reason| self.error(from, &reason))?,
+            none => {
+                *none = Some(frame);
+                true
+            }
+        };
+        if changed && !frames.queued[target] {
+            frames.queued[target] = true;
+            frames.pending.push(target);
+        }
+        Ok(())
+    }
+
+    /// The subroutines by the target of their `jsr` instructions.
+    fn find_subroutines(
+        &self,
+        instructions: &[bool],
+    ) -> Result<BTreeMap<usize, Subroutine>, Exception> {
+        let code = self.code.code();
+        let max_locals = self.code.max_locals() as usize;
+        let mut subroutines = BTreeMap::new();
+        for bci in (0..code.len()).filter(|&bci| instructions[bci]) {
+            if code[bci] == JSR || code[bci] == JSR_W {
+                let target = MethodVerifier::successors(code, bci).targets[0];
+                if !MethodVerifier::is_instruction(instructions, target) {
+                    return Err(self.error(bci, "Illegal target of jump or branch"));
+                }
+                subroutines
+                    .entry(target)
+                    .or_insert_with(|| Subroutine {
+                        callers: Vec::new(),
+                        instructions: vec![false; code.len()],
+                        rets: Vec::new(),
+                        calls: Vec::new(),
+                        locals_used: vec![false; max_locals],
+                    })
+                    .callers
+                    .push(bci);
+            }
+        }
+
+        for (&start, subroutine) in subroutines.iter_mut() {
+            let mut pending = vec![start];
+            while let Some(bci) = pending.pop() {
+                if !MethodVerifier::is_instruction(instructions, bci)
+                    || subroutine.instructions[bci]
+                {
+                    continue;
+                }
+                subroutine.instructions[bci] = true;
+                if let Some((index, size)) = local_access(code, bci) {
+                    for used in subroutine.locals_used.iter_mut().skip(index).take(size) {
+                        *used = true;
+                    }
+                }
+                let successors = MethodVerifier::successors(code, bci);
+                let next = bci + bytecodes::length_at(code, bci).unwrap();
+                match code[bci] {
+                    JSR | JSR_W => {
+                        // the called subroutine returns to the next instruction
+                        subroutine.calls.push(successors.targets[0]);
+                        pending.push(next);
+                    }
+                    RET | WIDE if MethodVerifier::is_subroutine_instruction(code, bci) => {
+                        subroutine.rets.push(bci)
+                    }
+                    _ => {
+                        pending.extend(&successors.targets);
+                        if successors.falls_through {
+                            pending.push(next);
+                        }
+                    }
+                }
+            }
+        }
+
+        // the locals used by the nested subroutines are used by their callers too
+        loop {
+            let used: BTreeMap<usize, Vec<bool>> = subroutines
+                .iter()
+                .map(|(&start, subroutine)| (start, subroutine.locals_used.clone()))
+                .collect();
+            let mut changed = false;
+            for subroutine in subroutines.values_mut() {
+                for call in &subroutine.calls {
+                    for (index, &nested) in used[call].iter().enumerate() {
+                        if nested && !subroutine.locals_used[index] {
+                            subroutine.locals_used[index] = true;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed {
+                return Ok(subroutines);
+            }
+        }
+    }
+}
+
+/// The local accessed by a load, a store, `iinc` or `ret`, and its number of slots.
+fn local_access(code: &[u8], bci: usize) -> Option<(usize, usize)> {
+    let (opcode, index) = match code[bci] {
+        WIDE => (code[bci + 1], bytecodes::u2_at(code, bci + 2)? as usize),
+        opcode => (opcode, *code.get(bci + 1).unwrap_or(&0) as usize),
+    };
+    let size = |kind: u8| if kind == 1 || kind == 3 { 2 } else { 1 };
+    match opcode {
+        ILOAD..=ALOAD => Some((index, size(opcode - ILOAD))),
+        ISTORE..=ASTORE => Some((index, size(opcode - ISTORE))),
+        IINC | RET => Some((index, 1)),
+        ILOAD_0..=ALOAD_3 => {
+            let n = opcode - ILOAD_0;
+            Some(((n % 4) as usize, size(n / 4)))
+        }
+        ISTORE_0..=ASTORE_3 => {
+            let n = opcode - ISTORE_0;
+            Some(((n % 4) as usize, size(n / 4)))
+        }
+        _ => None,
+    }
+}
+
+/// The frame after the `jsr` of the caller frame when the subroutine returns
+/// with the frame of its `ret`.
+fn return_frame(caller: &Frame, ret: &Frame, subroutine: &Subroutine) -> Frame {
+    let mut locals: Vec<VerificationType> = caller
+        .locals
+        .iter()
+        .zip(&ret.locals)
+        .zip(&subroutine.locals_used)
+        .map(|((caller, ret), &used)| if used { ret } else { caller }.clone())
+        .collect();
+    // a long or a double of the caller is lost when the subroutine uses its second half
+    for index in 1..locals.len() {
+        if subroutine.locals_used[index]
+            && !subroutine.locals_used[index - 1]
+            && locals[index - 1].is_category2()
+        {
+            locals[index - 1] = VerificationType::Top;
+        }
+    }
+    Frame {
+        locals,
+        stack: ret.stack.clone(),
+        max_stack: ret.max_stack,
+        flag_this_uninit: ret.flag_this_uninit,
+    }
+}
+
+/// Merge the frame into the frame of the same instruction, and tell whether it changed.
+fn merge_frames(
+    into: &mut Frame,
+    from: &Frame,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<bool, String> {
+    if into.stack.len() != from.stack.len() {
+        return Err(format!(
+            "Inconsistent stack height {} != {}",
+            into.stack.len(),
+            from.stack.len()
+        ));
+    }
+    let mut changed = false;
+    for (into, from) in into.stack.iter_mut().zip(&from.stack) {
+        let merged = merge_types(into, from, hierarchy);
+        if merged == VerificationType::Top && into != from {
+            return Err(format!("Mismatched stack types {} and {}", into, from));
+        }
+        if merged != *into {
+            *into = merged;
+            changed = true;
+        }
+    }
+    for (into, from) in into.locals.iter_mut().zip(&from.locals) {
+        let merged = merge_types(into, from, hierarchy);
+        if merged != *into {
+            *into = merged;
+            changed = true;
+        }
+    }
+    if from.flag_this_uninit && !into.flag_this_uninit {
+        into.flag_this_uninit = true;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// The most specific type both types can be assigned to.
+fn merge_types(
+    first: &VerificationType,
+    second: &VerificationType,
+    hierarchy: &dyn ClassHierarchy,
+) -> VerificationType {
+    match (first, second) {
+        (first, second) if first == second => first.clone(),
+        (VerificationType::Null, VerificationType::Reference(_)) => second.clone(),
+        (VerificationType::Reference(_), VerificationType::Null) => first.clone(),
+        (VerificationType::Reference(first), VerificationType::Reference(second)) => {
+            VerificationType::Reference(merge_references(first, second, hierarchy))
+        }
+        _ => VerificationType::Top,
+    }
+}
+
+/// The first common super class of two classes, or the array of the merged
+/// components of two arrays of references.
+fn merge_references(first: &str, second: &str, hierarchy: &dyn ClassHierarchy) -> String {
+    const OBJECT: &str = "java/lang/Object";
+    if first == second {
+        return first.to_string();
+    }
+    match (first.strip_prefix('['), second.strip_prefix('[')) {
+        (Some(first), Some(second)) => {
+            let component = |descriptor: &str| match descriptor.strip_prefix('L') {
+                Some(name) => Some(name[..name.len() - 1].to_string()),
+                None => descriptor.starts_with('[').then(|| descriptor.to_string()),
+            };
+            match (component(first), component(second)) {
+                (Some(first), Some(second)) => {
+                    let merged = merge_references(&first, &second, hierarchy);
+                    if merged.starts_with('[') {
+                        format!("[{}", merged)
+                    } else {
+                        format!("[L{};", merged)
+                    }
+                }
+                // arrays of different primitives
+                _ => OBJECT.to_string(),
+            }
+        }
+        (None, None) if !hierarchy.is_interface(first) && !hierarchy.is_interface(second) => {
+            let mut ancestors = vec![first.to_string()];
+            while let Some(super_class) = hierarchy.super_class(ancestors.last().unwrap()) {
+                ancestors.push(super_class);
+            }
+            let mut current = Some(second.to_string());
+            while let Some(class) = current {
+                if ancestors.contains(&class) {
+                    return class;
+                }
+                current = hierarchy.super_class(&class);
+            }
+            OBJECT.to_string()
+        }
+        _ => OBJECT.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_references;
+    use crate::classfile::test_class::{verify_error, verify_method, TestClass, TestHierarchy};
+
+    /// A class of version 49, before the stack map tables.
+    fn old_class() -> TestClass {
+        let mut class = TestClass::new("Test", "java/lang/Object");
+        class.major_version(49);
+        class
+    }
+
+    #[test]
+    fn we_can_merge_references() {
+        let hierarchy = TestHierarchy::new();
+        let merge = |first, second| merge_references(first, second, &hierarchy);
+        assert_eq!(
+            merge("java/lang/Integer", "java/lang/Number"),
+            "java/lang/Number"
+        );
+        assert_eq!(
+            merge("java/lang/Exception", "java/lang/Integer"),
+            "java/lang/Object"
+        );
+        assert_eq!(
+            merge("java/lang/Integer", "java/lang/Runnable"),
+            "java/lang/Object"
+        );
+        assert_eq!(
+            merge("[Ljava/lang/Integer;", "[Ljava/lang/Number;"),
+            "[Ljava/lang/Number;"
+        );
+        assert_eq!(merge("[[I", "[[J"), "[Ljava/lang/Object;");
+        assert_eq!(merge("[I", "[J"), "java/lang/Object");
+        assert_eq!(merge("[I", "java/lang/Number"), "java/lang/Object");
+    }
+
+    #[test]
+    fn we_can_infer_the_frames_of_branches() {
+        // static int m(int i) { return i > 0 ? i : -i; } without a stack map
+        let code = [
+            0x1A, 0x9E, 0x00, 0x07, 0x1A, 0xA7, 0x00, 0x05, 0x1A, 0x74, 0xAC,
+        ];
+        assert!(verify_method(&mut old_class(), "(I)I", 1, 1, &code, &[], None).is_ok());
+
+        // static Number m(boolean b) { return b ? (Integer) null : (Number) null; }
+        let number_code = |class: &mut TestClass| {
+            let integer = class.class("java/lang/Integer").to_be_bytes();
+            let number = class.class("java/lang/Number").to_be_bytes();
+            #[rustfmt::skip]
+            let code = vec![
+                0x1A, 0x99, 0x00, 0x0A, // iload_0 ifeq 11
+                0x01, 0xC0, integer[0], integer[1], // aconst_null checkcast Integer
+                0xA7, 0x00, 0x07, // goto 15
+                0x01, 0xC0, number[0], number[1], // 11: aconst_null checkcast Number
+                0xB0, // 15: areturn
+            ];
+            code
+        };
+        let mut class = old_class();
+        let code = number_code(&mut class);
+        let result = verify_method(&mut class, "(Z)Ljava/lang/Number;", 1, 1, &code, &[], None);
+        assert!(result.is_ok(), "{:?}", result);
+        let mut class = old_class();
+        let code = number_code(&mut class);
+        let result = verify_method(&mut class, "(Z)Ljava/lang/Integer;", 1, 1, &code, &[], None);
+        verify_error(result, "Bad type on operand stack", "@15: areturn");
+
+        // iload_0 ifeq 5, iconst_0, 5: iconst_1 ireturn
+        let code = [0x1A, 0x99, 0x00, 0x04, 0x03, 0x04, 0xAC];
+        let result = verify_method(&mut old_class(), "(I)I", 2, 1, &code, &[], None);
+        verify_error(result, "Inconsistent stack height", "@4: iconst_0");
+
+        // iconst_0, 1: iconst_1 goto 1
+        let code = [0x03, 0x04, 0xA7, 0xFF, 0xFF];
+        let result = verify_method(&mut old_class(), "()V", 2, 0, &code, &[], None);
+        verify_error(result, "Inconsistent stack height", "@2: goto");
+    }
+
+    #[test]
+    fn we_can_verify_subroutines() {
+        #[rustfmt::skip]
+        let code = [
+            0x04, 0x3B, // iconst_1 istore_0
+            0xA8, 0x00, 0x05, // jsr 7
+            0x1A, 0xAC, // iload_0 ireturn
+            0x4C, // 7: astore_1
+            0x00, 0x00, // nop nop
+            0xA9, 0x01, // ret 1
+        ];
+        assert!(verify_method(&mut old_class(), "()I", 1, 2, &code, &[], None).is_ok());
+
+        // the subroutine stores a float in the local 0
+        let mut float = code;
+        float[8..10].copy_from_slice(&[0x0B, 0x43]);
+        let result = verify_method(&mut old_class(), "()I", 1, 2, &float, &[], None);
+        verify_error(result, "Bad local variable type", "@5: iload_0");
+
+        // the subroutine keeps the local 0 of each of its callers
+        #[rustfmt::skip]
+        let code = [
+            0x04, 0x3B, // iconst_1 istore_0
+            0xA8, 0x00, 0x0C, // jsr 14
+            0x0B, 0x43, // fconst_0 fstore_0
+            0xA8, 0x00, 0x07, // jsr 14
+            0x22, 0xAE, // fload_0 freturn
+            0x00, 0x00, // nop nop
+            0x4C, 0xA9, 0x01, // 14: astore_1 ret 1
+        ];
+        let result = verify_method(&mut old_class(), "()F", 1, 2, &code, &[], None);
+        assert!(result.is_ok(), "{:?}", result);
+
+        // ret of an integer
+        let code = [0x04, 0x3C, 0xA9, 0x01];
+        let result = verify_method(&mut old_class(), "()V", 1, 2, &code, &[], None);
+        verify_error(result, "Bad local variable type", "@2: ret");
+    }
+
+    #[test]
+    fn the_version_picks_the_verifier() {
+        // jsr 4, return, 4: astore_0 ret 0
+        let code = [0xA8, 0x00, 0x04, 0xB1, 0x4B, 0xA9, 0x00];
+        for (major_version, verified) in [(49, true), (50, true), (51, false)] {
+            let mut class = old_class();
+            class.major_version(major_version);
+            let result = verify_method(&mut class, "()V", 1, 1, &code, &[], None);
+            if verified {
+                assert!(result.is_ok(), "{:?}", result);
+            } else {
+                verify_error(result, "jsr and ret are not allowed", "@0: jsr");
+            }
+        }
+    }
+}
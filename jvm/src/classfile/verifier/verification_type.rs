@@ -0,0 +1,262 @@
+use std::fmt;
+
+use crate::utilities::definition::u2;
+
+/// The super class and the kind of the classes the verifier checks assignments
+/// against, usually backed by the class loaders.
+pub trait ClassHierarchy {
+    /// Internal name of the super class of the class, `None` for
+    /// `java/lang/Object` or a class that cannot be found.
+    fn super_class(&self, name: &str) -> Option<String>;
+
+    fn is_interface(&self, name: &str) -> bool;
+}
+
+/// The type of a local variable or an operand stack slot (JVMS §4.10.1.2).
+///
+/// A long or a double takes two slots, the second is `Top`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor before the super constructor is called.
+    UninitializedThis,
+    /// The object created by the `new` at the offset, before its constructor is called.
+    Uninitialized(u2),
+    /// A class or an array, by internal name or array descriptor.
+    Reference(String),
+    /// The address pushed by `jsr`, only in class files before version 50.
+    ReturnAddress,
+}
+
+impl VerificationType {
+    pub fn object() -> Self {
+        VerificationType::Reference("java/lang/Object".to_string())
+    }
+
+    pub fn reference(name: &str) -> Self {
+        VerificationType::Reference(name.to_string())
+    }
+
+    /// The type of the field descriptor, booleans, bytes, chars and shorts are integers.
+    pub fn from_descriptor(descriptor: &str) -> Option<Self> {
+        let (verification_type, rest) = VerificationType::parse_descriptor(descriptor)?;
+        rest.is_empty().then_some(verification_type)
+    }
+
+    /// Parse the first field descriptor of the string, and return its type and the rest.
+    fn parse_descriptor(descriptor: &str) -> Option<(Self, &str)> {
+        let verification_type = match descriptor.as_bytes().first()? {
+            b'Z' | b'B' | b'C' | b'S' | b'I' => VerificationType::Integer,
+            b'F' => VerificationType::Float,
+            b'J' => VerificationType::Long,
+            b'D' => VerificationType::Double,
+            b'L' => {
+                let end = descriptor.find(';')?;
+                if end == 1 {
+                    return None;
+                }
+                return Some((
+                    VerificationType::reference(&descriptor[1..end]),
+                    &descriptor[end + 1..],
+                ));
+            }
+            b'[' => {
+                let dimensions = descriptor.bytes().take_while(|&b| b == b'[').count();
+                if dimensions > 255 {
+                    return None;
+                }
+                let (_, rest) = VerificationType::parse_descriptor(&descriptor[dimensions..])?;
+                let length = descriptor.len() - rest.len();
+                return Some((VerificationType::reference(&descriptor[..length]), rest));
+            }
+            _ => return None,
+        };
+        Some((verification_type, &descriptor[1..]))
+    }
+
+    /// The argument types and the return type, `None` for void, of the method descriptor.
+    pub fn from_method_descriptor(descriptor: &str) -> Option<(Vec<Self>, Option<Self>)> {
+        let mut rest = descriptor.strip_prefix('(')?;
+        let mut arguments = Vec::new();
+        while !rest.starts_with(')') {
+            let (argument, next) = VerificationType::parse_descriptor(rest)?;
+            arguments.push(argument);
+            rest = next;
+        }
+        let result = match &rest[1..] {
+            "V" => None,
+            result => Some(VerificationType::from_descriptor(result)?),
+        };
+        Some((arguments, result))
+    }
+
+    /// Long and double take two slots.
+    pub fn is_category2(&self) -> bool {
+        matches!(self, VerificationType::Long | VerificationType::Double)
+    }
+
+    /// A reference, null or an uninitialized object.
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            VerificationType::Null
+                | VerificationType::UninitializedThis
+                | VerificationType::Uninitialized(_)
+                | VerificationType::Reference(_)
+        )
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, VerificationType::Reference(name) if name.starts_with('['))
+    }
+
+    /// The component type of an array, `Null` for the null reference.
+    pub fn component_type(&self) -> Option<Self> {
+        match self {
+            VerificationType::Null => Some(VerificationType::Null),
+            VerificationType::Reference(name) => {
+                VerificationType::from_descriptor(name.strip_prefix('[')?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a value of this type can be used where a value of the other type
+    /// is expected (JVMS §4.10.1.2).
+    pub fn is_assignable_to(&self, other: &Self, hierarchy: &dyn ClassHierarchy) -> bool {
+        match (self, other) {
+            (from, to) if from == to => true,
+            (_, VerificationType::Top) => true,
+            (VerificationType::Null, VerificationType::Reference(_)) => true,
+            (VerificationType::Reference(from), VerificationType::Reference(to)) => {
+                is_java_assignable(from, to, hierarchy)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_java_assignable(from: &str, to: &str, hierarchy: &dyn ClassHierarchy) -> bool {
+    if from == to || to == "java/lang/Object" {
+        return true;
+    }
+    match (from.strip_prefix('['), to.strip_prefix('[')) {
+        (Some(_), None) => to == "java/lang/Cloneable" || to == "java/io/Serializable",
+        (None, Some(_)) => false,
+        (Some(from), Some(to)) => {
+            // arrays of primitives only to the same primitive arrays
+            match (from.strip_prefix('L'), to.strip_prefix('L')) {
+                (Some(from), Some(to)) => {
+                    is_java_assignable(&from[..from.len() - 1], &to[..to.len() - 1], hierarchy)
+                }
+                (Some(_), None) => false,
+                (None, Some(to)) => {
+                    from.starts_with('[')
+                        && is_java_assignable(from, &to[..to.len() - 1], hierarchy)
+                }
+                (None, None) => from.starts_with('[') && is_java_assignable(from, to, hierarchy),
+            }
+        }
+        (None, None) => {
+            // the verifier treats interfaces like java/lang/Object
+            if hierarchy.is_interface(to) {
+                return true;
+            }
+            let mut current = hierarchy.super_class(from);
+            while let Some(name) = current {
+                if name == to {
+                    return true;
+                }
+                current = hierarchy.super_class(&name);
+            }
+            false
+        }
+    }
+}
+
+impl fmt::Display for VerificationType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationType::Top => write!(f, "top"),
+            VerificationType::Integer => write!(f, "integer"),
+            VerificationType::Float => write!(f, "float"),
+            VerificationType::Long => write!(f, "long"),
+            VerificationType::Double => write!(f, "double"),
+            VerificationType::Null => write!(f, "null"),
+            VerificationType::UninitializedThis => write!(f, "uninitializedThis"),
+            VerificationType::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+            VerificationType::Reference(name) => write!(f, "'{}'", name),
+            VerificationType::ReturnAddress => write!(f, "returnAddress"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::VerificationType;
+    use crate::classfile::test_class::TestHierarchy;
+
+    #[test]
+    fn we_can_parse_descriptors() {
+        assert_eq!(
+            VerificationType::from_descriptor("Z"),
+            Some(VerificationType::Integer)
+        );
+        assert_eq!(
+            VerificationType::from_descriptor("[[Ljava/lang/String;"),
+            Some(VerificationType::reference("[[Ljava/lang/String;"))
+        );
+        assert_eq!(VerificationType::from_descriptor("L;"), None);
+        assert_eq!(VerificationType::from_descriptor("II"), None);
+        let (arguments, result) =
+            VerificationType::from_method_descriptor("(IJ[DLjava/lang/Object;)V").unwrap();
+        assert_eq!(
+            arguments,
+            vec![
+                VerificationType::Integer,
+                VerificationType::Long,
+                VerificationType::reference("[D"),
+                VerificationType::object()
+            ]
+        );
+        assert_eq!(result, None);
+        assert_eq!(VerificationType::from_method_descriptor("()"), None);
+        assert_eq!(
+            VerificationType::reference("[[I").component_type(),
+            Some(VerificationType::reference("[I"))
+        );
+    }
+
+    #[test]
+    fn we_can_check_assignability() {
+        let hierarchy = TestHierarchy(HashMap::from([
+            ("java/lang/Integer", (Some("java/lang/Number"), false)),
+            ("java/lang/Number", (Some("java/lang/Object"), false)),
+            ("java/lang/Runnable", (Some("java/lang/Object"), true)),
+        ]));
+        let assignable = |from: &str, to: &str| {
+            VerificationType::reference(from)
+                .is_assignable_to(&VerificationType::reference(to), &hierarchy)
+        };
+        assert!(assignable("java/lang/Integer", "java/lang/Number"));
+        assert!(!assignable("java/lang/Number", "java/lang/Integer"));
+        assert!(assignable("java/lang/Number", "java/lang/Runnable"));
+        assert!(assignable("[I", "java/lang/Cloneable"));
+        assert!(!assignable("[I", "[J"));
+        assert!(!assignable("[I", "[Ljava/lang/Object;"));
+        assert!(assignable("[[I", "[Ljava/lang/Object;"));
+        assert!(assignable("[Ljava/lang/Integer;", "[Ljava/lang/Number;"));
+        assert!(VerificationType::Null.is_assignable_to(&VerificationType::object(), &hierarchy));
+        assert!(!VerificationType::Null
+            .is_assignable_to(&VerificationType::UninitializedThis, &hierarchy));
+        assert!(VerificationType::Long.is_assignable_to(&VerificationType::Top, &hierarchy));
+        assert!(!VerificationType::Integer.is_assignable_to(&VerificationType::Float, &hierarchy));
+    }
+}
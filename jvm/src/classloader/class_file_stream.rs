@@ -1 +1,75 @@
-pub struct ClassFileStream {}
+use crate::utilities::{
+    definition::{u1, u2, u4},
+    exceptions::Exception,
+};
+
+/// The bytes of a class file and where they were loaded from, read from the start.
+pub struct ClassFileStream {
+    buffer: Vec<u8>,
+    source: String,
+    current: usize,
+}
+
+impl ClassFileStream {
+    pub fn new(buffer: Vec<u8>, source: String) -> Self {
+        ClassFileStream {
+            buffer,
+            source,
+            current: 0,
+        }
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn length(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The class path entry, e.g. the path of the jar or `jrt:/java.base`.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Offset of the next byte to read.
+    pub fn current_offset(&self) -> usize {
+        self.current
+    }
+
+    pub fn at_eos(&self) -> bool {
+        self.current == self.buffer.len()
+    }
+
+    fn truncated() -> Exception {
+        Exception::ClassFormatError("Truncated class file".to_string())
+    }
+
+    /// Read the next `length` bytes.
+    pub fn get_bytes(&mut self, length: usize) -> Result<&[u1], Exception> {
+        let end = self
+            .current
+            .checked_add(length)
+            .filter(|&end| end <= self.buffer.len())
+            .ok_or_else(ClassFileStream::truncated)?;
+        let bytes = &self.buffer[self.current..end];
+        self.current = end;
+        Ok(bytes)
+    }
+
+    pub fn get_u1(&mut self) -> Result<u1, Exception> {
+        Ok(self.get_bytes(1)?[0])
+    }
+
+    pub fn get_u2(&mut self) -> Result<u2, Exception> {
+        Ok(u2::from_be_bytes(self.get_bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn get_u4(&mut self) -> Result<u4, Exception> {
+        Ok(u4::from_be_bytes(self.get_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn skip(&mut self, length: usize) -> Result<(), Exception> {
+        self.get_bytes(length).map(|_| ())
+    }
+}
@@ -1,6 +1,7 @@
 pub struct ClassLoader {}
 
-enum ClassLoaderType {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassLoaderType {
     BootLoader = 1,
     PlatformLoader = 2,
     AppLoader = 3,
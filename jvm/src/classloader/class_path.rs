@@ -1,24 +1,70 @@
-use std::{cell::RefCell, fs::File, io::BufReader, sync::Arc};
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use lib::jimage::JImageFile;
+use lib::{
+    jimage::{
+        JIMAGE_FindResource, JIMAGE_GetResource, JIMAGE_Open, JIMAGE_PackageToModule, JImageFile,
+    },
+    zip::jar::JarFile,
+};
 
 use super::class_file_stream::ClassFileStream;
+
+/// Feature release of the running JVM, the highest release of the classes of
+/// multi-release jars and of the jimage resources loaded.
+pub const JVM_FEATURE_RELEASE: u32 = 17;
+
+/// Directory of the classes of a release in a multi-release jar.
+const VERSIONS_DIR: &str = "META-INF/versions/";
+
+/// The first release with multi-release jars, lower versions are ignored.
+const BASE_RELEASE: u32 = 9;
+
 pub trait ClassPathEntry {
-    fn open_stream(&self, name: String) -> ClassFileStream;
-    fn is_jar_file() -> bool {
+    /// The class file with the name, e.g. `java/lang/Object.class`, `None` when the
+    /// entry does not have it and an error when it cannot be read.
+    fn open_stream(&self, name: &str) -> io::Result<Option<ClassFileStream>>;
+    fn is_jar_file(&self) -> bool {
         false
     }
-    fn is_modules_image() -> bool {
+    fn is_modules_image(&self) -> bool {
         false
     }
-    fn from_class_path_attr() -> bool {
+    /// Whether the entry was added by the `Class-Path` attribute of a jar.
+    fn is_from_class_path_attr(&self) -> bool {
         false
     }
-    fn jimage() -> Option<Arc<JImageFile>> {
+    fn jimage(&self) -> Option<Arc<JImageFile>> {
         None
     }
-    fn name() -> String;
+    fn name(&self) -> &str;
 }
+
+/// The entry for the path, a directory, the jimage of the modules or a jar.
+pub fn create_class_path_entry(
+    path: &Path,
+    from_class_path_attr: bool,
+) -> io::Result<Box<dyn ClassPathEntry>> {
+    let name = path.to_string_lossy().into_owned();
+    if fs::metadata(path)?.is_dir() {
+        return Ok(Box::new(ClassPathDirEntry::new(name)));
+    }
+    if let Ok(jimage) = JIMAGE_Open(name.clone()) {
+        return Ok(Box::new(ClassPathImageEntry::new(name, jimage)));
+    }
+    let zip = JarFile::open(path)?;
+    Ok(Box::new(ClassPathZipEntry::new(
+        name,
+        zip,
+        from_class_path_attr,
+    )))
+}
+
 pub struct ClassPathDirEntry {
     dir: String,
 }
@@ -30,63 +76,282 @@ impl ClassPathDirEntry {
 }
 
 impl ClassPathEntry for ClassPathDirEntry {
-    fn open_stream(&self, name: String) -> ClassFileStream {
+    fn open_stream(&self, name: &str) -> io::Result<Option<ClassFileStream>> {
         assert!(
             !self.dir.is_empty() && !name.is_empty(),
             "invalid dir and name."
         );
-        ClassFileStream {}
+        match fs::read(Path::new(&self.dir).join(name)) {
+            Ok(bytes) => Ok(Some(ClassFileStream::new(bytes, self.dir.clone()))),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
     }
 
-    fn name() -> String {
-        todo!()
+    fn name(&self) -> &str {
+        &self.dir
     }
 }
 
 pub struct ClassPathZipEntry {
     zip_name: String,
-    zip: RefCell<BufReader<File>>,
+    zip: RefCell<JarFile<BufReader<File>>>,
+    from_class_path_attr: bool,
+    /// Releases with classes in `META-INF/versions` up to the running release,
+    /// highest first, empty unless the jar is multi-release.
+    versions: Vec<u32>,
 }
 
 impl ClassPathZipEntry {
-    fn new(zip_name: String, zip: BufReader<File>) -> Self {
+    fn new(
+        zip_name: String,
+        mut zip: JarFile<BufReader<File>>,
+        from_class_path_attr: bool,
+    ) -> Self {
+        let versions = if zip.is_multi_release() {
+            let names = zip
+                .archive()
+                .entries()
+                .iter()
+                .map(|entry| entry.name.as_str());
+            release_versions(names, JVM_FEATURE_RELEASE)
+        } else {
+            Vec::new()
+        };
         ClassPathZipEntry {
             zip_name,
             zip: RefCell::new(zip),
+            from_class_path_attr,
+            versions,
         }
     }
+
+    /// The entries of the `Class-Path` attribute of the manifest.
+    pub fn class_path(&self) -> Vec<PathBuf> {
+        self.zip.borrow().class_path()
+    }
+}
+
+/// The releases of the versioned directories of a multi-release jar from the highest
+/// release up to `release` down to the base release.
+fn release_versions<'a, I: Iterator<Item = &'a str>>(names: I, release: u32) -> Vec<u32> {
+    let mut versions: Vec<u32> = names
+        .filter_map(|name| name.strip_prefix(VERSIONS_DIR))
+        .filter_map(|name| name.split_once('/'))
+        .filter_map(|(version, _)| version.parse().ok())
+        .filter(|version| (BASE_RELEASE..=release).contains(version))
+        .collect();
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    versions.dedup();
+    versions
 }
 
 impl ClassPathEntry for ClassPathZipEntry {
-    fn open_stream(&self, name: String) -> ClassFileStream {
-        todo!()
+    fn open_stream(&self, name: &str) -> io::Result<Option<ClassFileStream>> {
+        let mut zip = self.zip.borrow_mut();
+        let versioned = self
+            .versions
+            .iter()
+            .map(|version| format!("{}{}/{}", VERSIONS_DIR, version, name));
+        let Some(name) = versioned
+            .chain(std::iter::once(name.to_string()))
+            .find(|name| zip.entry(name).is_some())
+        else {
+            return Ok(None);
+        };
+        let bytes = zip.read(&name)?;
+        Ok(bytes.map(|bytes| ClassFileStream::new(bytes, self.zip_name.clone())))
     }
 
-    fn is_jar_file() -> bool {
+    fn is_jar_file(&self) -> bool {
         true
     }
 
-    fn name() -> String {
-        todo!()
+    fn is_from_class_path_attr(&self) -> bool {
+        self.from_class_path_attr
+    }
+
+    fn name(&self) -> &str {
+        &self.zip_name
     }
 }
 
 pub struct ClassPathImageEntry {
     name: String,
+    jimage: Arc<JImageFile>,
 }
 
 impl ClassPathImageEntry {
-    fn new(name: String) -> Self {
-        ClassPathImageEntry { name }
+    fn new(name: String, jimage: Arc<JImageFile>) -> Self {
+        ClassPathImageEntry { name, jimage }
     }
 }
 
 impl ClassPathEntry for ClassPathImageEntry {
-    fn open_stream(&self, name: String) -> ClassFileStream {
-        todo!()
+    fn open_stream(&self, name: &str) -> io::Result<Option<ClassFileStream>> {
+        Ok(self.find_resource(name))
+    }
+
+    fn is_modules_image(&self) -> bool {
+        true
+    }
+
+    fn jimage(&self) -> Option<Arc<JImageFile>> {
+        Some(self.jimage.clone())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl ClassPathImageEntry {
+    fn find_resource(&self, name: &str) -> Option<ClassFileStream> {
+        let package = name.rsplit_once('/').map(|(package, _)| package)?;
+        let module = JIMAGE_PackageToModule(self.jimage.clone(), package.to_string())?;
+        let (location, _) = JIMAGE_FindResource(
+            self.jimage.clone(),
+            module.clone(),
+            JVM_FEATURE_RELEASE.to_string(),
+            name.to_string(),
+        )?;
+        let bytes = JIMAGE_GetResource(self.jimage.clone(), location)?;
+        Some(ClassFileStream::new(bytes, format!("jrt:/{}", module)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use lib::zip::checksum::Crc32;
+
+    use super::{create_class_path_entry, release_versions};
+
+    /// A zip of the stored entries.
+    fn write_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut directory = Vec::new();
+        for (name, data) in entries {
+            let mut crc = Crc32::new();
+            crc.update(data);
+            let offset = bytes.len() as u32;
+            let mut header = Vec::new();
+            header.extend_from_slice(&20u16.to_le_bytes());
+            header.extend_from_slice(&[0; 8]);
+            header.extend_from_slice(&crc.value().to_le_bytes());
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes());
+            bytes.extend_from_slice(&0x04034B50u32.to_le_bytes());
+            bytes.extend_from_slice(&header);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(data);
+            directory.extend_from_slice(&0x02014B50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&header);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = bytes.len() as u32;
+        bytes.extend_from_slice(&directory);
+        bytes.extend_from_slice(&0x06054B50u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&directory_offset.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn multi_release_versions_are_searched_from_the_highest() {
+        let names = [
+            "META-INF/MANIFEST.MF",
+            "META-INF/versions/11/org/json/Json.class",
+            "META-INF/versions/9/module-info.class",
+            "META-INF/versions/21/org/json/Json.class",
+            "META-INF/versions/11/org/json/Parser.class",
+            "META-INF/versions/8/org/json/Json.class",
+            "META-INF/versions/latest/org/json/Json.class",
+            "org/json/Json.class",
+        ];
+        assert_eq!(release_versions(names.into_iter(), 17), [11, 9]);
+        assert_eq!(release_versions(names.into_iter(), 21), [21, 11, 9]);
+    }
+
+    #[test]
+    fn we_can_open_a_class_of_a_directory() {
+        let dir = std::env::temp_dir().join(format!("class-path-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("p")).unwrap();
+        std::fs::write(dir.join("p/C.class"), [0xCA, 0xFE, 0xBA, 0xBE]).unwrap();
+        let entry = create_class_path_entry(&dir, false).unwrap();
+        assert!(!entry.is_jar_file() && !entry.is_modules_image());
+        let stream = entry.open_stream("p/C.class").unwrap().unwrap();
+        assert_eq!(stream.buffer(), [0xCA, 0xFE, 0xBA, 0xBE]);
+        assert_eq!(stream.source(), entry.name());
+        assert!(entry.open_stream("p/D.class").unwrap().is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn we_can_open_a_class_of_the_modules_image() {
+        if let Ok(java_home) = std::env::var("JAVA_HOME") {
+            let path = Path::new(&java_home).join("lib/modules");
+            let entry = create_class_path_entry(&path, false).unwrap();
+            assert!(entry.is_modules_image() && entry.jimage().is_some());
+            let stream = entry.open_stream("java/sql/Driver.class").unwrap().unwrap();
+            assert!(stream.buffer().starts_with(&[0xCA, 0xFE, 0xBA, 0xBE]));
+            assert_eq!(stream.source(), "jrt:/java.sql");
+            assert!(entry
+                .open_stream("java/lang/Missing.class")
+                .unwrap()
+                .is_none());
+        }
     }
 
-    fn name() -> String {
-        todo!()
+    #[test]
+    fn we_can_open_a_class_of_a_multi_release_jar() {
+        let jar = std::env::temp_dir().join(format!("class-path-{}.jar", std::process::id()));
+        let mut bytes = write_zip(&[
+            (
+                "META-INF/MANIFEST.MF",
+                b"Manifest-Version: 1.0\r\nMulti-Release: true\r\n",
+            ),
+            ("p/C.class", &[0xCA, 0xFE, 0xBA, 0xBE, 8]),
+            (
+                "META-INF/versions/11/p/C.class",
+                &[0xCA, 0xFE, 0xBA, 0xBE, 11],
+            ),
+            (
+                "META-INF/versions/21/p/C.class",
+                &[0xCA, 0xFE, 0xBA, 0xBE, 21],
+            ),
+            ("p/D.class", &[0xCA, 0xFE, 0xBA, 0xBE, 8]),
+            ("p/E.class", b"broken class"),
+        ]);
+        let broken = bytes
+            .windows(6)
+            .position(|window| window == b"broken")
+            .unwrap();
+        bytes[broken] = b'B';
+        std::fs::write(&jar, bytes).unwrap();
+        let entry = create_class_path_entry(&jar, true).unwrap();
+        assert!(entry.is_jar_file() && entry.is_from_class_path_attr());
+        let open = |name| entry.open_stream(name).unwrap().unwrap();
+        assert_eq!(open("p/C.class").buffer(), [0xCA, 0xFE, 0xBA, 0xBE, 11]);
+        assert_eq!(open("p/D.class").buffer(), [0xCA, 0xFE, 0xBA, 0xBE, 8]);
+        assert_eq!(open("p/C.class").source(), entry.name());
+        assert!(entry.open_stream("p/F.class").unwrap().is_none());
+        let Err(error) = entry.open_stream("p/E.class") else {
+            panic!("p/E.class should not be read.");
+        };
+        assert!(error
+            .to_string()
+            .starts_with("invalid entry CRC of p/E.class"));
+        std::fs::remove_file(jar).unwrap();
     }
 }
@@ -0,0 +1,5 @@
+//! The Java virtual machine: class files, class loading and the object model.
+pub mod classfile;
+pub mod classloader;
+pub mod model;
+pub mod utilities;
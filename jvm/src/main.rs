@@ -1,9 +1,5 @@
 #![warn(missing_docs)]
 
-mod classloader;
-mod model;
-mod utilities;
-
 fn main() {
     println!("Hello World!");
 }
@@ -0,0 +1,265 @@
+//! The classes of the arrays, one for each element type and dimension, created by the
+//! vm when an array type is first used.
+use std::sync::Arc;
+
+use gc::Address;
+
+use crate::utilities::{
+    definition::{jint, u1, u4},
+    exceptions::Exception,
+};
+
+use super::{
+    field_layout::{FieldType, KLASS_POINTER_OFFSET, OBJECT_HEADER_SIZE},
+    mirror::Mirror,
+};
+
+/// Offset of the `int` length of an array, after the object header.
+pub const ARRAY_LENGTH_OFFSET: u4 = OBJECT_HEADER_SIZE;
+
+/// The super types of every array class (JLS §10.8).
+const ARRAY_SUPER_TYPES: [&str; 3] = [
+    "java/lang/Object",
+    "java/lang/Cloneable",
+    "java/io/Serializable",
+];
+
+pub struct ArrayKlass {
+    /// The descriptor of the array, e.g. `[I` or `[Ljava/lang/String;`.
+    name: String,
+    component: Arc<Mirror>,
+}
+
+impl ArrayKlass {
+    /// The array class of the component type, a primitive type other than `void`, a
+    /// class or an array class.
+    pub fn new(component: Arc<Mirror>) -> Self {
+        ArrayKlass {
+            name: format!("[{}", component.descriptor()),
+            component,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn component(&self) -> &Arc<Mirror> {
+        &self.component
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.name.bytes().take_while(|&byte| byte == b'[').count()
+    }
+
+    /// The type of the components, `Reference` for arrays of objects and of arrays.
+    pub fn component_type(&self) -> FieldType {
+        FieldType::from_descriptor(&self.name[1..]).expect("array classes have a component type.")
+    }
+
+    /// `java/lang/Object` for every array class.
+    pub fn super_name(&self) -> &'static str {
+        ARRAY_SUPER_TYPES[0]
+    }
+
+    /// `java/lang/Cloneable` and `java/io/Serializable`.
+    pub fn interface_names(&self) -> &'static [&'static str] {
+        &ARRAY_SUPER_TYPES[1..]
+    }
+
+    /// Whether the class or interface is a super type of the array class.
+    pub fn is_super_type(name: &str) -> bool {
+        ARRAY_SUPER_TYPES.contains(&name)
+    }
+
+    /// Offset of the first component, after the length and aligned to the size of the
+    /// components.
+    pub fn base_offset(&self) -> u4 {
+        let size = self.component_type().size();
+        (ARRAY_LENGTH_OFFSET + 4 + size - 1) & !(size - 1)
+    }
+
+    pub fn component_offset(&self, index: usize) -> usize {
+        self.base_offset() as usize + index * self.component_type().size() as usize
+    }
+
+    /// Size of an array of the length, header included, allocated by `newarray`,
+    /// `anewarray` and `multianewarray` and copied by `clone`.
+    pub fn object_size(&self, length: jint) -> Result<usize, Exception> {
+        if length < 0 {
+            return Err(Exception::NegativeArraySizeException(length.to_string()));
+        }
+        let size = self.component_offset(length as usize);
+        Ok((size + 7) & !7)
+    }
+
+    /// `clone` of an array of the class at the address (JLS §10.7): a new array of the
+    /// same length and components, the objects of an array of references are shared.
+    pub fn clone_array(&self, array: Address) -> Result<Address, Exception> {
+        let length = array.plus(ARRAY_LENGTH_OFFSET as usize).load::<jint>();
+        let size = self.object_size(length)?;
+        let clone = gc::allocate(size)
+            .ok_or_else(|| Exception::OutOfMemoryError("Java heap space".to_string()))?;
+        // the header of the collector is not copied, the clone is neither marked nor pinned
+        let start = KLASS_POINTER_OFFSET as usize;
+        unsafe {
+            std::ptr::write_bytes(clone.as_mut_ptr::<u1>(), 0, start);
+            std::ptr::copy_nonoverlapping(
+                array.plus(start).as_ptr::<u1>(),
+                clone.plus(start).as_mut_ptr::<u1>(),
+                size - start,
+            );
+        }
+        Ok(clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        classfile::test_class::TestClass,
+        model::{
+            field_layout::{FieldType, KLASS_POINTER_OFFSET},
+            mirror::Mirrors,
+        },
+        utilities::definition::jint,
+    };
+
+    use super::ARRAY_LENGTH_OFFSET;
+
+    fn mirrors() -> Mirrors {
+        let mirrors = Mirrors::new();
+        for (name, super_name) in [
+            ("java/lang/Object", ""),
+            ("java/lang/Number", "java/lang/Object"),
+            ("java/lang/Integer", "java/lang/Number"),
+            ("java/lang/String", "java/lang/Object"),
+        ] {
+            mirrors.define(Arc::new(TestClass::new(name, super_name).parse()));
+        }
+        let mut cloneable = TestClass::new("java/lang/Cloneable", "java/lang/Object");
+        cloneable.access_flags(0x0601);
+        mirrors.define(Arc::new(cloneable.parse()));
+        mirrors
+    }
+
+    #[test]
+    fn we_can_lay_out_the_arrays() {
+        let mirrors = mirrors();
+        let ints = mirrors.get("[I").unwrap();
+        let ints = ints.array_klass().unwrap();
+        assert_eq!(ints.component_type(), FieldType::Int);
+        assert_eq!(ints.base_offset(), 28);
+        assert_eq!(ints.object_size(3), Ok(40));
+        let error = ints.object_size(-1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "java.lang.NegativeArraySizeException: -1"
+        );
+        let strings = mirrors.get("[[Ljava/lang/String;").unwrap();
+        let strings = strings.array_klass().unwrap();
+        assert_eq!(strings.dimension(), 2);
+        assert_eq!(strings.component_type(), FieldType::Reference);
+        assert_eq!(strings.base_offset(), 32);
+        assert_eq!(strings.component_offset(2), 48);
+        assert_eq!(strings.object_size(0), Ok(32));
+        assert_eq!(strings.super_name(), "java/lang/Object");
+        assert_eq!(
+            strings.interface_names(),
+            ["java/lang/Cloneable", "java/io/Serializable"]
+        );
+        assert_eq!(strings.component().name(), "[Ljava.lang.String;");
+        assert!(mirrors.get("[V").is_none());
+    }
+
+    #[test]
+    fn we_can_check_the_arrays_are_covariant() {
+        let mirrors = mirrors();
+        let get = |name| mirrors.get(name).unwrap();
+        assert_eq!(
+            mirrors.check_cast(&get("[Ljava/lang/Integer;"), &get("[Ljava/lang/Number;")),
+            Ok(())
+        );
+        assert_eq!(
+            mirrors.check_cast(&get("[[I"), &get("[Ljava/lang/Object;")),
+            Ok(())
+        );
+        assert_eq!(
+            mirrors.check_cast(&get("[I"), &get("java/lang/Cloneable")),
+            Ok(())
+        );
+        let error = mirrors
+            .check_cast(&get("[I"), &get("[Ljava/lang/Object;"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "java.lang.ClassCastException: class [I cannot be cast to class [Ljava.lang.Object;"
+        );
+        assert!(mirrors.check_cast(&get("[I"), &get("[J")).is_err());
+        assert!(mirrors
+            .check_cast(&get("java/lang/Object"), &get("[I"))
+            .is_err());
+
+        // a String[] seen as an Object[] only stores strings
+        let strings = get("[Ljava/lang/String;");
+        let strings = strings.array_klass().unwrap();
+        assert_eq!(
+            mirrors.check_store(strings, Some(&get("java/lang/String"))),
+            Ok(())
+        );
+        assert_eq!(mirrors.check_store(strings, None), Ok(()));
+        let error = mirrors
+            .check_store(strings, Some(&get("java/lang/Integer")))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "java.lang.ArrayStoreException: java.lang.Integer"
+        );
+        let objects = get("[Ljava/lang/Object;");
+        let objects = objects.array_klass().unwrap();
+        assert_eq!(mirrors.check_store(objects, Some(&get("[I"))), Ok(()));
+    }
+
+    #[test]
+    fn we_can_clone_the_arrays() {
+        let mirrors = mirrors();
+        let longs = mirrors.get("[J").unwrap();
+        let longs = longs.array_klass().unwrap();
+        let size = longs.object_size(3).unwrap();
+        let array = gc::allocate(size).unwrap();
+        array.store(0xFFu8);
+        array.plus(KLASS_POINTER_OFFSET as usize).store(0x1000usize);
+        array.plus(ARRAY_LENGTH_OFFSET as usize).store::<jint>(3);
+        for index in 0..3 {
+            let component = array.plus(longs.component_offset(index));
+            component.store(-(index as i64));
+        }
+        let clone = longs.clone_array(array).unwrap();
+        assert_ne!(clone, array);
+        assert_eq!(clone.load::<u8>(), 0);
+        let klass = clone.plus(KLASS_POINTER_OFFSET as usize);
+        assert_eq!(klass.load::<usize>(), 0x1000);
+        assert_eq!(clone.plus(ARRAY_LENGTH_OFFSET as usize).load::<jint>(), 3);
+        let components: Vec<i64> = (0..3)
+            .map(|index| clone.plus(longs.component_offset(index)).load())
+            .collect();
+        assert_eq!(components, [0, -1, -2]);
+        // larger than a block
+        let length = 5000;
+        let size = longs.object_size(length).unwrap();
+        let array = gc::allocate(size).unwrap();
+        array
+            .plus(ARRAY_LENGTH_OFFSET as usize)
+            .store::<jint>(length);
+        let last = longs.component_offset(length as usize - 1);
+        array.plus(last).store(7i64);
+        let clone = longs.clone_array(array).unwrap();
+        assert_eq!(
+            clone.plus(ARRAY_LENGTH_OFFSET as usize).load::<jint>(),
+            length
+        );
+        assert_eq!(clone.plus(last).load::<i64>(), 7);
+    }
+}
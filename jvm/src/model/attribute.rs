@@ -0,0 +1,206 @@
+use crate::utilities::definition::{u1, u2};
+
+/// An attribute of a class, field, method, `Code` or record component (JVMS §4.7).
+/// The constant pool indexes are checked by the parser.
+pub enum Attribute {
+    /// Index of the name of the source file.
+    SourceFile(u2),
+    SourceDebugExtension(Vec<u1>),
+    /// Index of the generic signature.
+    Signature(u2),
+    Deprecated,
+    Synthetic,
+    /// Indexes of the classes of the checked exceptions a method throws.
+    Exceptions(Vec<u2>),
+    InnerClasses(Vec<InnerClass>),
+    EnclosingMethod(EnclosingMethod),
+    /// Index of the class of the host of the nest.
+    NestHost(u2),
+    /// Indexes of the classes of the members of the nest.
+    NestMembers(Vec<u2>),
+    /// Indexes of the classes allowed to extend a sealed class.
+    PermittedSubclasses(Vec<u2>),
+    Record(Vec<RecordComponent>),
+    BootstrapMethods(Vec<BootstrapMethod>),
+    MethodParameters(Vec<MethodParameter>),
+    LocalVariableTable(Vec<LocalVariable>),
+    /// The local variables of generic types, with signatures instead of descriptors.
+    LocalVariableTypeTable(Vec<LocalVariable>),
+    RuntimeVisibleAnnotations(Vec<Annotation>),
+    RuntimeInvisibleAnnotations(Vec<Annotation>),
+    /// The annotations of each parameter.
+    RuntimeVisibleParameterAnnotations(Vec<Vec<Annotation>>),
+    RuntimeInvisibleParameterAnnotations(Vec<Vec<Annotation>>),
+    RuntimeVisibleTypeAnnotations(Vec<TypeAnnotation>),
+    RuntimeInvisibleTypeAnnotations(Vec<TypeAnnotation>),
+    /// Default value of an element of an annotation interface.
+    AnnotationDefault(ElementValue),
+    Module(Module),
+    /// Indexes of the packages of a module.
+    ModulePackages(Vec<u2>),
+    /// Index of the class of the main class of a module.
+    ModuleMainClass(u2),
+    /// An attribute that is not modeled, kept as its name and content for the tools.
+    Unknown {
+        name_index: u2,
+        info: Vec<u1>,
+    },
+}
+
+/// A class or interface that is not a package member (JVMS §4.7.6).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InnerClass {
+    pub inner_class_info_index: u2,
+    /// 0 for top level, local and anonymous classes.
+    pub outer_class_info_index: u2,
+    /// 0 for anonymous classes.
+    pub inner_name_index: u2,
+    pub inner_class_access_flags: u2,
+}
+
+/// The method or initializer declaring a local or anonymous class (JVMS §4.7.7).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnclosingMethod {
+    pub class_index: u2,
+    /// Index of the name and type of the method, 0 when the class is declared in an
+    /// initializer.
+    pub method_index: u2,
+}
+
+pub struct RecordComponent {
+    pub name_index: u2,
+    pub descriptor_index: u2,
+    pub attributes: Vec<Attribute>,
+}
+
+/// A bootstrap method of the dynamically-computed constants and call sites (JVMS §4.7.23).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootstrapMethod {
+    /// Index of the method handle of the bootstrap method.
+    pub method_ref: u2,
+    /// Indexes of the loadable constants of the static arguments.
+    pub arguments: Vec<u2>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodParameter {
+    /// 0 for a parameter without a name.
+    pub name_index: u2,
+    pub access_flags: u2,
+}
+
+/// A local variable in the code between `start_pc` included and `start_pc + length`
+/// excluded (JVMS §4.7.13).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalVariable {
+    pub start_pc: u2,
+    pub length: u2,
+    pub name_index: u2,
+    /// Index of the descriptor, or of the signature in a `LocalVariableTypeTable`.
+    pub descriptor_index: u2,
+    pub index: u2,
+}
+
+/// An annotation of the type at the field descriptor of `type_index` (JVMS §4.7.16).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub type_index: u2,
+    /// The index of the name and the value of the elements.
+    pub element_value_pairs: Vec<(u2, ElementValue)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementValue {
+    /// The tag, one of `BCDFIJSZs`, and the index of the constant.
+    Const(u1, u2),
+    Enum {
+        type_name_index: u2,
+        const_name_index: u2,
+    },
+    /// Index of the return descriptor of the class.
+    Class(u2),
+    Annotation(Annotation),
+    Array(Vec<ElementValue>),
+}
+
+/// An annotation of a type used in a declaration or in an expression (JVMS §4.7.20).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeAnnotation {
+    pub target_type: u1,
+    pub target_info: TargetInfo,
+    /// The kind and the type argument index of the steps to the annotated type.
+    pub type_path: Vec<(u1, u1)>,
+    pub annotation: Annotation,
+}
+
+/// Where the annotated type is, depending on the target type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetInfo {
+    TypeParameter(u1),
+    /// Index of the interface in the `interfaces`, 65535 for the super class.
+    SuperType(u2),
+    TypeParameterBound {
+        type_parameter_index: u1,
+        bound_index: u1,
+    },
+    /// A field, the return type or the receiver of a method.
+    Empty,
+    FormalParameter(u1),
+    /// Index of the exception in the `Exceptions` attribute.
+    Throws(u2),
+    /// The start pc, length and index of the ranges of the local variable.
+    LocalVariable(Vec<(u2, u2, u2)>),
+    /// Index of the handler in the exception table.
+    Catch(u2),
+    /// Offset of an `instanceof`, `new` or method reference expression.
+    Offset(u2),
+    TypeArgument {
+        offset: u2,
+        type_argument_index: u1,
+    },
+}
+
+/// The declaration of a module (JVMS §4.7.25).
+pub struct Module {
+    pub name_index: u2,
+    pub flags: u2,
+    /// 0 when the module has no version.
+    pub version_index: u2,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<ModulePackage>,
+    pub opens: Vec<ModulePackage>,
+    /// Indexes of the classes of the services used.
+    pub uses: Vec<u2>,
+    pub provides: Vec<ModuleProvides>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModuleRequires {
+    pub requires_index: u2,
+    pub flags: u2,
+    pub version_index: u2,
+}
+
+/// An `exports` or `opens` directive, to all the modules when `to` is empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModulePackage {
+    pub package_index: u2,
+    pub flags: u2,
+    pub to: Vec<u2>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleProvides {
+    /// Index of the class of the service.
+    pub provides_index: u2,
+    /// Indexes of the classes of the implementations.
+    pub with: Vec<u2>,
+}
+
+/// Index of the `Signature` among the attributes.
+pub(crate) fn signature_index(attributes: &[Attribute]) -> Option<u2> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Signature(index) => Some(*index),
+        _ => None,
+    })
+}
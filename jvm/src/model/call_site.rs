@@ -0,0 +1,366 @@
+//! The linkage of `invokedynamic` call sites (JVMS §5.4.3.6): the bootstrap method and
+//! its static arguments resolved from the constant pool, and the target each call site
+//! is bound to once linked. The bootstrap method itself is Java code, the upcall to
+//! `MethodHandleNatives.linkCallSite` that runs it is left to the interpreter.
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::utilities::{
+    definition::{jdouble, jfloat, jint, jlong, u1, u2},
+    exceptions::Exception,
+};
+
+use super::{constant_pool::ConstantPoolEntry, instance_klass::InstanceKlass, mirror::Mirrors};
+
+/// A `CONSTANT_MethodHandle` with the member it refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodHandleConstant {
+    /// The reference kind, e.g. 6 for `REF_invokeStatic` (JVMS §5.4.3.5).
+    pub kind: u1,
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// A static argument of a bootstrap method, from its loadable constant.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BootstrapArgument {
+    Integer(jint),
+    Float(jfloat),
+    Long(jlong),
+    Double(jdouble),
+    String(String),
+    /// Internal name of a class or descriptor of an array class.
+    Class(String),
+    /// The method descriptor of a `MethodType`.
+    MethodType(String),
+    MethodHandle(MethodHandleConstant),
+    /// Index of a dynamically-computed constant, resolved with its own bootstrap method
+    /// when the argument is used.
+    Dynamic(u2),
+}
+
+/// What `MethodHandleNatives.linkCallSite` and `linkDynamicConstant` are called with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BootstrapSpecifier {
+    pub bootstrap_method: MethodHandleConstant,
+    pub name: String,
+    /// The method descriptor of a call site, the field descriptor of a constant.
+    pub descriptor: String,
+    pub arguments: Vec<BootstrapArgument>,
+}
+
+impl BootstrapSpecifier {
+    /// The specifier of the `CONSTANT_InvokeDynamic` or `CONSTANT_Dynamic` at the index,
+    /// checked by the parser.
+    pub fn resolve(klass: &InstanceKlass, index: u2) -> Result<Self, Exception> {
+        let constants = klass.constants();
+        let bad_constant = || {
+            Exception::ClassFormatError(format!(
+                "Bad dynamic constant at index {} in class file {}",
+                index,
+                klass.name()
+            ))
+        };
+        let (bootstrap_index, name_and_type) = match constants.get(index) {
+            Some(ConstantPoolEntry::InvokeDynamic(bootstrap_index, name_and_type))
+            | Some(ConstantPoolEntry::Dynamic(bootstrap_index, name_and_type)) => {
+                (*bootstrap_index, *name_and_type)
+            }
+            _ => return Err(bad_constant()),
+        };
+        let bootstrap = klass
+            .bootstrap_methods()
+            .get(bootstrap_index as usize)
+            .ok_or_else(bad_constant)?;
+        let (name, descriptor) = constants
+            .name_and_type(name_and_type)
+            .ok_or_else(bad_constant)?;
+        let arguments = bootstrap
+            .arguments
+            .iter()
+            .map(|&argument| resolve_argument(klass, argument).ok_or_else(bad_constant))
+            .collect::<Result<_, _>>()?;
+        Ok(BootstrapSpecifier {
+            bootstrap_method: method_handle(klass, bootstrap.method_ref)
+                .ok_or_else(bad_constant)?,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            arguments,
+        })
+    }
+}
+
+fn method_handle(klass: &InstanceKlass, index: u2) -> Option<MethodHandleConstant> {
+    let constants = klass.constants();
+    let ConstantPoolEntry::MethodHandle(kind, reference) = constants.get(index)? else {
+        return None;
+    };
+    let (class, name, descriptor) = constants.member_ref(*reference)?;
+    Some(MethodHandleConstant {
+        kind: *kind,
+        class: class.to_string(),
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+    })
+}
+
+fn resolve_argument(klass: &InstanceKlass, index: u2) -> Option<BootstrapArgument> {
+    let constants = klass.constants();
+    Some(match constants.get(index)? {
+        ConstantPoolEntry::Integer(value) => BootstrapArgument::Integer(*value),
+        ConstantPoolEntry::Float(value) => BootstrapArgument::Float(*value),
+        ConstantPoolEntry::Long(value) => BootstrapArgument::Long(*value),
+        ConstantPoolEntry::Double(value) => BootstrapArgument::Double(*value),
+        ConstantPoolEntry::String(string) => {
+            BootstrapArgument::String(constants.utf8(*string)?.to_string())
+        }
+        ConstantPoolEntry::Class(_) => {
+            BootstrapArgument::Class(constants.class_name(index)?.to_string())
+        }
+        ConstantPoolEntry::MethodType(descriptor) => {
+            BootstrapArgument::MethodType(constants.utf8(*descriptor)?.to_string())
+        }
+        ConstantPoolEntry::MethodHandle(_, _) => {
+            BootstrapArgument::MethodHandle(method_handle(klass, index)?)
+        }
+        ConstantPoolEntry::Dynamic(_, _) => BootstrapArgument::Dynamic(index),
+        _ => return None,
+    })
+}
+
+/// The targets of the `invokedynamic` instructions of a class, by method index and bci,
+/// each instruction is a call site of its own. An address of a `CallSite` target once
+/// linked, or the error of the linkage that every later execution throws again.
+#[derive(Default)]
+pub struct CallSites {
+    targets: Mutex<HashMap<(usize, usize), Result<usize, Exception>>>,
+}
+
+impl CallSites {
+    /// The linkage of the call site, `None` before it is linked.
+    pub fn target(&self, method: usize, bci: usize) -> Option<Result<usize, Exception>> {
+        self.targets.lock().unwrap().get(&(method, bci)).cloned()
+    }
+
+    /// The target of the call site, linked the first time by `link`, which makes the
+    /// upcall to `MethodHandleNatives.linkCallSite`. Threads can race to link a call
+    /// site, the first linkage to complete is kept (JVMS §6.5 `invokedynamic`).
+    pub fn link(
+        &self,
+        mirrors: &Mirrors,
+        method: usize,
+        bci: usize,
+        link: impl FnOnce() -> Result<usize, Exception>,
+    ) -> Result<usize, Exception> {
+        if let Some(target) = self.target(method, bci) {
+            return target;
+        }
+        // the bootstrap method runs Java code, without holding the lock
+        let target = link().map_err(|exception| {
+            if is_error(mirrors, exception.class_name()) {
+                exception
+            } else {
+                Exception::BootstrapMethodError(format!(
+                    "CallSite bootstrap method initialization exception: {}",
+                    exception
+                ))
+            }
+        });
+        self.targets
+            .lock()
+            .unwrap()
+            .entry((method, bci))
+            .or_insert(target)
+            .clone()
+    }
+}
+
+/// Whether the class of a thrown exception, loaded by then, is `java/lang/Error` or one
+/// of its subclasses.
+fn is_error(mirrors: &Mirrors, class_name: &str) -> bool {
+    match (mirrors.get("java/lang/Error"), mirrors.get(class_name)) {
+        (Some(error), Some(class)) => mirrors.is_assignable(&error, &class),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Arc};
+
+    use crate::{
+        classfile::test_class::TestClass,
+        model::mirror::Mirrors,
+        utilities::{
+            definition::{u1, u2},
+            exceptions::Exception,
+        },
+    };
+
+    use super::{is_error, BootstrapArgument, BootstrapSpecifier, CallSites, MethodHandleConstant};
+
+    fn method_handle(class: &mut TestClass, owner: &str, name: &str, descriptor: &str) -> u2 {
+        let reference = class.method_ref(owner, name, descriptor);
+        let mut bytes = vec![15, 6];
+        bytes.extend_from_slice(&reference.to_be_bytes());
+        class.constant(bytes)
+    }
+
+    fn tag_and_indexes(class: &mut TestClass, tag: u1, indexes: &[u2]) -> u2 {
+        let mut bytes = vec![tag];
+        for index in indexes {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+        class.constant(bytes)
+    }
+
+    #[test]
+    fn we_can_resolve_the_bootstrap_specifiers() {
+        let mut class = TestClass::new("T", "java/lang/Object");
+        let metafactory = method_handle(
+            &mut class,
+            "java/lang/invoke/LambdaMetafactory",
+            "metafactory",
+            "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;\
+             Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;\
+             Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)\
+             Ljava/lang/invoke/CallSite;",
+        );
+        let descriptor = class.utf8("()V");
+        let method_type = tag_and_indexes(&mut class, 16, &[descriptor]);
+        let implementation = method_handle(&mut class, "T", "lambda$main$0", "()V");
+        let concat = method_handle(
+            &mut class,
+            "java/lang/invoke/StringConcatFactory",
+            "makeConcatWithConstants",
+            "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;\
+             Ljava/lang/invoke/MethodType;Ljava/lang/String;[Ljava/lang/Object;)\
+             Ljava/lang/invoke/CallSite;",
+        );
+        let recipe = class.string("\u{1}!");
+        let count = class.integer(2);
+        let name_and_type = class.name_and_type("run", "()Ljava/lang/Runnable;");
+        let lambda = tag_and_indexes(&mut class, 18, &[0, name_and_type]);
+        let name_and_type = class.name_and_type("makeConcatWithConstants", "(I)Ljava/lang/String;");
+        let string_concat = tag_and_indexes(&mut class, 18, &[1, name_and_type]);
+        let name_and_type = class.name_and_type("_", "I");
+        let dynamic = tag_and_indexes(&mut class, 17, &[1, name_and_type]);
+        let mut content = Vec::new();
+        for value in [2, metafactory, 3, method_type, implementation, method_type] {
+            content.extend_from_slice(&u2::to_be_bytes(value));
+        }
+        for value in [concat, 3, recipe, count, dynamic] {
+            content.extend_from_slice(&u2::to_be_bytes(value));
+        }
+        let bootstrap_methods = class.attribute("BootstrapMethods", &content);
+        class.class_attribute(bootstrap_methods);
+        let klass = class.parse();
+
+        let specifier = BootstrapSpecifier::resolve(&klass, lambda).unwrap();
+        assert_eq!(
+            specifier.bootstrap_method.class,
+            "java/lang/invoke/LambdaMetafactory"
+        );
+        assert_eq!(specifier.bootstrap_method.kind, 6);
+        assert_eq!(specifier.name, "run");
+        assert_eq!(specifier.descriptor, "()Ljava/lang/Runnable;");
+        assert_eq!(
+            specifier.arguments,
+            [
+                BootstrapArgument::MethodType("()V".to_string()),
+                BootstrapArgument::MethodHandle(MethodHandleConstant {
+                    kind: 6,
+                    class: "T".to_string(),
+                    name: "lambda$main$0".to_string(),
+                    descriptor: "()V".to_string(),
+                }),
+                BootstrapArgument::MethodType("()V".to_string()),
+            ]
+        );
+        let specifier = BootstrapSpecifier::resolve(&klass, string_concat).unwrap();
+        assert_eq!(specifier.bootstrap_method.name, "makeConcatWithConstants");
+        assert_eq!(
+            specifier.arguments,
+            [
+                BootstrapArgument::String("\u{1}!".to_string()),
+                BootstrapArgument::Integer(2),
+                BootstrapArgument::Dynamic(dynamic),
+            ]
+        );
+        assert!(BootstrapSpecifier::resolve(&klass, recipe).is_err());
+    }
+
+    fn mirrors() -> Mirrors {
+        let mirrors = Mirrors::new();
+        for (name, super_name) in [
+            ("java/lang/Object", ""),
+            ("java/lang/Throwable", "java/lang/Object"),
+            ("java/lang/Error", "java/lang/Throwable"),
+            ("java/lang/LinkageError", "java/lang/Error"),
+            ("java/lang/VerifyError", "java/lang/LinkageError"),
+            ("java/lang/ThreadDeath", "java/lang/Error"),
+            ("java/lang/Exception", "java/lang/Throwable"),
+            ("java/lang/RuntimeException", "java/lang/Exception"),
+            (
+                "java/lang/IllegalArgumentException",
+                "java/lang/RuntimeException",
+            ),
+            ("p/ParseError", "java/lang/Exception"),
+        ] {
+            mirrors.define(Arc::new(TestClass::new(name, super_name).parse()));
+        }
+        mirrors
+    }
+
+    #[test]
+    fn we_can_link_each_call_site_once() {
+        let mirrors = mirrors();
+        let call_sites = CallSites::default();
+        let calls = Cell::new(0);
+        let link = |target| {
+            calls.set(calls.get() + 1);
+            target
+        };
+        assert_eq!(call_sites.target(1, 4), None);
+        assert_eq!(
+            call_sites.link(&mirrors, 1, 4, || link(Ok(0x1000))),
+            Ok(0x1000)
+        );
+        assert_eq!(
+            call_sites.link(&mirrors, 1, 4, || link(Ok(0x2000))),
+            Ok(0x1000)
+        );
+        assert_eq!(
+            call_sites.link(&mirrors, 1, 9, || link(Ok(0x2000))),
+            Ok(0x2000)
+        );
+        assert_eq!(calls.get(), 2);
+
+        // the error of the linkage is thrown again, wrapped when it is not an error
+        let exception = Exception::IllegalArgumentException("bad recipe".to_string());
+        let error = call_sites
+            .link(&mirrors, 2, 0, || link(Err(exception)))
+            .unwrap_err();
+        assert_eq!(error.class_name(), "java/lang/BootstrapMethodError");
+        assert_eq!(
+            call_sites.link(&mirrors, 2, 0, || link(Ok(0x3000))),
+            Err(error)
+        );
+        let error = Exception::VerifyError("bad type".to_string());
+        assert_eq!(
+            call_sites.link(&mirrors, 3, 0, || link(Err(error.clone()))),
+            Err(error)
+        );
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn we_can_tell_the_errors_by_their_super_class() {
+        let mirrors = mirrors();
+        assert!(is_error(&mirrors, "java/lang/ThreadDeath"));
+        assert!(is_error(&mirrors, "java/lang/VerifyError"));
+        assert!(!is_error(&mirrors, "p/ParseError"));
+        assert!(!is_error(&mirrors, "java/lang/IllegalArgumentException"));
+        assert!(!is_error(&mirrors, "p/UnknownError"));
+    }
+}
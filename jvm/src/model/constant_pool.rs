@@ -1,4 +1,4 @@
-use crate::utilities::definition::{jdouble, jfloat, jint, jlong, u2};
+use crate::utilities::definition::{jdouble, jfloat, jint, jlong, u1, u2};
 
 pub enum ConstantPoolEntry {
     Utf8(String),
@@ -10,10 +10,129 @@ pub enum ConstantPoolEntry {
     String(u2),
     FieldRef(u2, u2),
     MethodRef(u2, u2),
+    InterfaceMethodRef(u2, u2),
     NameAndType(u2, u2),
+    /// Reference kind and index of the field or method reference.
+    MethodHandle(u1, u2),
+    MethodType(u2),
+    /// Index of the bootstrap method and of the name and type.
+    Dynamic(u2, u2),
+    InvokeDynamic(u2, u2),
+    Module(u2),
+    Package(u2),
+    /// Index 0 and the index after a long or a double.
     Invalid,
 }
 
+impl ConstantPoolEntry {
+    pub const JVM_CONSTANT_UTF8: u1 = 1;
+    pub const JVM_CONSTANT_INTEGER: u1 = 3;
+    pub const JVM_CONSTANT_FLOAT: u1 = 4;
+    pub const JVM_CONSTANT_LONG: u1 = 5;
+    pub const JVM_CONSTANT_DOUBLE: u1 = 6;
+    pub const JVM_CONSTANT_CLASS: u1 = 7;
+    pub const JVM_CONSTANT_STRING: u1 = 8;
+    pub const JVM_CONSTANT_FIELDREF: u1 = 9;
+    pub const JVM_CONSTANT_METHODREF: u1 = 10;
+    pub const JVM_CONSTANT_INTERFACE_METHODREF: u1 = 11;
+    pub const JVM_CONSTANT_NAME_AND_TYPE: u1 = 12;
+    pub const JVM_CONSTANT_METHOD_HANDLE: u1 = 15;
+    pub const JVM_CONSTANT_METHOD_TYPE: u1 = 16;
+    pub const JVM_CONSTANT_DYNAMIC: u1 = 17;
+    pub const JVM_CONSTANT_INVOKE_DYNAMIC: u1 = 18;
+    pub const JVM_CONSTANT_MODULE: u1 = 19;
+    pub const JVM_CONSTANT_PACKAGE: u1 = 20;
+
+    /// The tag of the entry in a class file, 0 for an invalid entry.
+    pub fn tag(&self) -> u1 {
+        match self {
+            ConstantPoolEntry::Utf8(_) => ConstantPoolEntry::JVM_CONSTANT_UTF8,
+            ConstantPoolEntry::Integer(_) => ConstantPoolEntry::JVM_CONSTANT_INTEGER,
+            ConstantPoolEntry::Float(_) => ConstantPoolEntry::JVM_CONSTANT_FLOAT,
+            ConstantPoolEntry::Double(_) => ConstantPoolEntry::JVM_CONSTANT_DOUBLE,
+            ConstantPoolEntry::Long(_) => ConstantPoolEntry::JVM_CONSTANT_LONG,
+            ConstantPoolEntry::Class(_) => ConstantPoolEntry::JVM_CONSTANT_CLASS,
+            ConstantPoolEntry::String(_) => ConstantPoolEntry::JVM_CONSTANT_STRING,
+            ConstantPoolEntry::FieldRef(_, _) => ConstantPoolEntry::JVM_CONSTANT_FIELDREF,
+            ConstantPoolEntry::MethodRef(_, _) => ConstantPoolEntry::JVM_CONSTANT_METHODREF,
+            ConstantPoolEntry::InterfaceMethodRef(_, _) => {
+                ConstantPoolEntry::JVM_CONSTANT_INTERFACE_METHODREF
+            }
+            ConstantPoolEntry::NameAndType(_, _) => ConstantPoolEntry::JVM_CONSTANT_NAME_AND_TYPE,
+            ConstantPoolEntry::MethodHandle(_, _) => ConstantPoolEntry::JVM_CONSTANT_METHOD_HANDLE,
+            ConstantPoolEntry::MethodType(_) => ConstantPoolEntry::JVM_CONSTANT_METHOD_TYPE,
+            ConstantPoolEntry::Dynamic(_, _) => ConstantPoolEntry::JVM_CONSTANT_DYNAMIC,
+            ConstantPoolEntry::InvokeDynamic(_, _) => {
+                ConstantPoolEntry::JVM_CONSTANT_INVOKE_DYNAMIC
+            }
+            ConstantPoolEntry::Module(_) => ConstantPoolEntry::JVM_CONSTANT_MODULE,
+            ConstantPoolEntry::Package(_) => ConstantPoolEntry::JVM_CONSTANT_PACKAGE,
+            ConstantPoolEntry::Invalid => 0,
+        }
+    }
+}
+
 pub struct ConstantPool {
     entries: Vec<ConstantPoolEntry>,
 }
+
+impl ConstantPool {
+    /// A constant pool of the entries, the first is the invalid entry at index 0.
+    pub fn new(entries: Vec<ConstantPoolEntry>) -> Self {
+        ConstantPool { entries }
+    }
+
+    /// Number of entries including the entry at index 0, the `constant_pool_count`.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: u2) -> Option<&ConstantPoolEntry> {
+        self.entries.get(index as usize)
+    }
+
+    pub fn entries(&self) -> &[ConstantPoolEntry] {
+        &self.entries
+    }
+
+    pub fn utf8(&self, index: u2) -> Option<&str> {
+        match self.get(index)? {
+            ConstantPoolEntry::Utf8(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Internal name of the class, or descriptor of the array type.
+    pub fn class_name(&self, index: u2) -> Option<&str> {
+        match self.get(index)? {
+            ConstantPoolEntry::Class(name) => self.utf8(*name),
+            _ => None,
+        }
+    }
+
+    pub fn name_and_type(&self, index: u2) -> Option<(&str, &str)> {
+        match self.get(index)? {
+            ConstantPoolEntry::NameAndType(name, descriptor) => {
+                Some((self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Class, name and descriptor of a field, method or interface method reference.
+    pub fn member_ref(&self, index: u2) -> Option<(&str, &str, &str)> {
+        match self.get(index)? {
+            ConstantPoolEntry::FieldRef(class, name_and_type)
+            | ConstantPoolEntry::MethodRef(class, name_and_type)
+            | ConstantPoolEntry::InterfaceMethodRef(class, name_and_type) => {
+                let (name, descriptor) = self.name_and_type(*name_and_type)?;
+                Some((self.class_name(*class)?, name, descriptor))
+            }
+            _ => None,
+        }
+    }
+}
@@ -1,8 +1,56 @@
-use crate::utilities::definition::{jint, u2};
+use crate::utilities::definition::u2;
+
+use super::attribute::{self, Attribute};
 
 pub struct Field {
     flags: u2,
     name_index: u2,
     descriptor_index: u2,
+    /// Index of the `ConstantValue`, 0 when the field has none.
     constant_value_index: u2,
+    /// The attributes other than `ConstantValue`.
+    attributes: Vec<Attribute>,
+}
+
+impl Field {
+    pub fn new(
+        flags: u2,
+        name_index: u2,
+        descriptor_index: u2,
+        constant_value_index: u2,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        Field {
+            flags,
+            name_index,
+            descriptor_index,
+            constant_value_index,
+            attributes,
+        }
+    }
+
+    pub fn flags(&self) -> u2 {
+        self.flags
+    }
+
+    pub fn name_index(&self) -> u2 {
+        self.name_index
+    }
+
+    pub fn descriptor_index(&self) -> u2 {
+        self.descriptor_index
+    }
+
+    pub fn constant_value_index(&self) -> u2 {
+        self.constant_value_index
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// Index of the generic signature of the type.
+    pub fn signature_index(&self) -> Option<u2> {
+        attribute::signature_index(&self.attributes)
+    }
 }
@@ -0,0 +1,318 @@
+//! Offsets of the fields in the instances of a class and in the static storage of its
+//! mirror.
+//!
+//! The instance fields of a class follow those of its super class. They are placed by
+//! decreasing size, references first so the collector finds them together, and a
+//! smaller field fills the padding left by the alignment of a larger one.
+use std::sync::Mutex;
+
+use crate::utilities::{
+    access_flags::JVM_ACC_STATIC,
+    definition::{jdouble, jfloat, jint, jlong, u1, u4},
+};
+
+use super::{constant_pool::ConstantPoolEntry, field::Field};
+
+/// Size of the collector header, its flags and forwarding pointer, and of the klass
+/// pointer at the start of each object.
+pub const OBJECT_HEADER_SIZE: u4 = 24;
+/// Offset of the klass pointer, the last word of the object header.
+pub const KLASS_POINTER_OFFSET: u4 = OBJECT_HEADER_SIZE - REFERENCE_SIZE;
+/// Instances are allocated at multiples of the alignment.
+pub const OBJECT_ALIGNMENT: u4 = 8;
+/// Size of a reference, an address of the heap.
+pub const REFERENCE_SIZE: u4 = std::mem::size_of::<usize>() as u4;
+
+/// The type of a field, from the first character of its descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Float,
+    Long,
+    Double,
+    /// A class, interface or array.
+    Reference,
+}
+
+impl FieldType {
+    pub fn from_descriptor(descriptor: &str) -> Option<FieldType> {
+        Some(match descriptor.as_bytes().first()? {
+            b'Z' => FieldType::Boolean,
+            b'B' => FieldType::Byte,
+            b'C' => FieldType::Char,
+            b'S' => FieldType::Short,
+            b'I' => FieldType::Int,
+            b'F' => FieldType::Float,
+            b'J' => FieldType::Long,
+            b'D' => FieldType::Double,
+            b'L' | b'[' => FieldType::Reference,
+            _ => return None,
+        })
+    }
+
+    /// Size in bytes, also the alignment of the field.
+    pub fn size(&self) -> u4 {
+        match self {
+            FieldType::Boolean | FieldType::Byte => 1,
+            FieldType::Char | FieldType::Short => 2,
+            FieldType::Int | FieldType::Float => 4,
+            FieldType::Long | FieldType::Double => 8,
+            FieldType::Reference => REFERENCE_SIZE,
+        }
+    }
+
+    /// The placement order, references first then by decreasing size.
+    fn group(&self) -> u4 {
+        match self {
+            FieldType::Reference => 0,
+            _ => 1 + 8 - self.size(),
+        }
+    }
+}
+
+fn align_up(offset: u4, alignment: u4) -> u4 {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+/// Places fields after a start offset, keeping track of the padding to fill.
+struct Allocator {
+    end: u4,
+    /// Free ranges of start and end offsets left by the alignment.
+    holes: Vec<(u4, u4)>,
+}
+
+impl Allocator {
+    fn new(start: u4) -> Self {
+        Allocator {
+            end: start,
+            holes: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, size: u4) -> u4 {
+        let hole = self
+            .holes
+            .iter()
+            .position(|&(start, end)| align_up(start, size) + size <= end);
+        if let Some(i) = hole {
+            let (start, end) = self.holes.remove(i);
+            let offset = align_up(start, size);
+            // keep what is left before and after the field for smaller fields
+            if offset > start {
+                self.holes.push((start, offset));
+            }
+            if offset + size < end {
+                self.holes.push((offset + size, end));
+            }
+            return offset;
+        }
+        let offset = align_up(self.end, size);
+        if offset > self.end {
+            self.holes.push((self.end, offset));
+        }
+        self.end = offset + size;
+        offset
+    }
+}
+
+/// The offsets of the fields of a class.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// Offset of each field, in the order of the fields of the class. Instance fields
+    /// are at their offset in the object, static fields in the static storage.
+    offsets: Vec<u4>,
+    /// End of the instance fields, where the fields of the subclasses start.
+    instance_fields_end: u4,
+    /// Offsets of the reference instance fields of the class and its super classes.
+    reference_offsets: Vec<u4>,
+    static_size: u4,
+    static_reference_offsets: Vec<u4>,
+}
+
+impl FieldLayout {
+    /// The layout of the fields of their types after the instance fields of the super
+    /// class, `None` for `java/lang/Object`.
+    pub fn new(fields: &[(&Field, FieldType)], super_layout: Option<&FieldLayout>) -> Self {
+        let start = super_layout.map_or(OBJECT_HEADER_SIZE, |layout| layout.instance_fields_end);
+        let mut instance = Allocator::new(start);
+        let mut statics = Allocator::new(0);
+        let mut offsets = vec![0; fields.len()];
+        let mut reference_offsets = super_layout
+            .map(|layout| layout.reference_offsets.clone())
+            .unwrap_or_default();
+        let mut static_reference_offsets = Vec::new();
+        let mut order: Vec<usize> = (0..fields.len()).collect();
+        order.sort_by_key(|&i| fields[i].1.group());
+        for i in order {
+            let (field, field_type) = fields[i];
+            let is_static = field.flags() & JVM_ACC_STATIC != 0;
+            let (allocator, references) = if is_static {
+                (&mut statics, &mut static_reference_offsets)
+            } else {
+                (&mut instance, &mut reference_offsets)
+            };
+            offsets[i] = allocator.allocate(field_type.size());
+            if field_type == FieldType::Reference {
+                references.push(offsets[i]);
+            }
+        }
+        FieldLayout {
+            offsets,
+            instance_fields_end: instance.end,
+            reference_offsets,
+            static_size: align_up(statics.end, OBJECT_ALIGNMENT),
+            static_reference_offsets,
+        }
+    }
+
+    /// Offset of the field at the index in the fields of the class, used by
+    /// `getfield`, `putfield`, `getstatic` and `putstatic`.
+    pub fn offset(&self, index: usize) -> u4 {
+        self.offsets[index]
+    }
+
+    pub fn instance_fields_end(&self) -> u4 {
+        self.instance_fields_end
+    }
+
+    /// Size of an instance, header included.
+    pub fn instance_size(&self) -> u4 {
+        align_up(self.instance_fields_end, OBJECT_ALIGNMENT)
+    }
+
+    /// Offsets of the references of an instance for the collector to trace.
+    pub fn reference_offsets(&self) -> &[u4] {
+        &self.reference_offsets
+    }
+
+    pub fn static_size(&self) -> u4 {
+        self.static_size
+    }
+
+    pub fn static_reference_offsets(&self) -> &[u4] {
+        &self.static_reference_offsets
+    }
+}
+
+/// The static fields of a class, held by its mirror. Their references are roots of
+/// the collector, for as long as the class is loaded.
+pub struct StaticFields {
+    values: Mutex<Vec<u1>>,
+    reference_offsets: Vec<u4>,
+}
+
+impl StaticFields {
+    /// Zeroed fields of the layout.
+    pub fn new(layout: &FieldLayout) -> Self {
+        StaticFields {
+            values: Mutex::new(vec![0; layout.static_size as usize]),
+            reference_offsets: layout.static_reference_offsets.clone(),
+        }
+    }
+
+    /// The value of the field at the offset, as a value of the operand stack: ints for
+    /// the types smaller than `int`, the bits of floats and doubles, the address of
+    /// references.
+    pub fn get(&self, offset: u4, field_type: FieldType) -> jlong {
+        let values = self.values.lock().unwrap();
+        let offset = offset as usize;
+        let bytes = &values[offset..offset + field_type.size() as usize];
+        match field_type {
+            FieldType::Boolean => bytes[0] as jlong,
+            FieldType::Byte => bytes[0] as i8 as jlong,
+            FieldType::Char => u16::from_ne_bytes([bytes[0], bytes[1]]) as jlong,
+            FieldType::Short => i16::from_ne_bytes([bytes[0], bytes[1]]) as jlong,
+            FieldType::Int => jint::from_ne_bytes(bytes.try_into().unwrap()) as jlong,
+            FieldType::Float => u32::from_ne_bytes(bytes.try_into().unwrap()) as jlong,
+            FieldType::Long | FieldType::Double => jlong::from_ne_bytes(bytes.try_into().unwrap()),
+            FieldType::Reference => usize::from_ne_bytes(bytes.try_into().unwrap()) as jlong,
+        }
+    }
+
+    /// Store the value, truncated to the type of the field, like `putstatic`.
+    pub fn put(&self, offset: u4, field_type: FieldType, value: jlong) {
+        let mut values = self.values.lock().unwrap();
+        let offset = offset as usize;
+        let size = field_type.size() as usize;
+        let bytes = match field_type {
+            // only the lowest bit of a boolean is stored
+            FieldType::Boolean => vec![value as u1 & 1],
+            FieldType::Reference => (value as usize).to_ne_bytes().to_vec(),
+            FieldType::Byte => vec![value as u1],
+            FieldType::Char | FieldType::Short => (value as i16).to_ne_bytes().to_vec(),
+            FieldType::Int | FieldType::Float => (value as jint).to_ne_bytes().to_vec(),
+            FieldType::Long | FieldType::Double => value.to_ne_bytes().to_vec(),
+        };
+        values[offset..offset + size].copy_from_slice(&bytes);
+    }
+
+    /// Initialize the fields of a `ConstantValue`, before the class is initialized.
+    pub fn put_constant(&self, offset: u4, field_type: FieldType, constant: &ConstantPoolEntry) {
+        let value = match constant {
+            ConstantPoolEntry::Integer(value) => *value as jlong,
+            ConstantPoolEntry::Long(value) => *value,
+            ConstantPoolEntry::Float(value) => jfloat::to_bits(*value) as jlong,
+            ConstantPoolEntry::Double(value) => jdouble::to_bits(*value) as jlong,
+            // strings are interned by the class loader
+            _ => return,
+        };
+        self.put(offset, field_type, value);
+    }
+
+    /// Visit the address of each non null reference, the roots held by the class.
+    pub fn for_each_reference(&self, visitor: &mut dyn FnMut(usize)) {
+        for &offset in &self.reference_offsets {
+            let address = self.get(offset, FieldType::Reference) as usize;
+            if address != 0 {
+                visitor(address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::classfile::test_class::TestClass;
+
+    #[test]
+    fn we_can_lay_out_the_fields_after_the_super_class() {
+        let mut class = TestClass::new("A", "java/lang/Object");
+        class.field(0, "a", "I", &[]);
+        class.field(0, "o", "Ljava/lang/Object;", &[]);
+        class.field(0x0008, "s", "J", &[]);
+        class.field(0x0008, "t", "[I", &[]);
+        class.field(0, "b", "B", &[]);
+        let value = class.integer(7);
+        let constant_value = class.attribute("ConstantValue", &value.to_be_bytes());
+        class.field(0x0018, "K", "I", &[constant_value]);
+        let a = class.parse();
+        let layout = a.link_fields(None).unwrap();
+        let offsets: Vec<_> = (0..6).map(|i| layout.offset(i)).collect();
+        assert_eq!(offsets, [32, 24, 8, 0, 36, 16]);
+        assert_eq!(layout.instance_fields_end(), 37);
+        assert_eq!(layout.instance_size(), 40);
+        assert_eq!(layout.reference_offsets(), [24]);
+        assert_eq!(layout.static_size(), 24);
+        assert_eq!(layout.static_reference_offsets(), [0]);
+
+        // the padding after the fields of the super class is filled
+        let mut class = TestClass::new("B", "A");
+        class.field(0, "l", "J", &[]);
+        class.field(0, "c", "S", &[]);
+        class.field(0, "z", "Z", &[]);
+        class.field(0, "p", "LA;", &[]);
+        let b = class.parse();
+        let layout = b.link_fields(Some(&a)).unwrap();
+        let offsets: Vec<_> = (0..4).map(|i| layout.offset(i)).collect();
+        assert_eq!(offsets, [48, 38, 37, 40]);
+        assert_eq!(layout.instance_size(), 56);
+        assert_eq!(layout.reference_offsets(), [24, 40]);
+        assert_eq!(layout.static_size(), 0);
+        assert_eq!(b.field_offset("p", "LA;"), Some(40));
+        assert_eq!(b.field_offset("a", "I"), None);
+    }
+}
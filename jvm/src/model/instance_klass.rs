@@ -1,10 +1,306 @@
-use crate::utilities::definition::u2;
+use once_cell::sync::OnceCell;
 
-use super::{constant_pool::ConstantPool, field::Field, method::Method};
+use crate::utilities::{
+    definition::{u2, u4},
+    exceptions::Exception,
+};
+
+use super::{
+    attribute::{
+        self, Attribute, BootstrapMethod, EnclosingMethod, InnerClass, Module, RecordComponent,
+    },
+    call_site::CallSites,
+    constant_pool::ConstantPool,
+    field::Field,
+    field_layout::{FieldLayout, FieldType},
+    method::Method,
+};
 
 pub struct InstanceKlass {
     constants: ConstantPool,
+    minor_version: u2,
+    major_version: u2,
+    access_flags: u2,
     this_class_index: u2,
+    /// 0 for `java/lang/Object`.
+    super_class_index: u2,
+    interfaces: Vec<u2>,
     methods: Vec<Method>,
     fields: Vec<Field>,
+    attributes: Vec<Attribute>,
+    /// Set when the class is linked.
+    field_layout: OnceCell<FieldLayout>,
+    call_sites: CallSites,
+}
+
+impl InstanceKlass {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        constants: ConstantPool,
+        minor_version: u2,
+        major_version: u2,
+        access_flags: u2,
+        this_class_index: u2,
+        super_class_index: u2,
+        interfaces: Vec<u2>,
+        fields: Vec<Field>,
+        methods: Vec<Method>,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        InstanceKlass {
+            constants,
+            minor_version,
+            major_version,
+            access_flags,
+            this_class_index,
+            super_class_index,
+            interfaces,
+            methods,
+            fields,
+            attributes,
+            field_layout: OnceCell::new(),
+            call_sites: CallSites::default(),
+        }
+    }
+
+    pub fn constants(&self) -> &ConstantPool {
+        &self.constants
+    }
+
+    pub fn minor_version(&self) -> u2 {
+        self.minor_version
+    }
+
+    pub fn major_version(&self) -> u2 {
+        self.major_version
+    }
+
+    pub fn access_flags(&self) -> u2 {
+        self.access_flags
+    }
+
+    pub fn this_class_index(&self) -> u2 {
+        self.this_class_index
+    }
+
+    pub fn super_class_index(&self) -> u2 {
+        self.super_class_index
+    }
+
+    /// Constant pool indexes of the direct super interfaces.
+    pub fn interfaces(&self) -> &[u2] {
+        &self.interfaces
+    }
+
+    /// Internal name of the class, e.g. `java/lang/Object`.
+    pub fn name(&self) -> &str {
+        self.constants
+            .class_name(self.this_class_index)
+            .expect("this class is checked by the parser.")
+    }
+
+    /// Internal name of the super class, `None` for `java/lang/Object`.
+    pub fn super_name(&self) -> Option<&str> {
+        self.constants.class_name(self.super_class_index)
+    }
+
+    /// Internal names of the direct super interfaces.
+    pub fn interface_names(&self) -> Vec<&str> {
+        self.interfaces
+            .iter()
+            .filter_map(|&index| self.constants.class_name(index))
+            .collect()
+    }
+
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// Name of the source file the class was compiled from.
+    pub fn source_file(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::SourceFile(index) => self.constants.utf8(*index),
+                _ => None,
+            })
+    }
+
+    /// Index of the generic signature of the type parameters and super types.
+    pub fn signature_index(&self) -> Option<u2> {
+        attribute::signature_index(&self.attributes)
+    }
+
+    pub fn inner_classes(&self) -> &[InnerClass] {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::InnerClasses(classes) => Some(&classes[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The method declaring the class when it is local or anonymous.
+    pub fn enclosing_method(&self) -> Option<EnclosingMethod> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::EnclosingMethod(method) => Some(*method),
+                _ => None,
+            })
+    }
+
+    /// Index of the class of the host of the nest of the class, `None` when it is
+    /// the host.
+    pub fn nest_host_index(&self) -> Option<u2> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::NestHost(index) => Some(*index),
+                _ => None,
+            })
+    }
+
+    /// Indexes of the classes of the members of the nest of a host.
+    pub fn nest_members(&self) -> &[u2] {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::NestMembers(members) => Some(&members[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Indexes of the permitted subclasses, `None` when the class is not sealed.
+    pub fn permitted_subclasses(&self) -> Option<&[u2]> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::PermittedSubclasses(classes) => Some(&classes[..]),
+                _ => None,
+            })
+    }
+
+    /// The components of a record class, `None` for the other classes.
+    pub fn record_components(&self) -> Option<&[RecordComponent]> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Record(components) => Some(&components[..]),
+                _ => None,
+            })
+    }
+
+    /// The bootstrap methods of the `Dynamic` and `InvokeDynamic` constants.
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethod] {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::BootstrapMethods(methods) => Some(&methods[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The targets of the `invokedynamic` instructions of the methods.
+    pub fn call_sites(&self) -> &CallSites {
+        &self.call_sites
+    }
+
+    /// The declaration of the module of a `module-info` class.
+    pub fn module(&self) -> Option<&Module> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Module(module) => Some(module),
+                _ => None,
+            })
+    }
+
+    /// Name of the method.
+    pub fn method_name(&self, method: &Method) -> &str {
+        self.constants.utf8(method.name_index()).unwrap_or_default()
+    }
+
+    /// Descriptor of the method.
+    pub fn method_descriptor(&self, method: &Method) -> &str {
+        self.constants
+            .utf8(method.descriptor_index())
+            .unwrap_or_default()
+    }
+
+    /// Name of the field.
+    pub fn field_name(&self, field: &Field) -> &str {
+        self.constants.utf8(field.name_index()).unwrap_or_default()
+    }
+
+    /// Descriptor of the field.
+    pub fn field_descriptor(&self, field: &Field) -> &str {
+        self.constants
+            .utf8(field.descriptor_index())
+            .unwrap_or_default()
+    }
+
+    pub fn field_type(&self, field: &Field) -> FieldType {
+        FieldType::from_descriptor(self.field_descriptor(field))
+            .expect("field descriptors are checked by the parser.")
+    }
+
+    /// Index of the field declared by the class with the name and descriptor.
+    pub fn find_field(&self, name: &str, descriptor: &str) -> Option<usize> {
+        self.fields.iter().position(|field| {
+            self.field_name(field) == name && self.field_descriptor(field) == descriptor
+        })
+    }
+
+    /// Lay out the fields after those of the linked super class. Only the first call
+    /// has an effect.
+    pub fn link_fields(
+        &self,
+        super_klass: Option<&InstanceKlass>,
+    ) -> Result<&FieldLayout, Exception> {
+        if let Some(layout) = self.field_layout.get() {
+            return Ok(layout);
+        }
+        let super_layout = match super_klass {
+            Some(klass) => Some(klass.field_layout().ok_or_else(|| {
+                Exception::LinkageError(format!(
+                    "{} is linked before its super class {}",
+                    self.name(),
+                    klass.name()
+                ))
+            })?),
+            None => None,
+        };
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|field| (field, self.field_type(field)))
+            .collect();
+        Ok(self
+            .field_layout
+            .get_or_init(|| FieldLayout::new(&fields, super_layout)))
+    }
+
+    /// The layout of the fields, `None` before the class is linked.
+    pub fn field_layout(&self) -> Option<&FieldLayout> {
+        self.field_layout.get()
+    }
+
+    /// Offset of the field declared by the class, in the instances or in the static
+    /// fields, once the class is linked.
+    pub fn field_offset(&self, name: &str, descriptor: &str) -> Option<u4> {
+        let index = self.find_field(name, descriptor)?;
+        Some(self.field_layout()?.offset(index))
+    }
 }
@@ -1,20 +1,160 @@
 use crate::utilities::definition::{u1, u2, u4};
 
+use super::attribute::{self, Attribute};
+
 pub struct Method {
     flags: u2,
     name_index: u2,
     descriptor_index: u2,
-    code: Code,
+    /// `None` for abstract and native methods.
+    code: Option<Code>,
+    /// The attributes other than `Code`.
+    attributes: Vec<Attribute>,
 }
 
-struct Code {
+impl Method {
+    pub fn new(
+        flags: u2,
+        name_index: u2,
+        descriptor_index: u2,
+        code: Option<Code>,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        Method {
+            flags,
+            name_index,
+            descriptor_index,
+            code,
+            attributes,
+        }
+    }
+
+    pub fn flags(&self) -> u2 {
+        self.flags
+    }
+
+    pub fn name_index(&self) -> u2 {
+        self.name_index
+    }
+
+    pub fn descriptor_index(&self) -> u2 {
+        self.descriptor_index
+    }
+
+    pub fn code(&self) -> Option<&Code> {
+        self.code.as_ref()
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// Index of the generic signature.
+    pub fn signature_index(&self) -> Option<u2> {
+        attribute::signature_index(&self.attributes)
+    }
+
+    /// Indexes of the classes of the declared exceptions.
+    pub fn exceptions(&self) -> &[u2] {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Exceptions(exceptions) => Some(&exceptions[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub struct Code {
     max_stack: u2,
     max_locals: u2,
     code_size: u4,
     code: Vec<u1>,
+    exception_table: Vec<ExceptionTableEntry>,
     line_number_table: Option<LineNumberTable>,
+    /// Content of the `StackMapTable` attribute.
+    stack_map_table: Option<Vec<u1>>,
+    /// The attributes other than `LineNumberTable` and `StackMapTable`.
+    attributes: Vec<Attribute>,
 }
 
-struct LineNumberTable {
+impl Code {
+    pub fn new(
+        max_stack: u2,
+        max_locals: u2,
+        code: Vec<u1>,
+        exception_table: Vec<ExceptionTableEntry>,
+        line_number_table: Option<LineNumberTable>,
+        stack_map_table: Option<Vec<u1>>,
+        attributes: Vec<Attribute>,
+    ) -> Self {
+        Code {
+            max_stack,
+            max_locals,
+            code_size: code.len() as u4,
+            code,
+            exception_table,
+            line_number_table,
+            stack_map_table,
+            attributes,
+        }
+    }
+
+    pub fn max_stack(&self) -> u2 {
+        self.max_stack
+    }
+
+    pub fn max_locals(&self) -> u2 {
+        self.max_locals
+    }
+
+    pub fn code_size(&self) -> u4 {
+        self.code_size
+    }
+
+    pub fn code(&self) -> &[u1] {
+        &self.code
+    }
+
+    pub fn exception_table(&self) -> &[ExceptionTableEntry] {
+        &self.exception_table
+    }
+
+    pub fn line_number_table(&self) -> Option<&LineNumberTable> {
+        self.line_number_table.as_ref()
+    }
+
+    pub fn stack_map_table(&self) -> Option<&[u1]> {
+        self.stack_map_table.as_deref()
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+}
+
+/// A handler of the exceptions thrown between `start_pc` included and `end_pc`
+/// excluded, of the class at `catch_type` or of any class when it is 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExceptionTableEntry {
+    pub start_pc: u2,
+    pub end_pc: u2,
+    pub handler_pc: u2,
+    pub catch_type: u2,
+}
+
+pub struct LineNumberTable {
     entries: Vec<(u2, u2)>,
 }
+
+impl LineNumberTable {
+    /// A table of start pc and line number pairs.
+    pub fn new(entries: Vec<(u2, u2)>) -> Self {
+        LineNumberTable { entries }
+    }
+
+    pub fn entries(&self) -> &[(u2, u2)] {
+        &self.entries
+    }
+}
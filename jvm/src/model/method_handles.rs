@@ -0,0 +1,627 @@
+//! What the vm knows about `java.lang.invoke`: the signature polymorphic methods of
+//! `MethodHandle` and `VarHandle`, the resolution of the `MemberName` of a method handle
+//! (JVMS §5.4.3.5) and the types of the access modes of a `VarHandle`.
+use std::sync::Arc;
+
+use crate::utilities::{
+    access_flags::{
+        JVM_ACC_INTERFACE, JVM_ACC_NATIVE, JVM_ACC_PRIVATE, JVM_ACC_STATIC, JVM_ACC_VARARGS,
+    },
+    definition::{u1, u4},
+    exceptions::Exception,
+};
+
+use super::{
+    field_layout::FieldType,
+    instance_klass::InstanceKlass,
+    method::Method,
+    mirror::{Mirror, Mirrors},
+};
+
+pub const REF_GET_FIELD: u1 = 1;
+pub const REF_GET_STATIC: u1 = 2;
+pub const REF_PUT_FIELD: u1 = 3;
+pub const REF_PUT_STATIC: u1 = 4;
+pub const REF_INVOKE_VIRTUAL: u1 = 5;
+pub const REF_INVOKE_STATIC: u1 = 6;
+pub const REF_INVOKE_SPECIAL: u1 = 7;
+pub const REF_NEW_INVOKE_SPECIAL: u1 = 8;
+pub const REF_INVOKE_INTERFACE: u1 = 9;
+
+/// The flags of a `MemberName`, from `MethodHandleNatives.Constants`.
+pub const MN_IS_METHOD: u4 = 0x0001_0000;
+pub const MN_IS_CONSTRUCTOR: u4 = 0x0002_0000;
+pub const MN_IS_FIELD: u4 = 0x0004_0000;
+pub const MN_REFERENCE_KIND_SHIFT: u4 = 24;
+
+const METHOD_HANDLE: &str = "java/lang/invoke/MethodHandle";
+const VAR_HANDLE: &str = "java/lang/invoke/VarHandle";
+
+/// Whether the method is signature polymorphic (JVMS §2.9.3): a native varargs method of
+/// `MethodHandle` or `VarHandle` taking an `Object[]`, that accepts any descriptor.
+pub fn is_signature_polymorphic(klass: &InstanceKlass, method: &Method) -> bool {
+    (klass.name() == METHOD_HANDLE || klass.name() == VAR_HANDLE)
+        && method.flags() & (JVM_ACC_NATIVE | JVM_ACC_VARARGS) == JVM_ACC_NATIVE | JVM_ACC_VARARGS
+        && klass
+            .method_descriptor(method)
+            .starts_with("([Ljava/lang/Object;)")
+}
+
+/// The signature polymorphic methods the vm implements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Intrinsic {
+    /// `MethodHandle.invoke` and `invokeExact`, linked by `MethodHandleNatives.linkMethod`
+    /// to a `LambdaForm` invoker.
+    Invoke,
+    InvokeExact,
+    /// Calls the `LambdaForm` of the receiving method handle.
+    InvokeBasic,
+    /// The `linkTo` methods, called with a trailing `MemberName` to dispatch to.
+    LinkToVirtual,
+    LinkToStatic,
+    LinkToSpecial,
+    LinkToInterface,
+    /// An access mode method of `VarHandle`.
+    AccessMode(AccessType),
+}
+
+impl Intrinsic {
+    /// The intrinsic of the method of `MethodHandle` or `VarHandle`.
+    pub fn of(class: &str, name: &str) -> Option<Intrinsic> {
+        match (class, name) {
+            (METHOD_HANDLE, "invoke") => Some(Intrinsic::Invoke),
+            (METHOD_HANDLE, "invokeExact") => Some(Intrinsic::InvokeExact),
+            (METHOD_HANDLE, "invokeBasic") => Some(Intrinsic::InvokeBasic),
+            (METHOD_HANDLE, "linkToVirtual") => Some(Intrinsic::LinkToVirtual),
+            (METHOD_HANDLE, "linkToStatic") => Some(Intrinsic::LinkToStatic),
+            (METHOD_HANDLE, "linkToSpecial") => Some(Intrinsic::LinkToSpecial),
+            (METHOD_HANDLE, "linkToInterface") => Some(Intrinsic::LinkToInterface),
+            (VAR_HANDLE, name) => AccessType::of(name).map(Intrinsic::AccessMode),
+            _ => None,
+        }
+    }
+
+    /// The kinds of the `MemberName` a `linkTo` method dispatches to, `linkToSpecial`
+    /// also calls constructors.
+    pub fn reference_kinds(&self) -> &'static [u1] {
+        match self {
+            Intrinsic::LinkToVirtual => &[REF_INVOKE_VIRTUAL],
+            Intrinsic::LinkToStatic => &[REF_INVOKE_STATIC],
+            Intrinsic::LinkToSpecial => &[REF_INVOKE_SPECIAL, REF_NEW_INVOKE_SPECIAL],
+            Intrinsic::LinkToInterface => &[REF_INVOKE_INTERFACE],
+            _ => &[],
+        }
+    }
+}
+
+/// `MethodHandle.invokeExact` only accepts the exact type of the method handle.
+pub fn check_exact_type(handle_type: &str, call_type: &str) -> Result<(), Exception> {
+    if handle_type != call_type {
+        return Err(Exception::WrongMethodTypeException(format!(
+            "expected {} but found {}",
+            method_type_string(handle_type),
+            method_type_string(call_type)
+        )));
+    }
+    Ok(())
+}
+
+/// The method descriptor like `MethodType.toString`, e.g. `(int,String[])void`.
+fn method_type_string(descriptor: &str) -> String {
+    let mut types = Vec::new();
+    let mut rest = descriptor;
+    while !rest.is_empty() {
+        let dimensions = rest
+            .bytes()
+            .take_while(|&byte| byte == b'[' || byte == b'(')
+            .count();
+        let arrays = rest[..dimensions].matches('[').count();
+        rest = &rest[dimensions..];
+        let (name, length) = match rest.as_bytes()[0] {
+            b'L' => {
+                let end = rest.find(';').unwrap_or(rest.len() - 1);
+                let name = &rest[1..end];
+                (name.rsplit('/').next().unwrap_or(name), end + 1)
+            }
+            b')' => {
+                types.push(")".to_string());
+                rest = &rest[1..];
+                continue;
+            }
+            byte => (primitive_name(byte), 1),
+        };
+        types.push(format!("{}{}", name, "[]".repeat(arrays)));
+        rest = &rest[length..];
+    }
+    let end = types
+        .iter()
+        .position(|name| name == ")")
+        .unwrap_or(types.len());
+    format!("({}){}", types[..end].join(","), types[end + 1..].concat())
+}
+
+fn primitive_name(descriptor: u1) -> &'static str {
+    match descriptor {
+        b'Z' => "boolean",
+        b'B' => "byte",
+        b'C' => "char",
+        b'S' => "short",
+        b'I' => "int",
+        b'F' => "float",
+        b'J' => "long",
+        b'D' => "double",
+        _ => "void",
+    }
+}
+
+/// A resolved `MemberName`: the declaring class of the member, and its flags with the
+/// modifiers, the kind of member and the reference kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberName {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+    pub flags: u4,
+}
+
+impl MemberName {
+    /// The kind checked by the `linkTo` methods before they dispatch to the member.
+    pub fn reference_kind(&self) -> u1 {
+        (self.flags >> MN_REFERENCE_KIND_SHIFT & 0x0F) as u1
+    }
+}
+
+/// Resolve the member of a method handle of the kind, like `MethodHandleNatives.resolve`,
+/// in the class then in its super types, a constructor in the class only. A signature
+/// polymorphic method matches any descriptor.
+pub fn resolve_member(
+    mirrors: &Mirrors,
+    kind: u1,
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<MemberName, Exception> {
+    let mirror = mirrors
+        .get(class)
+        .ok_or_else(|| Exception::NoClassDefFoundError(class.to_string()))?;
+    let klass = mirror.klass().ok_or_else(|| {
+        Exception::IncompatibleClassChangeError(format!("{} is not a class or interface", class))
+    })?;
+    let member = format!("{}.{}{}", class.replace('/', "."), name, descriptor);
+    let (declaring_class, flags, kind_flag) = match kind {
+        REF_GET_FIELD..=REF_PUT_STATIC => {
+            let (declaring_class, index) = find_field(mirrors, klass, name, descriptor)?
+                .ok_or_else(|| Exception::NoSuchFieldError(name.to_string()))?;
+            let flags = declaring_class.fields()[index].flags();
+            let is_static = matches!(kind, REF_GET_STATIC | REF_PUT_STATIC);
+            if (flags & JVM_ACC_STATIC != 0) != is_static {
+                return Err(Exception::IncompatibleClassChangeError(format!(
+                    "Expected {} field {}",
+                    if is_static { "static" } else { "non-static" },
+                    member
+                )));
+            }
+            (declaring_class, flags, MN_IS_FIELD)
+        }
+        REF_INVOKE_VIRTUAL..=REF_INVOKE_INTERFACE => {
+            let is_interface = klass.access_flags() & JVM_ACC_INTERFACE != 0;
+            match kind {
+                REF_INVOKE_INTERFACE if !is_interface => {
+                    return Err(Exception::IncompatibleClassChangeError(format!(
+                        "Found class {}, but interface was expected",
+                        class.replace('/', ".")
+                    )));
+                }
+                REF_INVOKE_VIRTUAL | REF_NEW_INVOKE_SPECIAL if is_interface => {
+                    return Err(Exception::IncompatibleClassChangeError(format!(
+                        "Found interface {}, but class was expected",
+                        class.replace('/', ".")
+                    )));
+                }
+                _ => {}
+            }
+            let is_constructor = name == "<init>";
+            if is_constructor != (kind == REF_NEW_INVOKE_SPECIAL) || name == "<clinit>" {
+                return Err(Exception::NoSuchMethodError(member));
+            }
+            let (declaring_class, index) = find_method(mirrors, klass, name, descriptor, false)?
+                .ok_or_else(|| Exception::NoSuchMethodError(member.clone()))?;
+            let flags = declaring_class.methods()[index].flags();
+            if (flags & JVM_ACC_STATIC != 0) != (kind == REF_INVOKE_STATIC) {
+                return Err(Exception::IncompatibleClassChangeError(format!(
+                    "Expected {} method {}",
+                    if kind == REF_INVOKE_STATIC {
+                        "static"
+                    } else {
+                        "non-static"
+                    },
+                    member
+                )));
+            }
+            let kind_flag = if is_constructor {
+                MN_IS_CONSTRUCTOR
+            } else {
+                MN_IS_METHOD
+            };
+            (declaring_class, flags, kind_flag)
+        }
+        _ => {
+            return Err(Exception::IllegalArgumentException(format!(
+                "Bad reference kind {}",
+                kind
+            )))
+        }
+    };
+    Ok(MemberName {
+        class: declaring_class.name().to_string(),
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+        flags: flags as u4 | kind_flag | (kind as u4) << MN_REFERENCE_KIND_SHIFT,
+    })
+}
+
+/// The classes of the names, a super type that is not loaded is a
+/// `NoClassDefFoundError`.
+fn klasses(mirrors: &Mirrors, names: Vec<&str>) -> Result<Vec<Arc<Mirror>>, Exception> {
+    names
+        .into_iter()
+        .map(|name| {
+            mirrors
+                .get(name)
+                .ok_or_else(|| Exception::NoClassDefFoundError(name.to_string()))
+        })
+        .collect()
+}
+
+/// The loaded class declaring a member.
+fn declaring_class(
+    mirrors: &Mirrors,
+    klass: &InstanceKlass,
+) -> Result<Arc<InstanceKlass>, Exception> {
+    mirrors
+        .get(klass.name())
+        .and_then(|mirror| mirror.klass().cloned())
+        .ok_or_else(|| Exception::NoClassDefFoundError(klass.name().to_string()))
+}
+
+/// The field in the class, its super interfaces then its super class (JVMS §5.4.3.2).
+fn find_field(
+    mirrors: &Mirrors,
+    klass: &InstanceKlass,
+    name: &str,
+    descriptor: &str,
+) -> Result<Option<(Arc<InstanceKlass>, usize)>, Exception> {
+    if let Some(index) = klass.find_field(name, descriptor) {
+        return Ok(Some((declaring_class(mirrors, klass)?, index)));
+    }
+    let mut names = klass.interface_names();
+    names.extend(klass.super_name());
+    for mirror in klasses(mirrors, names)? {
+        if let Some(klass) = mirror.klass() {
+            if let Some(field) = find_field(mirrors, klass, name, descriptor)? {
+                return Ok(Some(field));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The method in the class, its super classes then its super interfaces
+/// (JVMS §5.4.3.3). Constructors are not inherited, nor are the static and private
+/// methods of a super interface.
+fn find_method(
+    mirrors: &Mirrors,
+    klass: &InstanceKlass,
+    name: &str,
+    descriptor: &str,
+    is_super_interface: bool,
+) -> Result<Option<(Arc<InstanceKlass>, usize)>, Exception> {
+    let index = klass.methods().iter().position(|method| {
+        klass.method_name(method) == name
+            && (klass.method_descriptor(method) == descriptor
+                || is_signature_polymorphic(klass, method))
+            && !(is_super_interface && method.flags() & (JVM_ACC_STATIC | JVM_ACC_PRIVATE) != 0)
+    });
+    if let Some(index) = index {
+        return Ok(Some((declaring_class(mirrors, klass)?, index)));
+    }
+    if name == "<init>" {
+        return Ok(None);
+    }
+    let super_klasses = klasses(mirrors, klass.super_name().into_iter().collect())?;
+    let interfaces = klasses(mirrors, klass.interface_names())?;
+    let super_types = super_klasses
+        .iter()
+        .map(|mirror| (mirror, is_super_interface))
+        .chain(interfaces.iter().map(|mirror| (mirror, true)));
+    for (mirror, is_super_interface) in super_types {
+        if let Some(klass) = mirror.klass() {
+            if let Some(method) = find_method(mirrors, klass, name, descriptor, is_super_interface)?
+            {
+                return Ok(Some(method));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The kinds of access modes of `VarHandle`, by the shape of their method type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessType {
+    Get,
+    Set,
+    CompareAndSet,
+    CompareAndExchange,
+    GetAndSet,
+    /// Numeric types only.
+    GetAndAdd,
+    /// Integral types and booleans only.
+    GetAndBitwise,
+}
+
+impl AccessType {
+    /// The access type of the method name of an access mode, e.g. `getAndAddAcquire`.
+    pub fn of(name: &str) -> Option<AccessType> {
+        let base = ["Acquire", "Release", "Volatile", "Opaque", "Plain"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .unwrap_or(name);
+        Some(match base {
+            "get" => AccessType::Get,
+            "set" => AccessType::Set,
+            "compareAndSet" | "weakCompareAndSet" => AccessType::CompareAndSet,
+            "compareAndExchange" => AccessType::CompareAndExchange,
+            "getAndSet" => AccessType::GetAndSet,
+            "getAndAdd" => AccessType::GetAndAdd,
+            "getAndBitwiseOr" | "getAndBitwiseAnd" | "getAndBitwiseXor" => {
+                AccessType::GetAndBitwise
+            }
+            _ => return None,
+        })
+        .filter(|_| is_access_mode(name, base))
+    }
+
+    /// The method type of the access mode for a variable of the type, e.g. `J`, at the
+    /// coordinates, e.g. the receiver of an instance field.
+    pub fn method_type(&self, coordinates: &[&str], value: &str) -> Result<String, Exception> {
+        let field_type = FieldType::from_descriptor(value)
+            .ok_or_else(|| Exception::IllegalArgumentException(value.to_string()))?;
+        let supported = match self {
+            AccessType::GetAndAdd => {
+                !matches!(field_type, FieldType::Boolean | FieldType::Reference)
+            }
+            AccessType::GetAndBitwise => !matches!(
+                field_type,
+                FieldType::Float | FieldType::Double | FieldType::Reference
+            ),
+            _ => true,
+        };
+        if !supported {
+            return Err(Exception::UnsupportedOperationException(String::new()));
+        }
+        let coordinates = coordinates.concat();
+        Ok(match self {
+            AccessType::Get => format!("({}){}", coordinates, value),
+            AccessType::Set => format!("({}{})V", coordinates, value),
+            AccessType::CompareAndSet => format!("({}{}{})Z", coordinates, value, value),
+            AccessType::CompareAndExchange => {
+                format!("({}{}{}){}", coordinates, value, value, value)
+            }
+            AccessType::GetAndSet | AccessType::GetAndAdd | AccessType::GetAndBitwise => {
+                format!("({}{}){}", coordinates, value, value)
+            }
+        })
+    }
+}
+
+/// Whether the suffix of the name is one of the memory orders of the access mode.
+fn is_access_mode(name: &str, base: &str) -> bool {
+    let suffixes: &[&str] = match base {
+        "get" => &["", "Volatile", "Acquire", "Opaque"],
+        "set" => &["", "Volatile", "Release", "Opaque"],
+        "compareAndSet" => &[""],
+        "weakCompareAndSet" => &["", "Plain", "Acquire", "Release"],
+        "compareAndExchange" => &["", "Acquire", "Release"],
+        _ => &["", "Acquire", "Release"],
+    };
+    suffixes.contains(&&name[base.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{classfile::test_class::TestClass, model::mirror::Mirrors};
+
+    use super::{
+        check_exact_type, is_signature_polymorphic, resolve_member, AccessType, Intrinsic,
+        MN_IS_CONSTRUCTOR, MN_IS_FIELD, MN_IS_METHOD, REF_GET_FIELD, REF_GET_STATIC,
+        REF_INVOKE_INTERFACE, REF_INVOKE_STATIC, REF_INVOKE_VIRTUAL, REF_NEW_INVOKE_SPECIAL,
+    };
+
+    fn mirrors() -> Mirrors {
+        let mirrors = Mirrors::new();
+        let code = [0xB1];
+        let mut object = TestClass::new("java/lang/Object", "");
+        object.method(0x0001, "<init>", "()V", 0, 1, &code, &[], None);
+        object.method(0x0001, "hashCode", "()I", 0, 1, &code, &[], None);
+        mirrors.define(Arc::new(object.parse()));
+        let mut handle = TestClass::new("java/lang/invoke/MethodHandle", "java/lang/Object");
+        handle.access_flags(0x0421);
+        let flags = 0x0191;
+        let descriptor = "([Ljava/lang/Object;)Ljava/lang/Object;";
+        handle.method_with_attributes(flags, "invokeExact", descriptor, &[]);
+        mirrors.define(Arc::new(handle.parse()));
+        let mut interface = TestClass::new("p/I", "java/lang/Object");
+        interface.access_flags(0x0601);
+        interface.field(0x0019, "K", "I", &[]);
+        interface.method_with_attributes(0x0401, "run", "()V", &[]);
+        interface.method(0x0009, "t", "()V", 0, 1, &code, &[], None);
+        mirrors.define(Arc::new(interface.parse()));
+        let mut class = TestClass::new("p/A", "java/lang/Object");
+        class.interface("p/I");
+        class.field(0x0002, "a", "J", &[]);
+        class.method(0x0009, "s", "(I)V", 0, 1, &code, &[], None);
+        mirrors.define(Arc::new(class.parse()));
+        // a subclass without a constructor of its own
+        mirrors.define(Arc::new(TestClass::new("p/D", "java/lang/Object").parse()));
+        // the super class is not loaded
+        mirrors.define(Arc::new(TestClass::new("p/C", "p/B").parse()));
+        mirrors
+    }
+
+    #[test]
+    fn we_can_resolve_the_member_names() {
+        let mirrors = mirrors();
+        let member = resolve_member(&mirrors, REF_GET_FIELD, "p/A", "a", "J").unwrap();
+        assert_eq!(member.flags, 0x0002 | MN_IS_FIELD | 1 << 24);
+        assert_eq!(member.reference_kind(), REF_GET_FIELD);
+        let member = resolve_member(&mirrors, REF_GET_STATIC, "p/A", "K", "I").unwrap();
+        assert_eq!(member.class, "p/I");
+        let member = resolve_member(&mirrors, REF_INVOKE_VIRTUAL, "p/A", "hashCode", "()I");
+        assert_eq!(member.unwrap().class, "java/lang/Object");
+        let member = resolve_member(&mirrors, REF_INVOKE_STATIC, "p/A", "s", "(I)V").unwrap();
+        assert_eq!(member.flags, 0x0009 | MN_IS_METHOD | 6 << 24);
+        let member = resolve_member(
+            &mirrors,
+            REF_NEW_INVOKE_SPECIAL,
+            "java/lang/Object",
+            "<init>",
+            "()V",
+        );
+        assert_eq!(member.unwrap().flags & MN_IS_CONSTRUCTOR, MN_IS_CONSTRUCTOR);
+        let member = resolve_member(&mirrors, REF_INVOKE_INTERFACE, "p/I", "run", "()V");
+        assert_eq!(member.unwrap().reference_kind(), REF_INVOKE_INTERFACE);
+        let member = resolve_member(&mirrors, REF_INVOKE_STATIC, "p/I", "t", "()V");
+        assert_eq!(member.unwrap().class, "p/I");
+        // any descriptor of a signature polymorphic method
+        let handle = "java/lang/invoke/MethodHandle";
+        let member = resolve_member(&mirrors, REF_INVOKE_VIRTUAL, handle, "invokeExact", "(IJ)V");
+        assert_eq!(member.unwrap().descriptor, "(IJ)V");
+
+        let error = |kind, class, name, descriptor| {
+            resolve_member(&mirrors, kind, class, name, descriptor)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(REF_GET_STATIC, "p/A", "a", "J"),
+            "java.lang.IncompatibleClassChangeError: Expected static field p.A.aJ"
+        );
+        assert_eq!(
+            error(REF_INVOKE_VIRTUAL, "p/A", "s", "(I)V"),
+            "java.lang.IncompatibleClassChangeError: Expected non-static method p.A.s(I)V"
+        );
+        assert_eq!(
+            error(REF_INVOKE_INTERFACE, "p/A", "run", "()V"),
+            "java.lang.IncompatibleClassChangeError: Found class p.A, but interface was expected"
+        );
+        assert_eq!(
+            error(REF_INVOKE_VIRTUAL, "p/A", "s", "(J)V"),
+            "java.lang.NoSuchMethodError: p.A.s(J)V"
+        );
+        assert_eq!(
+            error(REF_INVOKE_VIRTUAL, "java/lang/Object", "<init>", "()V"),
+            "java.lang.NoSuchMethodError: java.lang.Object.<init>()V"
+        );
+        // constructors and static interface methods are not inherited
+        assert_eq!(
+            error(REF_NEW_INVOKE_SPECIAL, "p/D", "<init>", "()V"),
+            "java.lang.NoSuchMethodError: p.D.<init>()V"
+        );
+        assert_eq!(
+            error(REF_INVOKE_STATIC, "p/A", "t", "()V"),
+            "java.lang.NoSuchMethodError: p.A.t()V"
+        );
+        assert_eq!(
+            error(REF_GET_FIELD, "p/A", "b", "J"),
+            "java.lang.NoSuchFieldError: b"
+        );
+        assert_eq!(
+            error(REF_GET_FIELD, "p/B", "b", "J"),
+            "java.lang.NoClassDefFoundError: p/B"
+        );
+        assert_eq!(
+            error(REF_INVOKE_VIRTUAL, "p/C", "hashCode", "()I"),
+            "java.lang.NoClassDefFoundError: p/B"
+        );
+    }
+
+    #[test]
+    fn we_can_find_the_intrinsics() {
+        let mirrors = mirrors();
+        let handle = mirrors.get("java/lang/invoke/MethodHandle").unwrap();
+        let handle = handle.klass().unwrap();
+        assert!(is_signature_polymorphic(handle, &handle.methods()[0]));
+        let object = mirrors.get("java/lang/Object").unwrap();
+        let object = object.klass().unwrap();
+        assert!(!is_signature_polymorphic(object, &object.methods()[1]));
+
+        let handle = "java/lang/invoke/MethodHandle";
+        let var_handle = "java/lang/invoke/VarHandle";
+        assert_eq!(
+            Intrinsic::of(handle, "invokeExact"),
+            Some(Intrinsic::InvokeExact)
+        );
+        assert_eq!(
+            Intrinsic::of(handle, "linkToSpecial")
+                .unwrap()
+                .reference_kinds(),
+            [7, 8]
+        );
+        assert_eq!(
+            Intrinsic::of(var_handle, "getAndBitwiseXorAcquire"),
+            Some(Intrinsic::AccessMode(AccessType::GetAndBitwise))
+        );
+        assert_eq!(
+            AccessType::of("weakCompareAndSetPlain"),
+            Some(AccessType::CompareAndSet)
+        );
+        assert_eq!(AccessType::of("getVolatile"), Some(AccessType::Get));
+        assert_eq!(AccessType::of("compareAndSetAcquire"), None);
+        assert_eq!(AccessType::of("getAndAddOpaque"), None);
+        assert_eq!(Intrinsic::of(handle, "get"), None);
+    }
+
+    #[test]
+    fn we_can_type_the_access_modes() {
+        let receiver = ["Lp/A;"];
+        let method_type =
+            |access_type: AccessType, value| access_type.method_type(&receiver, value);
+        assert_eq!(method_type(AccessType::Get, "J").unwrap(), "(Lp/A;)J");
+        assert_eq!(method_type(AccessType::Set, "J").unwrap(), "(Lp/A;J)V");
+        assert_eq!(
+            method_type(AccessType::CompareAndSet, "I").unwrap(),
+            "(Lp/A;II)Z"
+        );
+        assert_eq!(
+            method_type(AccessType::CompareAndExchange, "Ljava/lang/String;").unwrap(),
+            "(Lp/A;Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;"
+        );
+        assert_eq!(
+            method_type(AccessType::GetAndAdd, "D").unwrap(),
+            "(Lp/A;D)D"
+        );
+        assert_eq!(
+            AccessType::GetAndBitwise
+                .method_type(&["[I", "I"], "I")
+                .unwrap(),
+            "([III)I"
+        );
+        assert!(method_type(AccessType::GetAndAdd, "Z").is_err());
+        let error = method_type(AccessType::GetAndBitwise, "F").unwrap_err();
+        assert_eq!(
+            error.class_name(),
+            "java/lang/UnsupportedOperationException"
+        );
+        assert!(method_type(AccessType::GetAndAdd, "Ljava/lang/Object;").is_err());
+
+        assert_eq!(check_exact_type("(I)V", "(I)V"), Ok(()));
+        let error = check_exact_type("(ILjava/lang/String;[[J)V", "()[Ljava/lang/Object;");
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "java.lang.invoke.WrongMethodTypeException: \
+             expected (int,String,long[][])void but found ()Object[]"
+        );
+    }
+}
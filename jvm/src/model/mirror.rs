@@ -0,0 +1,486 @@
+//! The mirrors of the loaded classes, the array classes and the primitive types: the
+//! state a `java.lang.Class` object stands for, its name, modifiers, super types and
+//! static fields.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use gc::{Address, GcRoot};
+use once_cell::sync::OnceCell;
+
+use crate::utilities::{
+    access_flags::{
+        JVM_ACC_ABSTRACT, JVM_ACC_FINAL, JVM_ACC_INTERFACE, JVM_ACC_PRIVATE, JVM_ACC_PROTECTED,
+        JVM_ACC_PUBLIC, JVM_ACC_SUPER,
+    },
+    definition::{u1, u2},
+    exceptions::Exception,
+};
+
+use super::{array_klass::ArrayKlass, field_layout::StaticFields, instance_klass::InstanceKlass};
+
+/// The primitive types and `void`, by descriptor and Java name.
+const PRIMITIVES: [(u1, &str); 9] = [
+    (b'Z', "boolean"),
+    (b'B', "byte"),
+    (b'C', "char"),
+    (b'S', "short"),
+    (b'I', "int"),
+    (b'F', "float"),
+    (b'J', "long"),
+    (b'D', "double"),
+    (b'V', "void"),
+];
+
+/// The flags of the class file that `Class.getModifiers` reports.
+const WRITTEN_FLAGS: u2 = 0x7FFF;
+
+/// The state of a `java.lang.Class` object. The klass pointer of an object is the
+/// address of the mirror of its class. No `Class` instance is allocated for a mirror,
+/// that waits for the interpreter which calls `getClass` and the natives of `Class`.
+pub enum Mirror {
+    /// A primitive type or `void`, by the character of its descriptor.
+    Primitive(u1),
+    /// A class or interface, with the static fields it holds once the class is linked.
+    Instance(Arc<InstanceKlass>, OnceCell<StaticFields>),
+    Array(Arc<ArrayKlass>),
+}
+
+impl Mirror {
+    /// The name returned by `Class.getName`, e.g. `int`, `java.lang.String` or
+    /// `[Ljava.lang.String;`.
+    pub fn name(&self) -> String {
+        match self {
+            Mirror::Primitive(descriptor) => PRIMITIVES
+                .iter()
+                .find(|(primitive, _)| primitive == descriptor)
+                .map(|(_, name)| name.to_string())
+                .expect("primitive mirrors are created for the primitive types."),
+            Mirror::Instance(klass, _) => klass.name().replace('/', "."),
+            Mirror::Array(klass) => klass.name().replace('/', "."),
+        }
+    }
+
+    /// The field descriptor of the type, the internal name of an array class.
+    pub fn descriptor(&self) -> String {
+        match self {
+            Mirror::Primitive(descriptor) => (*descriptor as char).to_string(),
+            Mirror::Instance(klass, _) => format!("L{};", klass.name()),
+            Mirror::Array(klass) => klass.name().to_string(),
+        }
+    }
+
+    /// The internal name of a class or array class, the key of the mirror.
+    fn internal_name(&self) -> String {
+        match self {
+            Mirror::Instance(klass, _) => klass.name().to_string(),
+            _ => self.descriptor(),
+        }
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        matches!(self, Mirror::Primitive(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Mirror::Array(_))
+    }
+
+    pub fn is_interface(&self) -> bool {
+        matches!(self, Mirror::Instance(klass, _) if klass.access_flags() & JVM_ACC_INTERFACE != 0)
+    }
+
+    pub fn klass(&self) -> Option<&Arc<InstanceKlass>> {
+        match self {
+            Mirror::Instance(klass, _) => Some(klass),
+            _ => None,
+        }
+    }
+
+    pub fn array_klass(&self) -> Option<&Arc<ArrayKlass>> {
+        match self {
+            Mirror::Array(klass) => Some(klass),
+            _ => None,
+        }
+    }
+
+    /// The type of the components of an array class.
+    pub fn component_type(&self) -> Option<&Arc<Mirror>> {
+        self.array_klass().map(|klass| klass.component())
+    }
+
+    /// Internal name of the super class, `None` for `java/lang/Object`, interfaces and
+    /// primitive types.
+    pub fn super_name(&self) -> Option<&str> {
+        match self {
+            Mirror::Primitive(_) => None,
+            Mirror::Instance(..) if self.is_interface() => None,
+            Mirror::Instance(klass, _) => klass.super_name(),
+            Mirror::Array(klass) => Some(klass.super_name()),
+        }
+    }
+
+    /// The static fields of a class, zeroed or set to their `ConstantValue` the first
+    /// time they are asked for, `None` before the class is linked.
+    pub fn static_fields(&self) -> Option<&StaticFields> {
+        let Mirror::Instance(klass, statics) = self else {
+            return None;
+        };
+        let layout = klass.field_layout()?;
+        Some(statics.get_or_init(|| {
+            let statics = StaticFields::new(layout);
+            let constants = klass.constants();
+            for (index, field) in klass.fields().iter().enumerate() {
+                let index_of_constant = field.constant_value_index();
+                if let Some(constant) = constants.get(index_of_constant) {
+                    if index_of_constant != 0 {
+                        let field_type = klass.field_type(field);
+                        statics.put_constant(layout.offset(index), field_type, constant);
+                    }
+                }
+            }
+            statics
+        }))
+    }
+
+    /// The modifiers of `Class.getModifiers`, those of the `InnerClasses` entry of a
+    /// nested class. Array classes have the access of their element type.
+    pub fn modifiers(&self) -> u2 {
+        match self {
+            Mirror::Primitive(_) => JVM_ACC_PUBLIC | JVM_ACC_ABSTRACT | JVM_ACC_FINAL,
+            Mirror::Instance(klass, _) => {
+                let constants = klass.constants();
+                let inner_class = klass.inner_classes().iter().find(|inner_class| {
+                    constants.class_name(inner_class.inner_class_info_index) == Some(klass.name())
+                });
+                let flags = match inner_class {
+                    Some(inner_class) => inner_class.inner_class_access_flags,
+                    None => klass.access_flags(),
+                };
+                flags & !JVM_ACC_SUPER & WRITTEN_FLAGS
+            }
+            Mirror::Array(klass) => {
+                let access = JVM_ACC_PUBLIC | JVM_ACC_PRIVATE | JVM_ACC_PROTECTED;
+                klass.component().modifiers() & access | JVM_ACC_ABSTRACT | JVM_ACC_FINAL
+            }
+        }
+    }
+}
+
+/// The mirrors of the classes by internal name, and of the primitive types.
+pub struct Mirrors {
+    primitives: Vec<Arc<Mirror>>,
+    mirrors: Mutex<HashMap<String, Arc<Mirror>>>,
+}
+
+impl Default for Mirrors {
+    fn default() -> Self {
+        Mirrors::new()
+    }
+}
+
+impl Mirrors {
+    pub fn new() -> Self {
+        Mirrors {
+            primitives: PRIMITIVES
+                .iter()
+                .map(|(descriptor, _)| Arc::new(Mirror::Primitive(*descriptor)))
+                .collect(),
+            mirrors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The mirror of `Class.getPrimitiveClass`, by Java name.
+    pub fn primitive(&self, name: &str) -> Option<Arc<Mirror>> {
+        let index = PRIMITIVES
+            .iter()
+            .position(|(_, primitive)| *primitive == name)?;
+        Some(self.primitives[index].clone())
+    }
+
+    /// Create the mirror of a class when it is loaded, or return the mirror it already
+    /// has.
+    pub fn define(&self, klass: Arc<InstanceKlass>) -> Arc<Mirror> {
+        let mut mirrors = self.mirrors.lock().unwrap();
+        mirrors
+            .entry(klass.name().to_string())
+            .or_insert_with(|| Arc::new(Mirror::Instance(klass, OnceCell::new())))
+            .clone()
+    }
+
+    /// The mirror of a loaded class, or of an array class of a loaded element type,
+    /// by internal name. Array classes are created with their mirror when first asked
+    /// for, so there is one for each element type and dimension.
+    pub fn get(&self, name: &str) -> Option<Arc<Mirror>> {
+        if let Some(mirror) = self.mirrors.lock().unwrap().get(name) {
+            return Some(mirror.clone());
+        }
+        let component = name.strip_prefix('[')?;
+        let component = match component.as_bytes().first()? {
+            b'L' => self.get(component[1..].strip_suffix(';')?)?,
+            b'[' => self.get(component)?,
+            _ if component.len() == 1 && component != "V" => {
+                let index = PRIMITIVES
+                    .iter()
+                    .position(|(descriptor, _)| *descriptor == component.as_bytes()[0])?;
+                self.primitives[index].clone()
+            }
+            _ => return None,
+        };
+        let mirror = Arc::new(Mirror::Array(Arc::new(ArrayKlass::new(component))));
+        let mut mirrors = self.mirrors.lock().unwrap();
+        Some(
+            mirrors
+                .entry(mirror.internal_name())
+                .or_insert(mirror)
+                .clone(),
+        )
+    }
+
+    /// The mirror of the binary name of `Class.forName`, e.g. `java.lang.String` or
+    /// `[Ljava.lang.String;`, loading the class or the element type when needed.
+    pub fn for_name(
+        &self,
+        name: &str,
+        load: &mut dyn FnMut(&str) -> Option<Arc<InstanceKlass>>,
+    ) -> Result<Arc<Mirror>, Exception> {
+        let not_found = || Exception::ClassNotFoundException(name.to_string());
+        if name.is_empty() || name.contains('/') {
+            return Err(not_found());
+        }
+        let internal_name = name.replace('.', "/");
+        let dimensions = internal_name.bytes().take_while(|&b| b == b'[').count();
+        let element = match &internal_name[dimensions..] {
+            element if dimensions == 0 => element,
+            element => match element.strip_prefix('L') {
+                Some(element) => element.strip_suffix(';').ok_or_else(not_found)?,
+                // a primitive element type
+                None => return self.get(&internal_name).ok_or_else(not_found),
+            },
+        };
+        if element.is_empty() || element.starts_with('[') {
+            return Err(not_found());
+        }
+        if self.get(element).is_none() {
+            let klass = load(element).ok_or_else(not_found)?;
+            self.define(klass);
+        }
+        self.get(&internal_name).ok_or_else(not_found)
+    }
+
+    /// The mirror the klass pointer of an object points to.
+    pub fn find(&self, klass: Address) -> Option<Arc<Mirror>> {
+        let mirrors = self.mirrors.lock().unwrap();
+        mirrors
+            .values()
+            .find(|mirror| Mirrors::address(mirror) == klass)
+            .cloned()
+    }
+
+    /// The klass pointer of the objects of the class, the address of its mirror.
+    pub fn address(mirror: &Arc<Mirror>) -> Address {
+        Address::new(Arc::as_ptr(mirror) as usize)
+    }
+
+    /// Visit the roots held by the classes: the classes themselves, which are never
+    /// unloaded, and the references of their static fields.
+    pub fn for_each_root(&self, visitor: &mut dyn FnMut(GcRoot)) {
+        let mirrors = self.mirrors.lock().unwrap();
+        for mirror in mirrors.values() {
+            if let Mirror::Instance(_, statics) = &**mirror {
+                let klass = Mirrors::address(mirror);
+                visitor(GcRoot::StickyClass(klass));
+                if let Some(statics) = statics.get() {
+                    statics.for_each_reference(&mut |address| {
+                        let object = Address::new(address);
+                        visitor(GcRoot::StaticField { object, klass })
+                    });
+                }
+            }
+        }
+    }
+
+    /// Whether an instance of the class `from` can be assigned to the class `to`, like
+    /// `Class.isAssignableFrom`, following the mirrors of the super types.
+    pub fn is_assignable(&self, to: &Mirror, from: &Mirror) -> bool {
+        match (to, from) {
+            (Mirror::Primitive(to), Mirror::Primitive(from)) => to == from,
+            (Mirror::Primitive(_), _) | (_, Mirror::Primitive(_)) => false,
+            // arrays are covariant, `String[]` is an `Object[]` but `int[]` is not
+            (Mirror::Array(to), Mirror::Array(from)) => {
+                let (to, from) = (to.component(), from.component());
+                to.is_primitive() == from.is_primitive() && self.is_assignable(to, from)
+            }
+            (Mirror::Array(_), Mirror::Instance(..)) => false,
+            (Mirror::Instance(to, _), Mirror::Array(_)) => ArrayKlass::is_super_type(to.name()),
+            (Mirror::Instance(to, _), Mirror::Instance(from, _)) => self.is_subtype(to, from),
+        }
+    }
+
+    /// `checkcast` of an object of the class `from`, null is never checked.
+    pub fn check_cast(&self, from: &Mirror, to: &Mirror) -> Result<(), Exception> {
+        if !self.is_assignable(to, from) {
+            return Err(Exception::ClassCastException(format!(
+                "class {} cannot be cast to class {}",
+                from.name(),
+                to.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// `aastore` of an object of the class `value` in an array of the class, `None`
+    /// for null.
+    pub fn check_store(&self, array: &ArrayKlass, value: Option<&Mirror>) -> Result<(), Exception> {
+        match value {
+            Some(value) if !self.is_assignable(array.component(), value) => {
+                Err(Exception::ArrayStoreException(value.name()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_subtype(&self, to: &InstanceKlass, from: &InstanceKlass) -> bool {
+        if to.name() == from.name() || to.name() == "java/lang/Object" {
+            return true;
+        }
+        from.super_name()
+            .into_iter()
+            .chain(from.interface_names())
+            .filter_map(|name| self.get(name))
+            .any(|mirror| matches!(&*mirror, Mirror::Instance(klass, _) if self.is_subtype(to, klass)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use gc::{Address, GcRoot};
+
+    use crate::{
+        classfile::test_class::TestClass,
+        model::{field_layout::FieldType, instance_klass::InstanceKlass},
+    };
+
+    use super::Mirrors;
+
+    fn klass(name: &str, super_name: &str, interfaces: &[&str]) -> Arc<InstanceKlass> {
+        let mut class = TestClass::new(name, super_name);
+        for interface in interfaces {
+            class.interface(interface);
+        }
+        Arc::new(class.parse())
+    }
+
+    #[test]
+    fn we_can_find_the_mirrors_by_name() {
+        let mirrors = Mirrors::new();
+        let object = mirrors.define(klass("java/lang/Object", "", &[]));
+        let mut load = |name: &str| match name {
+            "p/A" => Some(klass("p/A", "java/lang/Object", &["p/I"])),
+            _ => None,
+        };
+        let a = mirrors.for_name("p.A", &mut load).unwrap();
+        assert_eq!(a.name(), "p.A");
+        assert!(Arc::ptr_eq(&a, &mirrors.get("p/A").unwrap()));
+        let array = mirrors.for_name("[[Lp.A;", &mut load).unwrap();
+        assert_eq!(array.name(), "[[Lp.A;");
+        assert!(Arc::ptr_eq(
+            array.component_type().unwrap(),
+            &mirrors.get("[Lp/A;").unwrap()
+        ));
+        assert_eq!(array.super_name(), Some("java/lang/Object"));
+        let ints = mirrors.for_name("[I", &mut load).unwrap();
+        assert_eq!(ints.component_type().unwrap().name(), "int");
+        assert!(mirrors.primitive("int").unwrap().is_primitive());
+        for name in ["int", "p/A", "p.B", "[V", "[Lp.A", "[[", ""] {
+            let error = mirrors.for_name(name, &mut load).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("java.lang.ClassNotFoundException: {}", name)
+            );
+        }
+
+        // assignments of the super class and of arrays, the interface is not loaded
+        assert!(mirrors.is_assignable(&object, &a));
+        assert!(!mirrors.is_assignable(&a, &object));
+        assert!(mirrors.is_assignable(&object, &array));
+        let objects = mirrors.get("[Ljava/lang/Object;").unwrap();
+        assert!(mirrors.is_assignable(&objects, &array));
+        assert!(!mirrors.is_assignable(&objects, &ints));
+    }
+
+    #[test]
+    fn we_can_get_the_modifiers() {
+        let mirrors = Mirrors::new();
+        let mut class = TestClass::new("p/A$B", "java/lang/Object");
+        let inner = class.class("p/A$B");
+        let outer = class.class("p/A");
+        let name = class.utf8("B");
+        let mut content = 1u16.to_be_bytes().to_vec();
+        for value in [inner, outer, name, 0x001A] {
+            content.extend_from_slice(&value.to_be_bytes());
+        }
+        let inner_classes = class.attribute("InnerClasses", &content);
+        class.class_attribute(inner_classes);
+        let nested = mirrors.define(Arc::new(class.parse()));
+        assert_eq!(nested.modifiers(), 0x001A);
+        assert_eq!(mirrors.get("[Lp/A$B;").unwrap().modifiers(), 0x0412);
+        let a = mirrors.define(klass("p/A", "java/lang/Object", &[]));
+        assert_eq!(a.modifiers(), 0x0001);
+        assert_eq!(mirrors.primitive("void").unwrap().modifiers(), 0x0411);
+    }
+
+    #[test]
+    fn we_can_store_the_static_fields() {
+        let mirrors = Mirrors::new();
+        let mut class = TestClass::new("A", "java/lang/Object");
+        let value = class.long(-3);
+        let constant_value = class.attribute("ConstantValue", &value.to_be_bytes());
+        class.field(0x0018, "L", "J", &[constant_value]);
+        class.field(0x0008, "b", "B", &[]);
+        class.field(0x0008, "c", "C", &[]);
+        class.field(0x0008, "o", "Ljava/lang/Object;", &[]);
+        class.field(0x0008, "n", "Ljava/lang/Object;", &[]);
+        let a = mirrors.define(Arc::new(class.parse()));
+        assert!(a.static_fields().is_none());
+        let a_klass = a.klass().unwrap();
+        a_klass.link_fields(None).unwrap();
+        let statics = a.static_fields().unwrap();
+        let offset = |name, descriptor| a_klass.field_offset(name, descriptor).unwrap();
+        assert_eq!(statics.get(offset("L", "J"), FieldType::Long), -3);
+        statics.put(offset("b", "B"), FieldType::Byte, 0x1ff);
+        assert_eq!(statics.get(offset("b", "B"), FieldType::Byte), -1);
+        statics.put(offset("c", "C"), FieldType::Char, -1);
+        assert_eq!(statics.get(offset("c", "C"), FieldType::Char), 0xffff);
+        let object = offset("o", "Ljava/lang/Object;");
+        statics.put(object, FieldType::Reference, 0x1000);
+        let mut roots = Vec::new();
+        mirrors.for_each_root(&mut |root| roots.push(root));
+        let a_address = Mirrors::address(&a);
+        assert_eq!(
+            roots,
+            [
+                GcRoot::StickyClass(a_address),
+                GcRoot::StaticField {
+                    object: Address::new(0x1000),
+                    klass: a_address
+                }
+            ]
+        );
+        assert!(Arc::ptr_eq(&mirrors.find(a_address).unwrap(), &a));
+
+        // the super class is linked first
+        let b = klass("B", "A", &[]);
+        let mut class = TestClass::new("C", "B");
+        class.field(0, "i", "I", &[]);
+        let c = class.parse();
+        let error = c.link_fields(Some(&b)).map(|_| ()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "java.lang.LinkageError: C is linked before its super class B"
+        );
+    }
+}
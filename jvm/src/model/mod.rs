@@ -1,4 +1,12 @@
+pub mod array_klass;
+pub mod attribute;
+pub mod call_site;
 pub mod constant_pool;
 pub mod field;
+pub mod field_layout;
 pub mod instance_klass;
 pub mod method;
+pub mod method_handles;
+pub mod mirror;
+pub mod object_model;
+pub mod reflection;
@@ -0,0 +1,242 @@
+//! The layout of the objects as the collector sees it: the klass pointer of an object
+//! is the address of the mirror of its class, which gives the size of the object and
+//! the offsets of its references.
+use std::sync::Arc;
+
+use gc::{Address, GcRoot, KlassDescription, ObjectModel, Value};
+
+use crate::utilities::{
+    access_flags::JVM_ACC_STATIC,
+    definition::{jint, jlong},
+};
+
+use super::{
+    array_klass::ARRAY_LENGTH_OFFSET,
+    field_layout::{FieldType, KLASS_POINTER_OFFSET},
+    mirror::{Mirror, Mirrors},
+};
+
+pub struct VmObjectModel {
+    mirrors: Arc<Mirrors>,
+}
+
+impl VmObjectModel {
+    pub fn new(mirrors: Arc<Mirrors>) -> Self {
+        VmObjectModel { mirrors }
+    }
+
+    fn mirror(&self, object: Address) -> Arc<Mirror> {
+        self.mirrors
+            .find(self.klass(object))
+            .expect("objects point to the mirror of their class.")
+    }
+}
+
+/// Register the object model of the mirrors with the collector, once when the vm
+/// starts. Returns false when a model is already registered.
+pub fn register(mirrors: Arc<Mirrors>) -> bool {
+    gc::set_object_model(Box::new(VmObjectModel::new(mirrors)))
+}
+
+impl ObjectModel for VmObjectModel {
+    fn object_size(&self, object: Address) -> usize {
+        match &*self.mirror(object) {
+            Mirror::Instance(klass, _) => {
+                let layout = klass.field_layout();
+                layout
+                    .expect("instances are created after their class is linked.")
+                    .instance_size() as usize
+            }
+            Mirror::Array(klass) => {
+                let length = object.plus(ARRAY_LENGTH_OFFSET as usize).load::<jint>();
+                klass
+                    .object_size(length)
+                    .expect("arrays have a positive length.")
+            }
+            Mirror::Primitive(_) => unreachable!("primitive types have no instances."),
+        }
+    }
+
+    fn klass(&self, object: Address) -> Address {
+        object.plus(KLASS_POINTER_OFFSET as usize).load::<Address>()
+    }
+
+    fn is_klass(&self, klass: Address) -> bool {
+        self.mirrors.find(klass).is_some()
+    }
+
+    fn for_each_reference(&self, object: Address, visitor: &mut dyn FnMut(Address)) {
+        match &*self.mirror(object) {
+            Mirror::Instance(klass, _) => {
+                if let Some(layout) = klass.field_layout() {
+                    for &offset in layout.reference_offsets() {
+                        visitor(object.plus(offset as usize));
+                    }
+                }
+            }
+            Mirror::Array(klass) if klass.component_type() == FieldType::Reference => {
+                let length = object.plus(ARRAY_LENGTH_OFFSET as usize).load::<jint>();
+                for index in 0..length.max(0) as usize {
+                    visitor(object.plus(klass.component_offset(index)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn for_each_root(&self, visitor: &mut dyn FnMut(GcRoot)) {
+        self.mirrors.for_each_root(visitor);
+    }
+
+    fn describe_klass(&self, klass: Address) -> Option<KlassDescription> {
+        let mirror = self.mirrors.find(klass)?;
+        let super_klass = mirror
+            .super_name()
+            .and_then(|name| self.mirrors.get(name))
+            .map_or(Address::zero(), |mirror| Mirrors::address(&mirror));
+        let mut description = KlassDescription {
+            name: mirror.descriptor(),
+            super_klass,
+            class_loader: Address::zero(),
+            instance_size: 0,
+            static_fields: Vec::new(),
+            instance_fields: Vec::new(),
+        };
+        let Some(klass) = mirror.klass() else {
+            return Some(description);
+        };
+        description.name = klass.name().to_string();
+        let (Some(layout), Some(statics)) = (klass.field_layout(), mirror.static_fields()) else {
+            return Some(description);
+        };
+        description.instance_size = layout.instance_size();
+        for (index, field) in klass.fields().iter().enumerate() {
+            let name = klass.field_name(field).to_string();
+            let field_type = klass.field_type(field);
+            if field.flags() & JVM_ACC_STATIC != 0 {
+                let value = statics.get(layout.offset(index), field_type);
+                description
+                    .static_fields
+                    .push((name, value_of(field_type, value)));
+            } else {
+                let basic_type = value_of(field_type, 0).basic_type();
+                description.instance_fields.push((name, basic_type));
+            }
+        }
+        Some(description)
+    }
+}
+
+/// The value of a field as read by `StaticFields::get`.
+fn value_of(field_type: FieldType, value: jlong) -> Value {
+    match field_type {
+        FieldType::Boolean => Value::Boolean(value != 0),
+        FieldType::Byte => Value::Byte(value as i8),
+        FieldType::Char => Value::Char(value as u16),
+        FieldType::Short => Value::Short(value as i16),
+        FieldType::Int => Value::Int(value as jint),
+        FieldType::Float => Value::Float(f32::from_bits(value as u32)),
+        FieldType::Long => Value::Long(value),
+        FieldType::Double => Value::Double(f64::from_bits(value as u64)),
+        FieldType::Reference => Value::Object(Address::new(value as usize)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use gc::{Address, BasicType, GcRoot, ObjectModel, Value};
+
+    use crate::{
+        classfile::test_class::TestClass,
+        model::{
+            array_klass::ARRAY_LENGTH_OFFSET,
+            field_layout::{FieldType, KLASS_POINTER_OFFSET},
+            mirror::Mirrors,
+        },
+        utilities::definition::jint,
+    };
+
+    use super::VmObjectModel;
+
+    #[test]
+    fn we_can_trace_the_objects_of_the_mirrors() {
+        let mirrors = Arc::new(Mirrors::new());
+        let object = mirrors.define(Arc::new(TestClass::new("java/lang/Object", "").parse()));
+        object.klass().unwrap().link_fields(None).unwrap();
+        let mut class = TestClass::new("A", "java/lang/Object");
+        class.field(0, "i", "I", &[]);
+        class.field(0, "o", "Ljava/lang/Object;", &[]);
+        class.field(0x0008, "s", "Ljava/lang/Object;", &[]);
+        let a = mirrors.define(Arc::new(class.parse()));
+        let a_klass = a.klass().unwrap();
+        a_klass
+            .link_fields(object.klass().map(|klass| &**klass))
+            .unwrap();
+        let model = VmObjectModel::new(mirrors.clone());
+
+        // an instance, outside of the heap
+        let mut words = [0usize; 8];
+        let instance = Address::new(words.as_mut_ptr() as usize);
+        let klass = Mirrors::address(&a);
+        instance.plus(KLASS_POINTER_OFFSET as usize).store(klass);
+        assert_eq!(model.klass(instance), klass);
+        assert!(model.is_klass(klass));
+        assert!(!model.is_klass(Address::new(0x1000)));
+        let layout = a_klass.field_layout().unwrap();
+        assert_eq!(model.object_size(instance), layout.instance_size() as usize);
+        let mut references = Vec::new();
+        model.for_each_reference(instance, &mut |slot| references.push(slot));
+        let o = a_klass.field_offset("o", "Ljava/lang/Object;").unwrap();
+        assert_eq!(references, [instance.plus(o as usize)]);
+
+        // an array of references
+        let objects = mirrors.get("[Ljava/lang/Object;").unwrap();
+        let objects_klass = objects.array_klass().unwrap();
+        let array = Address::new(words.as_mut_ptr() as usize);
+        array
+            .plus(KLASS_POINTER_OFFSET as usize)
+            .store(Mirrors::address(&objects));
+        array.plus(ARRAY_LENGTH_OFFSET as usize).store::<jint>(2);
+        assert_eq!(
+            model.object_size(array),
+            objects_klass.object_size(2).unwrap()
+        );
+        let mut references = Vec::new();
+        model.for_each_reference(array, &mut |slot| references.push(slot));
+        let slots: Vec<Address> = (0..2)
+            .map(|index| array.plus(objects_klass.component_offset(index)))
+            .collect();
+        assert_eq!(references, slots);
+
+        // the statics are roots and are dumped with their class
+        let statics = a.static_fields().unwrap();
+        let s = a_klass.field_offset("s", "Ljava/lang/Object;").unwrap();
+        statics.put(s, FieldType::Reference, 0x2000);
+        let mut roots = Vec::new();
+        model.for_each_root(&mut |root| roots.push(root));
+        assert!(roots.contains(&GcRoot::StaticField {
+            object: Address::new(0x2000),
+            klass
+        }));
+        assert!(roots.contains(&GcRoot::StickyClass(Mirrors::address(&object))));
+        let description = model.describe_klass(klass).unwrap();
+        assert_eq!(description.name, "A");
+        assert_eq!(description.super_klass, Mirrors::address(&object));
+        assert_eq!(description.instance_size, layout.instance_size());
+        assert_eq!(
+            description.static_fields,
+            [("s".to_string(), Value::Object(Address::new(0x2000)))]
+        );
+        assert_eq!(
+            description.instance_fields,
+            [
+                ("i".to_string(), BasicType::Int),
+                ("o".to_string(), BasicType::Object)
+            ]
+        );
+        let description = model.describe_klass(Mirrors::address(&objects)).unwrap();
+        assert_eq!(description.name, "[Ljava/lang/Object;");
+    }
+}
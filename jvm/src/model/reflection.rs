@@ -0,0 +1,202 @@
+//! Core reflection: the declared members the natives of `Class` will return, and the
+//! checks `Method.invoke` and `Constructor.newInstance` will make before the call. The
+//! natives themselves are not registered, the vm has no interpreter to call them yet.
+use crate::utilities::{
+    access_flags::{JVM_ACC_ABSTRACT, JVM_ACC_INTERFACE, JVM_ACC_PUBLIC, JVM_ACC_STATIC},
+    definition::u2,
+    exceptions::Exception,
+};
+
+use super::{
+    instance_klass::InstanceKlass,
+    method::Method,
+    mirror::{Mirror, Mirrors},
+};
+
+/// Whether the member is kept by the `publicOnly` argument of the natives.
+fn is_visible(flags: u2, public_only: bool) -> bool {
+    !public_only || flags & JVM_ACC_PUBLIC != 0
+}
+
+/// Indexes of the fields of `getDeclaredFields0`, none for arrays and primitive types.
+pub fn declared_fields(mirror: &Mirror, public_only: bool) -> Vec<usize> {
+    let Some(klass) = mirror.klass() else {
+        return Vec::new();
+    };
+    (0..klass.fields().len())
+        .filter(|&index| is_visible(klass.fields()[index].flags(), public_only))
+        .collect()
+}
+
+/// Indexes of the methods of `getDeclaredMethods0`, without the constructors and the
+/// class initializer.
+pub fn declared_methods(mirror: &Mirror, public_only: bool) -> Vec<usize> {
+    declared_methods_where(mirror, public_only, |name| {
+        name != "<init>" && name != "<clinit>"
+    })
+}
+
+/// Indexes of the constructors of `getDeclaredConstructors0`. Interfaces have none, the
+/// parser rejects an `<init>` method in them.
+pub fn declared_constructors(mirror: &Mirror, public_only: bool) -> Vec<usize> {
+    declared_methods_where(mirror, public_only, |name| name == "<init>")
+}
+
+fn declared_methods_where(
+    mirror: &Mirror,
+    public_only: bool,
+    name_matches: fn(&str) -> bool,
+) -> Vec<usize> {
+    let Some(klass) = mirror.klass() else {
+        return Vec::new();
+    };
+    let methods = klass.methods();
+    (0..methods.len())
+        .filter(|&index| {
+            let method = &methods[index];
+            is_visible(method.flags(), public_only) && name_matches(klass.method_name(method))
+        })
+        .collect()
+}
+
+/// The number of parameters of a method descriptor, checked by the parser.
+fn parameter_count(descriptor: &str) -> usize {
+    let parameters = &descriptor[1..descriptor.find(')').unwrap_or(1)];
+    let mut count = 0;
+    let mut bytes = parameters.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'[' => continue,
+            b'L' => {
+                bytes.by_ref().find(|&byte| byte == b';');
+            }
+            _ => {}
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Check the receiver and the arguments of `Method.invoke`: the receiver of an instance
+/// method is an instance of the declaring class, `None` for null. The declaring class
+/// has a mirror once it is loaded.
+pub fn check_invoke(
+    mirrors: &Mirrors,
+    klass: &InstanceKlass,
+    method: &Method,
+    receiver: Option<&Mirror>,
+    arguments: usize,
+) -> Result<(), Exception> {
+    if method.flags() & JVM_ACC_STATIC == 0 {
+        let receiver = receiver.ok_or_else(|| Exception::NullPointerException(String::new()))?;
+        let declaring_class = mirrors
+            .get(klass.name())
+            .ok_or_else(|| Exception::NoClassDefFoundError(klass.name().to_string()))?;
+        if !mirrors.is_assignable(&declaring_class, receiver) {
+            return Err(Exception::IllegalArgumentException(
+                "object is not an instance of declaring class".to_string(),
+            ));
+        }
+    }
+    check_arguments(klass, method, arguments)
+}
+
+/// Check the class and the arguments of `Constructor.newInstance`, abstract classes and
+/// interfaces cannot be instantiated.
+pub fn check_new_instance(
+    klass: &InstanceKlass,
+    constructor: &Method,
+    arguments: usize,
+) -> Result<(), Exception> {
+    if klass.access_flags() & (JVM_ACC_ABSTRACT | JVM_ACC_INTERFACE) != 0 {
+        return Err(Exception::InstantiationException(
+            klass.name().replace('/', "."),
+        ));
+    }
+    check_arguments(klass, constructor, arguments)
+}
+
+fn check_arguments(
+    klass: &InstanceKlass,
+    method: &Method,
+    arguments: usize,
+) -> Result<(), Exception> {
+    if parameter_count(klass.method_descriptor(method)) != arguments {
+        return Err(Exception::IllegalArgumentException(
+            "wrong number of arguments".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{classfile::test_class::TestClass, model::mirror::Mirrors};
+
+    use super::{
+        check_invoke, check_new_instance, declared_constructors, declared_fields, declared_methods,
+    };
+
+    #[test]
+    fn we_can_reflect_on_the_declared_members() {
+        let mirrors = Mirrors::new();
+        let mut class = TestClass::new("A", "java/lang/Object");
+        class.field(0x0001, "a", "I", &[]);
+        class.field(0x0002, "b", "J", &[]);
+        let code = [0xB1];
+        class.method(0x0001, "<init>", "()V", 0, 1, &code, &[], None);
+        class.method(0x0008, "<clinit>", "()V", 0, 0, &code, &[], None);
+        class.method(
+            0x0001,
+            "m",
+            "(I[[JLjava/lang/String;D)V",
+            0,
+            6,
+            &code,
+            &[],
+            None,
+        );
+        class.method(0x000A, "n", "()V", 0, 0, &code, &[], None);
+        let a = mirrors.define(Arc::new(class.parse()));
+        assert_eq!(declared_fields(&a, false), [0, 1]);
+        assert_eq!(declared_fields(&a, true), [0]);
+        assert_eq!(declared_methods(&a, false), [2, 3]);
+        assert_eq!(declared_methods(&a, true), [2]);
+        assert_eq!(declared_constructors(&a, true), [0]);
+        assert!(declared_methods(&mirrors.primitive("int").unwrap(), false).is_empty());
+
+        let klass = a.klass().unwrap();
+        let methods = klass.methods();
+        assert_eq!(
+            check_invoke(&mirrors, klass, &methods[2], Some(&a), 4),
+            Ok(())
+        );
+        let error = check_invoke(&mirrors, klass, &methods[2], Some(&a), 3).unwrap_err();
+        assert_eq!(error.message(), "wrong number of arguments");
+        let error = check_invoke(&mirrors, klass, &methods[2], None, 4).unwrap_err();
+        assert_eq!(error.class_name(), "java/lang/NullPointerException");
+        let ints = mirrors.get("[I").unwrap();
+        let error = check_invoke(&mirrors, klass, &methods[2], Some(&ints), 4).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "object is not an instance of declaring class"
+        );
+        assert_eq!(check_invoke(&mirrors, klass, &methods[3], None, 0), Ok(()));
+        assert_eq!(check_new_instance(klass, &methods[0], 0), Ok(()));
+
+        // the method of a class that is not loaded
+        let mut class = TestClass::new("C", "java/lang/Object");
+        class.method(0x0001, "m", "()V", 0, 1, &code, &[], None);
+        let c = class.parse();
+        let error = check_invoke(&mirrors, &c, &c.methods()[0], Some(&a), 0).unwrap_err();
+        assert_eq!(error.to_string(), "java.lang.NoClassDefFoundError: C");
+
+        let mut class = TestClass::new("B", "java/lang/Object");
+        class.access_flags(0x0421);
+        let b = class.parse();
+        let error = check_new_instance(&b, &methods[0], 0).unwrap_err();
+        assert_eq!(error.to_string(), "java.lang.InstantiationException: B");
+    }
+}
@@ -0,0 +1,28 @@
+//! The access flags of classes, fields, methods and modules in class files.
+use super::definition::u2;
+
+pub const JVM_ACC_PUBLIC: u2 = 0x0001;
+pub const JVM_ACC_PRIVATE: u2 = 0x0002;
+pub const JVM_ACC_PROTECTED: u2 = 0x0004;
+pub const JVM_ACC_STATIC: u2 = 0x0008;
+pub const JVM_ACC_FINAL: u2 = 0x0010;
+pub const JVM_ACC_SYNCHRONIZED: u2 = 0x0020;
+pub const JVM_ACC_SUPER: u2 = 0x0020;
+pub const JVM_ACC_VOLATILE: u2 = 0x0040;
+pub const JVM_ACC_BRIDGE: u2 = 0x0040;
+pub const JVM_ACC_TRANSIENT: u2 = 0x0080;
+pub const JVM_ACC_VARARGS: u2 = 0x0080;
+pub const JVM_ACC_NATIVE: u2 = 0x0100;
+pub const JVM_ACC_INTERFACE: u2 = 0x0200;
+pub const JVM_ACC_ABSTRACT: u2 = 0x0400;
+pub const JVM_ACC_STRICT: u2 = 0x0800;
+pub const JVM_ACC_SYNTHETIC: u2 = 0x1000;
+pub const JVM_ACC_ANNOTATION: u2 = 0x2000;
+pub const JVM_ACC_ENUM: u2 = 0x4000;
+pub const JVM_ACC_MODULE: u2 = 0x8000;
+
+/// The flags of a module and of its `requires`, `exports` and `opens` directives.
+pub const JVM_ACC_OPEN: u2 = 0x0020;
+pub const JVM_ACC_TRANSITIVE: u2 = 0x0020;
+pub const JVM_ACC_STATIC_PHASE: u2 = 0x0040;
+pub const JVM_ACC_MANDATED: u2 = 0x8000;
@@ -8,7 +8,8 @@ pub type jshort = i16;
 pub type jint = i32;
 pub type jlong = i64;
 
-pub type jubyte = u8;
+// `u8` is the alias above in this module
+pub type jubyte = std::primitive::u8;
 pub type jushort = u16;
 pub type juint = u32;
 pub type julong = u64;
@@ -0,0 +1,91 @@
+use std::fmt;
+
+/// A Java exception thrown by the VM while loading, verifying or linking a class, or
+/// by its natives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum Exception {
+    ClassFormatError(String),
+    UnsupportedClassVersionError(String),
+    VerifyError(String),
+    ClassNotFoundException(String),
+    IllegalArgumentException(String),
+    InstantiationException(String),
+    NullPointerException(String),
+    ClassCastException(String),
+    ArrayStoreException(String),
+    NegativeArraySizeException(String),
+    OutOfMemoryError(String),
+    BootstrapMethodError(String),
+    NoClassDefFoundError(String),
+    LinkageError(String),
+    IncompatibleClassChangeError(String),
+    NoSuchFieldError(String),
+    NoSuchMethodError(String),
+    UnsupportedOperationException(String),
+    WrongMethodTypeException(String),
+}
+
+impl Exception {
+    /// Internal name of the class of the exception.
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            Exception::ClassFormatError(_) => "java/lang/ClassFormatError",
+            Exception::UnsupportedClassVersionError(_) => "java/lang/UnsupportedClassVersionError",
+            Exception::VerifyError(_) => "java/lang/VerifyError",
+            Exception::ClassNotFoundException(_) => "java/lang/ClassNotFoundException",
+            Exception::IllegalArgumentException(_) => "java/lang/IllegalArgumentException",
+            Exception::InstantiationException(_) => "java/lang/InstantiationException",
+            Exception::NullPointerException(_) => "java/lang/NullPointerException",
+            Exception::ClassCastException(_) => "java/lang/ClassCastException",
+            Exception::ArrayStoreException(_) => "java/lang/ArrayStoreException",
+            Exception::NegativeArraySizeException(_) => "java/lang/NegativeArraySizeException",
+            Exception::OutOfMemoryError(_) => "java/lang/OutOfMemoryError",
+            Exception::BootstrapMethodError(_) => "java/lang/BootstrapMethodError",
+            Exception::NoClassDefFoundError(_) => "java/lang/NoClassDefFoundError",
+            Exception::LinkageError(_) => "java/lang/LinkageError",
+            Exception::IncompatibleClassChangeError(_) => "java/lang/IncompatibleClassChangeError",
+            Exception::NoSuchFieldError(_) => "java/lang/NoSuchFieldError",
+            Exception::NoSuchMethodError(_) => "java/lang/NoSuchMethodError",
+            Exception::UnsupportedOperationException(_) => {
+                "java/lang/UnsupportedOperationException"
+            }
+            Exception::WrongMethodTypeException(_) => "java/lang/invoke/WrongMethodTypeException",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Exception::ClassFormatError(message)
+            | Exception::UnsupportedClassVersionError(message)
+            | Exception::VerifyError(message)
+            | Exception::ClassNotFoundException(message)
+            | Exception::IllegalArgumentException(message)
+            | Exception::InstantiationException(message)
+            | Exception::NullPointerException(message)
+            | Exception::ClassCastException(message)
+            | Exception::ArrayStoreException(message)
+            | Exception::NegativeArraySizeException(message)
+            | Exception::OutOfMemoryError(message)
+            | Exception::BootstrapMethodError(message)
+            | Exception::NoClassDefFoundError(message)
+            | Exception::LinkageError(message)
+            | Exception::IncompatibleClassChangeError(message)
+            | Exception::NoSuchFieldError(message)
+            | Exception::NoSuchMethodError(message)
+            | Exception::UnsupportedOperationException(message)
+            | Exception::WrongMethodTypeException(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.class_name().replace('/', "."),
+            self.message()
+        )
+    }
+}
@@ -1 +1,3 @@
+pub mod access_flags;
 pub mod definition;
+pub mod exceptions;
@@ -0,0 +1,352 @@
+//! The `jimage` tool: lists, extracts, describes and verifies the resources of jimage
+//! files, with the commands and output of the JDK tool.
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+use lib::jimage::{JImage, JImageError, ResourceEntry};
+
+const USAGE: &str = "\
+Usage: jimage <extract | info | list | verify> <options> jimage...
+
+  extract  - Extract all jimage entries and place in a directory specified
+             by the --dir=<directory> (default=.) option.
+
+  info     - Prints detailed information contained in the jimage header.
+
+  list     - Prints the names of all the entries in the jimage.  When used with
+             --verbose, list will also print entry size and offset attributes.
+
+  verify   - Decompresses every entry and reports the entries whose size does
+             not match their location attributes.
+
+Possible options include:
+          --dir                        Target directory for extract directive
+  -?, -h, --help                       Print this help message
+          --verbose                    Listing prints entry size and offset
+                                       attributes
+          --version                    Print version information";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Task {
+    Extract,
+    Info,
+    List,
+    Verify,
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    task: Task,
+    dir: PathBuf,
+    verbose: bool,
+    images: Vec<String>,
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        task: Task::Help,
+        dir: PathBuf::from("."),
+        verbose: false,
+        images: Vec::new(),
+    };
+    let mut task = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-?" | "-h" | "--help" => return Ok(options),
+            "--version" => {
+                options.task = Task::Version;
+                return Ok(options);
+            }
+            "--verbose" => options.verbose = true,
+            "--dir" => {
+                let dir = args.next().ok_or("no value given for --dir")?;
+                options.dir = PathBuf::from(dir);
+            }
+            _ if arg.starts_with("--dir=") => {
+                options.dir = PathBuf::from(&arg["--dir=".len()..]);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if task.is_none() => {
+                task = Some(match arg.as_str() {
+                    "extract" => Task::Extract,
+                    "info" => Task::Info,
+                    "list" => Task::List,
+                    "verify" => Task::Verify,
+                    _ => return Err(format!("unknown task: {}", arg)),
+                })
+            }
+            _ => options.images.push(arg),
+        }
+    }
+    options.task = task.ok_or("no task specified")?;
+    if options.images.is_empty() {
+        return Err("no jimage file specified".to_string());
+    }
+    Ok(options)
+}
+
+/// The resources of the image sorted by their full path like the JDK tool, so
+/// `java.management.rmi` comes before `java.management`.
+fn sorted_entries(image: &JImage) -> Vec<ResourceEntry> {
+    let mut entries: Vec<(String, ResourceEntry)> = image
+        .resources()
+        .map(|entry| (entry.path(), entry))
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn list(image: &JImage, path: &str, verbose: bool) {
+    println!("jimage: {}", path);
+    let mut module = None;
+    for entry in sorted_entries(image) {
+        if module.as_ref() != Some(&entry.module) {
+            println!();
+            println!("Module: {}", entry.module);
+            if verbose {
+                println!("Offset       Size       Compressed Entry");
+            }
+            module = Some(entry.module.clone());
+        }
+        if verbose {
+            println!(
+                "{:>12} {:>10} {:>10} {}",
+                entry.offset,
+                entry.size,
+                entry.compressed_size,
+                entry.name()
+            );
+        } else {
+            println!("    {}", entry.name());
+        }
+    }
+}
+
+/// Where the resource is extracted to under the directory, an error for a module or a
+/// name that would escape it, e.g. with `..` or an absolute path.
+fn extract_path(dir: &Path, module: &str, name: &str) -> Result<PathBuf, String> {
+    let mut target = dir.to_path_buf();
+    for part in [module, name] {
+        let path = Path::new(part);
+        if part.is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!("{}/{}: invalid entry name", module, name));
+        }
+        target.push(path);
+    }
+    Ok(target)
+}
+
+fn extract(image: &JImage, dir: &Path) -> Result<(), String> {
+    for entry in image {
+        let target = extract_path(dir, &entry.module, &entry.name())?;
+        let data = image
+            .read(&entry)
+            .map_err(|error| format!("{}: {}", entry.path(), error))?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("{}: {}", parent.display(), error))?;
+        }
+        fs::write(&target, data).map_err(|error| format!("{}: {}", target.display(), error))?;
+    }
+    Ok(())
+}
+
+fn info(image: &JImage) -> Result<(), JImageError> {
+    let header = image.header();
+    let table_size = header.table_length() as usize * 4;
+    let field = |name: &str, value: &dyn std::fmt::Display| println!(" {:<16}{}", name, value);
+    field("Major Version:", &header.major_version());
+    field("Minor Version:", &header.minor_version());
+    field("Flags:", &header.flags());
+    field("Resource Count:", &header.resource_count());
+    field("Table Length:", &header.table_length());
+    field("Offsets Size:", &table_size);
+    field("Redirects Size:", &table_size);
+    field("Locations Size:", &header.locations_size());
+    field("Strings Size:", &header.string_size());
+    field("Index Size:", &image.index_size());
+
+    let mut resources = 0;
+    let mut decompressors: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in image.entries() {
+        let entry = entry?;
+        resources += 1;
+        if let Some(name) = image.decompressor(&entry)? {
+            *decompressors.entry(name).or_default() += 1;
+        }
+    }
+    let compressed: usize = decompressors.values().sum();
+    let used: Vec<String> = decompressors
+        .iter()
+        .map(|(name, count)| format!("{} ({})", name, count))
+        .collect();
+    field("Modules:", &image.modules().len());
+    field("Resources:", &resources);
+    field("Compressed:", &compressed);
+    field(
+        "Decompressors:",
+        &if used.is_empty() {
+            "none".to_string()
+        } else {
+            used.join(", ")
+        },
+    );
+    Ok(())
+}
+
+/// Decompress every resource, returns whether all of them are valid.
+fn verify(image: &JImage, path: &str) -> bool {
+    println!("jimage: {}", path);
+    let mut resources = 0;
+    let mut errors = 0;
+    for entry in image.entries() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                println!("Error(s) in {}: {}", path, error);
+                errors += 1;
+                continue;
+            }
+        };
+        resources += 1;
+        let error = match image.read(&entry) {
+            Err(error) => Some(error.to_string()),
+            Ok(data) if data.len() as u64 != entry.size => Some(format!(
+                "decompressed to {} bytes instead of {}",
+                data.len(),
+                entry.size
+            )),
+            Ok(data)
+                if entry.extension == "class" && !data.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE]) =>
+            {
+                Some("not a class file".to_string())
+            }
+            Ok(_) => None,
+        };
+        if let Some(error) = error {
+            println!("Error(s) in {}: {}", entry.path(), error);
+            errors += 1;
+        }
+    }
+    println!(" {} resources verified, {} errors", resources, errors);
+    errors == 0
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    match options.task {
+        Task::Help => {
+            println!("{}", USAGE);
+            return;
+        }
+        Task::Version => {
+            println!("jimage {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        _ => {}
+    }
+    let mut success = true;
+    for path in &options.images {
+        let image = match JImage::open(path) {
+            Ok(image) => image,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                success = false;
+                continue;
+            }
+        };
+        let result = match options.task {
+            Task::List => {
+                list(&image, path, options.verbose);
+                Ok(())
+            }
+            Task::Extract => extract(&image, &options.dir),
+            Task::Info => info(&image).map_err(|error| error.to_string()),
+            Task::Verify => {
+                success &= verify(&image, path);
+                Ok(())
+            }
+            Task::Help | Task::Version => unreachable!(),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {}", error);
+            success = false;
+        }
+    }
+    if !success {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{extract_path, parse_options, Options, Task};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn we_can_parse_the_options() {
+        assert_eq!(
+            parse(&["extract", "--dir=out", "a/modules", "b/modules"]),
+            Ok(Options {
+                task: Task::Extract,
+                dir: PathBuf::from("out"),
+                verbose: false,
+                images: vec!["a/modules".to_string(), "b/modules".to_string()],
+            })
+        );
+        let options = parse(&["list", "--verbose", "--dir", "out", "modules"]).unwrap();
+        assert!(options.task == Task::List && options.verbose && options.dir == *"out");
+        assert_eq!(
+            parse(&["list", "-h"]).map(|options| options.task),
+            Ok(Task::Help)
+        );
+        assert!(parse(&["list"]).is_err());
+        assert!(parse(&["unpack", "modules"]).is_err());
+        assert!(parse(&["list", "--include", "modules"]).is_err());
+    }
+
+    #[test]
+    fn we_only_extract_below_the_directory() {
+        let dir = Path::new("out");
+        assert_eq!(
+            extract_path(dir, "java.base", "java/lang/Object.class"),
+            Ok(PathBuf::from("out/java.base/java/lang/Object.class"))
+        );
+        for (module, name) in [
+            ("java.base", "../../../etc/passwd"),
+            ("java.base", "java/../../../etc/passwd"),
+            ("java.base", "/etc/passwd"),
+            ("..", "etc/passwd"),
+            ("/tmp", "Object.class"),
+            ("", "Object.class"),
+            ("java.base", "./Object.class"),
+        ] {
+            assert_eq!(
+                extract_path(dir, module, name),
+                Err(format!("{}/{}: invalid entry name", module, name))
+            );
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::{borrow::Cow, path::Path, sync::Arc};
+
+use super::{
+    image_file::{ImageFileReader, ImageHeader, ImageLocation},
+    jimage_error::JImageError,
+};
+
+/// A jimage file, e.g. the `lib/modules` file of a java runtime.
+///
+/// Images are shared, opening the same path twice returns the same mapping.
+#[derive(Clone)]
+pub struct JImage {
+    reader: Arc<ImageFileReader>,
+}
+
+/// A resource of an image, e.g. `/java.base/java/lang/Object.class`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceEntry {
+    /// Module of the resource, e.g. `java.base`.
+    pub module: String,
+    /// Directory of the resource in the module, e.g. `java/lang`.
+    pub parent: String,
+    /// File name of the resource without its extension, e.g. `Object`.
+    pub base: String,
+    /// Extension of the resource without the dot, e.g. `class`.
+    pub extension: String,
+    /// Offset of the stored resource from the end of the index.
+    pub offset: u64,
+    /// Size of the resource content.
+    pub size: u64,
+    /// Size of the resource in the image, zero when it is not compressed.
+    pub compressed_size: u64,
+    /// Release of a multi-release resource, stored under `META-INF/versions/<n>/` of
+    /// its module.
+    pub version: Option<u32>,
+    location: u32,
+}
+
+impl ResourceEntry {
+    /// The path of the resource in its module, e.g. `java/lang/Object.class`.
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        if !self.parent.is_empty() {
+            name.push_str(&self.parent);
+            name.push('/');
+        }
+        name.push_str(&self.base);
+        if !self.extension.is_empty() {
+            name.push('.');
+            name.push_str(&self.extension);
+        }
+        name
+    }
+
+    /// The full path of the resource, e.g. `/java.base/java/lang/Object.class`.
+    pub fn path(&self) -> String {
+        format!("/{}/{}", self.module, self.name())
+    }
+
+    /// The package of the resource, the parent without the versions directory of a
+    /// multi-release resource.
+    pub fn package(&self) -> &str {
+        JImage::split_version(&self.parent).1
+    }
+}
+
+impl JImage {
+    /// Module of the directory entries listing the modules of the image.
+    const MODULES: &'static str = "modules";
+    /// Module of the directory entries mapping the packages to their modules.
+    const PACKAGES: &'static str = "packages";
+    /// Directory of the multi-release resources of a module.
+    const VERSIONS: &'static str = "META-INF/versions/";
+    /// The release of the unversioned resources, the first release with images.
+    pub const BASE_VERSION: u32 = 9;
+
+    /// Open the image file at the path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JImage, JImageError> {
+        let name = path.as_ref().to_string_lossy().into_owned();
+        ImageFileReader::open(name).map(|reader| JImage { reader })
+    }
+
+    /// The content of the resource at the path in the module, e.g.
+    /// `image.resource("java.base", "java/lang/Object.class")`. Uncompressed resources
+    /// are borrowed from the image.
+    pub fn resource(&self, module: &str, path: &str) -> Result<Cow<'_, [u8]>, JImageError> {
+        let full_path = format!("/{}/{}", module, path);
+        let (location, _) = self
+            .reader
+            .find_location_index(full_path.clone())
+            .ok_or(JImageError::ResourceNotFound(full_path))?;
+        self.reader.read_data(self.reader.get_location(location)?)
+    }
+
+    /// The content of the resource as seen by the release, the resource of the highest
+    /// version directory up to the release is preferred to the unversioned resource.
+    pub fn versioned_resource(
+        &self,
+        module: &str,
+        path: &str,
+        version: u32,
+    ) -> Result<Cow<'_, [u8]>, JImageError> {
+        let location = JImage::versioned_paths(module, path, version)
+            .find_map(|full_path| self.reader.find_location_index(full_path))
+            .map(|(location, _)| location)
+            .ok_or_else(|| JImageError::ResourceNotFound(format!("/{}/{}", module, path)))?;
+        self.reader.read_data(self.reader.get_location(location)?)
+    }
+
+    /// The paths to look up for the resource as seen by the release, highest version
+    /// first.
+    pub(super) fn versioned_paths<'a>(
+        module: &'a str,
+        path: &'a str,
+        version: u32,
+    ) -> impl Iterator<Item = String> + 'a {
+        (JImage::BASE_VERSION..=version)
+            .rev()
+            .map(move |version| format!("/{}/{}{}/{}", module, JImage::VERSIONS, version, path))
+            .chain(std::iter::once(format!("/{}/{}", module, path)))
+    }
+
+    /// Split the version of a multi-release parent directory, e.g.
+    /// `META-INF/versions/11/java/lang` is `java/lang` of release 11.
+    fn split_version(parent: &str) -> (Option<u32>, &str) {
+        let versioned = parent.strip_prefix(JImage::VERSIONS).and_then(|rest| {
+            let (version, package) = rest.split_once('/').unwrap_or((rest, ""));
+            version.parse().ok().map(|version| (Some(version), package))
+        });
+        versioned.unwrap_or((None, parent))
+    }
+
+    /// The content of a resource returned by the iterator.
+    pub fn read(&self, entry: &ResourceEntry) -> Result<Cow<'_, [u8]>, JImageError> {
+        self.reader
+            .read_data(self.reader.get_location(entry.location)?)
+    }
+
+    /// The header of the image.
+    pub fn header(&self) -> &ImageHeader {
+        self.reader.header()
+    }
+
+    /// Size of the header and the index tables of the image.
+    pub fn index_size(&self) -> usize {
+        self.reader.index_size()
+    }
+
+    /// Name of the outermost decompressor of a resource, `None` when the resource is
+    /// not compressed.
+    pub fn decompressor(&self, entry: &ResourceEntry) -> Result<Option<String>, JImageError> {
+        self.reader
+            .decompressor_name(&self.reader.get_location(entry.location)?)
+    }
+
+    /// The names of the modules in the image, sorted.
+    pub fn modules(&self) -> Vec<String> {
+        let mut modules: Vec<String> = self
+            .locations()
+            .filter_map(|(offset, location)| {
+                if self
+                    .string(offset, &location, ImageLocation::ATTRIBUTE_MODULE)
+                    .ok()?
+                    != JImage::MODULES
+                {
+                    return None;
+                }
+                let base = self
+                    .string(offset, &location, ImageLocation::ATTRIBUTE_BASE)
+                    .ok()?;
+                (!base.is_empty() && !base.contains('/')).then_some(base)
+            })
+            .collect();
+        modules.sort();
+        modules
+    }
+
+    /// The packages of the module that contain resources, sorted, in internal form,
+    /// e.g. `java/lang`.
+    pub fn packages(&self, module: &str) -> Vec<String> {
+        let strings = self.reader.get_strings();
+        let endian = self.reader.endian();
+        let mut packages: Vec<String> = self
+            .locations()
+            .filter_map(|(offset, location)| {
+                if self
+                    .string(offset, &location, ImageLocation::ATTRIBUTE_MODULE)
+                    .ok()?
+                    != JImage::PACKAGES
+                {
+                    return None;
+                }
+                let package = self
+                    .string(offset, &location, ImageLocation::ATTRIBUTE_BASE)
+                    .ok()?;
+                let data = self.reader.read_data(location).ok()?;
+                // pairs of an is empty flag and the string offset of a module name
+                data.chunks_exact(8)
+                    .any(|pair| {
+                        endian.get_u32(&pair[0..4]) == 0
+                            && strings.get_bytes(endian.get_u32(&pair[4..8]))
+                                == Some(module.as_bytes())
+                    })
+                    .then(|| package.replace('.', "/"))
+            })
+            .collect();
+        packages.sort();
+        packages
+    }
+
+    /// The resources of all modules, the directory entries of the image and malformed
+    /// locations are skipped.
+    pub fn resources(&self) -> Resources<'_> {
+        Resources {
+            entries: self.entries(),
+        }
+    }
+
+    /// The resources of the module.
+    pub fn module_resources(&self, module: &str) -> Resources<'_> {
+        Resources {
+            entries: Entries {
+                module: Some(module.to_string()),
+                ..self.entries()
+            },
+        }
+    }
+
+    /// The resources of all modules, malformed locations are reported as errors.
+    pub fn entries(&self) -> Entries<'_> {
+        Entries {
+            image: self,
+            index: 0,
+            module: None,
+        }
+    }
+
+    /// The locations of the image in table order with their attribute offsets.
+    fn locations(&self) -> impl Iterator<Item = (u32, ImageLocation)> + '_ {
+        (0..self.reader.table_length() as u32).filter_map(|index| {
+            let offset = self.reader.get_location_offset(index)?;
+            self.reader
+                .get_location(offset)
+                .ok()
+                .map(|location| (offset, location))
+        })
+    }
+
+    fn string(
+        &self,
+        offset: u32,
+        location: &ImageLocation,
+        kind: u8,
+    ) -> Result<String, JImageError> {
+        self.reader.get_location_string(offset, location, kind)
+    }
+
+    /// The resource of the location at the index of the tables, `None` for directory
+    /// entries.
+    fn entry(&self, index: u32) -> Result<Option<ResourceEntry>, JImageError> {
+        let offset = self
+            .reader
+            .get_location_offset(index)
+            .ok_or(JImageError::BadLocation(index, "index exceeds the tables"))?;
+        let location = self.reader.get_location(offset)?;
+        let module = self.string(offset, &location, ImageLocation::ATTRIBUTE_MODULE)?;
+        if module.is_empty() || module == JImage::MODULES || module == JImage::PACKAGES {
+            return Ok(None);
+        }
+        let parent = self.string(offset, &location, ImageLocation::ATTRIBUTE_PARENT)?;
+        Ok(Some(ResourceEntry {
+            module,
+            version: JImage::split_version(&parent).0,
+            parent,
+            base: self.string(offset, &location, ImageLocation::ATTRIBUTE_BASE)?,
+            extension: self.string(offset, &location, ImageLocation::ATTRIBUTE_EXTENSION)?,
+            offset: location.get_attribute(ImageLocation::ATTRIBUTE_OFFSET),
+            size: location.get_attribute(ImageLocation::ATTRIBUTE_UNCOMPRESSED),
+            compressed_size: location.get_attribute(ImageLocation::ATTRIBUTE_COMPRESSED),
+            location: offset,
+        }))
+    }
+}
+
+impl From<Arc<ImageFileReader>> for JImage {
+    fn from(reader: Arc<ImageFileReader>) -> Self {
+        JImage { reader }
+    }
+}
+
+impl<'a> IntoIterator for &'a JImage {
+    type Item = ResourceEntry;
+    type IntoIter = Resources<'a>;
+
+    fn into_iter(self) -> Resources<'a> {
+        self.resources()
+    }
+}
+
+/// Iterator over the resources of an image in the order of its tables, malformed
+/// locations are reported as errors.
+pub struct Entries<'a> {
+    image: &'a JImage,
+    index: u32,
+    module: Option<String>,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<ResourceEntry, JImageError>;
+
+    fn next(&mut self) -> Option<Result<ResourceEntry, JImageError>> {
+        while (self.index as usize) < self.image.reader.table_length() {
+            let index = self.index;
+            self.index += 1;
+            match self.image.entry(index) {
+                Ok(Some(entry)) => {
+                    if self.module.is_none() || self.module.as_ref() == Some(&entry.module) {
+                        return Some(Ok(entry));
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => return Some(Err(error)),
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the resources of an image in the order of its tables.
+pub struct Resources<'a> {
+    entries: Entries<'a>,
+}
+
+impl<'a> Iterator for Resources<'a> {
+    type Item = ResourceEntry;
+
+    fn next(&mut self) -> Option<ResourceEntry> {
+        self.entries.by_ref().find_map(Result::ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JImage, ResourceEntry};
+    use crate::jimage::{image_writer::write_test_image, ImageWriter, JImageError};
+
+    fn open(name: &str) -> (JImage, String) {
+        let path = write_test_image(name, ImageWriter::new());
+        (JImage::open(&path).ok().unwrap(), path)
+    }
+
+    #[test]
+    fn we_can_read_a_resource() {
+        let (image, path) = open("image-read");
+        let object = image.resource("java.base", "java/lang/Object.class");
+        assert!(matches!(object, Ok(bytes) if bytes.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE])));
+        assert!(matches!(
+            image.resource("java.base", "java/lang/Missing.class"),
+            Err(JImageError::ResourceNotFound(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_list_modules_and_packages() {
+        let (image, path) = open("image-modules");
+        let modules = image.modules();
+        assert!(modules.contains(&"java.base".to_string()));
+        assert!(!modules.contains(&"java.base/java".to_string()));
+        let packages = image.packages("java.base");
+        assert!(packages.contains(&"java/lang".to_string()));
+        assert!(!packages.contains(&"java/sql".to_string()));
+        assert!(!packages.contains(&"META-INF/services".to_string()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_iterate_resources() {
+        let (image, path) = open("image-resources");
+        let entry = image
+            .resources()
+            .find(|entry| entry.path() == "/java.base/java/lang/Object.class")
+            .expect("java/lang/Object.class should be in the image.");
+        assert_eq!(entry.name(), "java/lang/Object.class");
+        let bytes = image.read(&entry).ok().unwrap();
+        assert_eq!(bytes.len() as u64, entry.size);
+        assert!((&image).into_iter().all(|entry| entry.module != "modules"));
+        assert!((&image).into_iter().all(|entry| entry.module != "packages"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_iterate_the_resources_of_a_module() {
+        let (image, path) = open("image-module-resources");
+        let resources: Vec<ResourceEntry> = image.module_resources("java.sql").collect();
+        assert_eq!(resources.len(), 2);
+        assert!(resources.iter().all(|entry| entry.module == "java.sql"));
+        assert!(image.module_resources("java.logging").next().is_none());
+        assert!(image.entries().all(|entry| entry.is_ok()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn multi_release_resources_have_a_version() {
+        let entry = ResourceEntry {
+            module: "m".to_string(),
+            parent: "META-INF/versions/11/p/q".to_string(),
+            base: "C".to_string(),
+            extension: "class".to_string(),
+            offset: 0,
+            size: 0,
+            compressed_size: 0,
+            version: JImage::split_version("META-INF/versions/11/p/q").0,
+            location: 0,
+        };
+        assert_eq!(entry.version, Some(11));
+        assert_eq!(entry.package(), "p/q");
+        assert_eq!(
+            JImage::split_version("META-INF/versions/x/p"),
+            (None, "META-INF/versions/x/p")
+        );
+        assert_eq!(
+            JImage::split_version("META-INF/versions/17"),
+            (Some(17), "")
+        );
+        let paths: Vec<String> = JImage::versioned_paths("m", "p/C.class", 11).collect();
+        assert_eq!(
+            paths,
+            [
+                "/m/META-INF/versions/11/p/C.class",
+                "/m/META-INF/versions/10/p/C.class",
+                "/m/META-INF/versions/9/p/C.class",
+                "/m/p/C.class"
+            ]
+        );
+        assert_eq!(JImage::versioned_paths("m", "p/C.class", 8).count(), 1);
+    }
+}
@@ -1,8 +1,15 @@
-use std::sync::{Arc, Once};
+use std::{
+    borrow::Cow,
+    sync::{Arc, Once},
+};
 
 use once_cell::sync::OnceCell;
 
-use super::{image_file::ImageStrings, zip_utils::inflate};
+use super::{
+    image_file::{Endian, ImageStrings},
+    jimage_error::JImageError,
+    zip_utils::inflate,
+};
 
 /// Compressed resource located in image have an header
 /// The header contains:
@@ -26,18 +33,45 @@ pub struct ResourceHeader {
 
 impl ResourceHeader {
     const RESOURCE_HEADER_MAGIC: u32 = 0xCAFEFAFAu32;
+    /// The header is written packed, without the padding of the struct.
+    pub(super) const SIZE: usize = 29;
 
-    fn from_bytes(bytes: Vec<u8>) -> ResourceHeader {
+    /// Config offset of a decompressor without configuration.
+    const NO_CONFIG: u32 = u32::MAX;
+
+    /// Header of a resource compressed once, by the decompressor with the name at the
+    /// string offset.
+    pub(super) fn new(size: u64, uncompressed_size: u64, decompressor_name_offset: u32) -> Self {
+        ResourceHeader {
+            magic: ResourceHeader::RESOURCE_HEADER_MAGIC,
+            size,
+            uncompressed_size,
+            decompressor_name_offset,
+            decompressor_config_offset: ResourceHeader::NO_CONFIG,
+            is_terminal: 1,
+        }
+    }
+
+    pub(super) fn write(&self, out: &mut Vec<u8>, endian: Endian) {
+        endian.put_u32(out, self.magic);
+        endian.put_u64(out, self.size);
+        endian.put_u64(out, self.uncompressed_size);
+        endian.put_u32(out, self.decompressor_name_offset);
+        endian.put_u32(out, self.decompressor_config_offset);
+        out.push(self.is_terminal);
+    }
+
+    fn from_bytes(bytes: &[u8], endian: Endian) -> ResourceHeader {
         assert!(
-            bytes.len() == std::mem::size_of::<ResourceHeader>(),
+            bytes.len() == ResourceHeader::SIZE,
             "invalid resource header data"
         );
         ResourceHeader {
-            magic: u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
-            size: u64::from_ne_bytes(bytes[4..12].try_into().unwrap()),
-            uncompressed_size: u64::from_ne_bytes(bytes[12..20].try_into().unwrap()),
-            decompressor_name_offset: u32::from_ne_bytes(bytes[20..24].try_into().unwrap()),
-            decompressor_config_offset: u32::from_ne_bytes(bytes[24..28].try_into().unwrap()),
+            magic: endian.get_u32(&bytes[0..4]),
+            size: endian.get_u64(&bytes[4..12]),
+            uncompressed_size: endian.get_u64(&bytes[12..20]),
+            decompressor_name_offset: endian.get_u32(&bytes[20..24]),
+            decompressor_config_offset: endian.get_u32(&bytes[24..28]),
             is_terminal: bytes[28],
         }
     }
@@ -63,35 +97,76 @@ impl Decompressors {
             .map(|dec| Arc::clone(dec))
     }
 
+    /// Name of the decompressor of the outermost header of the compressed data.
+    pub fn decompressor_name(
+        compressed_data: &[u8],
+        strings: &ImageStrings,
+        endian: Endian,
+    ) -> Option<String> {
+        let header =
+            ResourceHeader::from_bytes(compressed_data.get(0..ResourceHeader::SIZE)?, endian);
+        (header.magic == ResourceHeader::RESOURCE_HEADER_MAGIC)
+            .then(|| strings.get(header.decompressor_name_offset))
+            .flatten()
+    }
+
+    /// Decompress the resource in a loop until there is no more header. The size each
+    /// header asks for is checked before it is allocated: the last one is at most the
+    /// uncompressed size of the location, an intermediate one at most the image size.
     pub fn decompress_resource(
-        compressed_data: Vec<u8>,
+        compressed_data: &[u8],
         uncompressed_size: u64,
+        image_size: u64,
         strings: ImageStrings,
-    ) -> Option<Vec<u8>> {
-        let mut data = compressed_data;
+        endian: Endian,
+    ) -> Result<Vec<u8>, JImageError> {
+        let mut data = Cow::Borrowed(compressed_data);
         loop {
-            let header_size = std::mem::size_of::<ResourceHeader>();
-            let header = ResourceHeader::from_bytes(data[0..header_size].to_vec());
+            let header_size = ResourceHeader::SIZE;
+            if data.len() < header_size {
+                break;
+            }
+            let header = ResourceHeader::from_bytes(&data[0..header_size], endian);
             let has_header = header.magic == ResourceHeader::RESOURCE_HEADER_MAGIC;
             if !has_header {
                 break;
             }
-            let decompressor_name = strings.get(header.decompressor_name_offset).unwrap();
-            let decompressor = Decompressors::get_decompressor(&decompressor_name)
-                .expect("unrecognized decompressor name.");
-            if let Some(content) =
-                decompressor.decompress_resource(data[header_size..].to_vec(), header, &strings)
-            {
-                data = content;
+            let max_size = if header.is_terminal == 1 {
+                uncompressed_size
             } else {
-                return None;
+                image_size.max(uncompressed_size)
             };
+            if header.uncompressed_size > max_size {
+                return Err(JImageError::DecompressError(format!(
+                    "invalid uncompressed size {} in the resource header",
+                    header.uncompressed_size
+                )));
+            }
+            let decompressor_name = strings
+                .get_bytes(header.decompressor_name_offset)
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .ok_or_else(|| {
+                    JImageError::DecompressError(format!(
+                        "invalid decompressor name offset {}",
+                        header.decompressor_name_offset
+                    ))
+                })?;
+            let decompressor = Decompressors::get_decompressor(&decompressor_name)
+                .ok_or(JImageError::UnknownDecompressor(decompressor_name))?;
+            data = Cow::Owned(decompressor.decompress_resource(
+                &data[header_size..],
+                header,
+                &strings,
+            )?);
         }
-        assert!(
-            data.len() == uncompressed_size as usize,
-            "fail to decompress compressed data."
-        );
-        Some(data)
+        if data.len() as u64 != uncompressed_size {
+            return Err(JImageError::DecompressError(format!(
+                "decompressed {} bytes instead of {}",
+                data.len(),
+                uncompressed_size
+            )));
+        }
+        Ok(data.into_owned())
     }
 }
 
@@ -106,10 +181,10 @@ pub trait ImageDecompressor: Sync + Send {
     fn get_name(&self) -> String;
     fn decompress_resource(
         &self,
-        compressed_data: Vec<u8>,
+        compressed_data: &[u8],
         resource_header: ResourceHeader,
         strings: &ImageStrings,
-    ) -> Option<Vec<u8>>;
+    ) -> Result<Vec<u8>, JImageError>;
 }
 
 pub struct ZipDecompressor {
@@ -123,21 +198,15 @@ impl ImageDecompressor for ZipDecompressor {
 
     fn decompress_resource(
         &self,
-        compressed_data: Vec<u8>,
+        compressed_data: &[u8],
         resource_header: ResourceHeader,
         strings: &ImageStrings,
-    ) -> Option<Vec<u8>> {
-        let mut in_buf = compressed_data;
+    ) -> Result<Vec<u8>, JImageError> {
         let in_len: u64 = resource_header.size;
         let mut out_buf: Vec<u8> = vec![0u8; resource_header.uncompressed_size as usize];
         let out_len = resource_header.uncompressed_size;
-        if let Err(super::jimage_error::JImageError::DecompressError(msg)) =
-            inflate(&mut out_buf, out_len, &mut in_buf, in_len)
-        {
-            println!("{}", msg);
-            return None;
-        }
-        Some(out_buf)
+        inflate(&mut out_buf, out_len, compressed_data, in_len)?;
+        Ok(out_buf)
     }
 }
 
@@ -160,18 +229,255 @@ impl ImageDecompressor for SharedStringDecompressor {
 
     fn decompress_resource(
         &self,
-        compressed_data: Vec<u8>,
+        compressed_data: &[u8],
         resource_header: ResourceHeader,
         strings: &ImageStrings,
-    ) -> Option<Vec<u8>> {
-        todo!()
+    ) -> Result<Vec<u8>, JImageError> {
+        SharedStringDecompressor::rebuild_class(compressed_data, &resource_header, strings)
+            .ok_or_else(|| {
+                JImageError::DecompressError("malformed compact-cp resource".to_string())
+            })
     }
 }
 
 impl SharedStringDecompressor {
+    const CONSTANT_UTF8: u8 = 1;
+    const CONSTANT_LONG: u8 = 5;
+    const CONSTANT_DOUBLE: u8 = 6;
+    const EXTERNALIZED_STRING: u8 = 23;
+    const EXTERNALIZED_STRING_DESCRIPTOR: u8 = 25;
+
+    /// Size of the entry after the tag for each constant pool tag, zero for tags that
+    /// have no fixed size.
+    const SIZES: [usize; 21] = [
+        0, 0, 0, 4, 4, 8, 8, 2, 2, 4, 4, 4, 4, 0, 0, 3, 2, 4, 4, 2, 2,
+    ];
+
     pub fn initialize() -> Self {
         SharedStringDecompressor {
             name: "compact-cp".to_string(),
         }
     }
+
+    /// Rebuild the constant pool, the class file header, the constant pool count and
+    /// every entry that is not an externalized Utf8 are copied as they are. The bytes
+    /// after the constant pool are copied unchanged.
+    fn rebuild_class(
+        compressed_data: &[u8],
+        resource_header: &ResourceHeader,
+        strings: &ImageStrings,
+    ) -> Option<Vec<u8>> {
+        let mut data = CompressedData::new(compressed_data);
+        let mut out: Vec<u8> = Vec::with_capacity(resource_header.uncompressed_size as usize);
+        // magic, minor and major version
+        out.extend_from_slice(data.read_bytes(8)?);
+        let cp_count = u16::from_be_bytes(data.read_bytes(2)?.try_into().unwrap());
+        out.extend_from_slice(&cp_count.to_be_bytes());
+        let mut index = 1;
+        while index < cp_count as usize {
+            let tag = data.read_u8()?;
+            match tag {
+                SharedStringDecompressor::EXTERNALIZED_STRING => {
+                    let string = strings.get_bytes(data.read_compressed_int()?)?;
+                    SharedStringDecompressor::put_utf8(&mut out, string)?;
+                }
+                SharedStringDecompressor::EXTERNALIZED_STRING_DESCRIPTOR => {
+                    let descriptor =
+                        SharedStringDecompressor::rebuild_descriptor(&mut data, strings)?;
+                    SharedStringDecompressor::put_utf8(&mut out, &descriptor)?;
+                }
+                SharedStringDecompressor::CONSTANT_UTF8 => {
+                    let length = data.read_bytes(2)?;
+                    let len = u16::from_be_bytes(length.try_into().unwrap()) as usize;
+                    out.push(tag);
+                    out.extend_from_slice(length);
+                    out.extend_from_slice(data.read_bytes(len)?);
+                }
+                _ => {
+                    let size = *SharedStringDecompressor::SIZES.get(tag as usize)?;
+                    if size == 0 {
+                        return None;
+                    }
+                    if tag == SharedStringDecompressor::CONSTANT_LONG
+                        || tag == SharedStringDecompressor::CONSTANT_DOUBLE
+                    {
+                        index += 1;
+                    }
+                    out.push(tag);
+                    out.extend_from_slice(data.read_bytes(size)?);
+                }
+            }
+            index += 1;
+        }
+        let remain = (resource_header.size as usize).checked_sub(data.position())?;
+        out.extend_from_slice(data.read_bytes(remain)?);
+        (out.len() as u64 == resource_header.uncompressed_size).then_some(out)
+    }
+
+    fn put_utf8(out: &mut Vec<u8>, string: &[u8]) -> Option<()> {
+        let length: u16 = string.len().try_into().ok()?;
+        out.push(SharedStringDecompressor::CONSTANT_UTF8);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend_from_slice(string);
+        Some(())
+    }
+
+    /// The descriptor in the strings table has every class name removed, e.g.
+    /// `(L;I)V`. Each `L` is followed by a package and a simple name index pair, the
+    /// package is empty for classes in the unnamed package, so the example becomes
+    /// `(Ljava/lang/String;I)V` with the pair `java/lang` and `String`.
+    fn rebuild_descriptor(data: &mut CompressedData, strings: &ImageStrings) -> Option<Vec<u8>> {
+        let descriptor = strings.get_bytes(data.read_compressed_int()?)?;
+        let indexes_length = data.read_compressed_int()? as usize;
+        if indexes_length == 0 {
+            return Some(descriptor.to_vec());
+        }
+        let mut indexes = CompressedData::new(data.read_bytes(indexes_length)?);
+        let mut rebuilt = Vec::with_capacity(descriptor.len() + indexes_length * 8);
+        for &c in descriptor {
+            rebuilt.push(c);
+            if c == b'L' {
+                let package = strings.get_bytes(indexes.read_compressed_int()?)?;
+                if !package.is_empty() {
+                    rebuilt.extend_from_slice(package);
+                    rebuilt.push(b'/');
+                }
+                rebuilt.extend_from_slice(strings.get_bytes(indexes.read_compressed_int()?)?);
+            }
+        }
+        Some(rebuilt)
+    }
+}
+
+/// Bounds checked reader of compressed resource content.
+struct CompressedData<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> CompressedData<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        CompressedData { bytes, position: 0 }
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    /// Integers written by jlink `CompressIndexes`, a first byte with the high bit set
+    /// holds the length in bits 5 and 6 and the high bits of the value in the low 5
+    /// bits, otherwise the value is a 4 bytes big endian integer.
+    fn read_compressed_int(&mut self) -> Option<u32> {
+        let first = *self.bytes.get(self.position)?;
+        if first & 0x80 == 0 {
+            return self
+                .read_bytes(4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()));
+        }
+        let len = ((first & 0x60) >> 5) as usize;
+        let bytes = self.read_bytes(len.max(1))?;
+        let value = bytes[1..]
+            .iter()
+            .fold((first & 0x1F) as u32, |value, &byte| {
+                value << 8 | byte as u32
+            });
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decompressors, ImageDecompressor, ResourceHeader, SharedStringDecompressor};
+    use crate::jimage::image_file::{Endian, ImageStrings};
+
+    #[test]
+    fn we_can_rebuild_a_shared_string_constant_pool() {
+        let table = b"\0java/lang\0String\0(L;I)V\0hello\0";
+        let strings = ImageStrings::new(table);
+        let mut compressed = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61, 0, 5];
+        // "hello" with an uncompressed index
+        compressed.extend_from_slice(&[23, 0, 0, 0, 25]);
+        compressed.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 42]);
+        // "(L;I)V" with the package and class name index pair
+        compressed.extend_from_slice(&[25, 0xB2, 0xA2, 0xA1, 0xAB]);
+        compressed.extend_from_slice(&[0xAA, 0xBB]);
+
+        let mut expected = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61, 0, 5];
+        expected.extend_from_slice(&[1, 0, 5]);
+        expected.extend_from_slice(b"hello");
+        expected.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 42]);
+        expected.extend_from_slice(&[1, 0, 22]);
+        expected.extend_from_slice(b"(Ljava/lang/String;I)V");
+        expected.extend_from_slice(&[0xAA, 0xBB]);
+
+        let header = ResourceHeader {
+            magic: ResourceHeader::RESOURCE_HEADER_MAGIC,
+            size: compressed.len() as u64,
+            uncompressed_size: expected.len() as u64,
+            decompressor_name_offset: 0,
+            decompressor_config_offset: 0,
+            is_terminal: 1,
+        };
+        let decompressor = SharedStringDecompressor::initialize();
+        assert_eq!(
+            decompressor
+                .decompress_resource(&compressed, header, &strings)
+                .ok(),
+            Some(expected.clone())
+        );
+
+        let truncated = ResourceHeader {
+            magic: ResourceHeader::RESOURCE_HEADER_MAGIC,
+            size: compressed.len() as u64,
+            uncompressed_size: expected.len() as u64,
+            decompressor_name_offset: 0,
+            decompressor_config_offset: 0,
+            is_terminal: 1,
+        };
+        compressed.truncate(20);
+        assert_eq!(
+            decompressor
+                .decompress_resource(&compressed, truncated, &strings)
+                .ok(),
+            None
+        );
+    }
+
+    #[test]
+    fn the_sizes_of_the_headers_are_checked() {
+        let compressed = |uncompressed_size, is_terminal| {
+            let mut header = ResourceHeader::new(2, uncompressed_size, 1);
+            header.is_terminal = is_terminal;
+            let mut data = Vec::new();
+            header.write(&mut data, Endian::Little);
+            data.extend_from_slice(&[0x03, 0x00]);
+            data
+        };
+        let decompress = |data: &[u8]| {
+            let strings = ImageStrings::new(b"\0zip\0");
+            Decompressors::decompress_resource(data, 8, 1024, strings, Endian::Little)
+                .unwrap_err()
+                .to_string()
+        };
+        let error = "invalid uncompressed size 18446744073709551615 in the resource header";
+        assert!(decompress(&compressed(u64::MAX, 1)).contains(error));
+        assert!(decompress(&compressed(u64::MAX, 0)).contains(error));
+        let error = "invalid uncompressed size 9 in the resource header";
+        assert!(decompress(&compressed(9, 1)).contains(error));
+        // an intermediate result can be larger than the resource, not than the image
+        assert!(!decompress(&compressed(512, 0)).contains("invalid uncompressed size"));
+        assert!(decompress(&compressed(1025, 0)).contains("invalid uncompressed size"));
+    }
 }
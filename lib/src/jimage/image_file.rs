@@ -1,25 +1,32 @@
-use super::{image_decompressor::Decompressors, jimage_error::JImageError};
+use super::{
+    image_decompressor::Decompressors, jimage_error::JImageError, mapped_file::MappedFile,
+};
 use once_cell::sync::OnceCell;
 
-use std::io::Read;
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fs::OpenOptions,
     mem,
+    ops::Range,
     sync::{Arc, Mutex},
 };
 
+/// Reader of a jimage file. The file is mapped read only and the index tables and the
+/// resources are slices of the mapping.
 #[derive(Default)]
 pub struct ImageFileReader {
     name: String,
     file_size: u64,
     header: ImageHeader,
+    endian: Endian,
     index_size: usize,
-    redirect_table: Option<Arc<Vec<i32>>>,
-    attribute_offsets: Option<Arc<Vec<u32>>>,
-    attribute_data: Option<Arc<Vec<u8>>>,
-    strings: Option<Arc<Vec<u8>>>,
-    resources: Option<Arc<Vec<u8>>>,
+    map: Option<MappedFile>,
+    redirect_table: Range<usize>,
+    attribute_offsets: Range<usize>,
+    attribute_data: Range<usize>,
+    strings: Range<usize>,
+    resources: Range<usize>,
 }
 
 static INSTANCE: OnceCell<Mutex<HashMap<String, Arc<ImageFileReader>>>> = OnceCell::new();
@@ -28,14 +35,7 @@ impl ImageFileReader {
     pub fn new(name: String) -> Self {
         ImageFileReader {
             name,
-            file_size: 0,
-            header: ImageHeader::default(),
-            index_size: 0,
-            redirect_table: None,
-            attribute_offsets: None,
-            attribute_data: None,
-            strings: None,
-            resources: None,
+            ..Default::default()
         }
     }
 
@@ -69,67 +69,108 @@ impl ImageFileReader {
     }
 
     fn open_image(&mut self) -> Result<(), JImageError> {
-        let mut file = OpenOptions::new()
+        let open_error = |error| JImageError::FileOpenError(self.name.clone(), error);
+        let file = OpenOptions::new()
             .read(true)
             .write(false)
             .open(self.name.clone())
-            .map_err(|_| JImageError::FileOpenError)?;
-        self.file_size = file.metadata().unwrap().len();
-        let mut buf: Vec<u8> = Vec::new();
-        assert_eq!(
-            file.read_to_end(&mut buf).unwrap(),
-            self.file_size as usize,
-            "fail to read data from file.",
-        );
-        let header_size = self.header_size();
-        if self.file_size < header_size as u64 {
-            return Err(JImageError::FileOpenError);
-        }
-        self.header = ImageHeader::read_from_bytes(&buf[0..header_size]);
-        self.index_size = self.index_size();
-        if self.file_size < self.index_size as u64
-            || self.header.magic() != 0xCAFEDADA
-            || self.header.major_version() != 1u32
-            || self.header.minor_version() != 0u32
+            .map_err(open_error)?;
+        let map = MappedFile::map(&file).map_err(open_error)?;
+        self.file_size = map.len() as u64;
+        let header = self.section("header", 0, self.header_size())?;
+        self.endian = ImageHeader::endian(&map[header.clone()])?;
+        self.header = ImageHeader::read_from_bytes(&map[header.clone()], self.endian);
+        if self.header.major_version() != ImageHeader::MAJOR_VERSION
+            || self.header.minor_version() != ImageHeader::MINOR_VERSION
         {
-            return Err(JImageError::FileOpenError);
+            return Err(JImageError::UnsupportedVersion {
+                major: self.header.major_version(),
+                minor: self.header.minor_version(),
+            });
         }
-        let tlen = self.table_length() as usize;
-        let mut index = self.header_size();
-        self.redirect_table = Some(Arc::new(
-            buf[index..index + tlen * mem::size_of::<u32>()]
-                .chunks_exact(4)
-                .into_iter()
-                .map(|bytes| i32::from_ne_bytes(bytes.try_into().unwrap()))
-                .collect(),
-        ));
-        index += tlen * mem::size_of::<u32>();
-        self.attribute_offsets = Some(Arc::new(
-            buf[index..index + tlen * mem::size_of::<u32>()]
-                .chunks_exact(mem::size_of::<u32>())
-                .into_iter()
-                .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
-                .collect(),
-        ));
-        index += tlen * mem::size_of::<u32>();
-        self.attribute_data = Some(Arc::new(buf[index..index + self.locations_size()].to_vec()));
-        index += self.locations_size();
-        self.strings = Some(Arc::new(buf[index..index + self.string_size()].to_vec()));
-        index += self.string_size();
-        self.resources = Some(Arc::new(buf[index..].to_vec()));
+        self.index_size = self.index_size();
+        let table_size = self.table_length() * mem::size_of::<u32>();
+        self.redirect_table = self.section("redirect table", header.end, table_size)?;
+        self.attribute_offsets =
+            self.section("attribute offsets", self.redirect_table.end, table_size)?;
+        self.attribute_data = self.section(
+            "attribute data",
+            self.attribute_offsets.end,
+            self.locations_size(),
+        )?;
+        self.strings = self.section("strings", self.attribute_data.end, self.string_size())?;
+        self.resources = self.strings.end..map.len();
+        self.map = Some(map);
         Ok(())
     }
 
+    fn section(
+        &self,
+        section: &'static str,
+        start: usize,
+        size: usize,
+    ) -> Result<Range<usize>, JImageError> {
+        let end = start as u64 + size as u64;
+        if end > self.file_size {
+            return Err(JImageError::TruncatedSection {
+                section,
+                end,
+                file_size: self.file_size,
+            });
+        }
+        Ok(start..start + size)
+    }
+
+    /// Byte order of the image, the words of the header, the tables and the package
+    /// entries are in this order.
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    fn data(&self, range: &Range<usize>) -> &[u8] {
+        &self.map.as_ref().expect("image is not open.")[range.clone()]
+    }
+
+    /// The tables follow the 28 bytes header, so they are aligned in the mapping.
+    fn words(&self, range: &Range<usize>) -> &[u32] {
+        let (prefix, words, _) = unsafe { self.data(range).align_to::<u32>() };
+        assert!(prefix.is_empty(), "misaligned image index.");
+        words
+    }
+
+    /// Perfect hash redirect table, a positive value is the seed to rehash the name
+    /// with and a negative value `-1 - index` is the index of the location. The values
+    /// are in the byte order of the image.
+    pub fn redirect_table(&self) -> &[i32] {
+        let words = self.words(&self.redirect_table);
+        unsafe { std::slice::from_raw_parts(words.as_ptr() as *const i32, words.len()) }
+    }
+
+    /// Offset in the attribute data of each location, in the byte order of the image.
+    pub fn attribute_offsets(&self) -> &[u32] {
+        self.words(&self.attribute_offsets)
+    }
+
+    /// The attribute streams of the locations.
+    pub fn attribute_data(&self) -> &[u8] {
+        self.data(&self.attribute_data)
+    }
+
+    /// The bytes following the index, the resource offsets are relative to this.
+    pub fn resources(&self) -> &[u8] {
+        self.data(&self.resources)
+    }
+
     pub fn package_to_module(&self, package_name: String) -> Option<String> {
         let mut path = "/packages/".to_string();
         path.push_str(&package_name.replace("/", "."));
 
         self.find_location(path)
-            .and_then(|loc| self.read_data(loc))
+            .and_then(|loc| self.read_data(loc).ok())
             .and_then(|data| {
                 data.chunks_exact(8).into_iter().find_map(|bytes| {
-                    (u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) == 0)
-                        .then(|| u32::from_ne_bytes(bytes[4..8].try_into().unwrap()))
+                    (self.endian.get_u32(&bytes[0..4]) == 0)
+                        .then(|| self.endian.get_u32(&bytes[4..8]))
                 })
             })
             .and_then(|offset| self.get_strings().get(offset))
@@ -140,6 +181,7 @@ impl ImageFileReader {
             .and_then(|index| self.get_location_offset(index))
             .and_then(|loc_offset| {
                 self.get_location(loc_offset)
+                    .ok()
                     .and_then(|loc| self.verify_location(loc, path))
                     .and_then(|loc| {
                         Some((
@@ -153,48 +195,78 @@ impl ImageFileReader {
     fn find_location(&self, path: String) -> Option<ImageLocation> {
         self.get_attribute_offset(path.clone())
             .and_then(|index| self.get_location_offset(index))
-            .and_then(|loc_offset| self.get_location(loc_offset))
+            .and_then(|loc_offset| self.get_location(loc_offset).ok())
             .and_then(|loc| self.verify_location(loc, path))
     }
 
     pub fn get_resource(&self, offset: u32) -> Option<Vec<u8>> {
         self.get_location(offset)
-            .and_then(|loc| self.read_data(loc))
+            .ok()
+            .and_then(|loc| self.read_data(loc).ok())
+            .map(Cow::into_owned)
     }
 
-    fn read_data(&self, location: ImageLocation) -> Option<Vec<u8>> {
-        let start = location.get_attribute(ImageLocation::ATTRIBUTE_OFFSET) as usize;
-        let uncompressed_size =
-            location.get_attribute(ImageLocation::ATTRIBUTE_UNCOMPRESSED) as usize;
-        let compressed_size = location.get_attribute(ImageLocation::ATTRIBUTE_COMPRESSED) as usize;
-        assert!(self.resources.is_some(), "no resource data loaded.");
-        let resource_bytes = Arc::clone(self.resources.as_ref().unwrap());
-        let end = if compressed_size == 0 {
-            uncompressed_size + start
-        } else {
-            compressed_size + start
-        };
-        assert!(
-            end <= resource_bytes.len(),
-            "invalid index access to resource bytes."
-        );
-        let data = resource_bytes[start..end].to_vec();
-        if compressed_size != 0 {
+    /// The content of the resource, uncompressed resources are borrowed from the
+    /// mapping.
+    pub fn read_data(&self, location: ImageLocation) -> Result<Cow<'_, [u8]>, JImageError> {
+        let uncompressed_size = location.get_attribute(ImageLocation::ATTRIBUTE_UNCOMPRESSED);
+        let data = self.read_stored_data(&location)?;
+        if location.get_attribute(ImageLocation::ATTRIBUTE_COMPRESSED) != 0 {
             return Decompressors::decompress_resource(
                 data,
-                uncompressed_size as u64,
+                uncompressed_size,
+                self.file_size,
                 self.get_strings(),
-            );
+                self.endian,
+            )
+            .map(Cow::Owned);
         }
-        Some(data)
+        Ok(Cow::Borrowed(data))
+    }
+
+    /// The resource as it is stored in the image, compressed resources start with the
+    /// header of their outermost compression.
+    pub fn read_stored_data(&self, location: &ImageLocation) -> Result<&[u8], JImageError> {
+        let start = location.get_attribute(ImageLocation::ATTRIBUTE_OFFSET);
+        let uncompressed_size = location.get_attribute(ImageLocation::ATTRIBUTE_UNCOMPRESSED);
+        let compressed_size = location.get_attribute(ImageLocation::ATTRIBUTE_COMPRESSED);
+        let resource_bytes = self.resources();
+        let end = if compressed_size == 0 {
+            start.saturating_add(uncompressed_size)
+        } else {
+            start.saturating_add(compressed_size)
+        };
+        if end > resource_bytes.len() as u64 {
+            return Err(JImageError::TruncatedSection {
+                section: "resource",
+                end: (self.resources.start as u64).saturating_add(end),
+                file_size: self.file_size,
+            });
+        }
+        Ok(&resource_bytes[start as usize..end as usize])
+    }
+
+    /// Name of the outermost decompressor of the resource, `None` when the resource is
+    /// not compressed.
+    pub fn decompressor_name(
+        &self,
+        location: &ImageLocation,
+    ) -> Result<Option<String>, JImageError> {
+        if location.get_attribute(ImageLocation::ATTRIBUTE_COMPRESSED) == 0 {
+            return Ok(None);
+        }
+        let data = self.read_stored_data(location)?;
+        Ok(Decompressors::decompressor_name(
+            data,
+            &self.get_strings(),
+            self.endian,
+        ))
     }
 
     fn verify_location(&self, location: ImageLocation, path: String) -> Option<ImageLocation> {
         let mut path = path;
         let strings = self.get_strings();
-        let module = strings
-            .get(location.get_attribute(ImageLocation::ATTRIBUTE_MODULE) as u32)
-            .expect("expect module string.");
+        let module = strings.get(location.get_attribute(ImageLocation::ATTRIBUTE_MODULE) as u32)?;
         if !module.is_empty() {
             let prefix = &format!("/{}/", module);
             if let Some(result) = path.strip_prefix(prefix) {
@@ -203,9 +275,7 @@ impl ImageFileReader {
                 return None;
             }
         }
-        let parent = strings
-            .get(location.get_attribute(ImageLocation::ATTRIBUTE_PARENT) as u32)
-            .expect("expect parent string.");
+        let parent = strings.get(location.get_attribute(ImageLocation::ATTRIBUTE_PARENT) as u32)?;
         if !parent.is_empty() {
             let prefix = &format!("{}/", parent);
             if let Some(result) = path.strip_prefix(prefix) {
@@ -214,9 +284,7 @@ impl ImageFileReader {
                 return None;
             }
         }
-        let base = strings
-            .get(location.get_attribute(ImageLocation::ATTRIBUTE_BASE) as u32)
-            .expect("expect base name string.");
+        let base = strings.get(location.get_attribute(ImageLocation::ATTRIBUTE_BASE) as u32)?;
         if !base.is_empty() {
             if let Some(result) = path.strip_prefix(&base) {
                 path = result.to_owned();
@@ -224,9 +292,8 @@ impl ImageFileReader {
                 return None;
             }
         }
-        let extension = strings
-            .get(location.get_attribute(ImageLocation::ATTRIBUTE_EXTENSION) as u32)
-            .expect("expect extension string.");
+        let extension =
+            strings.get(location.get_attribute(ImageLocation::ATTRIBUTE_EXTENSION) as u32)?;
         if !extension.is_empty() {
             let prefix = &format!(".{}", extension);
             if let Some(result) = path.strip_prefix(prefix) {
@@ -238,10 +305,17 @@ impl ImageFileReader {
         path.is_empty().then_some(location)
     }
 
-    fn index_size(&self) -> usize {
-        self.table_length() as usize * mem::size_of::<u32>() * 2
-            + self.locations_size() as usize
-            + self.string_size() as usize
+    /// Size of the header and the index tables, the resources start after the index.
+    pub fn index_size(&self) -> usize {
+        self.header_size()
+            + self.table_length() * mem::size_of::<u32>() * 2
+            + self.locations_size()
+            + self.string_size()
+    }
+
+    /// The header of the image.
+    pub fn header(&self) -> &ImageHeader {
+        &self.header
     }
 
     pub fn table_length(&self) -> usize {
@@ -257,61 +331,77 @@ impl ImageFileReader {
     }
 
     fn header_size(&self) -> usize {
-        std::mem::size_of::<ImageHeader>()
+        ImageHeader::SIZE
     }
 
-    pub fn get_strings(&self) -> ImageStrings {
-        assert!(self.strings.is_some(), "No content of string bytes.");
-        ImageStrings {
-            data: Some(Arc::clone(self.strings.as_ref().unwrap())),
-            size: self.header.string_size(),
-        }
+    pub fn get_strings(&self) -> ImageStrings<'_> {
+        ImageStrings::new(self.data(&self.strings))
     }
 
-    fn get_location_offset(&self, index: u32) -> Option<u32> {
-        self.attribute_offsets
-            .as_ref()
-            .and_then(|data| data.get(index as usize).map(|value| value.clone()))
+    /// Offset of the attributes of the location at the index of the tables.
+    pub fn get_location_offset(&self, index: u32) -> Option<u32> {
+        self.attribute_offsets()
+            .get(index as usize)
+            .map(|&offset| self.endian.get(offset))
     }
 
     fn get_attribute_offset(&self, path: String) -> Option<u32> {
-        self.redirect_table
-            .as_ref()
-            .and_then(|data| ImageStrings::find(path, Arc::clone(data), self.table_length() as u32))
-    }
-
-    pub fn get_location(&self, index: u32) -> Option<ImageLocation> {
-        let attribute_data = Arc::clone(
-            self.attribute_data
-                .as_ref()
-                .expect("attribute data cannot be null."),
-        );
-        assert!(
-            (index as usize) < attribute_data.len(),
-            "invalid location offset."
-        );
+        ImageStrings::find(
+            path,
+            self.redirect_table(),
+            self.table_length() as u32,
+            self.endian,
+        )
+    }
+
+    /// The string of the attribute of the location at the offset.
+    pub fn get_location_string(
+        &self,
+        offset: u32,
+        location: &ImageLocation,
+        kind: u8,
+    ) -> Result<String, JImageError> {
+        self.get_strings()
+            .get_bytes(location.get_attribute(kind) as u32)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .ok_or(JImageError::BadLocation(
+                offset,
+                "string offset exceeds the strings",
+            ))
+    }
+
+    /// Decode the attribute stream at the offset in the attribute data.
+    pub fn get_location(&self, offset: u32) -> Result<ImageLocation, JImageError> {
+        let attribute_data = self.attribute_data();
+        if offset as usize >= attribute_data.len() {
+            return Err(JImageError::BadLocation(
+                offset,
+                "offset exceeds the attribute data",
+            ));
+        }
         let mut attributes: [u64; 8] = [0; ImageLocation::ATTRIBUTE_COUNT as usize];
-        let mut index = index as usize;
+        let mut index = offset as usize;
         while let Some(&byte) = attribute_data.get(index) {
             if byte == ImageLocation::ATTRIBUTE_END {
                 break;
             }
+            if byte >> 3 >= ImageLocation::ATTRIBUTE_COUNT {
+                return Err(JImageError::BadLocation(offset, "invalid attribute kind"));
+            }
             let kind = ImageLocation::attribute_kind(byte);
-            assert!(
-                kind < ImageLocation::ATTRIBUTE_COUNT,
-                "invalid attribute kind"
-            );
             let len = ImageLocation::attribute_length(byte);
             index += 1;
             if let Some(values) = attribute_data.get(index..index + len as usize) {
                 attributes[kind as usize] = ImageLocation::attribute_value(values, len);
                 index += len as usize;
             } else {
-                println!("invalid attribute value bytes.");
-                return None;
+                return Err(JImageError::BadLocation(
+                    offset,
+                    "attribute value exceeds the attribute data",
+                ));
             }
         }
-        Some(ImageLocation::new(attributes))
+        Ok(ImageLocation::new(attributes))
     }
 }
 
@@ -372,6 +462,77 @@ impl ImageLocation {
     }
 }
 
+/// Byte order of an image, jlink writes images in the byte order of the target
+/// platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+impl Endian {
+    /// Byte order of this platform.
+    pub fn native() -> Endian {
+        if cfg!(target_endian = "big") {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Convert a word read in native order, e.g. from the tables of the image.
+    pub fn get(self, value: u32) -> u32 {
+        if self == Endian::native() {
+            value
+        } else {
+            value.swap_bytes()
+        }
+    }
+
+    /// Read a word in this order from the start of the bytes.
+    pub fn get_u32(self, bytes: &[u8]) -> u32 {
+        let bytes = bytes[0..4].try_into().unwrap();
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    /// Read a double word in this order from the start of the bytes.
+    pub fn get_u64(self, bytes: &[u8]) -> u64 {
+        let bytes = bytes[0..8].try_into().unwrap();
+        match self {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big => u64::from_be_bytes(bytes),
+        }
+    }
+
+    /// Append a word in this order.
+    pub fn put_u32(self, out: &mut Vec<u8>, value: u32) {
+        match self {
+            Endian::Little => out.extend_from_slice(&value.to_le_bytes()),
+            Endian::Big => out.extend_from_slice(&value.to_be_bytes()),
+        }
+    }
+
+    /// Append a double word in this order.
+    pub fn put_u64(self, out: &mut Vec<u8>, value: u64) {
+        match self {
+            Endian::Little => out.extend_from_slice(&value.to_le_bytes()),
+            Endian::Big => out.extend_from_slice(&value.to_be_bytes()),
+        }
+    }
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Endian::native()
+    }
+}
+
+/// The header at the start of an image, decoded in the byte order of the image.
 #[derive(Default, Debug)]
 pub struct ImageHeader {
     magic: u32,
@@ -384,66 +545,91 @@ pub struct ImageHeader {
 }
 
 impl ImageHeader {
-    fn read_from_bytes(bytes: &[u8]) -> Self {
+    pub(super) const MAGIC: u32 = 0xCAFEDADA;
+    pub(super) const MAJOR_VERSION: u32 = 1;
+    pub(super) const MINOR_VERSION: u32 = 0;
+    pub(super) const SIZE: usize = 28;
+
+    /// The byte order in which the magic reads correctly.
+    fn endian(bytes: &[u8]) -> Result<Endian, JImageError> {
+        match Endian::Little.get_u32(bytes) {
+            ImageHeader::MAGIC => Ok(Endian::Little),
+            magic if magic.swap_bytes() == ImageHeader::MAGIC => Ok(Endian::Big),
+            magic => Err(JImageError::BadMagic(magic)),
+        }
+    }
+
+    fn read_from_bytes(bytes: &[u8], endian: Endian) -> Self {
         ImageHeader {
-            magic: u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
-            version: u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
-            flags: u32::from_ne_bytes(bytes[8..12].try_into().unwrap()),
-            resource_count: u32::from_ne_bytes(bytes[12..16].try_into().unwrap()),
-            table_length: u32::from_ne_bytes(bytes[16..20].try_into().unwrap()),
-            locations_size: u32::from_ne_bytes(bytes[20..24].try_into().unwrap()),
-            string_size: u32::from_ne_bytes(bytes[24..28].try_into().unwrap()),
+            magic: endian.get_u32(&bytes[0..4]),
+            version: endian.get_u32(&bytes[4..8]),
+            flags: endian.get_u32(&bytes[8..12]),
+            resource_count: endian.get_u32(&bytes[12..16]),
+            table_length: endian.get_u32(&bytes[16..20]),
+            locations_size: endian.get_u32(&bytes[20..24]),
+            string_size: endian.get_u32(&bytes[24..28]),
         }
     }
 
-    fn magic(&self) -> u32 {
+    /// The magic of the image, `0xCAFEDADA`.
+    pub fn magic(&self) -> u32 {
         self.magic
     }
 
-    fn major_version(&self) -> u32 {
+    /// Major version of the image format.
+    pub fn major_version(&self) -> u32 {
         self.version >> 16
     }
 
-    fn minor_version(&self) -> u32 {
+    /// Minor version of the image format.
+    pub fn minor_version(&self) -> u32 {
         self.version & 0xFFFFu32
     }
 
-    fn flags(&self) -> u32 {
+    /// Flags of the image, currently always zero.
+    pub fn flags(&self) -> u32 {
         self.flags
     }
 
-    fn table_length(&self) -> u32 {
+    /// Number of resources of the image, including the directory entries.
+    pub fn resource_count(&self) -> u32 {
+        self.resource_count
+    }
+
+    /// Number of entries of the redirect and offsets tables.
+    pub fn table_length(&self) -> u32 {
         self.table_length
     }
 
-    fn locations_size(&self) -> u32 {
+    /// Size of the attribute data of the locations.
+    pub fn locations_size(&self) -> u32 {
         self.locations_size
     }
 
-    fn string_size(&self) -> u32 {
+    /// Size of the strings table.
+    pub fn string_size(&self) -> u32 {
         self.string_size
     }
 }
 
-pub struct ImageStrings {
-    data: Option<Arc<Vec<u8>>>,
-    size: u32,
+pub struct ImageStrings<'a> {
+    data: &'a [u8],
 }
 
-impl ImageStrings {
-    const HASH_MULTIPLIER: u32 = 0x01000193u32;
+impl<'a> ImageStrings<'a> {
+    pub(super) const HASH_MULTIPLIER: u32 = 0x01000193u32;
 
-    fn new(data: Arc<Vec<u8>>, size: u32) -> Self {
-        ImageStrings {
-            data: Some(data),
-            size,
-        }
+    pub fn new(data: &'a [u8]) -> Self {
+        ImageStrings { data }
     }
 
-    fn find(name: String, redirect_table: Arc<Vec<i32>>, length: u32) -> Option<u32> {
+    fn find(name: String, redirect_table: &[i32], length: u32, endian: Endian) -> Option<u32> {
         let mut hash_code = ImageStrings::hash_code(name.clone(), ImageStrings::HASH_MULTIPLIER);
         let index = hash_code % length;
-        if let Some(&value) = redirect_table.get(index as usize) {
+        if let Some(value) = redirect_table
+            .get(index as usize)
+            .map(|&value| endian.get(value as u32) as i32)
+        {
             if value > 0 {
                 hash_code = ImageStrings::hash_code(name, value as u32);
                 return Some(hash_code % length);
@@ -454,7 +640,7 @@ impl ImageStrings {
         None
     }
 
-    fn hash_code(name: String, seed: u32) -> u32 {
+    pub(super) fn hash_code(name: String, seed: u32) -> u32 {
         let bytes = name.bytes();
         let mut value = seed as u32;
         for byte in bytes {
@@ -463,49 +649,102 @@ impl ImageStrings {
         value & 0x7FFF_FFFFu32
     }
 
+    /// The string at the offset, `None` when the offset is outside of the table or the
+    /// string is not valid UTF-8.
     pub fn get(&self, offset: u32) -> Option<String> {
-        assert!(offset < self.size, "offset exceeds string table size");
-        if let Some(data) = self.data.as_ref() {
-            let mut bytes: Vec<u8> = Vec::new();
-            for byte in data.iter().skip(offset as usize) {
-                if *byte == 0 {
-                    break;
-                }
-                bytes.push(byte.clone());
-            }
-            return Some(String::from_utf8(bytes).unwrap());
-        }
-        None
+        self.get_bytes(offset)
+            .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+    }
+
+    /// The bytes of the nul terminated string at the offset, the strings are modified
+    /// UTF-8 and copied into class files as they are.
+    pub fn get_bytes(&self, offset: u32) -> Option<&'a [u8]> {
+        let bytes = self.data.get(offset as usize..)?;
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+        Some(&bytes[..end])
     }
 }
 
 #[cfg(test)]
 mod image_file {
-    use std::env;
+    use std::{borrow::Cow, env};
 
-    use super::ImageFileReader;
+    use super::{Endian, ImageFileReader, ImageHeader};
+    use crate::jimage::{image_writer::write_test_image, ImageWriter, JImageError};
 
     #[test]
     fn should_open_image_file_and_get_module_from_package() {
-        if let Ok(java_home) = env::var("JAVA_HOME") {
-            let name = match cfg!(windows) {
-                true => format!("{}\\lib\\modules", java_home),
-                false => format!("{}/lib/modules", java_home),
-            };
-            if let Ok(reader) = ImageFileReader::open(name) {
-                assert_eq!(
-                    reader.package_to_module("java/lang".to_string()),
-                    Some("java.base".to_string())
-                );
-                assert_eq!(
-                    reader.package_to_module("java/nio".to_string()),
-                    Some("java.base".to_string())
-                );
-                assert_eq!(
-                    reader.package_to_module("java/lang/instrument".to_string()),
-                    Some("java.instrument".to_string())
-                );
-            }
+        let path = write_test_image("file-packages", ImageWriter::new());
+        let reader = ImageFileReader::open(path.clone()).ok().unwrap();
+        assert_eq!(
+            reader.package_to_module("java/lang".to_string()),
+            Some("java.base".to_string())
+        );
+        assert_eq!(
+            reader.package_to_module("java/lang/ref".to_string()),
+            Some("java.base".to_string())
+        );
+        assert_eq!(
+            reader.package_to_module("java/sql".to_string()),
+            Some("java.sql".to_string())
+        );
+        assert_eq!(reader.package_to_module("java/nio".to_string()), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn uncompressed_resources_are_borrowed_from_the_mapping() {
+        let path = write_test_image("file-borrowed", ImageWriter::new());
+        let reader = ImageFileReader::open(path.clone()).ok().unwrap();
+        assert!(reader.redirect_table().len() == reader.table_length());
+        assert!(reader.attribute_offsets().len() == reader.table_length());
+        let data = reader
+            .find_location("/packages/java.lang".to_string())
+            .map(|location| reader.read_data(location));
+        assert!(matches!(data, Some(Ok(Cow::Borrowed(_)))));
+        std::fs::remove_file(path).unwrap();
+
+        let path = write_test_image("file-owned", ImageWriter::new().compress(true));
+        let reader = ImageFileReader::open(path.clone()).ok().unwrap();
+        let data = reader
+            .find_location("/java.base/java/lang/Object.class".to_string())
+            .map(|location| reader.read_data(location));
+        assert!(matches!(data, Some(Ok(Cow::Owned(_)))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_read_a_byte_swapped_header() {
+        let fields: [u32; 7] = [0xCAFEDADA, 1 << 16, 0, 3, 5, 7, 11];
+        let little: Vec<u8> = fields.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let big: Vec<u8> = fields.iter().flat_map(|f| f.to_be_bytes()).collect();
+        for (bytes, endian) in [(little, Endian::Little), (big, Endian::Big)] {
+            assert_eq!(ImageHeader::endian(&bytes).ok(), Some(endian));
+            let header = ImageHeader::read_from_bytes(&bytes, endian);
+            assert_eq!(header.major_version(), 1);
+            assert_eq!(header.table_length(), 5);
+            assert_eq!(header.string_size(), 11);
+        }
+        assert!(matches!(
+            ImageHeader::endian(&[0xCA, 0xFE, 0xBA, 0xBE]),
+            Err(JImageError::BadMagic(0xBEBAFECA))
+        ));
+    }
+
+    #[test]
+    fn a_truncated_image_is_reported() {
+        let fields: [u32; 7] = [0xCAFEDADA, 1 << 16, 0, 3, 5, 7, 11];
+        let bytes: Vec<u8> = fields.iter().flat_map(|f| f.to_be_bytes()).collect();
+        let path = env::temp_dir().join(format!("truncated-{}.jimage", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let result = ImageFileReader::open(path.to_string_lossy().to_string());
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(error @ JImageError::TruncatedSection { .. }) => assert_eq!(
+                error.to_string(),
+                "image redirect table ends at 48 past the end of the file at 28"
+            ),
+            _ => panic!("expected a truncated section"),
         }
     }
 }
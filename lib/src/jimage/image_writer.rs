@@ -0,0 +1,569 @@
+//! Writer of jimage files, used to build small images without a JDK.
+//!
+//! Besides the resources, the image holds the directory entries written by jlink:
+//! `/modules` and `/modules/<module>/<directory>` list the location offsets of their
+//! children, `/packages` lists the `/packages/<package>` entries which hold pairs of an
+//! is empty flag and the string offset of a module containing the package.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, Write},
+    mem,
+    path::Path,
+};
+
+use crate::zip::deflater::deflate;
+
+use super::{
+    image_decompressor::ResourceHeader,
+    image_file::{Endian, ImageHeader, ImageLocation, ImageStrings},
+};
+
+/// Builder of a jimage file from resources of modules.
+pub struct ImageWriter {
+    /// Resource content by module and path in the module.
+    resources: BTreeMap<(String, String), Vec<u8>>,
+    endian: Endian,
+    compress: bool,
+}
+
+enum Content {
+    Resource(Vec<u8>),
+    /// Full paths of the children.
+    Directory(Vec<String>),
+    /// Is empty flag and module name of each module with the package.
+    Package(Vec<(bool, String)>),
+}
+
+/// A location with the strings of its attributes and its content.
+struct Entry {
+    path: String,
+    module: String,
+    parent: String,
+    base: String,
+    extension: String,
+    content: Content,
+}
+
+impl Entry {
+    /// A resource `/module/parent/base.extension`.
+    fn resource(module: &str, name: &str, data: Vec<u8>) -> Entry {
+        let (parent, file) = name.rsplit_once('/').unwrap_or(("", name));
+        let (base, extension) = file.rsplit_once('.').unwrap_or((file, ""));
+        Entry {
+            path: format!("/{}/{}", module, name),
+            module: module.to_string(),
+            parent: parent.to_string(),
+            base: base.to_string(),
+            extension: extension.to_string(),
+            content: Content::Resource(data),
+        }
+    }
+
+    /// A directory entry, `/modules` and `/packages` have no module, the name of
+    /// their children is the whole path after the root.
+    fn directory(root: &str, name: &str, content: Content) -> Entry {
+        let (path, module, base) = if name.is_empty() {
+            (format!("/{}", root), "", format!("/{}", root))
+        } else {
+            (format!("/{}/{}", root, name), root, name.to_string())
+        };
+        Entry {
+            path,
+            module: module.to_string(),
+            parent: String::new(),
+            base,
+            extension: String::new(),
+            content,
+        }
+    }
+}
+
+/// Strings table, the empty string is at offset zero.
+struct StringsWriter {
+    bytes: Vec<u8>,
+    offsets: HashMap<String, u32>,
+}
+
+impl StringsWriter {
+    fn new() -> Self {
+        let mut strings = StringsWriter {
+            bytes: Vec::new(),
+            offsets: HashMap::new(),
+        };
+        strings.add("");
+        strings
+    }
+
+    fn add(&mut self, string: &str) -> u32 {
+        if let Some(&offset) = self.offsets.get(string) {
+            return offset;
+        }
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(string.as_bytes());
+        self.bytes.push(0);
+        self.offsets.insert(string.to_string(), offset);
+        offset
+    }
+}
+
+impl ImageWriter {
+    const MODULES: &'static str = "modules";
+    const PACKAGES: &'static str = "packages";
+    const ZIP: &'static str = "zip";
+
+    /// An empty image in the native byte order without compression.
+    pub fn new() -> Self {
+        ImageWriter {
+            resources: BTreeMap::new(),
+            endian: Endian::native(),
+            compress: false,
+        }
+    }
+
+    /// Write the image in the byte order, the native order by default.
+    pub fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Compress every resource with the zip decompressor.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Add a resource, e.g. `add_resource("java.base", "java/lang/Object.class", data)`.
+    pub fn add_resource(&mut self, module: &str, name: &str, data: Vec<u8>) -> &mut Self {
+        self.resources
+            .insert((module.to_string(), name.to_string()), data);
+        self
+    }
+
+    /// Add the files of a module directory tree, each directory of the root is a
+    /// module, e.g. `<root>/java.base/java/lang/Object.class`.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, root: P) -> io::Result<&mut Self> {
+        for module in fs::read_dir(root)? {
+            let module = module?;
+            if module.file_type()?.is_dir() {
+                let name = module.file_name().to_string_lossy().into_owned();
+                self.add_files(&name, &module.path(), "")?;
+            }
+        }
+        Ok(self)
+    }
+
+    fn add_files(&mut self, module: &str, dir: &Path, prefix: &str) -> io::Result<()> {
+        for file in fs::read_dir(dir)? {
+            let file = file?;
+            let name = format!("{}{}", prefix, file.file_name().to_string_lossy());
+            if file.file_type()?.is_dir() {
+                self.add_files(module, &file.path(), &format!("{}/", name))?;
+            } else {
+                self.add_resource(module, &name, fs::read(file.path())?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The resources followed by the directory entries of the modules and packages.
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        // children of each directory of /modules, by name after /modules/
+        let mut directories: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        // modules of each package with whether the package has no resources there,
+        // like jlink the directories of META-INF are not packages
+        let mut packages: BTreeMap<String, BTreeMap<String, bool>> = BTreeMap::new();
+        for ((module, name), data) in &self.resources {
+            let resource = Entry::resource(module, name, data.clone());
+            let mut child = resource.path.clone();
+            let mut directory = format!("{}/{}", module, name);
+            while let Some((parent, _)) = directory.rsplit_once('/') {
+                directory = parent.to_string();
+                directories
+                    .entry(directory.clone())
+                    .or_default()
+                    .insert(child);
+                child = format!("/{}/{}", ImageWriter::MODULES, directory);
+                let package = directory.split_once('/').map(|(_, package)| package);
+                if let Some(package) = package.filter(|package| !package.starts_with("META-INF")) {
+                    let empty = package != resource.parent;
+                    let package = packages.entry(package.replace('/', ".")).or_default();
+                    let module_empty = package.entry(module.clone()).or_insert(true);
+                    *module_empty &= empty;
+                }
+            }
+            directories.entry(String::new()).or_default().insert(child);
+            entries.push(resource);
+        }
+        for (name, children) in directories {
+            let content = Content::Directory(children.into_iter().collect());
+            entries.push(Entry::directory(ImageWriter::MODULES, &name, content));
+        }
+        let package_paths = packages
+            .keys()
+            .map(|package| format!("/{}/{}", ImageWriter::PACKAGES, package))
+            .collect();
+        for (package, modules) in packages {
+            let content = Content::Package(
+                modules
+                    .into_iter()
+                    .map(|(module, empty)| (empty, module))
+                    .collect(),
+            );
+            entries.push(Entry::directory(ImageWriter::PACKAGES, &package, content));
+        }
+        entries.push(Entry::directory(
+            ImageWriter::PACKAGES,
+            "",
+            Content::Directory(package_paths),
+        ));
+        entries
+    }
+
+    /// Place each path in the table so that the reader finds it, paths sharing a
+    /// bucket get a seed to rehash them with, a path alone in its bucket gets its
+    /// index directly. Returns the redirect table and the index of each path.
+    fn perfect_hash(paths: &[&str]) -> (Vec<i32>, Vec<usize>) {
+        let length = paths.len() as u32;
+        let hash = |path: &str, seed: u32| {
+            (ImageStrings::hash_code(path.to_string(), seed) % length) as usize
+        };
+        let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); paths.len()];
+        for (index, path) in paths.iter().enumerate() {
+            buckets[hash(path, ImageStrings::HASH_MULTIPLIER)].push(index);
+        }
+        let mut order: Vec<usize> = (0..buckets.len()).collect();
+        order.sort_by_key(|&bucket| std::cmp::Reverse(buckets[bucket].len()));
+        let mut redirect = vec![0i32; paths.len()];
+        let mut slots: Vec<Option<usize>> = vec![None; paths.len()];
+        let mut free = 0;
+        for bucket in order {
+            match buckets[bucket].len() {
+                0 => break,
+                1 => {
+                    while slots[free].is_some() {
+                        free += 1;
+                    }
+                    slots[free] = Some(buckets[bucket][0]);
+                    redirect[bucket] = -1 - free as i32;
+                }
+                _ => {
+                    let mut seed = 1;
+                    let placed = loop {
+                        let mut placed: Vec<usize> = Vec::new();
+                        for &index in &buckets[bucket] {
+                            let slot = hash(paths[index], seed);
+                            if slots[slot].is_some() || placed.contains(&slot) {
+                                break;
+                            }
+                            placed.push(slot);
+                        }
+                        if placed.len() == buckets[bucket].len() {
+                            break placed;
+                        }
+                        seed += 1;
+                    };
+                    for (&index, slot) in buckets[bucket].iter().zip(placed) {
+                        slots[slot] = Some(index);
+                    }
+                    redirect[bucket] = seed as i32;
+                }
+            }
+        }
+        let mut indexes = vec![0; paths.len()];
+        for (slot, index) in slots.into_iter().enumerate() {
+            indexes[index.expect("every slot is used.")] = slot;
+        }
+        (redirect, indexes)
+    }
+
+    /// Attribute stream of a location, the values are big endian with the fewest
+    /// bytes, zero values are left out.
+    fn write_location(
+        out: &mut Vec<u8>,
+        attributes: &[u64; ImageLocation::ATTRIBUTE_COUNT as usize],
+    ) {
+        for (kind, &value) in attributes.iter().enumerate().skip(1) {
+            if value == 0 {
+                continue;
+            }
+            let len = (8 - value.leading_zeros() as usize / 8).max(1);
+            out.push((kind as u8) << 3 | (len as u8 - 1));
+            out.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+        }
+        out.push(ImageLocation::ATTRIBUTE_END);
+    }
+
+    /// Write the image.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let endian = self.endian;
+        let mut entries = self.entries();
+        let mut strings = StringsWriter::new();
+        let zip = self.compress.then(|| strings.add(ImageWriter::ZIP));
+
+        // the content of each entry, directories are filled in once the location
+        // offsets are known
+        let mut contents: Vec<u8> = Vec::new();
+        let mut placed: Vec<(u64, u64, u64)> = Vec::with_capacity(entries.len());
+        for entry in &mut entries {
+            let offset = contents.len() as u64;
+            let (size, compressed) = match &mut entry.content {
+                Content::Resource(data) => match zip {
+                    Some(zip) => {
+                        let compressed = deflate(data);
+                        let header =
+                            ResourceHeader::new(compressed.len() as u64, data.len() as u64, zip);
+                        header.write(&mut contents, endian);
+                        contents.extend_from_slice(&compressed);
+                        (data.len() as u64, contents.len() as u64 - offset)
+                    }
+                    None => {
+                        contents.extend_from_slice(data);
+                        (data.len() as u64, 0)
+                    }
+                },
+                Content::Directory(children) => {
+                    let size = children.len() * mem::size_of::<u32>();
+                    contents.resize(contents.len() + size, 0);
+                    (size as u64, 0)
+                }
+                Content::Package(modules) => {
+                    let size = modules.len() * 2 * mem::size_of::<u32>();
+                    contents.resize(contents.len() + size, 0);
+                    (size as u64, 0)
+                }
+            };
+            placed.push((offset, size, compressed));
+        }
+
+        let mut locations: Vec<u8> = vec![ImageLocation::ATTRIBUTE_END];
+        let mut location_offsets: HashMap<&str, u32> = HashMap::new();
+        for (entry, &(offset, size, compressed)) in entries.iter().zip(&placed) {
+            let mut attributes = [0u64; ImageLocation::ATTRIBUTE_COUNT as usize];
+            attributes[ImageLocation::ATTRIBUTE_MODULE as usize] =
+                strings.add(&entry.module) as u64;
+            attributes[ImageLocation::ATTRIBUTE_PARENT as usize] =
+                strings.add(&entry.parent) as u64;
+            attributes[ImageLocation::ATTRIBUTE_BASE as usize] = strings.add(&entry.base) as u64;
+            attributes[ImageLocation::ATTRIBUTE_EXTENSION as usize] =
+                strings.add(&entry.extension) as u64;
+            attributes[ImageLocation::ATTRIBUTE_OFFSET as usize] = offset;
+            attributes[ImageLocation::ATTRIBUTE_COMPRESSED as usize] = compressed;
+            attributes[ImageLocation::ATTRIBUTE_UNCOMPRESSED as usize] = size;
+            location_offsets.insert(&entry.path, locations.len() as u32);
+            ImageWriter::write_location(&mut locations, &attributes);
+        }
+
+        for (entry, &(offset, _, _)) in entries.iter().zip(&placed) {
+            let mut content: Vec<u8> = Vec::new();
+            match &entry.content {
+                Content::Resource(_) => continue,
+                Content::Directory(children) => children.iter().for_each(|child| {
+                    endian.put_u32(&mut content, location_offsets[child.as_str()])
+                }),
+                Content::Package(modules) => modules.iter().for_each(|(empty, module)| {
+                    endian.put_u32(&mut content, *empty as u32);
+                    endian.put_u32(&mut content, strings.add(module));
+                }),
+            }
+            let offset = offset as usize;
+            contents[offset..offset + content.len()].copy_from_slice(&content);
+        }
+
+        let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        let (redirect, indexes) = ImageWriter::perfect_hash(&paths);
+        let mut offsets = vec![0u32; paths.len()];
+        for (path, index) in paths.iter().zip(indexes) {
+            offsets[index] = location_offsets[path];
+        }
+
+        let mut index: Vec<u8> = Vec::new();
+        endian.put_u32(&mut index, ImageHeader::MAGIC);
+        endian.put_u32(
+            &mut index,
+            ImageHeader::MAJOR_VERSION << 16 | ImageHeader::MINOR_VERSION,
+        );
+        endian.put_u32(&mut index, 0);
+        endian.put_u32(&mut index, entries.len() as u32);
+        endian.put_u32(&mut index, entries.len() as u32);
+        endian.put_u32(&mut index, locations.len() as u32);
+        endian.put_u32(&mut index, strings.bytes.len() as u32);
+        debug_assert_eq!(index.len(), ImageHeader::SIZE);
+        redirect
+            .iter()
+            .for_each(|&value| endian.put_u32(&mut index, value as u32));
+        offsets
+            .iter()
+            .for_each(|&offset| endian.put_u32(&mut index, offset));
+        out.write_all(&index)?;
+        out.write_all(&locations)?;
+        out.write_all(&strings.bytes)?;
+        out.write_all(&contents)?;
+        out.flush()
+    }
+
+    /// Write the image to the file at the path.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(io::BufWriter::new(fs::File::create(path)?))
+    }
+}
+
+impl Default for ImageWriter {
+    fn default() -> Self {
+        ImageWriter::new()
+    }
+}
+
+/// Write a small image of two modules to a temporary file, returns its path.
+#[cfg(test)]
+pub(crate) fn write_test_image(name: &str, writer: ImageWriter) -> String {
+    let mut writer = writer;
+    writer
+        .add_resource(
+            "java.base",
+            "java/lang/Object.class",
+            vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61],
+        )
+        .add_resource(
+            "java.base",
+            "java/lang/String.class",
+            vec![0xCA, 0xFE, 0xBA, 0xBE, 1, 2, 3],
+        )
+        .add_resource(
+            "java.base",
+            "java/lang/ref/Reference.class",
+            [0xCA, 0xFE, 0xBA, 0xBE].repeat(100),
+        )
+        .add_resource(
+            "java.base",
+            "META-INF/services/java.nio.file.spi.FileSystemProvider",
+            b"jdk.nio.zipfs.ZipFileSystemProvider\n".to_vec(),
+        )
+        .add_resource(
+            "java.sql",
+            "java/sql/Driver.class",
+            vec![0xCA, 0xFE, 0xBA, 0xBE, 9],
+        )
+        .add_resource(
+            "java.sql",
+            "META-INF/versions/11/java/sql/Driver.class",
+            vec![0xCA, 0xFE, 0xBA, 0xBE, 11],
+        );
+    let path = std::env::temp_dir().join(format!("{}-{}.jimage", name, std::process::id()));
+    writer.write_to_file(&path).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_test_image, ImageWriter};
+    use crate::jimage::{image_file::Endian, JIMAGE_Open, JIMAGE_PackageToModule, JImage};
+
+    fn check_image(path: &str) {
+        let image = JImage::open(path).ok().unwrap();
+        assert_eq!(image.modules(), ["java.base", "java.sql"]);
+        assert_eq!(image.packages("java.base"), ["java/lang", "java/lang/ref"]);
+        assert_eq!(image.packages("java.sql"), ["java/sql"]);
+        assert_eq!(
+            image
+                .resource("java.base", "java/lang/String.class")
+                .ok()
+                .as_deref(),
+            Some(&[0xCA, 0xFE, 0xBA, 0xBE, 1, 2, 3][..])
+        );
+        assert_eq!(
+            image
+                .resource("java.base", "java/lang/ref/Reference.class")
+                .ok()
+                .map(|data| data.len()),
+            Some(400)
+        );
+        assert_eq!(
+            image
+                .versioned_resource("java.sql", "java/sql/Driver.class", 17)
+                .ok()
+                .as_deref(),
+            Some(&[0xCA, 0xFE, 0xBA, 0xBE, 11][..])
+        );
+        assert!(image
+            .resource("java.sql", "java/lang/Object.class")
+            .is_err());
+        let mut names: Vec<String> = image.resources().map(|entry| entry.path()).collect();
+        names.sort();
+        assert_eq!(names.len(), 6);
+        assert_eq!(
+            names[0],
+            "/java.base/META-INF/services/java.nio.file.spi.FileSystemProvider"
+        );
+        assert!(image.entries().all(|entry| entry.is_ok()));
+        for entry in image.resources() {
+            assert_eq!(
+                image.read(&entry).ok().map(|data| data.len() as u64),
+                Some(entry.size)
+            );
+        }
+        let module = JIMAGE_Open(path.to_string())
+            .ok()
+            .and_then(|image| JIMAGE_PackageToModule(image, "java/lang/ref".to_string()));
+        assert_eq!(module, Some("java.base".to_string()));
+    }
+
+    #[test]
+    fn we_can_write_an_image() {
+        let path = write_test_image("writer", ImageWriter::new());
+        check_image(&path);
+        let image = JImage::open(&path).ok().unwrap();
+        assert!(image.resources().all(|entry| entry.compressed_size == 0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_write_a_compressed_image() {
+        let path = write_test_image("writer-zip", ImageWriter::new().compress(true));
+        check_image(&path);
+        let image = JImage::open(&path).ok().unwrap();
+        for entry in image.resources() {
+            assert!(entry.compressed_size > 0);
+            assert_eq!(
+                image.decompressor(&entry).ok().flatten().as_deref(),
+                Some("zip")
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_write_a_byte_swapped_image() {
+        let swapped = match Endian::native() {
+            Endian::Little => Endian::Big,
+            Endian::Big => Endian::Little,
+        };
+        let path = write_test_image(
+            "writer-swapped",
+            ImageWriter::new().endian(swapped).compress(true),
+        );
+        check_image(&path);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_write_an_image_of_a_directory_tree() {
+        let root = std::env::temp_dir().join(format!("writer-tree-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("m/p/q")).unwrap();
+        std::fs::write(root.join("m/p/q/C.class"), [0xCA, 0xFE, 0xBA, 0xBE]).unwrap();
+        std::fs::write(root.join("m/module-info.class"), [0xCA, 0xFE]).unwrap();
+        let path = root.join("modules");
+        let mut writer = ImageWriter::new();
+        writer.add_directory(&root).unwrap();
+        writer.write_to_file(&path).unwrap();
+        let image = JImage::open(&path).ok().unwrap();
+        assert_eq!(image.modules(), ["m"]);
+        assert_eq!(image.packages("m"), ["p/q"]);
+        assert_eq!(
+            image.resource("m", "module-info.class").ok().as_deref(),
+            Some(&[0xCA, 0xFE][..])
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,4 +1,73 @@
+use std::{error::Error, fmt, io};
+
+/// Errors reading a jimage file.
+#[derive(Debug)]
 pub enum JImageError {
-    FileOpenError,
+    /// The image file could not be opened or mapped.
+    FileOpenError(String, io::Error),
+    /// The file does not start with the jimage magic in either byte order.
+    BadMagic(u32),
+    /// The image was written by a version of jlink this reader does not know.
+    UnsupportedVersion {
+        /// Major version of the image.
+        major: u32,
+        /// Minor version of the image.
+        minor: u32,
+    },
+    /// A section of the image ends past the end of the file.
+    TruncatedSection {
+        /// Name of the section.
+        section: &'static str,
+        /// Offset of the end of the section.
+        end: u64,
+        /// Size of the image file.
+        file_size: u64,
+    },
+    /// The attributes of the location at the offset are malformed or point outside of
+    /// the image.
+    BadLocation(u32, &'static str),
+    /// The image has no resource at the path.
+    ResourceNotFound(String),
+    /// A compressed resource names a decompressor that is not available.
+    UnknownDecompressor(String),
+    /// A compressed resource could not be decompressed.
     DecompressError(String),
 }
+
+impl fmt::Display for JImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JImageError::FileOpenError(name, error) => {
+                write!(f, "cannot open image file {}: {}", name, error)
+            }
+            JImageError::BadMagic(magic) => write!(f, "bad image magic 0x{:08X}", magic),
+            JImageError::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported image version {}.{}", major, minor)
+            }
+            JImageError::TruncatedSection {
+                section,
+                end,
+                file_size,
+            } => write!(
+                f,
+                "image {} ends at {} past the end of the file at {}",
+                section, end, file_size
+            ),
+            JImageError::BadLocation(offset, reason) => {
+                write!(f, "bad location at offset {}: {}", offset, reason)
+            }
+            JImageError::ResourceNotFound(path) => write!(f, "resource {} not found", path),
+            JImageError::UnknownDecompressor(name) => write!(f, "unknown decompressor {}", name),
+            JImageError::DecompressError(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for JImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JImageError::FileOpenError(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fs::File, io, ops::Deref};
+
+/// Read only view of a whole file. On unix the file is memory mapped, elsewhere it is
+/// read into memory.
+pub struct MappedFile {
+    #[cfg(unix)]
+    address: *mut libc::c_void,
+    #[cfg(not(unix))]
+    data: Vec<u64>,
+    len: usize,
+}
+
+// The mapping is never written, so it can be shared between threads.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    #[cfg(unix)]
+    pub fn map(file: &File) -> io::Result<MappedFile> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(MappedFile {
+                address: std::ptr::null_mut(),
+                len,
+            });
+        }
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MappedFile { address, len })
+    }
+
+    /// The words keep the data aligned like a mapping, so the tables can be viewed as
+    /// `u32` slices.
+    #[cfg(not(unix))]
+    pub fn map(file: &File) -> io::Result<MappedFile> {
+        use std::io::Read;
+
+        let len = file.metadata()?.len() as usize;
+        let mut data = vec![0u64; (len + 7) / 8];
+        let bytes = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, len) };
+        (&*file).read_exact(bytes)?;
+        Ok(MappedFile { data, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.address as *const u8, self.len) }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr() as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.address, self.len) };
+        }
+    }
+}
@@ -1,11 +1,18 @@
+mod image;
 mod image_decompressor;
 mod image_file;
+mod image_writer;
 mod jimage_error;
+mod mapped_file;
 mod zip_utils;
 use std::sync::Arc;
 
-use self::{
-    image_file::{ImageFileReader, ImageLocation},
+use self::image_file::ImageFileReader;
+
+pub use self::{
+    image::{Entries, JImage, ResourceEntry, Resources},
+    image_file::{Endian, ImageHeader},
+    image_writer::ImageWriter,
     jimage_error::JImageError,
 };
 
@@ -27,19 +34,25 @@ pub fn JIMAGE_PackageToModule(jimage: Arc<JImageFile>, package_name: String) ->
     jimage.package_to_module(package_name)
 }
 
+/// Find the resource as seen by the release of the version, e.g. `17` or `17.0.2`.
+/// Multi-release resources of the module are preferred up to that release, an empty or
+/// unparsable version finds the unversioned resource only.
 pub fn JIMAGE_FindResource(
     jimage: Arc<JImageFile>,
     module_name: String,
     version: String,
     name: String,
 ) -> Option<(JImageLocationRef, ResourceSize)> {
-    let full_path = format!("/{}/{}", module_name, name);
-    assert!(!name.is_empty(), "name should not be empty.");
-    assert!(
-        full_path.len() <= JIMAGE_MAX_PATH,
-        "full path should not exceed max path."
-    );
-    jimage.find_location_index(full_path)
+    if name.is_empty() || module_name.len() + name.len() + 2 > JIMAGE_MAX_PATH {
+        return None;
+    }
+    let release = version
+        .split('.')
+        .next()
+        .and_then(|major| major.parse::<u32>().ok())
+        .unwrap_or(0);
+    JImage::versioned_paths(&module_name, &name, release)
+        .find_map(|full_path| jimage.find_location_index(full_path))
 }
 
 pub fn JIMAGE_GetResource(jimage: Arc<JImageFile>, location: JImageLocationRef) -> Option<Vec<u8>> {
@@ -56,89 +69,165 @@ type JImageResourceVisitor = fn(
     arg: *const (),
 ) -> bool;
 
+/// Visit the resources of the image until the visitor returns true. Multi-release
+/// resources are visited with their release and package, unversioned resources with
+/// the base version `9`. A malformed location stops the iteration with an error.
 pub fn JIMAGE_ResourceIterator(
     jimage: Arc<JImageFile>,
     visitor: JImageResourceVisitor,
     arg: *const (),
-) {
-    let n_entries = jimage.table_length();
-    let strings = jimage.get_strings();
-    for i in 0..n_entries {
-        if let Some(location) = jimage.get_location(i as u32) {
-            let module_offset = location.get_attribute(ImageLocation::ATTRIBUTE_MODULE) as u32;
-            if module_offset == 0 {
-                continue;
-            }
-
-            let module = strings.get(module_offset).unwrap();
-            if module == "modules".to_owned() || module == "packages".to_owned() {
-                continue;
-            }
-
-            let parent_offset = location.get_attribute(ImageLocation::ATTRIBUTE_PARENT) as u32;
-            let parent = strings.get(parent_offset).unwrap();
-            let base_offset = location.get_attribute(ImageLocation::ATTRIBUTE_BASE) as u32;
-            let base = strings.get(base_offset).unwrap();
-            let ext_offset = location.get_attribute(ImageLocation::ATTRIBUTE_EXTENSION) as u32;
-            let extension = strings.get(ext_offset).unwrap();
-
-            if visitor(
-                Arc::clone(&jimage),
-                module,
-                "9".to_owned(),
-                parent,
-                base,
-                extension,
-                arg.clone(),
-            ) {
-                break;
-            }
+) -> Result<(), JImageError> {
+    let image = JImage::from(Arc::clone(&jimage));
+    for entry in image.entries() {
+        let entry = entry?;
+        let version = entry.version.unwrap_or(JImage::BASE_VERSION);
+        if visitor(
+            Arc::clone(&jimage),
+            entry.module.clone(),
+            version.to_string(),
+            entry.package().to_string(),
+            entry.base.clone(),
+            entry.extension.clone(),
+            arg,
+        ) {
+            break;
         }
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{JIMAGE_FindResource, JIMAGE_Open, JIMAGE_PackageToModule};
+    use std::sync::Arc;
+
+    use super::{
+        image_writer::write_test_image, ImageWriter, JIMAGE_FindResource, JIMAGE_GetResource,
+        JIMAGE_Open, JIMAGE_PackageToModule, JIMAGE_ResourceIterator, JImageFile,
+    };
 
     #[test]
     fn we_can_open_an_image() {
-        if let Ok(java_home) = std::env::var("JAVA_HOME") {
-            let path = format!("{}/lib/modules", java_home);
-            let result = JIMAGE_Open(path);
-            assert!(result.is_ok(), "JImage failed to open.")
-        }
+        let path = write_test_image("open", ImageWriter::new());
+        let result = JIMAGE_Open(path.clone());
+        assert!(result.is_ok(), "JImage failed to open.");
+        assert!(JIMAGE_Open(format!("{}.missing", path)).is_err());
+        std::fs::remove_file(path).unwrap();
     }
 
     #[test]
     fn we_can_get_module_of_packages() {
-        if let Ok(java_home) = std::env::var("JAVA_HOME") {
-            let path = format!("{}/lib/modules", java_home);
-            let module = JIMAGE_Open(path)
-                .ok()
-                .and_then(|image| JIMAGE_PackageToModule(image, "sun/reflect".to_string()));
-            assert_eq!(
-                module,
-                Some("jdk.unsupported".to_owned()),
-                "fail to find module of packages."
-            );
-        }
+        let path = write_test_image("package-to-module", ImageWriter::new());
+        let module = JIMAGE_Open(path.clone())
+            .ok()
+            .and_then(|image| JIMAGE_PackageToModule(image, "java/lang/ref".to_string()));
+        assert_eq!(
+            module,
+            Some("java.base".to_owned()),
+            "fail to find module of packages."
+        );
+        std::fs::remove_file(path).unwrap();
     }
 
     #[test]
     fn we_can_find_a_resource() {
-        if let Ok(java_home) = std::env::var("JAVA_HOME") {
-            let path = format!("{}/lib/modules", java_home);
-            let result = JIMAGE_Open(path).ok().and_then(|image| {
-                JIMAGE_FindResource(
-                    image,
-                    "java.base".to_owned(),
-                    "9.0".to_owned(),
-                    "java/lang/String.class".to_owned(),
-                )
-            });
-            assert!(result.is_some(), "fail to get resource offset");
+        let path = write_test_image("find-resource", ImageWriter::new());
+        let result = JIMAGE_Open(path.clone()).ok().and_then(|image| {
+            JIMAGE_FindResource(
+                image,
+                "java.base".to_owned(),
+                "9.0".to_owned(),
+                "java/lang/String.class".to_owned(),
+            )
+        });
+        assert_eq!(result.map(|(_, size)| size), Some(7));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn collect_java_sql(
+        _jimage: Arc<JImageFile>,
+        module_name: String,
+        version: String,
+        package: String,
+        name: String,
+        extension: String,
+        arg: *const (),
+    ) -> bool {
+        if module_name == "java.sql" {
+            let resources = unsafe { &mut *(arg as *mut Vec<String>) };
+            resources.push(format!("{} {}/{}.{}", version, package, name, extension));
         }
+        false
+    }
+
+    #[test]
+    fn we_can_iterate_the_resources() {
+        let path = write_test_image("iterate", ImageWriter::new());
+        let mut resources: Vec<String> = Vec::new();
+        let result = JIMAGE_Open(path.clone()).and_then(|image| {
+            JIMAGE_ResourceIterator(
+                image,
+                collect_java_sql,
+                &mut resources as *mut Vec<String> as *const (),
+            )
+        });
+        assert!(result.is_ok(), "fail to iterate resources");
+        resources.sort();
+        assert_eq!(
+            resources,
+            ["11 java/sql/Driver.class", "9 java/sql/Driver.class"]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_find_a_resource_of_a_release() {
+        let path = write_test_image("find-release", ImageWriter::new());
+        let image = JIMAGE_Open(path.clone()).ok().unwrap();
+        let base = JIMAGE_FindResource(
+            image.clone(),
+            "java.base".to_owned(),
+            "".to_owned(),
+            "java/lang/String.class".to_owned(),
+        );
+        let release = JIMAGE_FindResource(
+            image.clone(),
+            "java.base".to_owned(),
+            "17.0.2".to_owned(),
+            "java/lang/String.class".to_owned(),
+        );
+        assert!(base.is_some());
+        assert_eq!(base, release);
+        assert!(JIMAGE_FindResource(
+            image,
+            "java.base".to_owned(),
+            "17".to_owned(),
+            "".to_owned()
+        )
+        .is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn we_can_find_the_resources_of_a_written_image() {
+        let path = write_test_image("find", ImageWriter::new());
+        let image = JIMAGE_Open(path.clone()).ok().unwrap();
+        let find = |release: &str| {
+            JIMAGE_FindResource(
+                image.clone(),
+                "java.sql".to_owned(),
+                release.to_owned(),
+                "java/sql/Driver.class".to_owned(),
+            )
+            .and_then(|(location, _)| JIMAGE_GetResource(image.clone(), location))
+        };
+        assert_eq!(find("9"), Some(vec![0xCA, 0xFE, 0xBA, 0xBE, 9]));
+        assert_eq!(find("17.0.2"), Some(vec![0xCA, 0xFE, 0xBA, 0xBE, 11]));
+        assert_eq!(
+            JIMAGE_PackageToModule(image.clone(), "java/sql".to_string()),
+            Some("java.sql".to_string())
+        );
+        assert_eq!(JIMAGE_PackageToModule(image, "javax/sql".to_string()), None);
+        std::fs::remove_file(path).unwrap();
     }
 }
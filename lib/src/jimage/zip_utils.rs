@@ -1,106 +1,58 @@
-use std::ffi::{c_char, c_int, c_longlong, c_ulonglong, c_void, CStr};
+use crate::zip::inflater;
 
 use super::jimage_error::JImageError;
 
-#[link(name = "zip")]
-extern "C" {
-    fn zip_inflate(
-        out_buf: *mut c_void,
-        out_len: c_longlong,
-        in_buf: *mut c_void,
-        in_len: c_longlong,
-        pmsg: *mut *const c_char,
-    ) -> c_int;
-
-    fn zip_deflate(
-        out_buf: *mut c_void,
-        out_len: c_longlong,
-        in_buf: *mut c_void,
-        in_len: c_longlong,
-        pmsg: *mut *const c_char,
-    ) -> c_ulonglong;
-}
-
+/// Inflate the zlib data of `in_buf` into `out_buf`, the data must decompress to
+/// exactly `out_len` bytes.
 pub fn inflate(
-    out_buf: &mut Vec<u8>,
+    out_buf: &mut [u8],
     out_len: u64,
-    in_buf: &mut Vec<u8>,
+    in_buf: &[u8],
     in_len: u64,
 ) -> Result<(), JImageError> {
-    let mut pmsg: *const c_char = std::ptr::null();
-    unsafe {
-        if zip_inflate(
-            out_buf.as_mut_ptr() as *mut c_void,
-            out_len as c_longlong,
-            in_buf.as_mut_ptr() as *mut c_void,
-            in_len as c_longlong,
-            &mut pmsg,
-        ) == 0
-        {
-            if !pmsg.is_null() {
-                return Err(JImageError::DecompressError(
-                    CStr::from_ptr(pmsg).to_str().unwrap().to_string(),
-                ));
-            }
-            return Err(JImageError::DecompressError(
-                "fail to decomrpess data.".to_string(),
-            ));
-        }
-        Ok(())
+    let input = in_buf.get(..in_len as usize).ok_or_else(|| {
+        JImageError::DecompressError("input length exceeds the buffer.".to_string())
+    })?;
+    let output = out_buf.get_mut(..out_len as usize).ok_or_else(|| {
+        JImageError::DecompressError("output length exceeds the buffer.".to_string())
+    })?;
+    match inflater::inflate(input, output) {
+        Ok(len) if len as u64 == out_len => Ok(()),
+        Ok(_) => Err(JImageError::DecompressError(
+            "INFLATER_inflateFully: Unexpected end of stream".to_string(),
+        )),
+        Err(error) => Err(JImageError::DecompressError(format!(
+            "INFLATER_inflateFully: {}",
+            error
+        ))),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::ffi::{c_char, c_longlong, CStr};
+    use crate::zip::deflater::deflate;
 
     #[test]
     fn we_can_compression_data() {
-        let mut in_buf: Vec<u8> = "hello hello hello"
-            .to_string()
-            .chars()
-            .into_iter()
-            .map(|c| c as u8)
-            .collect();
-        let mut out_buf: Vec<u8> = vec![0u8; 16];
-        let in_len = in_buf.len() as c_longlong;
-        let out_len = in_buf.len() as c_longlong;
-        let mut pmsg: *const c_char = std::ptr::null();
-        let result = unsafe {
-            zip_deflate(
-                out_buf.as_mut_ptr() as *mut c_void,
-                out_len,
-                in_buf.as_mut_ptr() as *mut c_void,
-                in_len,
-                &mut pmsg,
-            )
-        };
-        assert!(result == 16);
-        assert!(
-            out_buf == vec![120, 156, 203, 72, 205, 201, 201, 87, 200, 64, 144, 0, 58, 46, 6, 125,]
-        )
+        let in_buf: Vec<u8> = deflate(b"hello hello hello");
+        let mut out_buf: Vec<u8> = vec![0u8; 17];
+        let in_len = in_buf.len() as u64;
+        assert!(inflate(&mut out_buf, 17, &in_buf, in_len).is_ok());
+        assert!(String::from_utf8(out_buf) == Ok("hello hello hello".to_string()))
     }
 
     #[test]
     fn we_can_decompress_data() {
-        let mut in_buf: Vec<u8> = vec![
+        let in_buf: Vec<u8> = vec![
             120, 156, 203, 72, 205, 201, 201, 87, 200, 64, 144, 0, 58, 46, 6, 125,
         ];
         let mut out_buf: Vec<u8> = vec![0u8; 17];
-        let in_len = in_buf.len() as c_longlong;
-        let out_len = out_buf.len() as c_longlong;
-        let mut pmsg: *const c_char = std::ptr::null();
-        let result = unsafe {
-            zip_inflate(
-                out_buf.as_mut_ptr() as *mut c_void,
-                out_len,
-                in_buf.as_mut_ptr() as *mut c_void,
-                in_len,
-                &mut pmsg,
-            )
-        };
-        assert!(result != 0);
-        assert!(String::from_utf8(out_buf) == Ok("hello hello hello".to_string()))
+        let in_len = in_buf.len() as u64;
+        let out_len = out_buf.len() as u64;
+        assert!(inflate(&mut out_buf, out_len, &in_buf, in_len).is_ok());
+        assert!(String::from_utf8(out_buf) == Ok("hello hello hello".to_string()));
+        let mut out_buf: Vec<u8> = vec![0u8; 18];
+        assert!(inflate(&mut out_buf, 18, &in_buf, in_len).is_err());
     }
 }
@@ -1,6 +1,7 @@
 #![warn(missing_docs)]
 
 pub mod jimage;
+pub mod zip;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,611 @@
+//! Reader of zip archives, the format of jar files, described by the APPNOTE.TXT of
+//! PKWARE.
+//!
+//! The entries are listed from the central directory found with the end of central
+//! directory record, the local header of an entry is only read to find its data.
+//! Sizes and offsets that do not fit in 32 bits are read from the ZIP64 end of
+//! central directory record and extra fields. Data before the archive, like the
+//! launcher of a self extracting archive, is skipped. Any malformed archive and any
+//! entry that does not match its CRC or size is reported as an `InvalidData` error.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::{checksum::Crc32, inflater::Inflater};
+
+/// How the data of an entry is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionMethod {
+    /// Not compressed.
+    Stored,
+    /// Compressed into raw deflate data.
+    Deflated,
+    /// A method this reader does not support.
+    Unsupported(u16),
+}
+
+impl From<u16> for CompressionMethod {
+    fn from(method: u16) -> Self {
+        match method {
+            0 => CompressionMethod::Stored,
+            8 => CompressionMethod::Deflated,
+            method => CompressionMethod::Unsupported(method),
+        }
+    }
+}
+
+/// An entry of the central directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// Path of the entry in the archive, directories end with a `/`.
+    pub name: String,
+    /// How the data is stored.
+    pub method: CompressionMethod,
+    /// General purpose bit flags.
+    pub flags: u16,
+    /// CRC-32 of the uncompressed data.
+    pub crc: u32,
+    /// Size of the stored data.
+    pub compressed_size: u64,
+    /// Size of the uncompressed data.
+    pub size: u64,
+    /// Offset of the local header from the start of the archive.
+    pub header_offset: u64,
+}
+
+impl ZipEntry {
+    /// Flag of an encrypted entry.
+    const ENCRYPTED: u16 = 0x1;
+
+    /// Whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// A zip archive read from a seekable reader.
+pub struct ZipArchive<R> {
+    reader: R,
+    /// Offset of the start of the archive in the reader.
+    prefix: u64,
+    entries: Vec<ZipEntry>,
+    /// Index of the first entry of each name, later entries of the name are ignored.
+    names: HashMap<String, usize>,
+    comment: Vec<u8>,
+}
+
+fn invalid_data<T>(message: String) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+impl ZipArchive<BufReader<File>> {
+    /// Open the archive at the path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        ZipArchive::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    const LOCAL_HEADER: u32 = 0x0403_4B50;
+    const CENTRAL_HEADER: u32 = 0x0201_4B50;
+    const END_HEADER: u32 = 0x0605_4B50;
+    const ZIP64_END_HEADER: u32 = 0x0606_4B50;
+    const ZIP64_LOCATOR: u32 = 0x0706_4B50;
+    const LOCAL_HEADER_SIZE: usize = 30;
+    const CENTRAL_HEADER_SIZE: usize = 46;
+    const END_SIZE: usize = 22;
+    const ZIP64_END_SIZE: usize = 56;
+    const ZIP64_LOCATOR_SIZE: usize = 20;
+    const ZIP64_EXTRA: u16 = 0x0001;
+    const MAX_COMMENT: usize = 0xFFFF;
+
+    /// Read the central directory of the archive.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        let tail_length =
+            length.min((ZipArchive::<R>::END_SIZE + ZipArchive::<R>::MAX_COMMENT) as u64);
+        let tail_start = length - tail_length;
+        let tail = ZipArchive::read_at(&mut reader, tail_start, tail_length as usize)?;
+        let end = ZipArchive::<R>::find_end(&tail).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "zip END header not found")
+        })?;
+        let end_position = tail_start + end as u64;
+        let comment_length = u16_at(&tail, end + 20) as usize;
+        let comment = tail[end + 22..end + 22 + comment_length].to_vec();
+        let mut count = u16_at(&tail, end + 10) as u64;
+        let mut directory_size = u32_at(&tail, end + 12) as u64;
+        let mut directory_offset = u32_at(&tail, end + 16) as u64;
+        let mut directory_end = end_position;
+        // without ZIP64 the count of an archive with more entries wraps around
+        let mut count_mask = u16::MAX as u64;
+
+        // the ZIP64 record is found with the locator just before the END header
+        let locator = end_position.checked_sub(ZipArchive::<R>::ZIP64_LOCATOR_SIZE as u64);
+        let locator = match locator {
+            Some(position) => {
+                let bytes = ZipArchive::read_at(
+                    &mut reader,
+                    position,
+                    ZipArchive::<R>::ZIP64_LOCATOR_SIZE,
+                )?;
+                (u32_at(&bytes, 0) == ZipArchive::<R>::ZIP64_LOCATOR).then_some((position, bytes))
+            }
+            None => None,
+        };
+        if let Some((locator_position, locator)) = locator {
+            // the record is at the offset of the locator unless the archive has a
+            // prefix, then it is right before the locator
+            let stated = u64_at(&locator, 8);
+            let before = locator_position.checked_sub(ZipArchive::<R>::ZIP64_END_SIZE as u64);
+            let mut record = None;
+            for position in [Some(stated), before].into_iter().flatten() {
+                if position.saturating_add(ZipArchive::<R>::ZIP64_END_SIZE as u64)
+                    > locator_position
+                {
+                    continue;
+                }
+                let bytes =
+                    ZipArchive::read_at(&mut reader, position, ZipArchive::<R>::ZIP64_END_SIZE)?;
+                if u32_at(&bytes, 0) == ZipArchive::<R>::ZIP64_END_HEADER {
+                    record = Some((position, bytes));
+                    break;
+                }
+            }
+            let (position, record) = record.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "zip64 END header not found")
+            })?;
+            count = u64_at(&record, 32);
+            directory_size = u64_at(&record, 40);
+            directory_offset = u64_at(&record, 48);
+            directory_end = position;
+            count_mask = u64::MAX;
+        }
+
+        let prefix = directory_end
+            .checked_sub(directory_offset)
+            .and_then(|end| end.checked_sub(directory_size))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid zip END header"))?;
+        let directory = ZipArchive::read_at(
+            &mut reader,
+            prefix + directory_offset,
+            directory_size as usize,
+        )?;
+        let mut archive = ZipArchive {
+            reader,
+            prefix,
+            entries: Vec::new(),
+            names: HashMap::new(),
+            comment,
+        };
+        let mut position = 0;
+        while position < directory.len() {
+            let (entry, size) = ZipArchive::<R>::read_central_header(&directory[position..])?;
+            archive
+                .names
+                .entry(entry.name.clone())
+                .or_insert(archive.entries.len());
+            archive.entries.push(entry);
+            position += size;
+        }
+        if archive.entries.len() as u64 & count_mask != count {
+            return invalid_data(format!(
+                "zip central directory has {} entries instead of {}",
+                archive.entries.len(),
+                count
+            ));
+        }
+        Ok(archive)
+    }
+
+    /// Offset of the END header in the tail of the archive, the last signature followed
+    /// by a comment that fits.
+    fn find_end(tail: &[u8]) -> Option<usize> {
+        let last = tail.len().checked_sub(ZipArchive::<R>::END_SIZE)?;
+        (0..=last).rev().find(|&position| {
+            u32_at(tail, position) == ZipArchive::<R>::END_HEADER
+                && position + ZipArchive::<R>::END_SIZE + u16_at(tail, position + 20) as usize
+                    <= tail.len()
+        })
+    }
+
+    fn read_at(reader: &mut R, position: u64, length: usize) -> io::Result<Vec<u8>> {
+        reader.seek(SeekFrom::Start(position))?;
+        let mut bytes = Vec::new();
+        reader.take(length as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != length {
+            return invalid_data(format!(
+                "zip file truncated at {}",
+                position + bytes.len() as u64
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// Read a central directory header, returns the entry and the size of the header.
+    fn read_central_header(bytes: &[u8]) -> io::Result<(ZipEntry, usize)> {
+        let fixed = ZipArchive::<R>::CENTRAL_HEADER_SIZE;
+        if bytes.len() < fixed || u32_at(bytes, 0) != ZipArchive::<R>::CENTRAL_HEADER {
+            return invalid_data("invalid zip CEN header (bad signature)".to_string());
+        }
+        let name_length = u16_at(bytes, 28) as usize;
+        let extra_length = u16_at(bytes, 30) as usize;
+        let comment_length = u16_at(bytes, 32) as usize;
+        let size = fixed + name_length + extra_length + comment_length;
+        if bytes.len() < size {
+            return invalid_data("invalid zip CEN header (bad header size)".to_string());
+        }
+        let name = String::from_utf8_lossy(&bytes[fixed..fixed + name_length]).into_owned();
+        let mut entry = ZipEntry {
+            method: u16_at(bytes, 10).into(),
+            flags: u16_at(bytes, 8),
+            crc: u32_at(bytes, 16),
+            compressed_size: u32_at(bytes, 20) as u64,
+            size: u32_at(bytes, 24) as u64,
+            header_offset: u32_at(bytes, 42) as u64,
+            name,
+        };
+        let extra = &bytes[fixed + name_length..fixed + name_length + extra_length];
+        ZipArchive::<R>::read_zip64_extra(&mut entry, extra)?;
+        Ok((entry, size))
+    }
+
+    /// Replace the sizes and offset saturated at 32 bits by the values of the ZIP64
+    /// extra field, which only holds the saturated ones in that order.
+    fn read_zip64_extra(entry: &mut ZipEntry, mut extra: &[u8]) -> io::Result<()> {
+        while extra.len() >= 4 {
+            let id = u16_at(extra, 0);
+            let length = u16_at(extra, 2) as usize;
+            if extra.len() < 4 + length {
+                break;
+            }
+            if id == ZipArchive::<R>::ZIP64_EXTRA {
+                let mut data = &extra[4..4 + length];
+                for value in [
+                    &mut entry.size,
+                    &mut entry.compressed_size,
+                    &mut entry.header_offset,
+                ] {
+                    if *value == u32::MAX as u64 {
+                        if data.len() < 8 {
+                            return invalid_data(format!(
+                                "invalid zip64 extra data of {}",
+                                entry.name
+                            ));
+                        }
+                        *value = u64_at(data, 0);
+                        data = &data[8..];
+                    }
+                }
+            }
+            extra = &extra[4 + length..];
+        }
+        Ok(())
+    }
+
+    /// The entries in the order of the central directory.
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// The entry with the name.
+    pub fn entry(&self, name: &str) -> Option<&ZipEntry> {
+        self.names.get(name).map(|&index| &self.entries[index])
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the archive has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The comment of the archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Read the uncompressed data of the entry and check its size and CRC.
+    pub fn read(&mut self, entry: &ZipEntry) -> io::Result<Vec<u8>> {
+        if entry.flags & ZipEntry::ENCRYPTED != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("zip entry {} is encrypted", entry.name),
+            ));
+        }
+        let bad_offset = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid LOC header offset of {}", entry.name),
+            )
+        };
+        let position = self
+            .prefix
+            .checked_add(entry.header_offset)
+            .ok_or_else(bad_offset)?;
+        let header = ZipArchive::read_at(
+            &mut self.reader,
+            position,
+            ZipArchive::<R>::LOCAL_HEADER_SIZE,
+        )?;
+        if u32_at(&header, 0) != ZipArchive::<R>::LOCAL_HEADER {
+            return invalid_data(format!(
+                "invalid LOC header of {} (bad signature)",
+                entry.name
+            ));
+        }
+        let data_offset = ZipArchive::<R>::LOCAL_HEADER_SIZE as u64
+            + u16_at(&header, 26) as u64
+            + u16_at(&header, 28) as u64;
+        let data_position = position.checked_add(data_offset).ok_or_else(bad_offset)?;
+        self.reader.seek(SeekFrom::Start(data_position))?;
+        let stored = (&mut self.reader).take(entry.compressed_size);
+        // one byte more than the size to see data that would not fit
+        let limit = entry.size.saturating_add(1);
+        let mut data = Vec::new();
+        match entry.method {
+            CompressionMethod::Stored => stored.take(limit).read_to_end(&mut data)?,
+            CompressionMethod::Deflated => Inflater::new_raw(stored)
+                .take(limit)
+                .read_to_end(&mut data)?,
+            CompressionMethod::Unsupported(method) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "zip entry {} has unsupported compression method {}",
+                        entry.name, method
+                    ),
+                ))
+            }
+        };
+        if data.len() as u64 != entry.size {
+            return invalid_data(format!(
+                "zip entry {} has {} bytes instead of {}",
+                entry.name,
+                data.len(),
+                entry.size
+            ));
+        }
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        if crc.value() != entry.crc {
+            return invalid_data(format!(
+                "invalid entry CRC of {} (expected 0x{:08x} but got 0x{:08x})",
+                entry.name,
+                entry.crc,
+                crc.value()
+            ));
+        }
+        Ok(data)
+    }
+
+    /// Read the entry with the name, `None` if there is no such entry.
+    pub fn read_by_name(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.entry(name).cloned() {
+            Some(entry) => self.read(&entry).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Build an archive of the entries, deflated or stored, with ZIP64 records and extra
+/// fields for every entry when asked.
+#[cfg(test)]
+pub(crate) fn write_test_zip(entries: &[(&str, &[u8], bool)], zip64: bool) -> Vec<u8> {
+    use super::deflater::deflate_raw;
+
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    for &(name, data, deflated) in entries {
+        let stored = if deflated {
+            deflate_raw(data)
+        } else {
+            data.to_vec()
+        };
+        let mut crc = Crc32::new();
+        crc.update(data);
+        let offset = out.len() as u64;
+        let (size32, compressed32, offset32) = if zip64 {
+            (u32::MAX, u32::MAX, u32::MAX)
+        } else {
+            (data.len() as u32, stored.len() as u32, offset as u32)
+        };
+        let mut extra = Vec::new();
+        if zip64 {
+            extra.extend_from_slice(&1u16.to_le_bytes());
+            extra.extend_from_slice(&24u16.to_le_bytes());
+            extra.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            extra.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            extra.extend_from_slice(&offset.to_le_bytes());
+        }
+        let method: u16 = if deflated { 8 } else { 0 };
+        let common = |header: &mut Vec<u8>| {
+            header.extend_from_slice(&20u16.to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes());
+            header.extend_from_slice(&method.to_le_bytes());
+            header.extend_from_slice(&[0; 4]);
+            header.extend_from_slice(&crc.value().to_le_bytes());
+            header.extend_from_slice(&compressed32.to_le_bytes());
+            header.extend_from_slice(&size32.to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        };
+        out.extend_from_slice(&0x0403_4B50u32.to_le_bytes());
+        common(&mut out);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&extra);
+        out.extend_from_slice(&stored);
+
+        directory.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        common(&mut directory);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset32.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+        directory.extend_from_slice(&extra);
+    }
+    let directory_offset = out.len() as u64;
+    out.extend_from_slice(&directory);
+    let count = entries.len() as u64;
+    if zip64 {
+        let record = out.len() as u64;
+        out.extend_from_slice(&0x0606_4B50u32.to_le_bytes());
+        out.extend_from_slice(&44u64.to_le_bytes());
+        out.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u64).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&0x0706_4B50u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&record.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+    }
+    let (count16, offset32) = if zip64 {
+        (u16::MAX, u32::MAX)
+    } else {
+        (count as u16, directory_offset as u32)
+    };
+    out.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&count16.to_le_bytes());
+    out.extend_from_slice(&count16.to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&offset32.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use super::{write_test_zip, CompressionMethod, ZipArchive};
+
+    const CLASS: &[u8] = &[0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61];
+
+    fn text() -> Vec<u8> {
+        b"Manifest-Version: 1.0\r\n".repeat(100)
+    }
+
+    fn test_zip(zip64: bool) -> Vec<u8> {
+        write_test_zip(
+            &[
+                ("META-INF/", b"", false),
+                ("META-INF/MANIFEST.MF", &text(), true),
+                ("p/C.class", CLASS, false),
+            ],
+            zip64,
+        )
+    }
+
+    #[test]
+    fn we_can_read_an_archive() {
+        for zip64 in [false, true] {
+            let mut archive = ZipArchive::new(Cursor::new(test_zip(zip64))).unwrap();
+            assert_eq!(archive.len(), 3);
+            let names: Vec<&str> = archive
+                .entries()
+                .iter()
+                .map(|entry| entry.name.as_str())
+                .collect();
+            assert_eq!(names, ["META-INF/", "META-INF/MANIFEST.MF", "p/C.class"]);
+            assert!(archive.entries()[0].is_dir());
+            let manifest = archive.entry("META-INF/MANIFEST.MF").unwrap().clone();
+            assert_eq!(manifest.method, CompressionMethod::Deflated);
+            assert!(manifest.compressed_size < manifest.size);
+            assert_eq!(archive.read(&manifest).unwrap(), text());
+            assert_eq!(
+                archive.read_by_name("p/C.class").unwrap().as_deref(),
+                Some(CLASS)
+            );
+            assert_eq!(archive.read_by_name("p/D.class").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn we_can_read_an_archive_with_a_prefix_and_a_comment() {
+        let mut bytes = b"#!/bin/sh\nexec java -jar $0\n".to_vec();
+        bytes.extend_from_slice(&test_zip(false));
+        let length = bytes.len();
+        bytes[length - 2..].copy_from_slice(&7u16.to_le_bytes());
+        bytes.extend_from_slice(b"comment");
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.comment(), b"comment");
+        assert_eq!(
+            archive.read_by_name("p/C.class").unwrap().as_deref(),
+            Some(CLASS)
+        );
+    }
+
+    #[test]
+    fn corrupted_archives_are_rejected() {
+        let error = |bytes: Vec<u8>| -> io::Error {
+            match ZipArchive::new(Cursor::new(bytes)) {
+                Ok(mut archive) => archive.read_by_name("p/C.class").unwrap_err(),
+                Err(error) => error,
+            }
+        };
+        assert_eq!(
+            error(b"not a zip file".to_vec()).kind(),
+            io::ErrorKind::InvalidData
+        );
+        let bytes = test_zip(false);
+        assert_eq!(
+            error(bytes[..bytes.len() - 30].to_vec()).kind(),
+            io::ErrorKind::InvalidData
+        );
+        let mut bytes = test_zip(false);
+        let class = bytes
+            .windows(CLASS.len())
+            .position(|window| window == CLASS)
+            .unwrap();
+        bytes[class + 7] = 62;
+        let crc = error(bytes);
+        assert_eq!(crc.kind(), io::ErrorKind::InvalidData);
+        assert!(crc
+            .to_string()
+            .starts_with("invalid entry CRC of p/C.class"));
+
+        let mut bytes = b"prefix".to_vec();
+        bytes.extend_from_slice(&test_zip(false));
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut entry = archive.entry("p/C.class").unwrap().clone();
+        entry.header_offset = u64::MAX - 2;
+        let offset = archive.read(&entry).unwrap_err();
+        assert_eq!(offset.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(offset.to_string(), "invalid LOC header offset of p/C.class");
+    }
+
+    #[test]
+    fn the_first_entry_of_a_name_is_kept() {
+        let bytes = write_test_zip(
+            &[("p/C.class", CLASS, false), ("p/C.class", b"shadow", true)],
+            false,
+        );
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.entry("p/C.class"), Some(&archive.entries()[0]));
+        assert_eq!(
+            archive.read_by_name("p/C.class").unwrap().as_deref(),
+            Some(CLASS)
+        );
+    }
+}
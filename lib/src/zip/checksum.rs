@@ -0,0 +1,338 @@
+//! Checksums used by the zlib and zip formats, and the SHA-256 digest of signed jar
+//! entries.
+
+/// Adler-32 checksum of the zlib format, RFC 1950.
+#[derive(Clone, Copy, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MOD_ADLER: u32 = 65521;
+    /// Largest number of bytes that can be summed before the sums must be reduced.
+    const NMAX: usize = 5552;
+
+    /// A checksum of no data.
+    pub fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    /// Add the bytes to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(Adler32::NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= Adler32::MOD_ADLER;
+            self.b %= Adler32::MOD_ADLER;
+        }
+    }
+
+    /// The checksum of all bytes added so far.
+    pub fn value(&self) -> u32 {
+        self.b << 16 | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Adler32::new()
+    }
+}
+
+/// CRC-32 checksum of the zip and gzip formats, ISO 3309 with the reflected
+/// polynomial 0xEDB88320.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    const TABLE: [u32; 256] = Crc32::table();
+
+    const fn table() -> [u32; 256] {
+        let mut table = [0u32; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    }
+
+    /// A checksum of no data.
+    pub fn new() -> Self {
+        Crc32 { crc: 0xFFFF_FFFF }
+    }
+
+    /// Add the bytes to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.crc = Crc32::TABLE[((self.crc ^ byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    /// The checksum of all bytes added so far.
+    pub fn value(&self) -> u32 {
+        self.crc ^ 0xFFFF_FFFF
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+/// SHA-256 digest of FIPS 180-4, the digest of the entries of signed jars and of the
+/// class files printed by `javap -v`.
+#[derive(Clone, Debug)]
+pub struct Sha256 {
+    state: [u32; 8],
+    /// Bytes added that do not fill a block yet.
+    block: Vec<u8>,
+    length: u64,
+}
+
+impl Sha256 {
+    const BLOCK_SIZE: usize = 64;
+    const INITIAL_STATE: [u32; 8] = [
+        0x6A09_E667,
+        0xBB67_AE85,
+        0x3C6E_F372,
+        0xA54F_F53A,
+        0x510E_527F,
+        0x9B05_688C,
+        0x1F83_D9AB,
+        0x5BE0_CD19,
+    ];
+    const K: [u32; 64] = [
+        0x428A_2F98,
+        0x7137_4491,
+        0xB5C0_FBCF,
+        0xE9B5_DBA5,
+        0x3956_C25B,
+        0x59F1_11F1,
+        0x923F_82A4,
+        0xAB1C_5ED5,
+        0xD807_AA98,
+        0x1283_5B01,
+        0x2431_85BE,
+        0x550C_7DC3,
+        0x72BE_5D74,
+        0x80DE_B1FE,
+        0x9BDC_06A7,
+        0xC19B_F174,
+        0xE49B_69C1,
+        0xEFBE_4786,
+        0x0FC1_9DC6,
+        0x240C_A1CC,
+        0x2DE9_2C6F,
+        0x4A74_84AA,
+        0x5CB0_A9DC,
+        0x76F9_88DA,
+        0x983E_5152,
+        0xA831_C66D,
+        0xB003_27C8,
+        0xBF59_7FC7,
+        0xC6E0_0BF3,
+        0xD5A7_9147,
+        0x06CA_6351,
+        0x1429_2967,
+        0x27B7_0A85,
+        0x2E1B_2138,
+        0x4D2C_6DFC,
+        0x5338_0D13,
+        0x650A_7354,
+        0x766A_0ABB,
+        0x81C2_C92E,
+        0x9272_2C85,
+        0xA2BF_E8A1,
+        0xA81A_664B,
+        0xC24B_8B70,
+        0xC76C_51A3,
+        0xD192_E819,
+        0xD699_0624,
+        0xF40E_3585,
+        0x106A_A070,
+        0x19A4_C116,
+        0x1E37_6C08,
+        0x2748_774C,
+        0x34B0_BCB5,
+        0x391C_0CB3,
+        0x4ED8_AA4A,
+        0x5B9C_CA4F,
+        0x682E_6FF3,
+        0x748F_82EE,
+        0x78A5_636F,
+        0x84C8_7814,
+        0x8CC7_0208,
+        0x90BE_FFFA,
+        0xA450_6CEB,
+        0xBEF9_A3F7,
+        0xC671_78F2,
+    ];
+
+    /// A digest of no data.
+    pub fn new() -> Self {
+        Sha256 {
+            state: Sha256::INITIAL_STATE,
+            block: Vec::with_capacity(Sha256::BLOCK_SIZE),
+            length: 0,
+        }
+    }
+
+    /// Add the bytes to the digest.
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len() as u64);
+        while !bytes.is_empty() {
+            let take = (Sha256::BLOCK_SIZE - self.block.len()).min(bytes.len());
+            self.block.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if self.block.len() == Sha256::BLOCK_SIZE {
+                let block: [u8; Sha256::BLOCK_SIZE] = self.block[..].try_into().unwrap();
+                self.compress(&block);
+                self.block.clear();
+            }
+        }
+    }
+
+    /// The digest of all bytes added, padded with a one bit, zeros and the length in
+    /// bits.
+    pub fn finish(mut self) -> [u8; 32] {
+        let bits = self.length.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block.len() != Sha256::BLOCK_SIZE - 8 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (k, w) in Sha256::K.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adler32, Crc32, Sha256};
+
+    #[test]
+    fn we_can_compute_adler32() {
+        let mut adler = Adler32::new();
+        assert_eq!(adler.value(), 1);
+        adler.update(b"Wikipedia");
+        assert_eq!(adler.value(), 0x11E6_0398);
+        let mut adler = Adler32::new();
+        adler.update(&vec![0xFF; 100_000]);
+        let mut split = Adler32::new();
+        split.update(&vec![0xFF; 40_000]);
+        split.update(&vec![0xFF; 60_000]);
+        assert_eq!(adler.value(), split.value());
+    }
+
+    #[test]
+    fn we_can_compute_crc32() {
+        let mut crc = Crc32::new();
+        assert_eq!(crc.value(), 0);
+        crc.update(b"123456789");
+        assert_eq!(crc.value(), 0xCBF4_3926);
+        crc.update(b"The quick brown fox jumps over the lazy dog");
+        let mut split = Crc32::new();
+        split.update(b"123456789The quick brown fox");
+        split.update(b" jumps over the lazy dog");
+        assert_eq!(crc.value(), split.value());
+    }
+
+    #[test]
+    fn we_can_compute_sha256() {
+        let hex = |digest: [u8; 32]| {
+            digest
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        assert_eq!(
+            hex(Sha256::new().finish()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let mut sha = Sha256::new();
+        sha.update(b"abc");
+        assert_eq!(
+            hex(sha.finish()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // two blocks after the padding, added in parts
+        let mut sha = Sha256::new();
+        sha.update(b"abcdbcdecdefdefgefghfghighijhijkijkl");
+        sha.update(b"jklmklmnlmnomnopnopq");
+        assert_eq!(
+            hex(sha.finish()),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        let mut sha = Sha256::new();
+        sha.update(&vec![b'a'; 1_000_000]);
+        assert_eq!(
+            hex(sha.finish()),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+}
@@ -0,0 +1,446 @@
+//! Compression into deflate data, RFC 1951, with or without the zlib wrapper of
+//! RFC 1950.
+//!
+//! Matches are found with hash chains over a 32K window and lazy matching like the
+//! default level of zlib. Each block is written with the fixed Huffman codes or stored
+//! when that is smaller, the output is valid deflate data for any inflater.
+use std::io::{self, Write};
+
+use super::checksum::Adler32;
+use super::inflater::{DIST_BASE, DIST_EXTRA, LENGTH_BASE, LENGTH_EXTRA, WINDOW_SIZE};
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Input collected before a block is compressed.
+const BLOCK_SIZE: usize = 64 * 1024;
+const MAX_STORED: usize = 65535;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 128;
+const MAX_LAZY: usize = 16;
+const GOOD_LENGTH: usize = 8;
+const NICE_LENGTH: usize = 128;
+/// Matches of minimum length that far away cost more than the literals.
+const TOO_FAR: usize = 4096;
+
+enum Token {
+    Literal(u8),
+    Match { len: usize, dist: usize },
+}
+
+/// Writes bits least significant first.
+struct BitWriter<W> {
+    writer: W,
+    bit_buffer: u64,
+    bit_count: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BitWriter<W> {
+    fn new(writer: W) -> Self {
+        BitWriter {
+            writer,
+            bit_buffer: 0,
+            bit_count: 0,
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+        }
+    }
+
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.buffer.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, codes are stored most significant bit first.
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit_count > 0 {
+            self.bits(0, 8 - self.bit_count);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        debug_assert!(self.bit_count == 0, "bytes must be byte aligned.");
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Fixed Huffman code of a literal/length symbol.
+fn fixed_literal_code(symbol: usize) -> (u32, u32) {
+    match symbol {
+        0..=143 => (0x30 + symbol as u32, 8),
+        144..=255 => (0x190 + (symbol - 144) as u32, 9),
+        256..=279 => ((symbol - 256) as u32, 7),
+        _ => (0xC0 + (symbol - 280) as u32, 8),
+    }
+}
+
+fn length_symbol(len: usize) -> usize {
+    LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= len)
+        .unwrap()
+}
+
+fn dist_symbol(dist: usize) -> usize {
+    DIST_BASE
+        .iter()
+        .rposition(|&base| base as usize <= dist)
+        .unwrap()
+}
+
+/// A writer that compresses everything written to it, [`Deflater::finish`] must be
+/// called to write the end of the stream.
+pub struct Deflater<W: Write> {
+    output: BitWriter<W>,
+    zlib: bool,
+    header_written: bool,
+    /// The window followed by the input that is not compressed yet.
+    input: Vec<u8>,
+    /// Position in the stream of the first byte of `input`.
+    input_start: usize,
+    /// Position in the stream of the first byte that is not compressed yet.
+    pending: usize,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    checksum: Adler32,
+}
+
+impl<W: Write> Deflater<W> {
+    /// Compress into zlib wrapped data, what `java.util.zip.Deflater` writes by default.
+    pub fn new(writer: W) -> Self {
+        Deflater::with_wrapper(writer, true)
+    }
+
+    /// Compress into raw deflate data without zlib header and trailer, as stored in
+    /// zip and jar entries.
+    pub fn new_raw(writer: W) -> Self {
+        Deflater::with_wrapper(writer, false)
+    }
+
+    fn with_wrapper(writer: W, zlib: bool) -> Self {
+        Deflater {
+            output: BitWriter::new(writer),
+            zlib,
+            header_written: false,
+            input: Vec::with_capacity(WINDOW_SIZE + BLOCK_SIZE),
+            input_start: 0,
+            pending: 0,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            prev: vec![usize::MAX; WINDOW_SIZE],
+            checksum: Adler32::new(),
+        }
+    }
+
+    /// Compress the remaining input, write the end of the stream and return the inner
+    /// writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress_block(true)?;
+        self.output.align_to_byte();
+        if self.zlib {
+            let checksum = self.checksum.value().to_be_bytes();
+            self.output.bytes(&checksum);
+        }
+        self.output.flush()?;
+        Ok(self.output.writer)
+    }
+
+    fn end(&self) -> usize {
+        self.input_start + self.input.len()
+    }
+
+    fn byte_at(&self, position: usize) -> u8 {
+        self.input[position - self.input_start]
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let index = position - self.input_start;
+        let value = (self.input[index] as usize) << 16
+            | (self.input[index + 1] as usize) << 8
+            | self.input[index + 2] as usize;
+        (value.wrapping_mul(0x9E37_79B1) >> 8) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, position: usize) {
+        if position + MIN_MATCH > self.end() {
+            return;
+        }
+        let hash = self.hash(position);
+        self.prev[position % WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = position;
+    }
+
+    /// The longest earlier match of the bytes at the position, longer than `min_len`.
+    fn longest_match(&self, position: usize, min_len: usize) -> Option<(usize, usize)> {
+        let max_len = MAX_MATCH.min(self.end() - position);
+        if max_len < MIN_MATCH {
+            return None;
+        }
+        let lowest = position.saturating_sub(WINDOW_SIZE).max(self.input_start);
+        let mut chain = if min_len >= GOOD_LENGTH {
+            MAX_CHAIN / 4
+        } else {
+            MAX_CHAIN
+        };
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_len = min_len;
+        let mut candidate = self.head[self.hash(position)];
+        let base = position - self.input_start;
+        while candidate != usize::MAX && candidate < position && candidate >= lowest && chain > 0 {
+            let start = candidate - self.input_start;
+            if self.input[start + best_len.min(max_len - 1)]
+                == self.input[base + best_len.min(max_len - 1)]
+            {
+                let len = (0..max_len)
+                    .take_while(|&i| self.input[start + i] == self.input[base + i])
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best = Some((len, position - candidate));
+                    if len >= NICE_LENGTH.min(max_len) {
+                        break;
+                    }
+                }
+            }
+            let previous = self.prev[candidate % WINDOW_SIZE];
+            if previous >= candidate {
+                break;
+            }
+            candidate = previous;
+            chain -= 1;
+        }
+        best.filter(|&(len, dist)| len > MIN_MATCH || dist <= TOO_FAR)
+    }
+
+    /// Tokenize the pending input, all of it for the last block, otherwise only what
+    /// leaves enough lookahead for the longest match.
+    fn tokenize(&mut self, last: bool) -> Vec<Token> {
+        let limit = if last {
+            self.end()
+        } else {
+            self.end() - MAX_MATCH - 1
+        };
+        let mut tokens = Vec::new();
+        let mut position = self.pending;
+        // the match found at the previous position, it is emitted unless the match at
+        // the current position is longer
+        let mut previous: Option<(usize, usize)> = None;
+        let mut literal_pending = false;
+        while position < limit {
+            let current = match previous {
+                Some((len, _)) if len >= MAX_LAZY => None,
+                _ => self.longest_match(position, previous.map_or(MIN_MATCH - 1, |m| m.0)),
+            };
+            self.insert(position);
+            match (previous, current) {
+                (Some((len, dist)), None) => {
+                    tokens.push(Token::Match { len, dist });
+                    let end = position - 1 + len;
+                    (position + 1..end).for_each(|p| self.insert(p));
+                    position = end;
+                    previous = None;
+                    literal_pending = false;
+                }
+                _ => {
+                    if literal_pending {
+                        tokens.push(Token::Literal(self.byte_at(position - 1)));
+                    }
+                    previous = current;
+                    literal_pending = true;
+                    position += 1;
+                }
+            }
+        }
+        if literal_pending {
+            match previous {
+                Some((len, dist)) => {
+                    tokens.push(Token::Match { len, dist });
+                    let end = position - 1 + len;
+                    (position..end).for_each(|p| self.insert(p));
+                    position = end;
+                }
+                None => tokens.push(Token::Literal(self.byte_at(position - 1))),
+            }
+        }
+        self.pending = position;
+        tokens
+    }
+
+    fn fixed_size(tokens: &[Token]) -> usize {
+        let bits: usize = tokens
+            .iter()
+            .map(|token| match *token {
+                Token::Literal(byte) => fixed_literal_code(byte as usize).1 as usize,
+                Token::Match { len, dist } => {
+                    let symbol = length_symbol(len);
+                    fixed_literal_code(257 + symbol).1 as usize
+                        + LENGTH_EXTRA[symbol] as usize
+                        + 5
+                        + DIST_EXTRA[dist_symbol(dist)] as usize
+                }
+            })
+            .sum();
+        (bits + 3).div_ceil(8) + 1
+    }
+
+    fn compress_block(&mut self, last: bool) -> io::Result<()> {
+        if !self.header_written {
+            if self.zlib {
+                self.output.bytes(&[0x78, 0x9C]);
+            }
+            self.header_written = true;
+        }
+        let start = self.pending;
+        let tokens = self.tokenize(last);
+        let len = self.pending - start;
+        let stored_size = len + (len / MAX_STORED + 1) * 5;
+        if Deflater::<W>::fixed_size(&tokens) <= stored_size {
+            self.write_fixed(&tokens, last);
+        } else {
+            self.write_stored(start, last);
+        }
+        self.output.flush()?;
+        // keep a window of history for the next block
+        let keep_from = self
+            .pending
+            .saturating_sub(WINDOW_SIZE)
+            .max(self.input_start);
+        self.input.drain(..keep_from - self.input_start);
+        self.input_start = keep_from;
+        Ok(())
+    }
+
+    fn write_fixed(&mut self, tokens: &[Token], last: bool) {
+        self.output.bits(last as u32, 1);
+        self.output.bits(1, 2);
+        for token in tokens {
+            match *token {
+                Token::Literal(byte) => {
+                    let (code, len) = fixed_literal_code(byte as usize);
+                    self.output.code(code, len);
+                }
+                Token::Match { len, dist } => {
+                    let symbol = length_symbol(len);
+                    let (code, code_len) = fixed_literal_code(257 + symbol);
+                    self.output.code(code, code_len);
+                    self.output.bits(
+                        (len - LENGTH_BASE[symbol] as usize) as u32,
+                        LENGTH_EXTRA[symbol] as u32,
+                    );
+                    let symbol = dist_symbol(dist);
+                    self.output.code(symbol as u32, 5);
+                    self.output.bits(
+                        (dist - DIST_BASE[symbol] as usize) as u32,
+                        DIST_EXTRA[symbol] as u32,
+                    );
+                }
+            }
+        }
+        let (code, len) = fixed_literal_code(256);
+        self.output.code(code, len);
+    }
+
+    fn write_stored(&mut self, start: usize, last: bool) {
+        let data = self.input[start - self.input_start..self.pending - self.input_start].to_vec();
+        let mut chunks = data.chunks(MAX_STORED).peekable();
+        if chunks.peek().is_none() {
+            self.write_stored_chunk(&[], last);
+        }
+        while let Some(chunk) = chunks.next() {
+            let final_chunk = last && chunks.peek().is_none();
+            self.write_stored_chunk(chunk, final_chunk);
+        }
+    }
+
+    fn write_stored_chunk(&mut self, chunk: &[u8], last: bool) {
+        self.output.bits(last as u32, 1);
+        self.output.bits(0, 2);
+        self.output.align_to_byte();
+        let len = chunk.len() as u16;
+        self.output.bytes(&len.to_le_bytes());
+        self.output.bytes(&(!len).to_le_bytes());
+        self.output.bytes(chunk);
+    }
+}
+
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(BLOCK_SIZE);
+        self.input.extend_from_slice(&buf[..len]);
+        if self.zlib {
+            self.checksum.update(&buf[..len]);
+        }
+        if self.end() - self.pending >= BLOCK_SIZE {
+            self.compress_block(false)?;
+        }
+        Ok(len)
+    }
+
+    /// Pending input is only compressed once a block is full or the stream finished.
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()?;
+        self.output.writer.flush()
+    }
+}
+
+/// Compress the data into zlib wrapped data.
+pub fn deflate(input: &[u8]) -> Vec<u8> {
+    let mut deflater = Deflater::new(Vec::new());
+    deflater.write_all(input).unwrap();
+    deflater.finish().unwrap()
+}
+
+/// Compress the data into raw deflate data.
+pub fn deflate_raw(input: &[u8]) -> Vec<u8> {
+    let mut deflater = Deflater::new_raw(Vec::new());
+    deflater.write_all(input).unwrap();
+    deflater.finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deflate, deflate_raw};
+    use crate::zip::inflater::{inflate, inflate_raw};
+
+    #[test]
+    fn we_can_compress_data() {
+        // "hello " followed by a match of length 11 at distance 6
+        assert_eq!(
+            deflate(b"hello hello hello"),
+            vec![120, 156, 203, 72, 205, 201, 201, 87, 64, 34, 1, 58, 46, 6, 125]
+        );
+    }
+
+    #[test]
+    fn compressed_data_can_be_inflated() {
+        let mut text = Vec::new();
+        for i in 0..20_000 {
+            text.extend_from_slice(format!("line {} of {}\n", i % 977, i % 13).as_bytes());
+        }
+        let noise: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        for input in [Vec::new(), b"a".to_vec(), text, noise] {
+            let compressed = deflate(&input);
+            let mut output = vec![0u8; input.len()];
+            assert_eq!(inflate(&compressed, &mut output).unwrap(), input.len());
+            assert!(output == input);
+            let compressed = deflate_raw(&input);
+            assert_eq!(inflate_raw(&compressed, &mut output).unwrap(), input.len());
+            assert!(output == input);
+        }
+    }
+}
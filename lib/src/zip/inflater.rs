@@ -0,0 +1,535 @@
+//! Decompression of deflate data, RFC 1951, with or without the zlib wrapper of
+//! RFC 1950.
+//!
+//! [`Inflater`] decompresses while it is read, so a stream never has to be held in
+//! memory completely. Only the last 32K of output are kept for back references. Any
+//! malformed input is reported as an `InvalidData` error and a stream that ends early
+//! as `UnexpectedEof`, the input is never trusted.
+use std::io::{self, Read};
+
+use super::checksum::Adler32;
+
+pub(super) const MAX_BITS: usize = 15;
+pub(super) const WINDOW_SIZE: usize = 32 * 1024;
+
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(super) const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(super) const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which the code length code lengths of a dynamic block are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Upper bound of bytes produced by one decoding step, bounds the output buffer.
+const STEP_SIZE: usize = 16 * 1024;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Canonical Huffman code, decoded one bit at a time with the count of codes of each
+/// length.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build the code from the code length of each symbol, zero for unused symbols.
+    /// Incomplete codes are allowed, over-subscribed codes are not.
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        lengths.iter().for_each(|&len| counts[len as usize] += 1);
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid_data("over-subscribed huffman code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn fixed() -> (Huffman, Huffman) {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        (
+            Huffman::new(&lengths).unwrap(),
+            Huffman::new(&[5u8; 30]).unwrap(),
+        )
+    }
+}
+
+/// Reads bits least significant first, the input is read in chunks so the inflater
+/// may read past the end of the compressed data.
+struct BitReader<R> {
+    reader: R,
+    buffer: Box<[u8]>,
+    position: usize,
+    limit: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> Self {
+        BitReader {
+            reader,
+            buffer: vec![0u8; 4096].into_boxed_slice(),
+            position: 0,
+            limit: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        if self.position == self.limit {
+            self.limit = loop {
+                match self.reader.read(&mut self.buffer) {
+                    Ok(0) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "unexpected end of compressed data",
+                        ))
+                    }
+                    Ok(len) => break len,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(error),
+                }
+            };
+            self.position = 0;
+        }
+        self.position += 1;
+        Ok(self.buffer[self.position - 1])
+    }
+
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.bit_count < count {
+            self.bit_buffer |= (self.next_byte()? as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer = self.bit_buffer.checked_shr(count).unwrap_or(0);
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Drop the bits left in the current byte.
+    fn align_to_byte(&mut self) {
+        let remainder = self.bit_count % 8;
+        self.bit_buffer >>= remainder;
+        self.bit_count -= remainder;
+    }
+
+    /// A byte of a byte aligned stream.
+    fn byte(&mut self) -> io::Result<u8> {
+        if self.bit_count >= 8 {
+            return self.bits(8).map(|value| value as u8);
+        }
+        self.next_byte()
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for &count in &huffman.counts[1..] {
+            code |= self.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid huffman code"))
+    }
+}
+
+enum State {
+    Header,
+    BlockHeader,
+    Stored(usize),
+    Codes(Box<(Huffman, Huffman)>),
+    Trailer,
+    Done,
+}
+
+/// A reader that decompresses the data read from the inner reader.
+///
+/// The compressed data is read in chunks, so the inner reader should not be used
+/// after the inflater for anything else.
+pub struct Inflater<R> {
+    input: BitReader<R>,
+    zlib: bool,
+    state: State,
+    last_block: bool,
+    /// Output that is still referenced by back references or not read yet.
+    output: Vec<u8>,
+    read_position: usize,
+    checksum: Adler32,
+    total_out: u64,
+}
+
+impl<R: Read> Inflater<R> {
+    /// Inflate zlib wrapped data, what `java.util.zip.Inflater` expects by default.
+    pub fn new(reader: R) -> Self {
+        Inflater::with_wrapper(reader, true)
+    }
+
+    /// Inflate raw deflate data without a zlib header and trailer, as stored in zip
+    /// and jar entries.
+    pub fn new_raw(reader: R) -> Self {
+        Inflater::with_wrapper(reader, false)
+    }
+
+    fn with_wrapper(reader: R, zlib: bool) -> Self {
+        Inflater {
+            input: BitReader::new(reader),
+            zlib,
+            state: State::Header,
+            last_block: false,
+            output: Vec::with_capacity(2 * WINDOW_SIZE),
+            read_position: 0,
+            checksum: Adler32::new(),
+            total_out: 0,
+        }
+    }
+
+    /// Number of bytes decompressed so far, including bytes not read yet.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Whether the end of the compressed stream was reached and verified.
+    pub fn finished(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Decode until there is unread output or the stream is finished.
+    fn fill(&mut self) -> io::Result<()> {
+        while self.read_position == self.output.len() && !self.finished() {
+            let start = self.output.len();
+            self.step()?;
+            if self.zlib {
+                self.checksum.update(&self.output[start..]);
+            }
+            self.total_out += (self.output.len() - start) as u64;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> io::Result<()> {
+        match std::mem::replace(&mut self.state, State::Done) {
+            State::Header => {
+                if self.zlib {
+                    self.read_zlib_header()?;
+                }
+                self.state = State::BlockHeader;
+            }
+            State::BlockHeader => {
+                self.state = if self.last_block {
+                    State::Trailer
+                } else {
+                    self.read_block_header()?
+                };
+            }
+            State::Stored(remaining) => {
+                let len = remaining.min(STEP_SIZE);
+                for _ in 0..len {
+                    let byte = self.input.next_byte()?;
+                    self.output.push(byte);
+                }
+                self.state = match remaining - len {
+                    0 => State::BlockHeader,
+                    remaining => State::Stored(remaining),
+                };
+            }
+            State::Codes(codes) => {
+                let end_of_block = self.decode_codes(&codes.0, &codes.1)?;
+                self.state = if end_of_block {
+                    State::BlockHeader
+                } else {
+                    State::Codes(codes)
+                };
+            }
+            State::Trailer => {
+                if self.zlib {
+                    self.input.align_to_byte();
+                    let mut expected = 0u32;
+                    for _ in 0..4 {
+                        expected = expected << 8 | self.input.byte()? as u32;
+                    }
+                    if expected != self.checksum.value() {
+                        return Err(invalid_data("incorrect data check"));
+                    }
+                }
+            }
+            State::Done => {}
+        }
+        Ok(())
+    }
+
+    fn read_zlib_header(&mut self) -> io::Result<()> {
+        let cmf = self.input.byte()? as u16;
+        let flg = self.input.byte()? as u16;
+        if !(cmf << 8 | flg).is_multiple_of(31) {
+            return Err(invalid_data("incorrect header check"));
+        }
+        if cmf & 0x0F != 8 {
+            return Err(invalid_data("unknown compression method"));
+        }
+        if cmf >> 4 > 7 {
+            return Err(invalid_data("invalid window size"));
+        }
+        if flg & 0x20 != 0 {
+            return Err(invalid_data("preset dictionary is not supported"));
+        }
+        Ok(())
+    }
+
+    fn read_block_header(&mut self) -> io::Result<State> {
+        self.last_block = self.input.bits(1)? == 1;
+        match self.input.bits(2)? {
+            0 => {
+                self.input.align_to_byte();
+                let len = self.input.bits(16)?;
+                let nlen = self.input.bits(16)?;
+                if len != !nlen & 0xFFFF {
+                    return Err(invalid_data("invalid stored block lengths"));
+                }
+                Ok(match len {
+                    0 => State::BlockHeader,
+                    len => State::Stored(len as usize),
+                })
+            }
+            1 => Ok(State::Codes(Box::new(Huffman::fixed()))),
+            2 => Ok(State::Codes(Box::new(self.read_dynamic_codes()?))),
+            _ => Err(invalid_data("invalid block type")),
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+        let nlen = self.input.bits(5)? as usize + 257;
+        let ndist = self.input.bits(5)? as usize + 1;
+        let ncode = self.input.bits(4)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return Err(invalid_data("too many length or distance symbols"));
+        }
+        let mut code_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..ncode] {
+            code_lengths[index] = self.input.bits(3)? as u8;
+        }
+        let code_length_code = Huffman::new(&code_lengths)?;
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut index = 0;
+        while index < nlen + ndist {
+            let symbol = self.input.decode(&code_length_code)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    if index == 0 {
+                        return Err(invalid_data("repeat with no first length"));
+                    }
+                    (lengths[index - 1], 3 + self.input.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.bits(3)? as usize),
+                _ => (0, 11 + self.input.bits(7)? as usize),
+            };
+            if index + repeat > nlen + ndist {
+                return Err(invalid_data("too many code lengths"));
+            }
+            lengths[index..index + repeat].fill(value);
+            index += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(invalid_data("missing end of block code"));
+        }
+        Ok((
+            Huffman::new(&lengths[..nlen])?,
+            Huffman::new(&lengths[nlen..])?,
+        ))
+    }
+
+    /// Decode until the end of block or the step size is reached, returns whether the
+    /// end of block was found.
+    fn decode_codes(&mut self, literals: &Huffman, distances: &Huffman) -> io::Result<bool> {
+        let limit = self.output.len() + STEP_SIZE;
+        while self.output.len() < limit {
+            let symbol = self.input.decode(literals)? as usize;
+            if symbol < 256 {
+                self.output.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(true);
+            }
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return Err(invalid_data("invalid literal/length code"));
+            }
+            let len = LENGTH_BASE[symbol] as usize
+                + self.input.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+            let symbol = self.input.decode(distances)? as usize;
+            if symbol >= DIST_BASE.len() {
+                return Err(invalid_data("invalid distance code"));
+            }
+            let dist =
+                DIST_BASE[symbol] as usize + self.input.bits(DIST_EXTRA[symbol] as u32)? as usize;
+            // at least a window of output is kept, so anything beyond is too far back
+            if dist > self.output.len() {
+                return Err(invalid_data("invalid distance too far back"));
+            }
+            let start = self.output.len() - dist;
+            for index in start..start + len {
+                let byte = self.output[index];
+                self.output.push(byte);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Drop output that is read and out of the window.
+    fn compact(&mut self) {
+        if self.read_position > 2 * WINDOW_SIZE {
+            let drop = self.read_position - WINDOW_SIZE;
+            self.output.drain(..drop);
+            self.read_position -= drop;
+        }
+    }
+}
+
+impl<R: Read> Read for Inflater<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill()?;
+        let available = &self.output[self.read_position..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.read_position += len;
+        self.compact();
+        Ok(len)
+    }
+}
+
+/// Inflate zlib wrapped data into `output`, returns the number of bytes written.
+///
+/// Fails if the data is corrupted, incomplete or decompresses to more bytes than the
+/// output can hold.
+pub fn inflate(input: &[u8], output: &mut [u8]) -> io::Result<usize> {
+    inflate_into(Inflater::new(input), output)
+}
+
+/// Inflate raw deflate data into `output`, see [`inflate`].
+pub fn inflate_raw(input: &[u8], output: &mut [u8]) -> io::Result<usize> {
+    inflate_into(Inflater::new_raw(input), output)
+}
+
+fn inflate_into<R: Read>(mut inflater: Inflater<R>, output: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < output.len() {
+        match inflater.read(&mut output[len..])? {
+            0 => return Ok(len),
+            read => len += read,
+        }
+    }
+    if inflater.read(&mut [0u8; 1])? != 0 {
+        return Err(invalid_data("decompressed data exceeds the output size"));
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read};
+
+    use super::{inflate, inflate_raw, Inflater};
+
+    const HELLO: [u8; 16] = [
+        120, 156, 203, 72, 205, 201, 201, 87, 200, 64, 144, 0, 58, 46, 6, 125,
+    ];
+
+    #[test]
+    fn we_can_inflate_zlib_data() {
+        let mut output = [0u8; 17];
+        assert_eq!(inflate(&HELLO, &mut output).unwrap(), 17);
+        assert_eq!(&output, b"hello hello hello");
+        assert_eq!(
+            inflate_raw(&HELLO[2..14], &mut output).unwrap(),
+            17,
+            "raw data has no zlib header and trailer"
+        );
+    }
+
+    #[test]
+    fn we_can_inflate_stored_blocks() {
+        let stored = [0x01, 0x05, 0x00, 0xFA, 0xFF, b'h', b'e', b'l', b'l', b'o'];
+        let mut output = [0u8; 5];
+        assert_eq!(inflate_raw(&stored, &mut output).unwrap(), 5);
+        assert_eq!(&output, b"hello");
+    }
+
+    #[test]
+    fn corrupted_data_is_rejected() {
+        let mut output = [0u8; 17];
+        let mut checksum = HELLO;
+        checksum[15] ^= 1;
+        let error = inflate(&checksum, &mut output).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "incorrect data check");
+        let error = inflate(&HELLO[..10], &mut output).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        let error = inflate(&HELLO, &mut output[..16]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "decompressed data exceeds the output size"
+        );
+        let error = inflate_raw(&[0x07], &mut output).unwrap_err();
+        assert_eq!(error.to_string(), "invalid block type");
+        // a fixed block starting with a back reference
+        let error = inflate_raw(&[0x03, 0x02], &mut output).unwrap_err();
+        assert_eq!(error.to_string(), "invalid distance too far back");
+    }
+
+    #[test]
+    fn we_can_inflate_while_reading() {
+        let mut inflater = Inflater::new(&HELLO[..]);
+        let mut first = [0u8; 6];
+        inflater.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"hello ");
+        let mut rest = Vec::new();
+        inflater.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"hello hello");
+        assert!(inflater.finished());
+        assert_eq!(inflater.total_out(), 17);
+    }
+}
@@ -0,0 +1,175 @@
+//! Jar files, zip archives with a manifest.
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    archive::{ZipArchive, ZipEntry},
+    manifest::Manifest,
+};
+
+/// A jar file with its manifest read when opened.
+pub struct JarFile<R> {
+    path: PathBuf,
+    archive: ZipArchive<R>,
+    manifest: Option<Manifest>,
+}
+
+impl JarFile<BufReader<File>> {
+    /// Open the jar file at the path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        JarFile::new(path.as_ref(), ZipArchive::open(path.as_ref())?)
+    }
+}
+
+impl<R: Read + Seek> JarFile<R> {
+    /// A jar of the archive at the path, the path is the base of the `Class-Path`.
+    pub fn new<P: Into<PathBuf>>(path: P, mut archive: ZipArchive<R>) -> io::Result<Self> {
+        let manifest = archive
+            .read_by_name(Manifest::PATH)?
+            .map(|bytes| Manifest::parse(&bytes))
+            .transpose()?;
+        Ok(JarFile {
+            path: path.into(),
+            archive,
+            manifest,
+        })
+    }
+
+    /// Path of the jar file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The manifest, `None` when the jar has none.
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Whether the manifest declares a multi-release jar.
+    pub fn is_multi_release(&self) -> bool {
+        self.manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.is_multi_release())
+    }
+
+    /// The paths of the `Class-Path` of the manifest, relative to the directory of the
+    /// jar. URLs that are not `file:` URLs are left out.
+    pub fn class_path(&self) -> Vec<PathBuf> {
+        let Some(manifest) = &self.manifest else {
+            return Vec::new();
+        };
+        let base = self.path.parent().unwrap_or_else(|| Path::new(""));
+        manifest
+            .class_path()
+            .into_iter()
+            .filter_map(|url| {
+                let path = match url.split_once(':') {
+                    // a scheme is at least two characters, unlike a drive letter
+                    Some((scheme, path)) if scheme.len() > 1 && !scheme.contains('/') => {
+                        if !scheme.eq_ignore_ascii_case("file") {
+                            return None;
+                        }
+                        path.strip_prefix("//").unwrap_or(path)
+                    }
+                    _ => url,
+                };
+                Some(base.join(percent_decode(path)))
+            })
+            .collect()
+    }
+
+    /// The zip archive of the jar.
+    pub fn archive(&mut self) -> &mut ZipArchive<R> {
+        &mut self.archive
+    }
+
+    /// The entry with the name.
+    pub fn entry(&self, name: &str) -> Option<&ZipEntry> {
+        self.archive.entry(name)
+    }
+
+    /// Read the entry with the name, `None` if there is no such entry.
+    pub fn read(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        self.archive.read_by_name(name)
+    }
+}
+
+/// Decode the `%XX` escapes of a URL path, malformed escapes are kept.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = (bytes[index] == b'%')
+            .then(|| path.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::PathBuf};
+
+    use super::JarFile;
+    use crate::zip::archive::{write_test_zip, ZipArchive};
+
+    fn jar(manifest: Option<&[u8]>) -> JarFile<Cursor<Vec<u8>>> {
+        let mut entries: Vec<(&str, &[u8], bool)> =
+            vec![("p/C.class", &[0xCA, 0xFE, 0xBA, 0xBE], true)];
+        if let Some(manifest) = manifest {
+            entries.insert(0, ("META-INF/MANIFEST.MF", manifest, true));
+        }
+        let archive = ZipArchive::new(Cursor::new(write_test_zip(&entries, false))).unwrap();
+        JarFile::new("/opt/app/lib/app.jar", archive).unwrap()
+    }
+
+    #[test]
+    fn we_can_expand_the_class_path() {
+        let jar = jar(Some(
+            b"Manifest-Version: 1.0\r\n\
+              Class-Path: a.jar ../b.jar classes/ /opt/c.jar file:d%20e.jar\r\n  \
+              file:///opt/f.jar http://example.com/g.jar\r\n\
+              Multi-Release: true\r\n",
+        ));
+        assert!(jar.is_multi_release());
+        let expected: Vec<PathBuf> = [
+            "/opt/app/lib/a.jar",
+            "/opt/app/lib/../b.jar",
+            "/opt/app/lib/classes/",
+            "/opt/c.jar",
+            "/opt/app/lib/d e.jar",
+            "/opt/f.jar",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(jar.class_path(), expected);
+    }
+
+    #[test]
+    fn a_jar_may_have_no_manifest() {
+        let mut jar = jar(None);
+        assert!(jar.manifest().is_none() && !jar.is_multi_release());
+        assert!(jar.class_path().is_empty());
+        assert_eq!(
+            jar.read("p/C.class").unwrap().map(|data| data.len()),
+            Some(4)
+        );
+        assert!(jar.entry("p/D.class").is_none());
+    }
+}
@@ -0,0 +1,214 @@
+//! Parser of `META-INF/MANIFEST.MF`, the JAR File Specification.
+//!
+//! A manifest is a main section followed by sections separated by empty lines, each
+//! a list of `Name: value` headers. Lines end with CR LF, LF or CR, a line starting
+//! with a space continues the value of the previous line. The sections after the
+//! main one start with a `Name` header naming an entry of the jar.
+use std::io;
+
+/// Headers of a section, names are compared ignoring case.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Attributes {
+    values: Vec<(String, String)>,
+}
+
+impl Attributes {
+    /// The value of the header with the name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The headers in the order of the manifest.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Number of headers.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether the section has no headers.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The main attributes and the attributes of the entries of a jar.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    main: Attributes,
+    entries: Vec<(String, Attributes)>,
+}
+
+fn invalid_data<T>(message: String) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+impl Manifest {
+    /// Path of the manifest in a jar.
+    pub const PATH: &'static str = "META-INF/MANIFEST.MF";
+    const CLASS_PATH: &'static str = "Class-Path";
+    const MULTI_RELEASE: &'static str = "Multi-Release";
+    const MAIN_CLASS: &'static str = "Main-Class";
+    const NAME: &'static str = "Name";
+
+    /// Parse the bytes of a manifest.
+    pub fn parse(bytes: &[u8]) -> io::Result<Manifest> {
+        let text = String::from_utf8_lossy(bytes);
+        let mut lines: Vec<String> = Vec::new();
+        let mut sections: Vec<Vec<String>> = Vec::new();
+        let mut rest = text.as_ref();
+        while !rest.is_empty() {
+            let end = rest.find(['\r', '\n']).unwrap_or(rest.len());
+            let line = &rest[..end];
+            rest = &rest[end..];
+            rest = rest
+                .strip_prefix("\r\n")
+                .or_else(|| rest.strip_prefix(['\r', '\n']))
+                .unwrap_or(rest);
+            if let Some(continuation) = line.strip_prefix(' ') {
+                match lines.last_mut() {
+                    Some(last) => last.push_str(continuation),
+                    None => {
+                        return invalid_data(
+                            "invalid manifest format (line starts with a space)".to_string(),
+                        )
+                    }
+                }
+            } else if line.is_empty() {
+                if !lines.is_empty() || sections.is_empty() {
+                    sections.push(std::mem::take(&mut lines));
+                }
+            } else {
+                lines.push(line.to_string());
+            }
+        }
+        if !lines.is_empty() || sections.is_empty() {
+            sections.push(lines);
+        }
+
+        let mut manifest = Manifest::default();
+        for (index, section) in sections.into_iter().enumerate() {
+            let mut attributes = Attributes::default();
+            for line in section {
+                let (name, value) = line
+                    .split_once(": ")
+                    .or_else(|| line.strip_suffix(':').map(|name| (name, "")))
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid header field: {}", line),
+                        )
+                    })?;
+                attributes
+                    .values
+                    .push((name.to_string(), value.to_string()));
+            }
+            if index == 0 {
+                manifest.main = attributes;
+            } else if !attributes.is_empty() {
+                let name = match attributes.values.first() {
+                    Some((key, value)) if key.eq_ignore_ascii_case(Manifest::NAME) => value.clone(),
+                    _ => {
+                        return invalid_data(
+                            "invalid manifest format (section without a Name)".to_string(),
+                        )
+                    }
+                };
+                manifest.entries.push((name, attributes));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The attributes of the main section.
+    pub fn main_attributes(&self) -> &Attributes {
+        &self.main
+    }
+
+    /// The attributes of the section of the entry.
+    pub fn attributes(&self, name: &str) -> Option<&Attributes> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, attributes)| attributes)
+    }
+
+    /// The value of the main attribute with the name.
+    pub fn main_attribute(&self, name: &str) -> Option<&str> {
+        self.main.get(name)
+    }
+
+    /// The relative URLs of the `Class-Path` attribute.
+    pub fn class_path(&self) -> Vec<&str> {
+        self.main_attribute(Manifest::CLASS_PATH)
+            .map(|class_path| class_path.split_ascii_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether the `Multi-Release` attribute is `true`, then the classes in
+    /// `META-INF/versions/<release>` replace the classes of the jar on that release.
+    pub fn is_multi_release(&self) -> bool {
+        self.main_attribute(Manifest::MULTI_RELEASE)
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// The `Main-Class` attribute.
+    pub fn main_class(&self) -> Option<&str> {
+        self.main_attribute(Manifest::MAIN_CLASS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Manifest;
+
+    #[test]
+    fn we_can_parse_a_manifest() {
+        let manifest = Manifest::parse(
+            b"Manifest-Version: 1.0\r\n\
+              Main-Class: app.Main\r\n\
+              Class-Path: lib/a.jar  lib/b.jar\r\n  \
+              lib/c.jar\r\n\
+              multi-release: TRUE\r\n\
+              \r\n\
+              Name: app/Main.class\n\
+              SHA-256-Digest: abc=\n\
+              \n",
+        )
+        .unwrap();
+        assert_eq!(manifest.main_attribute("manifest-version"), Some("1.0"));
+        assert_eq!(manifest.main_class(), Some("app.Main"));
+        assert_eq!(
+            manifest.class_path(),
+            ["lib/a.jar", "lib/b.jar", "lib/c.jar"]
+        );
+        assert!(manifest.is_multi_release());
+        assert_eq!(manifest.main_attributes().len(), 4);
+        let entry = manifest.attributes("app/Main.class").unwrap();
+        assert_eq!(entry.get("SHA-256-Digest"), Some("abc="));
+        assert!(manifest.attributes("app/Other.class").is_none());
+    }
+
+    #[test]
+    fn a_manifest_may_be_empty_or_unterminated() {
+        let manifest = Manifest::parse(b"").unwrap();
+        assert!(manifest.main_attributes().is_empty() && manifest.class_path().is_empty());
+        let manifest = Manifest::parse(b"Manifest-Version: 1.0\rMulti-Release: false").unwrap();
+        assert_eq!(manifest.main_attribute("Manifest-Version"), Some("1.0"));
+        assert!(!manifest.is_multi_release());
+    }
+
+    #[test]
+    fn malformed_manifests_are_rejected() {
+        assert!(Manifest::parse(b" continued\r\n").is_err());
+        assert!(Manifest::parse(b"Manifest-Version 1.0\r\n").is_err());
+        assert!(Manifest::parse(b"Manifest-Version: 1.0\r\n\r\nSHA-256-Digest: abc=\r\n").is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! Zip archives and jar files, with the deflate compression they share with jimage
+//! resources.
+pub mod archive;
+pub mod checksum;
+pub mod deflater;
+pub mod inflater;
+pub mod jar;
+pub mod manifest;